@@ -30,14 +30,38 @@ fn main() {
     //#define VMA_HEAVY_ASSERT(expr) assert(expr)
     //#define VMA_USE_STL_CONTAINERS 1
     //#define VMA_DEDICATED_ALLOCATION 0
-    //#define VMA_DEBUG_MARGIN 16
-    //#define VMA_DEBUG_DETECT_CORRUPTION 1
     //#define VMA_DEBUG_INITIALIZE_ALLOCATIONS 1
     //#define VMA_DEBUG_MIN_BUFFER_IMAGE_GRANULARITY 256
 
+    // Leave a margin of 16 bytes between allocations, filled with a magic value when corruption
+    // detection is enabled.
+    #[cfg(feature = "debug_margin")]
+    build.define("VMA_DEBUG_MARGIN", "16");
+
+    #[cfg(feature = "detect_corruption")]
+    build.define("VMA_DEBUG_DETECT_CORRUPTION", "1");
+
+    #[cfg(feature = "debug_always_dedicated")]
+    build.define("VMA_DEBUG_ALWAYS_DEDICATED_MEMORY", "1");
+
+    #[cfg(feature = "stats_string_disabled")]
+    build.define("VMA_STATS_STRING_ENABLED", "0");
+
+    // Route VMA_DEBUG_LOG_FORMAT to the log crate, see src/vma_log.rs. Leak messages are always
+    // routed.
+    #[cfg(feature = "debug_log")]
+    build.define("VK_MEM_RS_DEBUG_LOG", None);
+
     #[cfg(feature = "recording")]
     build.define("VMA_RECORDING_ENABLED", "1");
 
+    // Expose VK_KHR_external_memory_win32 so that VmaVulkanFunctions matches
+    // the layout declared in the bindings on Windows. vulkan_win32.h isn't
+    // vendored, wrapper/vma_win32.h declares the parts VMA needs.
+    if env::var("CARGO_CFG_WINDOWS").is_ok() {
+        build.define("VMA_EXTERNAL_MEMORY_WIN32", "1");
+    }
+
     // Add the files we build
     let source_files = ["wrapper/vma_lib.cpp"];
 
@@ -155,6 +179,10 @@ fn link_vulkan() {}
 fn generate_bindings(output_file: &str) {
     let bindings = bindgen::Builder::default()
         .clang_arg("-I./wrapper")
+        // Declare the VK_KHR_external_memory_win32 parts of VMA on every platform, they are put
+        // behind cfg(windows) below.
+        .clang_arg("-DVMA_EXTERNAL_MEMORY_WIN32=1")
+        .header("wrapper/vma_win32.h")
         .header("vendor/include/vk_mem_alloc.h")
         .rustfmt_bindings(true)
         .size_t_is_usize(true)
@@ -163,15 +191,36 @@ fn generate_bindings(output_file: &str) {
         .parse_callbacks(Box::new(FixAshTypes))
         .blocklist_type("Vk.*")
         .blocklist_type("PFN_vk.*")
+        .blocklist_type("HANDLE")
         .raw_line("use ash::vk::*;")
+        // ash 0.38 gave `AllocationCallbacks` a lifetime, VMA keeps a pointer to it.
+        .raw_line("use crate::ash_compat::StaticAllocationCallbacks as AllocationCallbacks;")
         .trust_clang_mangling(false)
         .layout_tests(false)
         .generate()
         .expect("Unable to generate bindings!");
 
-    bindings
-        .write_to_file(std::path::Path::new(output_file))
-        .expect("Unable to write bindings!");
+    // bindgen can't add attributes to items, so mark the Windows-only parts by hand. The
+    // function pointer may be null, VMA only calls it for `vmaGetMemoryWin32Handle`.
+    let bindings = bindings
+        .to_string()
+        .replacen(
+            "    pub vkGetMemoryWin32HandleKHR: PFN_vkGetMemoryWin32HandleKHR,",
+            "    #[cfg(windows)]\n    pub vkGetMemoryWin32HandleKHR: Option<PFN_vkGetMemoryWin32HandleKHR>,",
+            1,
+        )
+        .replacen(
+            "extern \"C\" {\n    #[doc = \"\\\\brief Given an allocation, returns Win32 handle",
+            "#[cfg(windows)]\nextern \"C\" {\n    #[doc = \"\\\\brief Given an allocation, returns Win32 handle",
+            1,
+        );
+    assert_eq!(
+        bindings.matches("#[cfg(windows)]").count(),
+        2,
+        "VMA's win32 declarations changed, update the cfg(windows) rules in build.rs"
+    );
+
+    std::fs::write(output_file, bindings).expect("Unable to write bindings!");
 }
 
 #[cfg(not(feature = "generate_bindings"))]
@@ -181,13 +230,20 @@ fn generate_bindings(_: &str) {}
 #[derive(Debug)]
 struct FixAshTypes;
 
+/// Extension functions VMA uses that were never promoted to core, ash keeps their `KHR` suffix.
+#[cfg(feature = "generate_bindings")]
+const KHR_ONLY_FUNCTIONS: &[&str] = &["PFN_vkGetMemoryWin32HandleKHR"];
+
 #[cfg(feature = "generate_bindings")]
 impl bindgen::callbacks::ParseCallbacks for FixAshTypes {
     fn item_name(&self, original_item_name: &str) -> Option<String> {
         if original_item_name.starts_with("Vk") {
             // Strip `Vk` prefix, will use `ash::vk::*` instead
             Some(original_item_name.trim_start_matches("Vk").to_string())
-        } else if original_item_name.starts_with("PFN_vk") && original_item_name.ends_with("KHR") {
+        } else if original_item_name.starts_with("PFN_vk")
+            && original_item_name.ends_with("KHR")
+            && !KHR_ONLY_FUNCTIONS.contains(&original_item_name)
+        {
             // VMA uses a few extensions like `PFN_vkGetBufferMemoryRequirements2KHR`,
             // ash keeps these as `PFN_vkGetBufferMemoryRequirements2`
             Some(original_item_name.trim_end_matches("KHR").to_string())
@@ -0,0 +1,108 @@
+//! Watches the memory budget while a simulated level load fills device-local memory, the way a
+//! streaming system decides whether it may load more.
+//!
+//! The load reserves its expected size with `Allocator::reserve_budget` before allocating, so a
+//! second system planning against `Allocator::available_budget` sees the space as taken. Each step
+//! prints the per-heap budgets and the allocator's one-line summary.
+//!
+//! Runs headless: `cargo run --example budget_monitor`.
+
+extern crate vk_mem;
+
+use vk_mem::ash;
+
+mod common;
+
+use ash::vk;
+
+const TEXTURE_SIZE: vk::DeviceSize = 4 * 1024 * 1024;
+const TEXTURE_COUNT: usize = 16;
+
+fn print_budgets(allocator: &vk_mem::Allocator, heap_count: u32) {
+    for (heap, budget) in allocator
+        .get_heap_budgets(heap_count as usize)
+        .iter()
+        .enumerate()
+    {
+        println!(
+            "  heap {}: usage {} MiB / budget {} MiB, reserved {} MiB, available {} MiB",
+            heap,
+            budget.usage / (1024 * 1024),
+            budget.budget / (1024 * 1024),
+            allocator.reserved_budget(heap as u32) / (1024 * 1024),
+            allocator.available_budget(heap as u32) / (1024 * 1024)
+        );
+    }
+    println!("  {}", allocator.one_line_summary());
+}
+
+fn main() {
+    let context = common::Context::new("vk-mem budget monitor example");
+    let allocator = context
+        .create_allocator(vk_mem::AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_EXT_MEMORY_BUDGET_BIT);
+
+    unsafe {
+        let properties = allocator.get_memory_properties().unwrap();
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(TEXTURE_SIZE)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build();
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::AutoPreferDevice,
+            ..Default::default()
+        };
+        let memory_type_index = allocator
+            .find_memory_type_index_for_buffer_info(&buffer_info, &allocation_info)
+            .expect("No memory type for buffers");
+        let heap_index = properties.memory_types[memory_type_index as usize].heap_index;
+
+        println!("before load:");
+        print_budgets(&allocator, properties.memory_heap_count);
+
+        let mut buffers = Vec::with_capacity(TEXTURE_COUNT);
+        {
+            let mut reservation = match allocator
+                .reserve_budget(heap_index, TEXTURE_SIZE * TEXTURE_COUNT as vk::DeviceSize)
+            {
+                Ok(reservation) => reservation,
+                Err(_) => {
+                    println!("heap {} has no room for the load, skipping it", heap_index);
+                    return;
+                }
+            };
+            println!("reserved for load:");
+            print_budgets(&allocator, properties.memory_heap_count);
+
+            for _ in 0..TEXTURE_COUNT {
+                let (buffer, allocation, _) = reservation
+                    .create_buffer(&buffer_info, &allocation_info)
+                    .expect("Buffer creation error");
+                buffers.push((buffer, allocation));
+            }
+            println!(
+                "loaded, {} bytes of the reservation left:",
+                reservation.remaining()
+            );
+            print_budgets(&allocator, properties.memory_heap_count);
+        }
+
+        let statistics = allocator.calculate_statistics().unwrap();
+        for (heap, heap_statistics) in statistics.iter_heaps() {
+            println!(
+                "heap {}: {} allocations in {} blocks, largest allocation {} KiB",
+                heap,
+                heap_statistics.statistics.allocation_count,
+                heap_statistics.statistics.block_count,
+                heap_statistics.allocation_size_max / 1024
+            );
+        }
+
+        for (buffer, allocation) in &buffers {
+            allocator.destroy_buffer(*buffer, allocation);
+        }
+        println!("after unload:");
+        print_budgets(&allocator, properties.memory_heap_count);
+    }
+
+    drop(allocator);
+}
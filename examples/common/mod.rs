@@ -0,0 +1,111 @@
+//! Headless Vulkan setup shared by the examples.
+//!
+//! Creates an instance without any surface extensions, picks the first physical device with a queue
+//! family that supports transfers, and creates a device with one queue of that family.
+
+#![allow(dead_code)]
+
+use vk_mem::ash::{self, vk};
+
+pub struct Context {
+    pub entry: ash::Entry,
+    pub instance: ash::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: ash::Device,
+    pub queue_family_index: u32,
+    pub queue: vk::Queue,
+}
+
+impl Context {
+    pub fn new(name: &str) -> Self {
+        let app_name = std::ffi::CString::new(name).unwrap();
+        let app_info = vk::ApplicationInfo::builder()
+            .application_name(&app_name)
+            .application_version(0)
+            .engine_name(&app_name)
+            .engine_version(0)
+            .api_version(vk::make_api_version(0, 1, 1, 0));
+        let create_info = vk::InstanceCreateInfo::builder().application_info(&app_info);
+
+        let entry = ash::Entry::linked();
+        let instance = unsafe {
+            entry
+                .create_instance(&create_info, None)
+                .expect("Instance creation error")
+        };
+
+        let (physical_device, queue_family_index) = unsafe {
+            instance
+                .enumerate_physical_devices()
+                .expect("Physical device error")
+                .into_iter()
+                .find_map(|physical_device| {
+                    instance
+                        .get_physical_device_queue_family_properties(physical_device)
+                        .iter()
+                        .position(|family| {
+                            family.queue_flags.intersects(
+                                vk::QueueFlags::TRANSFER
+                                    | vk::QueueFlags::GRAPHICS
+                                    | vk::QueueFlags::COMPUTE,
+                            )
+                        })
+                        .map(|index| (physical_device, index as u32))
+                })
+                .expect("Couldn't find suitable device.")
+        };
+
+        let priorities = [1.0];
+        let queue_info = [vk::DeviceQueueCreateInfo::builder()
+            .queue_family_index(queue_family_index)
+            .queue_priorities(&priorities)
+            .build()];
+        let device_create_info = vk::DeviceCreateInfo::builder().queue_create_infos(&queue_info);
+        let device = unsafe {
+            instance
+                .create_device(physical_device, &device_create_info, None)
+                .expect("Device creation error")
+        };
+        let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+
+        Context {
+            entry,
+            instance,
+            physical_device,
+            device,
+            queue_family_index,
+            queue,
+        }
+    }
+
+    /// Creates an allocator for the device. It must be dropped before the context.
+    pub fn create_allocator(&self, flags: vk_mem::AllocatorCreateFlags) -> vk_mem::Allocator {
+        let create_info = vk_mem::AllocatorCreateInfo {
+            flags,
+            physical_device: self.physical_device,
+            device: self.device.clone(),
+            preferred_large_heap_block_size: 0,
+            allocation_callbacks: None,
+            device_memory_callbacks: None,
+            heap_size_limit: None,
+            heap_size_limit_fractions: None,
+            vulkan_functions: None,
+            get_instance_proc_addr: Some(self.entry.static_fn().get_instance_proc_addr),
+            instance: self.instance.clone(),
+            vulkan_api_version: vk::make_api_version(0, 1, 1, 0),
+            api_version_from_instance: false,
+            external_memory_handle_type: std::ptr::null(),
+        };
+        unsafe { vk_mem::Allocator::new(&create_info) }.expect("Allocator creation error")
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Fragments a custom pool by freeing every other allocation and compacts it again with incremental
+//! defragmentation passes, printing the pool statistics before and after.
+//!
+//! The allocations carry no content and are not bound to resources, so the moves of each pass are
+//! committed without recording any copies. Real code has to copy the data of every move (and
+//! recreate the resources bound to it) between `Allocator::begin_defragmentation_pass` and
+//! `Allocator::end_defragmentation_pass`.
+//!
+//! Runs headless: `cargo run --example defragmentation`.
+
+extern crate vk_mem;
+
+use vk_mem::ash;
+
+mod common;
+
+use ash::vk;
+
+const ALLOCATION_SIZE: vk::DeviceSize = 64 * 1024;
+const ALLOCATION_COUNT: usize = 256;
+
+fn print_statistics(label: &str, statistics: &vk_mem::Statistics) {
+    println!(
+        "{:>7}: {} blocks, {} KiB in blocks, {} allocations, {} KiB allocated",
+        label,
+        statistics.block_count,
+        statistics.block_bytes / 1024,
+        statistics.allocation_count,
+        statistics.allocation_bytes / 1024
+    );
+}
+
+fn main() {
+    let context = common::Context::new("vk-mem defragmentation example");
+    let allocator = context.create_allocator(vk_mem::AllocatorCreateFlags::empty());
+
+    unsafe {
+        let memory_requirements = vk::MemoryRequirements {
+            size: ALLOCATION_SIZE,
+            alignment: 256,
+            memory_type_bits: u32::MAX,
+        };
+        let memory_type_index = allocator
+            .find_memory_type_index(
+                memory_requirements.memory_type_bits,
+                &vk_mem::AllocationCreateInfo {
+                    required_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    ..Default::default()
+                },
+            )
+            .expect("No device-local memory type");
+
+        // Small blocks, so that freeing every other allocation leaves many partially used blocks behind.
+        let pool = allocator
+            .create_pool(&vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                block_size: ALLOCATION_SIZE * 16,
+                ..Default::default()
+            })
+            .expect("Pool creation error");
+
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            pool: Some(pool),
+            ..Default::default()
+        };
+        let mut allocations = Vec::with_capacity(ALLOCATION_COUNT);
+        for _ in 0..ALLOCATION_COUNT {
+            let (allocation, _) = allocator
+                .allocate_memory(&memory_requirements, &allocation_info)
+                .expect("Allocation error");
+            allocations.push(allocation);
+        }
+        print_statistics("filled", &allocator.get_pool_statistics(&pool));
+
+        let mut kept = Vec::with_capacity(ALLOCATION_COUNT / 2);
+        for (index, allocation) in allocations.into_iter().enumerate() {
+            if index % 2 == 0 {
+                allocator.free_memory(&allocation);
+            } else {
+                kept.push(allocation);
+            }
+        }
+        print_statistics("freed", &allocator.get_pool_statistics(&pool));
+
+        let mut defragmentation = allocator
+            .begin_defragmentation(&vk_mem::DefragmentationInfo {
+                pool: Some(pool),
+                max_bytes_per_pass: ALLOCATION_SIZE * 32,
+                ..Default::default()
+            })
+            .expect("Defragmentation error");
+        let mut passes = 0;
+        loop {
+            let (result, mut pass) = allocator.begin_defragmentation_pass(&mut defragmentation);
+            if result.is_ok() {
+                // No more moves are possible.
+                break;
+            }
+            passes += 1;
+            println!("pass {}: {} moves", passes, pass.moves().len());
+            match allocator.end_defragmentation_pass(&mut defragmentation, &mut pass) {
+                Ok(()) => break,
+                Err(vk::Result::INCOMPLETE) => continue,
+                Err(error) => panic!("Defragmentation pass error: {}", error),
+            }
+        }
+        let stats = allocator
+            .end_defragmentation(&mut defragmentation)
+            .expect("Defragmentation error");
+        println!(
+            "{} passes moved {} allocations ({} KiB) and freed {} blocks ({} KiB)",
+            passes,
+            stats.allocations_moved,
+            stats.bytes_moved / 1024,
+            stats.device_memory_blocks_freed,
+            stats.bytes_freed / 1024
+        );
+        print_statistics("compact", &allocator.get_pool_statistics(&pool));
+
+        for allocation in &kept {
+            allocator.free_memory(allocation);
+        }
+        allocator.destroy_pool(pool);
+    }
+
+    drop(allocator);
+}
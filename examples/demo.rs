@@ -1,6 +1,7 @@
-extern crate ash;
 extern crate vk_mem;
 
+use vk_mem::ash;
+
 /*
 use ash::extensions::DebugReport;
 use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
@@ -0,0 +1,121 @@
+//! Streams data into a device-local buffer through a `StagingManager` for a number of frames and
+//! prints how the staging capacity follows the upload volume, including the shrink after a run of
+//! idle frames.
+//!
+//! Runs headless: `cargo run --example staging`.
+
+extern crate vk_mem;
+
+use vk_mem::ash;
+
+mod common;
+
+use ash::vk;
+use vk_mem::staging::{StagingConfig, StagingManager};
+
+const BUFFER_SIZE: vk::DeviceSize = 16 * 1024 * 1024;
+
+fn main() {
+    let context = common::Context::new("vk-mem staging example");
+    let allocator = context
+        .create_allocator(vk_mem::AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_EXT_MEMORY_BUDGET_BIT);
+    let device = &context.device;
+
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &vk::BufferCreateInfo::builder().size(BUFFER_SIZE).usage(
+                    vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::STORAGE_BUFFER,
+                ),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            )
+            .expect("Buffer creation error");
+
+        let command_pool = device
+            .create_command_pool(
+                &vk::CommandPoolCreateInfo::builder()
+                    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                    .queue_family_index(context.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let command_buffer = device
+            .allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1),
+            )
+            .unwrap()[0];
+        let fence = device
+            .create_fence(&vk::FenceCreateInfo::default(), None)
+            .unwrap();
+
+        let config = StagingConfig {
+            min_chunk_size: 256 * 1024,
+            idle_frames_before_shrink: 8,
+            ..Default::default()
+        };
+        let mut staging = StagingManager::new(&allocator, config);
+
+        // A loading burst with growing uploads, followed by idle frames.
+        for frame in 0..24u32 {
+            let upload_size: vk::DeviceSize = if frame < 12 {
+                (frame as vk::DeviceSize + 1) * 512 * 1024
+            } else {
+                0
+            };
+
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::builder()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .unwrap();
+
+            if upload_size > 0 {
+                let region = staging.allocate(upload_size, 4).expect("Staging error");
+                std::ptr::write_bytes(region.mapped, frame as u8, region.size as usize);
+                staging.flush(&region).unwrap();
+
+                let copy = vk::BufferCopy {
+                    src_offset: region.offset,
+                    dst_offset: 0,
+                    size: upload_size.min(BUFFER_SIZE),
+                };
+                device.cmd_copy_buffer(command_buffer, region.buffer, buffer, &[copy]);
+            }
+
+            device.end_command_buffer(command_buffer).unwrap();
+            let command_buffers = [command_buffer];
+            let submit = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+            device
+                .queue_submit(context.queue, &[submit.build()], fence)
+                .unwrap();
+            device.wait_for_fences(&[fence], true, u64::MAX).unwrap();
+            device.reset_fences(&[fence]).unwrap();
+
+            // The device is done with this frame's regions, so they can be recycled.
+            staging.end_frame();
+            println!(
+                "frame {:2}: uploaded {:6} KiB, staging capacity {:6} KiB, target {:6} KiB | {}",
+                frame,
+                upload_size / 1024,
+                staging.capacity() / 1024,
+                staging.target_capacity() / 1024,
+                allocator.one_line_summary()
+            );
+        }
+
+        drop(staging);
+        device.destroy_fence(fence, None);
+        device.destroy_command_pool(command_pool, None);
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+
+    drop(allocator);
+}
@@ -0,0 +1,109 @@
+//! Sub-allocates a single persistently mapped buffer with a `VirtualBlock`, the way an engine packs
+//! many small uniform or vertex ranges into one `ash::vk::Buffer`.
+//!
+//! The virtual block only does the bookkeeping of offsets; the memory itself comes from one
+//! allocation made with the allocator.
+//!
+//! Runs headless: `cargo run --example virtual_block`.
+
+extern crate vk_mem;
+
+use vk_mem::ash;
+
+mod common;
+
+use ash::vk;
+
+const ARENA_SIZE: vk::DeviceSize = 1024 * 1024;
+
+fn main() {
+    let context = common::Context::new("vk-mem virtual block example");
+    let allocator = context.create_allocator(vk_mem::AllocatorCreateFlags::empty());
+
+    unsafe {
+        let (buffer, allocation, mut allocation_info) = allocator
+            .create_buffer(
+                &vk::BufferCreateInfo::builder()
+                    .size(ARENA_SIZE)
+                    .usage(vk::BufferUsageFlags::UNIFORM_BUFFER),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::Auto,
+                    flags: vk_mem::AllocationCreateFlags::MAPPED
+                        | vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+            )
+            .expect("Buffer creation error");
+        let mapped = allocation_info
+            .mapped_slice_mut()
+            .expect("Buffer is not mapped");
+
+        let mut arena = vk_mem::VirtualBlock::new(vk_mem::VirtualBlockCreateInfo {
+            size: ARENA_SIZE,
+            flags: vk_mem::VirtualBlockCreateFlags::NONE,
+            allocation_callbacks: None,
+        })
+        .expect("Virtual block creation error");
+
+        // Ranges of mixed sizes, as a set of materials with differently sized parameter blocks would need.
+        let mut ranges = Vec::new();
+        for index in 0..64u64 {
+            let size = 256 * (1 + index % 7);
+            let (range, offset) = arena
+                .allocate(size, 256, None, None)
+                .expect("Arena is full");
+            mapped[offset as usize..(offset + size) as usize].fill(index as u8);
+            ranges.push(range);
+        }
+        print_statistics("filled", &arena.get_statistics());
+
+        // Release every third range; the holes are reused by the next allocations.
+        let mut index = 0;
+        ranges.retain(|&range| {
+            index += 1;
+            if index % 3 == 0 {
+                arena.free(range);
+                false
+            } else {
+                true
+            }
+        });
+        print_statistics("freed", &arena.get_statistics());
+
+        let (range, offset) = arena
+            .allocate(
+                512,
+                256,
+                vk_mem::VirtualAllocationCreateFlags::STRATEGY_MIN_OFFSET,
+                None,
+            )
+            .expect("Arena is full");
+        let info = arena.get_virtual_allocation_info(range);
+        println!(
+            "reused a hole at offset {} for {} bytes (buffer {:?})",
+            offset, info.size, buffer
+        );
+        ranges.push(range);
+
+        allocator
+            .flush_allocation(&allocation, 0, ARENA_SIZE as usize)
+            .unwrap();
+
+        for range in ranges {
+            arena.free(range);
+        }
+        assert!(arena.is_empty());
+        arena.destroy();
+
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+
+    drop(allocator);
+}
+
+fn print_statistics(label: &str, statistics: &vk_mem::Statistics) {
+    println!(
+        "{:>6}: {} ranges, {} of {} bytes used",
+        label, statistics.allocation_count, statistics.allocation_bytes, statistics.block_bytes
+    );
+}
@@ -1,6 +1,7 @@
 /* automatically generated by rust-bindgen 0.59.2 */
 
 use ash::vk::*;
+use crate::ash_compat::StaticAllocationCallbacks as AllocationCallbacks;
 
 #[doc = " See #VmaAllocatorCreateFlagBits."]
 pub type VmaAllocatorCreateFlags = Flags;
@@ -204,6 +205,9 @@ pub struct VmaVulkanFunctions {
     pub vkGetDeviceBufferMemoryRequirements: PFN_vkGetDeviceBufferMemoryRequirements,
     #[doc = " Fetch from \"vkGetDeviceImageMemoryRequirements\" on Vulkan >= 1.3, but you can also fetch it from \"vkGetDeviceImageMemoryRequirementsKHR\" if you enabled extension VK_KHR_maintenance4."]
     pub vkGetDeviceImageMemoryRequirements: PFN_vkGetDeviceImageMemoryRequirements,
+    #[doc = " Fetch from \"vkGetMemoryWin32HandleKHR\" when using VK_KHR_external_memory_win32 extension. Optional, can be null."]
+    #[cfg(windows)]
+    pub vkGetMemoryWin32HandleKHR: Option<PFN_vkGetMemoryWin32HandleKHR>,
 }
 #[doc = " Description of a Allocator to be created."]
 #[repr(C)]
@@ -988,6 +992,36 @@ extern "C" {
         pAllocationInfo: *mut VmaAllocationInfo,
     );
 }
+#[doc = " Extended parameters of a #VmaAllocation object that can be retrieved using function vmaGetAllocationInfo2()."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VmaAllocationInfo2 {
+    #[doc = " \\brief Basic parameters of the allocation."]
+    #[doc = ""]
+    #[doc = "If you need only these, you can use function vmaGetAllocationInfo() and structure #VmaAllocationInfo instead."]
+    pub allocationInfo: VmaAllocationInfo,
+    #[doc = " \\brief Size of the `VkDeviceMemory` block that the allocation belongs to."]
+    #[doc = ""]
+    #[doc = "In case of an allocation with dedicated memory, it will be equal to `allocationInfo.size`."]
+    pub blockSize: DeviceSize,
+    #[doc = " \\brief `VK_TRUE` if the allocation has dedicated memory, `VK_FALSE` if it was placed as part of a larger memory block."]
+    #[doc = ""]
+    #[doc = "When `VK_TRUE`, it also means `VkMemoryDedicatedAllocateInfo` was used when creating the allocation"]
+    #[doc = "(if VK_KHR_dedicated_allocation extension or Vulkan version >= 1.1 is enabled)."]
+    pub dedicatedMemory: Bool32,
+}
+extern "C" {
+    #[doc = " \\brief Returns extended information about specified allocation."]
+    #[doc = ""]
+    #[doc = "Current parameters of given allocation are returned in `pAllocationInfo`."]
+    #[doc = "Extended parameters in structure #VmaAllocationInfo2 include memory block size"]
+    #[doc = "and a flag telling whether the allocation has dedicated memory."]
+    pub fn vmaGetAllocationInfo2(
+        allocator: VmaAllocator,
+        allocation: VmaAllocation,
+        pAllocationInfo: *mut VmaAllocationInfo2,
+    );
+}
 extern "C" {
     #[doc = " \\brief Sets pUserData in given allocation to new value."]
     #[doc = ""]
@@ -1178,6 +1212,86 @@ extern "C" {
         sizes: *const DeviceSize,
     ) -> Result;
 }
+extern "C" {
+    #[doc = " \\brief Maps the allocation temporarily if needed, copies data from specified host pointer to it, and flushes the memory from the host caches if needed."]
+    #[doc = ""]
+    #[doc = "\\param allocator"]
+    #[doc = "\\param pSrcHostPointer Pointer to the host data that become source of the copy."]
+    #[doc = "\\param dstAllocation   Handle to the allocation that becomes destination of the copy."]
+    #[doc = "\\param dstAllocationLocalOffset  Offset within `dstAllocation` where to write copied data, in bytes."]
+    #[doc = "\\param size            Number of bytes to copy."]
+    #[doc = ""]
+    #[doc = "This is a convenience function that allows to copy data from a host pointer to an allocation easily."]
+    #[doc = "Same behavior can be achieved by calling vmaMapMemory(), `memcpy()`, vmaUnmapMemory(), vmaFlushAllocation()."]
+    #[doc = ""]
+    #[doc = "This function can be called only for allocations created in a memory type that has `VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT` flag."]
+    #[doc = "It can be ensured e.g. by using #VMA_MEMORY_USAGE_AUTO and #VMA_ALLOCATION_CREATE_HOST_ACCESS_SEQUENTIAL_WRITE_BIT or"]
+    #[doc = "#VMA_ALLOCATION_CREATE_HOST_ACCESS_RANDOM_BIT."]
+    #[doc = "Otherwise, the function will fail and generate a Validation Layers error."]
+    #[doc = ""]
+    #[doc = "`dstAllocationLocalOffset` is relative to the contents of given `dstAllocation`."]
+    #[doc = "If you mean whole allocation, you should pass 0."]
+    #[doc = "Do not pass allocation's offset within device memory block this parameter!"]
+    pub fn vmaCopyMemoryToAllocation(
+        allocator: VmaAllocator,
+        pSrcHostPointer: *const ::std::os::raw::c_void,
+        dstAllocation: VmaAllocation,
+        dstAllocationLocalOffset: DeviceSize,
+        size: DeviceSize,
+    ) -> Result;
+}
+extern "C" {
+    #[doc = " \\brief Invalidates memory from the host caches if needed, maps the allocation temporarily if needed, and copies data from it to a specified host pointer."]
+    #[doc = ""]
+    #[doc = "\\param allocator"]
+    #[doc = "\\param srcAllocation   Handle to the allocation that becomes source of the copy."]
+    #[doc = "\\param srcAllocationLocalOffset  Offset within `srcAllocation` where to read copied data, in bytes."]
+    #[doc = "\\param pDstHostPointer Pointer to the host memory that become destination of the copy."]
+    #[doc = "\\param size            Number of bytes to copy."]
+    #[doc = ""]
+    #[doc = "This is a convenience function that allows to copy data from an allocation to a host pointer easily."]
+    #[doc = "Same behavior can be achieved by calling vmaInvalidateAllocation(), vmaMapMemory(), `memcpy()`, vmaUnmapMemory()."]
+    #[doc = ""]
+    #[doc = "This function should be called only for allocations created in a memory type that has `VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT`"]
+    #[doc = "and `VK_MEMORY_PROPERTY_HOST_CACHED_BIT` flag."]
+    #[doc = "It can be ensured e.g. by using #VMA_MEMORY_USAGE_AUTO and #VMA_ALLOCATION_CREATE_HOST_ACCESS_RANDOM_BIT."]
+    #[doc = "Otherwise, the function may fail and generate a Validation Layers error."]
+    #[doc = "It may also work very slowly when reading from an uncached memory."]
+    #[doc = ""]
+    #[doc = "`srcAllocationLocalOffset` is relative to the contents of given `srcAllocation`."]
+    #[doc = "If you mean whole allocation, you should pass 0."]
+    #[doc = "Do not pass allocation's offset within device memory block as this parameter!"]
+    pub fn vmaCopyAllocationToMemory(
+        allocator: VmaAllocator,
+        srcAllocation: VmaAllocation,
+        srcAllocationLocalOffset: DeviceSize,
+        pDstHostPointer: *mut ::std::os::raw::c_void,
+        size: DeviceSize,
+    ) -> Result;
+}
+#[cfg(windows)]
+extern "C" {
+    #[doc = "\\brief Given an allocation, returns Win32 handle that may be imported by other processes or APIs."]
+    #[doc = ""]
+    #[doc = "\\param allocator The allocator."]
+    #[doc = "\\param allocation The allocation to get the handle for."]
+    #[doc = "\\param hTargetProcess Handle to the target process. Can be null, which means the current process."]
+    #[doc = "\\param[out] pHandle Output parameter that receives the handle."]
+    #[doc = ""]
+    #[doc = "The function fills `pHandle` with a handle that can be used in the target process."]
+    #[doc = "The handle is fetched using function `vkGetMemoryWin32HandleKHR` once and cached internally,"]
+    #[doc = "then duplicated with `DuplicateHandle()` on every call, so the caller must close the returned handle"]
+    #[doc = "with `CloseHandle()` when it is no longer needed."]
+    #[doc = ""]
+    #[doc = "The allocator must have been created with #VMA_ALLOCATOR_CREATE_KHR_EXTERNAL_MEMORY_WIN32_BIT and"]
+    #[doc = "the memory must have been allocated with `VK_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_WIN32_BIT` export info."]
+    pub fn vmaGetMemoryWin32Handle(
+        allocator: VmaAllocator,
+        allocation: VmaAllocation,
+        hTargetProcess: HANDLE,
+        pHandle: *mut HANDLE,
+    ) -> Result;
+}
 extern "C" {
     #[doc = " \\brief Checks magic number in margins around all allocations in given memory types (in both default and custom pools) in search for corruptions."]
     #[doc = ""]
@@ -0,0 +1,208 @@
+//! Detection of logical misuse of aliased memory.
+//!
+//! Resources created with `AllocationCreateFlags::CAN_ALIAS`, `Allocator::create_aliasing_buffer`,
+//! `Allocator::create_aliasing_image` or bound with `Allocator::bind_buffer_memory2` and
+//! `Allocator::bind_image_memory2` may share memory. That is only correct as long as resources
+//! whose regions overlap are never used at the same time, which the validation layers don't check.
+//!
+//! `AliasingValidator` is a debugging aid the application feeds with the bindings of its aliased
+//! resources and with the resources each pass (render pass, compute dispatch group, or any other
+//! unit the application considers simultaneous) uses. Whenever a pass uses two resources whose
+//! regions of the same allocation overlap, an `AliasingHazard` is logged and recorded.
+
+use crate::Allocation;
+use ash::vk;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// A resource bound to aliased memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AliasedResource {
+    Buffer(vk::Buffer),
+    Image(vk::Image),
+}
+
+/// Two resources with overlapping memory used in the same pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasingHazard {
+    /// Name of the pass, as passed to `AliasingValidator::begin_pass`.
+    pub pass: String,
+
+    /// The resource used first in the pass.
+    pub first: AliasedResource,
+
+    /// The resource whose use revealed the hazard.
+    pub second: AliasedResource,
+
+    /// Allocation the two resources share.
+    pub allocation: Allocation,
+
+    /// Overlapping bytes, relative to the start of the allocation.
+    pub overlap: Range<vk::DeviceSize>,
+}
+
+impl fmt::Display for AliasingHazard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pass `{}` uses {:?} and {:?}, which overlap in bytes {}..{} of allocation {:p}",
+            self.pass,
+            self.first,
+            self.second,
+            self.overlap.start,
+            self.overlap.end,
+            self.allocation
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    allocation: Allocation,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+}
+
+impl Binding {
+    fn overlap(&self, other: &Binding) -> Option<Range<vk::DeviceSize>> {
+        if self.allocation != other.allocation {
+            return None;
+        }
+        let start = self.offset.max(other.offset);
+        let end = (self.offset + self.size).min(other.offset + other.size);
+        (start < end).then_some(start..end)
+    }
+}
+
+#[derive(Debug)]
+struct Pass {
+    name: String,
+    used: Vec<AliasedResource>,
+}
+
+/// Records which regions of which allocations live resources are bound to, and flags overlapping
+/// resources used in the same pass.
+#[derive(Debug, Default)]
+pub struct AliasingValidator {
+    bindings: HashMap<AliasedResource, Binding>,
+    pass: Option<Pass>,
+    hazards: Vec<AliasingHazard>,
+}
+
+impl AliasingValidator {
+    /// Creates a validator without bindings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `resource` is bound to `size` bytes of `allocation` starting at `offset`, both
+    /// relative to the start of the allocation. Binding a resource again replaces its binding.
+    pub fn bind(
+        &mut self,
+        resource: AliasedResource,
+        allocation: &Allocation,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) {
+        self.bindings.insert(
+            resource,
+            Binding {
+                allocation: *allocation,
+                offset,
+                size,
+            },
+        );
+    }
+
+    /// Forgets `resource`, e.g. when it is destroyed.
+    pub fn unbind(&mut self, resource: AliasedResource) {
+        self.bindings.remove(&resource);
+    }
+
+    /// Forgets every resource bound to `allocation`, e.g. when it is freed.
+    pub fn unbind_allocation(&mut self, allocation: &Allocation) {
+        self.bindings
+            .retain(|_, binding| binding.allocation != *allocation);
+    }
+
+    /// Resources currently bound to memory overlapping with `resource`.
+    pub fn aliases_of(&self, resource: AliasedResource) -> Vec<AliasedResource> {
+        let binding = match self.bindings.get(&resource) {
+            Some(binding) => binding,
+            None => return Vec::new(),
+        };
+        self.bindings
+            .iter()
+            .filter(|(&other, other_binding)| {
+                other != resource && binding.overlap(other_binding).is_some()
+            })
+            .map(|(&other, _)| other)
+            .collect()
+    }
+
+    /// Starts a pass, ending the previous one if it is still open.
+    pub fn begin_pass(&mut self, name: &str) {
+        self.pass = Some(Pass {
+            name: name.to_owned(),
+            used: Vec::new(),
+        });
+    }
+
+    /// Records that the current pass uses `resource`, returning the hazards this causes. Each
+    /// hazard is also logged with `log::error!` and kept for `AliasingValidator::hazards`.
+    ///
+    /// Uses outside of a pass and of resources that were not bound are ignored.
+    pub fn use_resource(&mut self, resource: AliasedResource) -> Vec<AliasingHazard> {
+        let pass = match self.pass.as_mut() {
+            Some(pass) => pass,
+            None => return Vec::new(),
+        };
+        let binding = match self.bindings.get(&resource) {
+            Some(binding) => binding,
+            None => return Vec::new(),
+        };
+        if pass.used.contains(&resource) {
+            return Vec::new();
+        }
+
+        let mut found = Vec::new();
+        for &first in &pass.used {
+            let overlap = match self
+                .bindings
+                .get(&first)
+                .and_then(|first_binding| first_binding.overlap(binding))
+            {
+                Some(overlap) => overlap,
+                None => continue,
+            };
+            let hazard = AliasingHazard {
+                pass: pass.name.clone(),
+                first,
+                second: resource,
+                allocation: binding.allocation,
+                overlap,
+            };
+            log::error!("Aliasing hazard: {}", hazard);
+            found.push(hazard);
+        }
+        pass.used.push(resource);
+        self.hazards.extend(found.iter().cloned());
+        found
+    }
+
+    /// Ends the current pass.
+    pub fn end_pass(&mut self) {
+        self.pass = None;
+    }
+
+    /// All hazards found so far.
+    pub fn hazards(&self) -> &[AliasingHazard] {
+        &self.hazards
+    }
+
+    /// Forgets the hazards found so far.
+    pub fn clear_hazards(&mut self) {
+        self.hazards.clear();
+    }
+}
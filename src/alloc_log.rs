@@ -0,0 +1,377 @@
+//! Structured allocation event log, enabled with the `alloc_log` feature.
+//!
+//! `Allocator::set_allocation_log` hands the allocator a writer to which it appends one JSON object
+//! per line (JSON Lines) for every allocation, free, map, name change and defragmentation move.
+//! The log is meant for offline analysis, e.g. loading it into a script to find churn, or diffing
+//! the logs of two runs to see where their memory use diverges.
+//!
+//! Every line has these members:
+//!
+//! - `event`: `"allocate"`, `"free"`, `"map"`, `"name"` or `"defrag_move"`.
+//! - `sequence`: number of the event, starting at 0 when the writer was set.
+//! - `timestamp_us`: microseconds since the Unix epoch.
+//! - `allocation`: address of the allocation handle, only meaningful within one run.
+//! - `size`: size of the allocation in bytes.
+//! - `memory_type`: index of the memory type of the allocation.
+//! - `memory_heap`: index of the heap of that memory type.
+//! - `pool`: name of its custom pool, the pool handle if the pool is unnamed, or `null` for the
+//!   default pools.
+//! - `name`: name set with `Allocator::set_allocation_name`, or `null`.
+//!
+//! `allocate` events also have `operation`, the function that made the allocation, and
+//! `defrag_move` events have `dst_memory_type` and `dst_memory_heap`, where the allocation moves to.
+//! Names are usually set after an allocation has been made, so `allocate` events of named
+//! resources have no name yet; the `name` event that follows carries it.
+//!
+//! `write_chrome_trace` turns a log into a trace for `chrome://tracing` or Perfetto.
+//!
+//! Writes happen under a lock while the allocator call is in progress, so the writer should be
+//! buffered. If it fails, the error is logged and the log is turned off.
+
+use crate::json::Value;
+use crate::{Allocation, AllocatorPool};
+use ash::vk;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone)]
+struct Record {
+    size: vk::DeviceSize,
+    memory_type: u32,
+    memory_heap: u32,
+    pool: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Default)]
+struct State {
+    writer: Option<Box<dyn Write + Send>>,
+    next_sequence: u64,
+    allocations: HashMap<usize, Record>,
+}
+
+/// Writer and live allocations of the event log, shared between clones of an `Allocator`.
+#[derive(Default)]
+pub(crate) struct AllocationLog {
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for AllocationLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AllocationLog")
+            .field("enabled", &self.state().writer.is_some())
+            .finish()
+    }
+}
+
+impl AllocationLog {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Replaces the writer, flushing the previous one.
+    pub(crate) fn set_writer(&self, writer: Option<Box<dyn Write + Send>>) {
+        let mut state = self.state();
+        if let Some(mut previous) = std::mem::replace(&mut state.writer, writer) {
+            if let Err(error) = previous.flush() {
+                log::warn!("Flushing the allocation log failed: {}", error);
+            }
+        }
+        state.next_sequence = 0;
+    }
+
+    pub(crate) fn on_create(
+        &self,
+        operation: &'static str,
+        allocation: &Allocation,
+        size: vk::DeviceSize,
+        memory_type: u32,
+        memory_heap: u32,
+        pool: Option<String>,
+    ) {
+        let record = Record {
+            size,
+            memory_type,
+            memory_heap,
+            pool,
+            name: None,
+        };
+        let mut state = self.state();
+        state.write("allocate", allocation, &record, |line| {
+            let _ = write!(line, ",\"operation\":");
+            write_string(line, operation);
+        });
+        state.allocations.insert(*allocation as usize, record);
+    }
+
+    pub(crate) fn on_free(&self, allocation: &Allocation) {
+        let mut state = self.state();
+        if let Some(record) = state.allocations.remove(&(*allocation as usize)) {
+            state.write("free", allocation, &record, |_| {});
+        }
+    }
+
+    pub(crate) fn on_map(&self, allocation: &Allocation) {
+        let mut state = self.state();
+        if let Some(record) = state.allocations.get(&(*allocation as usize)).cloned() {
+            state.write("map", allocation, &record, |_| {});
+        }
+    }
+
+    pub(crate) fn on_name(&self, allocation: &Allocation, name: &str) {
+        let mut state = self.state();
+        if let Some(record) = state.allocations.get_mut(&(*allocation as usize)) {
+            record.name = Some(name.to_owned());
+            let record = record.clone();
+            state.write("name", allocation, &record, |_| {});
+        }
+    }
+
+    pub(crate) fn on_move(
+        &self,
+        allocation: &Allocation,
+        dst_memory_type: u32,
+        dst_memory_heap: u32,
+    ) {
+        let mut state = self.state();
+        if let Some(record) = state.allocations.get_mut(&(*allocation as usize)) {
+            let src = record.clone();
+            record.memory_type = dst_memory_type;
+            record.memory_heap = dst_memory_heap;
+            state.write("defrag_move", allocation, &src, |line| {
+                let _ = write!(
+                    line,
+                    ",\"dst_memory_type\":{},\"dst_memory_heap\":{}",
+                    dst_memory_type, dst_memory_heap
+                );
+            });
+        }
+    }
+}
+
+impl State {
+    /// Writes one event line, if a writer is set.
+    fn write(
+        &mut self,
+        event: &str,
+        allocation: &Allocation,
+        record: &Record,
+        extra: impl FnOnce(&mut String),
+    ) {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => return,
+        };
+        let timestamp_us = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_micros());
+
+        let mut line = String::with_capacity(192);
+        let _ = write!(
+            line,
+            "{{\"event\":\"{}\",\"sequence\":{},\"timestamp_us\":{},\"allocation\":\"{:p}\",\
+             \"size\":{},\"memory_type\":{},\"memory_heap\":{},\"pool\":",
+            event,
+            self.next_sequence,
+            timestamp_us,
+            *allocation,
+            record.size,
+            record.memory_type,
+            record.memory_heap
+        );
+        write_optional_string(&mut line, record.pool.as_deref());
+        line.push_str(",\"name\":");
+        write_optional_string(&mut line, record.name.as_deref());
+        extra(&mut line);
+        line.push_str("}\n");
+        self.next_sequence += 1;
+
+        if let Err(error) = writer.write_all(line.as_bytes()) {
+            log::warn!(
+                "Writing the allocation log failed, turning it off: {}",
+                error
+            );
+            self.writer = None;
+        }
+    }
+}
+
+/// An allocation of a log being converted by `write_chrome_trace`, from its creation or last move
+/// to another heap.
+struct Span {
+    id: u64,
+    start_us: u64,
+    size: u64,
+    memory_type: u64,
+    memory_heap: u64,
+    pool: Option<String>,
+    name: Option<String>,
+    operation: String,
+}
+
+impl Span {
+    /// Appends the begin and end events of the span to `events`.
+    fn finish(&self, end_us: u64, freed: bool, events: &mut Vec<String>) {
+        let mut track = String::new();
+        write_string(&mut track, self.pool.as_deref().unwrap_or("default pools"));
+        let mut begin = format!(
+            "{{\"name\":{},\"cat\":\"allocation\",\"ph\":\"b\",\"id\":\"0x{:x}\",\"pid\":{},\
+             \"tid\":0,\"ts\":{},\"args\":{{\"size\":{},\"memory_type\":{},\"operation\":",
+            track, self.id, self.memory_heap, self.start_us, self.size, self.memory_type
+        );
+        write_string(&mut begin, &self.operation);
+        begin.push_str(",\"name\":");
+        write_optional_string(&mut begin, self.name.as_deref());
+        begin.push_str("}}");
+        events.push(begin);
+        events.push(format!(
+            "{{\"name\":{},\"cat\":\"allocation\",\"ph\":\"e\",\"id\":\"0x{:x}\",\"pid\":{},\
+             \"tid\":0,\"ts\":{},\"args\":{{\"freed\":{}}}}}",
+            track, self.id, self.memory_heap, end_us, freed
+        ));
+    }
+}
+
+/// Converts a log written by `Allocator::set_allocation_log` to the Trace Event Format read by
+/// `chrome://tracing` and Perfetto.
+///
+/// Every allocation becomes an async event spanning from its creation to its free, with its size,
+/// memory type, name and the function that created it as arguments. Each memory heap is shown as
+/// a process, and the allocations of each pool are grouped in a track of their own within it.
+/// Timestamps start at 0 with the first event of the log.
+///
+/// Allocations that are not freed in the log end at its last event and have `freed` set to
+/// `false`. An allocation moved to another heap by defragmentation is split into two events.
+///
+/// Fails with `std::io::ErrorKind::InvalidData` on a line that isn't an event of the log.
+pub fn write_chrome_trace(log: impl BufRead, mut trace: impl Write) -> std::io::Result<()> {
+    let mut spans: HashMap<String, Span> = HashMap::new();
+    let mut events = Vec::new();
+    let mut heaps = BTreeSet::new();
+    let mut next_id = 0;
+    let mut first_us = None;
+    let mut last_us = 0;
+
+    for (index, line) in log.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line {} is not an allocation log event", index + 1),
+            )
+        };
+        let event = Value::parse(&line).ok_or_else(invalid)?;
+        let number = |key: &str| event.get(key).and_then(Value::as_u64).ok_or_else(invalid);
+        let text = |key: &str| event.get(key).and_then(Value::as_str).map(str::to_owned);
+        let kind = text("event").ok_or_else(invalid)?;
+        let allocation = text("allocation").ok_or_else(invalid)?;
+        let absolute_us = number("timestamp_us")?;
+        let timestamp_us = absolute_us.saturating_sub(*first_us.get_or_insert(absolute_us));
+        last_us = last_us.max(timestamp_us);
+
+        match kind.as_str() {
+            "allocate" => {
+                let span = Span {
+                    id: next_id,
+                    start_us: timestamp_us,
+                    size: number("size")?,
+                    memory_type: number("memory_type")?,
+                    memory_heap: number("memory_heap")?,
+                    pool: text("pool"),
+                    name: text("name"),
+                    operation: text("operation").unwrap_or_default(),
+                };
+                next_id += 1;
+                heaps.insert(span.memory_heap);
+                spans.insert(allocation, span);
+            }
+            "name" => {
+                if let Some(span) = spans.get_mut(&allocation) {
+                    span.name = text("name");
+                }
+            }
+            "defrag_move" => {
+                let dst_memory_heap = number("dst_memory_heap")?;
+                if let Some(span) = spans.get_mut(&allocation) {
+                    span.memory_type = number("dst_memory_type")?;
+                    if span.memory_heap != dst_memory_heap {
+                        span.finish(timestamp_us, true, &mut events);
+                        span.id = next_id;
+                        next_id += 1;
+                        span.start_us = timestamp_us;
+                        span.memory_heap = dst_memory_heap;
+                        heaps.insert(dst_memory_heap);
+                    }
+                }
+            }
+            "free" => {
+                if let Some(span) = spans.remove(&allocation) {
+                    span.finish(timestamp_us, true, &mut events);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut live: Vec<Span> = spans.into_values().collect();
+    live.sort_by_key(|span| span.id);
+    for span in live {
+        span.finish(last_us, false, &mut events);
+    }
+    for heap in heaps {
+        events.push(format!(
+            "{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{},\"args\":{{\"name\":\"Heap {}\"}}}}",
+            heap, heap
+        ));
+    }
+
+    writeln!(trace, "{{\"traceEvents\":[")?;
+    for (index, event) in events.iter().enumerate() {
+        let separator = if index + 1 < events.len() { "," } else { "" };
+        writeln!(trace, "{}{}", event, separator)?;
+    }
+    writeln!(trace, "]}}")?;
+    trace.flush()
+}
+
+/// How a custom pool is identified in the log: its name, or its handle if it has none.
+pub(crate) fn pool_label(pool: &AllocatorPool, name: &str) -> String {
+    if name.is_empty() {
+        format!("{:p}", *pool)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn write_optional_string(line: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => write_string(line, value),
+        None => line.push_str("null"),
+    }
+}
+
+fn write_string(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(line, "\\u{:04x}", c as u32);
+            }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
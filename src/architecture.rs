@@ -0,0 +1,152 @@
+//! Classification of the memory of a device, and the upload strategy that suits it.
+//!
+//! On a discrete GPU the fastest memory for the device is usually not visible to the host, so
+//! data is written to a staging buffer and copied on the device. Integrated GPUs and unified
+//! memory architectures like Apple silicon share one physical memory between host and device:
+//! memory that is both device local and host visible is just as fast for the device, and
+//! writing resources through a persistent mapping saves the copy and the staging memory.
+//!
+//! Discrete GPUs with resizable BAR expose all or most of their memory as host visible too.
+//! Writes to it cross the bus, so they should be sequential, and the space is shared with
+//! everything else placed there, so `UploadStrategy::MappedOrStaging` lets VMA fall back to memory
+//! that isn't host visible, after which the upload goes through a staging buffer as usual.
+
+use crate::{Allocation, AllocationCreateFlags, AllocationCreateInfo, Allocator, MemoryUsage};
+use ash::vk;
+
+/// Size above which a device local heap with host visible memory types is considered a resizable
+/// BAR rather than the classic 256 MiB window.
+pub const REBAR_MIN_HEAP_SIZE: vk::DeviceSize = 256 * 1024 * 1024;
+
+/// Index of the largest device local heap larger than `REBAR_MIN_HEAP_SIZE` that has a memory type
+/// that is both device local and host visible.
+pub(crate) fn rebar_heap_index(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+) -> Option<u32> {
+    let types = &memory_properties.memory_types[..memory_properties.memory_type_count as usize];
+    types
+        .iter()
+        .filter(|memory_type| {
+            memory_type.property_flags.contains(
+                vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )
+        })
+        .map(|memory_type| memory_type.heap_index)
+        .filter(|&heap_index| {
+            let heap = &memory_properties.memory_heaps[heap_index as usize];
+            heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+                && heap.size > REBAR_MIN_HEAP_SIZE
+        })
+        .max_by_key(|&heap_index| memory_properties.memory_heaps[heap_index as usize].size)
+}
+
+/// How host and device memory relate on a device, as returned by
+/// `Allocator::memory_architecture`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryArchitecture {
+    /// Separate device memory, with at least one heap that isn't device local.
+    Discrete,
+
+    /// Integrated GPU that reports host memory in a separate heap.
+    Integrated,
+
+    /// All heaps are device local and some device local memory is host visible.
+    Uma,
+}
+
+impl MemoryArchitecture {
+    pub(crate) fn classify(
+        properties: &vk::PhysicalDeviceProperties,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    ) -> Self {
+        let heaps = &memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize];
+        let types = &memory_properties.memory_types[..memory_properties.memory_type_count as usize];
+        let all_heaps_device_local = heaps
+            .iter()
+            .all(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL));
+        let device_local_host_visible = types.iter().any(|memory_type| {
+            memory_type.property_flags.contains(
+                vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )
+        });
+
+        if all_heaps_device_local && device_local_host_visible {
+            MemoryArchitecture::Uma
+        } else if properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU
+            || properties.device_type == vk::PhysicalDeviceType::CPU
+        {
+            MemoryArchitecture::Integrated
+        } else {
+            MemoryArchitecture::Discrete
+        }
+    }
+
+    /// Whether host and device share physical memory.
+    pub fn is_unified(self) -> bool {
+        self != MemoryArchitecture::Discrete
+    }
+}
+
+/// How to fill a resource with data from the host, as suggested by
+/// `Allocator::suggest_upload_strategy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UploadStrategy {
+    /// Create the resource in device local, host visible memory and write it through a persistent
+    /// mapping.
+    Mapped,
+
+    /// Create the resource in device local memory and copy into it from a staging buffer.
+    Staging,
+
+    /// Create the resource preferably in device local, host visible memory, and find out with
+    /// `UploadStrategy::resolve` whether it got it or needs a staging copy after all.
+    MappedOrStaging,
+}
+
+impl UploadStrategy {
+    /// Allocation parameters for the resource itself.
+    ///
+    /// For `UploadStrategy::Mapped` the allocation is persistently mapped; for the other
+    /// strategies the resource also needs `TRANSFER_DST` in its usage.
+    pub fn allocation_create_info(self) -> AllocationCreateInfo {
+        match self {
+            UploadStrategy::Mapped => AllocationCreateInfo {
+                flags: AllocationCreateFlags::MAPPED
+                    | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+                usage: MemoryUsage::AutoPreferDevice,
+                required_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL
+                    | vk::MemoryPropertyFlags::HOST_VISIBLE,
+                ..Default::default()
+            },
+            UploadStrategy::Staging => AllocationCreateInfo {
+                usage: MemoryUsage::AutoPreferDevice,
+                ..Default::default()
+            },
+            UploadStrategy::MappedOrStaging => AllocationCreateInfo {
+                flags: AllocationCreateFlags::MAPPED
+                    | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE
+                    | AllocationCreateFlags::HOST_ACCESS_ALLOW_TRANSFER_INSTEAD,
+                usage: MemoryUsage::AutoPreferDevice,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The strategy to use for `allocation`, made with `UploadStrategy::allocation_create_info`:
+    /// `UploadStrategy::Mapped` if its memory is host visible, `UploadStrategy::Staging` if not.
+    pub fn resolve(self, allocator: &Allocator, allocation: &Allocation) -> UploadStrategy {
+        match self {
+            UploadStrategy::MappedOrStaging => {
+                if allocator
+                    .get_allocation_memory_properties(allocation)
+                    .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+                {
+                    UploadStrategy::Mapped
+                } else {
+                    UploadStrategy::Staging
+                }
+            }
+            strategy => strategy,
+        }
+    }
+}
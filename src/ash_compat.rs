@@ -0,0 +1,151 @@
+//! Differences between the supported versions of ash, selected with the `ash_0_36`, `ash_0_37`
+//! and `ash_0_38` features.
+//!
+//! ash 0.38 gave the Vulkan structs a lifetime parameter, removed their `builder()`/`build()`
+//! pair in favor of setters on the structs themselves, and moved the extension function tables
+//! out of `vk`. The crate is written against the older API; this module fills the gaps:
+//!
+//! - `Builder` brings `builder()` and `build()` back on the structs the crate sets up, as
+//!   `Default::default()` and a no-op. It only exists, and is only imported, with `ash_0_38`.
+//! - The struct aliases take a lifetime with every version, so types storing Vulkan structs can
+//!   name them the same way. Stored structs use `'static`: they only hold raw pointers.
+//! - The function table aliases keep the `vk::*Fn` names of the older versions.
+
+use ash::vk;
+
+/// `builder()` and `build()` of ash 0.37 and older, for the structs of ash 0.38.
+#[cfg(feature = "ash_0_38")]
+pub(crate) trait Builder: Default {
+    fn builder() -> Self {
+        Self::default()
+    }
+
+    fn build(self) -> Self {
+        self
+    }
+}
+
+#[cfg(feature = "ash_0_38")]
+macro_rules! builders {
+    ($($name:ident),* $(,)?) => {
+        $(impl Builder for vk::$name<'_> {})*
+    };
+}
+
+#[cfg(feature = "ash_0_38")]
+builders!(
+    BufferCreateInfo,
+    BufferDeviceAddressInfo,
+    BufferMemoryBarrier,
+    CommandBufferAllocateInfo,
+    CommandBufferBeginInfo,
+    CommandPoolCreateInfo,
+    ExportMemoryAllocateInfo,
+    ImageMemoryBarrier,
+    ImportMemoryFdInfoKHR,
+    ImportMemoryHostPointerInfoEXT,
+    ImportMemoryWin32HandleInfoKHR,
+    MemoryAllocateInfo,
+    MemoryBarrier,
+    MemoryGetAndroidHardwareBufferInfoANDROID,
+    MemoryGetFdInfoKHR,
+    PhysicalDeviceProperties2,
+    SemaphoreCreateInfo,
+    SemaphoreTypeCreateInfo,
+    SemaphoreWaitInfo,
+    SparseBufferMemoryBindInfo,
+    SparseImageMemoryBindInfo,
+    SubmitInfo,
+    TimelineSemaphoreSubmitInfo,
+);
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type AllocationCallbacks<'a> = vk::AllocationCallbacks<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type AllocationCallbacks<'a> = vk::AllocationCallbacks;
+
+/// `AllocationCallbacks` as named by the generated bindings, see `generate_bindings` in build.rs.
+pub(crate) type StaticAllocationCallbacks = AllocationCallbacks<'static>;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type BufferCreateInfo<'a> = vk::BufferCreateInfo<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type BufferCreateInfo<'a> = vk::BufferCreateInfo;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type ImageCreateInfo<'a> = vk::ImageCreateInfo<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type ImageCreateInfo<'a> = vk::ImageCreateInfo;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type BufferMemoryBarrier<'a> = vk::BufferMemoryBarrier<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type BufferMemoryBarrier<'a> = vk::BufferMemoryBarrier;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type ImageMemoryBarrier<'a> = vk::ImageMemoryBarrier<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type ImageMemoryBarrier<'a> = vk::ImageMemoryBarrier;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type ExportMemoryAllocateInfo<'a> = vk::ExportMemoryAllocateInfo<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type ExportMemoryAllocateInfo<'a> = vk::ExportMemoryAllocateInfo;
+
+#[cfg(all(feature = "cuda_interop", feature = "ash_0_38"))]
+pub(crate) type ExternalMemoryBufferCreateInfo<'a> = vk::ExternalMemoryBufferCreateInfo<'a>;
+#[cfg(all(feature = "cuda_interop", not(feature = "ash_0_38")))]
+pub(crate) type ExternalMemoryBufferCreateInfo<'a> = vk::ExternalMemoryBufferCreateInfo;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type ImportMemoryWin32HandleInfoKHR<'a> = vk::ImportMemoryWin32HandleInfoKHR<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type ImportMemoryWin32HandleInfoKHR<'a> = vk::ImportMemoryWin32HandleInfoKHR;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type PhysicalDeviceDescriptorBufferPropertiesEXT<'a> =
+    vk::PhysicalDeviceDescriptorBufferPropertiesEXT<'a>;
+#[cfg(all(feature = "ash_0_37", not(feature = "ash_0_38")))]
+pub(crate) type PhysicalDeviceDescriptorBufferPropertiesEXT<'a> =
+    vk::PhysicalDeviceDescriptorBufferPropertiesEXT;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type SparseBufferMemoryBindInfo<'a> = vk::SparseBufferMemoryBindInfo<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type SparseBufferMemoryBindInfo<'a> = vk::SparseBufferMemoryBindInfo;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) type SparseImageMemoryBindInfo<'a> = vk::SparseImageMemoryBindInfo<'a>;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) type SparseImageMemoryBindInfo<'a> = vk::SparseImageMemoryBindInfo;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) use ash::StaticFn;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) use vk::StaticFn;
+
+#[cfg(feature = "ash_0_38")]
+pub(crate) use ash::khr::maintenance4::DeviceFn as KhrMaintenance4Fn;
+#[cfg(not(feature = "ash_0_38"))]
+pub(crate) use vk::KhrMaintenance4Fn;
+
+#[cfg(all(windows, feature = "ash_0_38"))]
+pub(crate) use ash::khr::external_memory_win32::DeviceFn as KhrExternalMemoryWin32Fn;
+#[cfg(all(windows, not(feature = "ash_0_38")))]
+pub(crate) use vk::KhrExternalMemoryWin32Fn;
+
+#[cfg(all(
+    any(feature = "cuda_interop", feature = "dma_buf_interop"),
+    feature = "ash_0_38"
+))]
+pub(crate) use ash::khr::external_memory_fd::DeviceFn as KhrExternalMemoryFdFn;
+#[cfg(all(
+    any(feature = "cuda_interop", feature = "dma_buf_interop"),
+    not(feature = "ash_0_38")
+))]
+pub(crate) use vk::KhrExternalMemoryFdFn;
+
+#[cfg(all(feature = "android_interop", feature = "ash_0_38"))]
+pub(crate) use ash::android::external_memory_android_hardware_buffer::DeviceFn as AndroidExternalMemoryAndroidHardwareBufferFn;
+#[cfg(all(feature = "android_interop", not(feature = "ash_0_38")))]
+pub(crate) use vk::AndroidExternalMemoryAndroidHardwareBufferFn;
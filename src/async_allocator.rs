@@ -0,0 +1,353 @@
+//! Allocation on a background thread, enabled with the `async_allocator` feature.
+//!
+//! `AsyncAllocator` owns a worker thread that executes allocation and creation requests in the order
+//! they are submitted. Each request returns a `Pending` handle that can be waited on, polled, or
+//! awaited as a `std::future::Future`, so streaming systems can keep the latency of `vmaCreateBuffer`
+//! and `vmaCreateImage` (which may allocate new `ash::vk::DeviceMemory` blocks) off the render thread.
+//! Destruction requests are queued behind the creations submitted before them.
+
+use crate::{
+    ash_compat, Allocation, AllocationCreateInfo, AllocationInfo, Allocator, Error, Result,
+};
+use ash::vk;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// Buffer created by `AsyncAllocator::create_buffer`.
+#[derive(Debug)]
+pub struct BufferAllocation {
+    pub buffer: vk::Buffer,
+    pub allocation: Allocation,
+    pub info: AllocationInfo,
+}
+
+/// Image created by `AsyncAllocator::create_image`.
+#[derive(Debug)]
+pub struct ImageAllocation {
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub info: AllocationInfo,
+}
+
+/// Memory allocated by `AsyncAllocator::allocate_memory`.
+#[derive(Debug)]
+pub struct MemoryAllocation {
+    pub allocation: Allocation,
+    pub info: AllocationInfo,
+}
+
+// Allocations are only handles into the internally synchronized allocator and may be used from any thread.
+unsafe impl Send for BufferAllocation {}
+unsafe impl Send for ImageAllocation {}
+unsafe impl Send for MemoryAllocation {}
+
+/// Moves request parameters that contain raw pointers to the worker thread.
+///
+/// The submitting functions are `unsafe` and require the pointed-to data to outlive the request.
+struct AssertSend<T>(T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+impl<T> AssertSend<T> {
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+type Job = Box<dyn FnOnce(&Allocator) + Send>;
+
+struct Slot<T> {
+    state: Mutex<SlotState<T>>,
+    ready: Condvar,
+}
+
+struct SlotState<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T> Slot<T> {
+    fn fulfill(&self, result: Result<T>) {
+        let waker = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            state.waker.take()
+        };
+        self.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Fulfills its slot with an `Error` carrying `ash::vk::Result::ERROR_UNKNOWN` if the request is
+/// dropped without being executed, e.g. because the worker thread has stopped.
+struct Responder<T> {
+    slot: Option<Arc<Slot<T>>>,
+}
+
+impl<T> Responder<T> {
+    fn respond(mut self, result: Result<T>) {
+        if let Some(slot) = self.slot.take() {
+            slot.fulfill(result);
+        }
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            slot.fulfill(Err(Error::new(vk::Result::ERROR_UNKNOWN, "AsyncAllocator")));
+        }
+    }
+}
+
+/// Result of a request submitted to an `AsyncAllocator`.
+///
+/// Dropping it does not cancel the request: if it creates a resource, the resource leaks unless the
+/// result is retrieved and destroyed.
+pub struct Pending<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> Pending<T> {
+    /// Whether the request has completed and `Pending::wait` will return immediately.
+    pub fn is_ready(&self) -> bool {
+        self.slot
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .result
+            .is_some()
+    }
+
+    /// Returns the result if the request has completed, or gives the handle back otherwise.
+    pub fn try_wait(self) -> Result<Result<T>, Self> {
+        let result = self
+            .slot
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .result
+            .take();
+        result.ok_or(self)
+    }
+
+    /// Blocks until the request has completed and returns its result.
+    pub fn wait(self) -> Result<T> {
+        let mut state = self.slot.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            state = self
+                .slot
+                .ready
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl<T> Future for Pending<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.slot.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Facade that executes allocation requests on a dedicated worker thread.
+///
+/// The allocator is shared with the worker through an `Arc`, so it stays usable directly on other
+/// threads, e.g. for mapping or for destroying resources synchronously. Dropping the facade waits for
+/// all queued requests to complete.
+pub struct AsyncAllocator {
+    allocator: Arc<Allocator>,
+    sender: Option<mpsc::Sender<Job>>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl AsyncAllocator {
+    /// Starts the worker thread.
+    pub fn new(allocator: Arc<Allocator>) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let worker_allocator = allocator.clone();
+        let worker = thread::Builder::new()
+            .name("vk-mem-async".to_owned())
+            .spawn(move || {
+                for job in receiver {
+                    job(&worker_allocator);
+                }
+            })?;
+        Ok(AsyncAllocator {
+            allocator,
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    /// The allocator requests are executed on.
+    pub fn allocator(&self) -> &Arc<Allocator> {
+        &self.allocator
+    }
+
+    /// Queues `Allocator::create_buffer`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`. Everything `buffer_info` and `allocation_info` point to
+    /// (`p_next` chains, queue family indices, user data) must stay valid until the request has
+    /// completed.
+    pub unsafe fn create_buffer(
+        &self,
+        buffer_info: &vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Pending<BufferAllocation> {
+        // The copy outlives the borrow of `buffer_info`; the caller keeps what it points to alive.
+        let buffer_info = (buffer_info as *const vk::BufferCreateInfo)
+            .cast::<ash_compat::BufferCreateInfo<'static>>()
+            .read();
+        let request = AssertSend((buffer_info, allocation_info.clone()));
+        self.submit(move |allocator| {
+            let (buffer_info, allocation_info) = request.into_inner();
+            allocator.create_buffer(&buffer_info, &allocation_info).map(
+                |(buffer, allocation, info)| BufferAllocation {
+                    buffer,
+                    allocation,
+                    info,
+                },
+            )
+        })
+    }
+
+    /// Queues `Allocator::create_image`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_image`. Everything `image_info` and `allocation_info` point to
+    /// (`p_next` chains, queue family indices, user data) must stay valid until the request has
+    /// completed.
+    pub unsafe fn create_image(
+        &self,
+        image_info: &vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Pending<ImageAllocation> {
+        // The copy outlives the borrow of `image_info`; the caller keeps what it points to alive.
+        let image_info = (image_info as *const vk::ImageCreateInfo)
+            .cast::<ash_compat::ImageCreateInfo<'static>>()
+            .read();
+        let request = AssertSend((image_info, allocation_info.clone()));
+        self.submit(move |allocator| {
+            let (image_info, allocation_info) = request.into_inner();
+            allocator.create_image(&image_info, &allocation_info).map(
+                |(image, allocation, info)| ImageAllocation {
+                    image,
+                    allocation,
+                    info,
+                },
+            )
+        })
+    }
+
+    /// Queues `Allocator::allocate_memory`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::allocate_memory`. The user data of `allocation_info` must stay valid
+    /// until the request has completed.
+    pub unsafe fn allocate_memory(
+        &self,
+        memory_requirements: &vk::MemoryRequirements,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Pending<MemoryAllocation> {
+        let request = AssertSend((*memory_requirements, allocation_info.clone()));
+        self.submit(move |allocator| {
+            let (memory_requirements, allocation_info) = request.into_inner();
+            allocator
+                .allocate_memory(&memory_requirements, &allocation_info)
+                .map(|(allocation, info)| MemoryAllocation { allocation, info })
+        })
+    }
+
+    /// Queues `Allocator::destroy_buffer`. It runs after all requests submitted before it.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::destroy_buffer`, once the request runs.
+    pub unsafe fn destroy_buffer(&self, buffer: vk::Buffer, allocation: Allocation) -> Pending<()> {
+        let allocation = AssertSend(allocation);
+        self.submit(move |allocator| {
+            allocator.destroy_buffer(buffer, &allocation.into_inner());
+            Ok(())
+        })
+    }
+
+    /// Queues `Allocator::destroy_image`. It runs after all requests submitted before it.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::destroy_image`, once the request runs.
+    pub unsafe fn destroy_image(&self, image: vk::Image, allocation: Allocation) -> Pending<()> {
+        let allocation = AssertSend(allocation);
+        self.submit(move |allocator| {
+            allocator.destroy_image(image, &allocation.into_inner());
+            Ok(())
+        })
+    }
+
+    /// Queues `Allocator::free_memory`. It runs after all requests submitted before it.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::free_memory`, once the request runs.
+    pub unsafe fn free_memory(&self, allocation: Allocation) -> Pending<()> {
+        let allocation = AssertSend(allocation);
+        self.submit(move |allocator| {
+            allocator.free_memory(&allocation.into_inner());
+            Ok(())
+        })
+    }
+
+    fn submit<T, F>(&self, request: F) -> Pending<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Allocator) -> Result<T> + Send + 'static,
+    {
+        let slot = Arc::new(Slot {
+            state: Mutex::new(SlotState {
+                result: None,
+                waker: None,
+            }),
+            ready: Condvar::new(),
+        });
+        let responder = Responder {
+            slot: Some(slot.clone()),
+        };
+        let job: Job = Box::new(move |allocator| responder.respond(request(allocator)));
+        if let Some(sender) = &self.sender {
+            // If the worker has stopped the job is dropped here and its responder reports the failure.
+            let _ = sender.send(job);
+        }
+        Pending { slot }
+    }
+}
+
+impl Drop for AsyncAllocator {
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! A large buffer split into fixed-size slots for bindless access.
+//!
+//! `BindlessTable` creates one buffer holding `slot_count` slots of `slot_size` bytes and hands out
+//! slot indices that stay valid until the slot is freed, so shaders can index the buffer (or an
+//! array of descriptors pointing into it) with them. Freed slots are recycled lowest index first,
+//! which keeps the used range compact; `BindlessTable::defragment` moves the highest slots into the
+//! holes that remain and reports the new indices.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{Allocation, AllocationCreateInfo, Allocator, Result};
+use ash::vk;
+use std::collections::BTreeSet;
+
+/// Parameters of a `BindlessTable`.
+#[derive(Debug, Clone)]
+pub struct BindlessTableInfo {
+    /// Size of a slot in bytes. It must be a multiple of the alignment the slots are bound with,
+    /// e.g. `minStorageBufferOffsetAlignment`.
+    pub slot_size: vk::DeviceSize,
+
+    /// Number of slots.
+    pub slot_count: u32,
+
+    /// Usage of the buffer. `ash::vk::BufferUsageFlags::TRANSFER_SRC` and `TRANSFER_DST` are added
+    /// for `BindlessTable::defragment`. With `ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS` the
+    /// buffer is created with `Allocator::create_buffer_with_address`.
+    pub usage: vk::BufferUsageFlags,
+
+    /// Parameters of the allocation, usually `MemoryUsage::AutoPreferDevice`.
+    pub allocation_info: AllocationCreateInfo,
+}
+
+/// A slot moved by `BindlessTable::defragment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotMove {
+    /// Index the slot had.
+    pub from: u32,
+
+    /// Index the slot has now.
+    pub to: u32,
+}
+
+/// A buffer suballocated into fixed-size slots with stable indices.
+pub struct BindlessTable<'a> {
+    allocator: &'a Allocator,
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    address: Option<vk::DeviceAddress>,
+    slot_size: vk::DeviceSize,
+    slot_count: u32,
+
+    /// Number of slots that were ever handed out; slots from here on are untouched.
+    high_water: u32,
+
+    /// Freed slots below `high_water`.
+    free: BTreeSet<u32>,
+}
+
+impl<'a> BindlessTable<'a> {
+    /// Creates the buffer of the table.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device `allocator` was created for.
+    pub unsafe fn new(
+        allocator: &'a Allocator,
+        device: &ash::Device,
+        info: &BindlessTableInfo,
+    ) -> Result<Self> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(info.slot_size * info.slot_count as vk::DeviceSize)
+            .usage(
+                info.usage
+                    | vk::BufferUsageFlags::TRANSFER_SRC
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let (buffer, allocation, address) = if info
+            .usage
+            .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+        {
+            let (buffer, allocation, _, address) = allocator.create_buffer_with_address(
+                device,
+                &buffer_info,
+                &info.allocation_info,
+            )?;
+            (buffer, allocation, Some(address))
+        } else {
+            let (buffer, allocation, _) =
+                allocator.create_buffer(&buffer_info, &info.allocation_info)?;
+            (buffer, allocation, None)
+        };
+        Ok(BindlessTable {
+            allocator,
+            buffer,
+            allocation,
+            address,
+            slot_size: info.slot_size,
+            slot_count: info.slot_count,
+            high_water: 0,
+            free: BTreeSet::new(),
+        })
+    }
+
+    /// The buffer holding the slots.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// The allocation of the buffer.
+    pub fn allocation(&self) -> &Allocation {
+        &self.allocation
+    }
+
+    /// Size of a slot in bytes.
+    pub fn slot_size(&self) -> vk::DeviceSize {
+        self.slot_size
+    }
+
+    /// Total number of slots.
+    pub fn capacity(&self) -> u32 {
+        self.slot_count
+    }
+
+    /// Number of slots handed out and not freed.
+    pub fn len(&self) -> u32 {
+        self.high_water - self.free.len() as u32
+    }
+
+    /// Whether no slot is in use.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the lowest free slot, or `None` if all slots are in use.
+    pub fn allocate(&mut self) -> Option<u32> {
+        if let Some(slot) = self.free.pop_first() {
+            return Some(slot);
+        }
+        if self.high_water == self.slot_count {
+            return None;
+        }
+        self.high_water += 1;
+        Some(self.high_water - 1)
+    }
+
+    /// Returns `slot` to the table. Freeing a slot that is not in use is ignored.
+    pub fn free(&mut self, slot: u32) {
+        if slot >= self.high_water {
+            return;
+        }
+        self.free.insert(slot);
+        // Keep `high_water` tight, so new slots come from the compact range.
+        while self.high_water > 0 && self.free.remove(&(self.high_water - 1)) {
+            self.high_water -= 1;
+        }
+    }
+
+    /// Offset of `slot` in the buffer.
+    pub fn offset(&self, slot: u32) -> vk::DeviceSize {
+        slot as vk::DeviceSize * self.slot_size
+    }
+
+    /// Descriptor of `slot`, for writing it into a descriptor set.
+    pub fn descriptor_info(&self, slot: u32) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo {
+            buffer: self.buffer,
+            offset: self.offset(slot),
+            range: self.slot_size,
+        }
+    }
+
+    /// Device address of `slot`, if the buffer was created with
+    /// `ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`.
+    pub fn device_address(&self, slot: u32) -> Option<vk::DeviceAddress> {
+        self.address.map(|address| address + self.offset(slot))
+    }
+
+    /// Fraction of the slots below the highest slot in use that are free.
+    pub fn fragmentation(&self) -> f32 {
+        if self.high_water == 0 {
+            0.0
+        } else {
+            self.free.len() as f32 / self.high_water as f32
+        }
+    }
+
+    /// Moves the highest slots in use into the lowest free ones, recording the copies of their
+    /// contents into `command_buffer`, until the slots in use are contiguous from zero. Returns the
+    /// moves, after which the old indices are free.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device of the allocator and `command_buffer` must be in the recording
+    /// state. The application must rewrite every reference to a moved slot, and the device must
+    /// not access the moved slots while the copies execute. The copies synchronize with all
+    /// commands before and after them on the same queue.
+    pub unsafe fn defragment(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+    ) -> Vec<SlotMove> {
+        let mut moves = Vec::new();
+        while let Some(&to) = self.free.first() {
+            let from = self.high_water - 1;
+            if to >= from {
+                break;
+            }
+            self.free.remove(&to);
+            self.free(from);
+            moves.push(SlotMove { from, to });
+        }
+        if moves.is_empty() {
+            return moves;
+        }
+
+        let barrier = |src_access, dst_access| vk::MemoryBarrier {
+            src_access_mask: src_access,
+            dst_access_mask: dst_access,
+            ..Default::default()
+        };
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[barrier(
+                vk::AccessFlags::MEMORY_WRITE,
+                vk::AccessFlags::TRANSFER_READ,
+            )],
+            &[],
+            &[],
+        );
+        let regions: Vec<vk::BufferCopy> = moves
+            .iter()
+            .map(|slot_move| vk::BufferCopy {
+                src_offset: self.offset(slot_move.from),
+                dst_offset: self.offset(slot_move.to),
+                size: self.slot_size,
+            })
+            .collect();
+        device.cmd_copy_buffer(command_buffer, self.buffer, self.buffer, &regions);
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::DependencyFlags::empty(),
+            &[barrier(
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            )],
+            &[],
+            &[],
+        );
+        moves
+    }
+}
+
+impl<'a> Drop for BindlessTable<'a> {
+    fn drop(&mut self) {
+        unsafe { self.allocator.destroy_buffer(self.buffer, &self.allocation) };
+    }
+}
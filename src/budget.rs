@@ -0,0 +1,283 @@
+//! Soft reservations of heap budget for upcoming loads.
+//!
+//! A `ReservationToken` returned by `Allocator::reserve_budget` holds a number of bytes of a heap's
+//! budget that nobody else can plan against. It doesn't allocate anything: the reserved bytes are only
+//! subtracted from `Allocator::available_budget`. As the load is performed through the token, the size
+//! of each new allocation is moved from the reservation to the heap's actual usage, and whatever is
+//! left over is returned when the token is dropped.
+//!
+//! `Allocator::create_buffer_within_budget` creates buffers that must not push a heap over its
+//! budget, and reports the budgets when that can't be done, so streaming systems can decide to
+//! evict, wait or lower quality instead of running out of memory later.
+
+use crate::{Allocation, AllocationCreateInfo, AllocationInfo, Allocator, Error, Result};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// Bytes reserved per heap, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct Reservations {
+    reserved: Mutex<[vk::DeviceSize; vk::MAX_MEMORY_HEAPS]>,
+}
+
+impl Reservations {
+    fn lock(&self) -> MutexGuard<'_, [vk::DeviceSize; vk::MAX_MEMORY_HEAPS]> {
+        self.reserved
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Reserves `bytes` of `heap` if `free` (`budget - usage`) still covers them after existing reservations.
+    pub(crate) fn reserve(
+        &self,
+        heap: usize,
+        bytes: vk::DeviceSize,
+        free: vk::DeviceSize,
+    ) -> VkResult<()> {
+        let mut reserved = self.lock();
+        if reserved[heap].saturating_add(bytes) > free {
+            return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+        }
+        reserved[heap] += bytes;
+        Ok(())
+    }
+
+    pub(crate) fn release(&self, heap: usize, bytes: vk::DeviceSize) {
+        let mut reserved = self.lock();
+        reserved[heap] = reserved[heap].saturating_sub(bytes);
+    }
+
+    pub(crate) fn reserved(&self, heap: usize) -> vk::DeviceSize {
+        if heap >= vk::MAX_MEMORY_HEAPS {
+            return 0;
+        }
+        self.lock()[heap]
+    }
+}
+
+/// Budget of one heap reserved with `Allocator::reserve_budget`. The remaining reservation is
+/// released when the token is dropped.
+pub struct ReservationToken<'a> {
+    allocator: &'a Allocator,
+    heap_index: u32,
+    remaining: vk::DeviceSize,
+}
+
+impl<'a> ReservationToken<'a> {
+    pub(crate) fn new(allocator: &'a Allocator, heap_index: u32, bytes: vk::DeviceSize) -> Self {
+        ReservationToken {
+            allocator,
+            heap_index,
+            remaining: bytes,
+        }
+    }
+
+    /// Heap the budget is reserved in.
+    pub fn heap_index(&self) -> u32 {
+        self.heap_index
+    }
+
+    /// Bytes still reserved.
+    pub fn remaining(&self) -> vk::DeviceSize {
+        self.remaining
+    }
+
+    /// Releases up to `bytes` of the reservation, e.g. after making an allocation of that size in
+    /// the heap outside of the token.
+    pub fn consume(&mut self, bytes: vk::DeviceSize) {
+        let bytes = bytes.min(self.remaining);
+        self.remaining -= bytes;
+        self.allocator
+            .reservations
+            .release(self.heap_index as usize, bytes);
+    }
+
+    /// Calls `Allocator::create_buffer` and deducts the size of the new allocation from the
+    /// reservation if it landed in the reserved heap.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`.
+    pub unsafe fn create_buffer(
+        &mut self,
+        buffer_info: &vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Buffer, Allocation, AllocationInfo)> {
+        let result = self.allocator.create_buffer(buffer_info, allocation_info)?;
+        if let Err(error) = self.consume_allocation(&result.2) {
+            self.allocator.destroy_buffer(result.0, &result.1);
+            return Err(Error::new(error, "ReservationToken::create_buffer"));
+        }
+        Ok(result)
+    }
+
+    /// Calls `Allocator::create_image` and deducts the size of the new allocation from the
+    /// reservation if it landed in the reserved heap.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_image`.
+    pub unsafe fn create_image(
+        &mut self,
+        image_info: &vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Image, Allocation, AllocationInfo)> {
+        let result = self.allocator.create_image(image_info, allocation_info)?;
+        if let Err(error) = self.consume_allocation(&result.2) {
+            self.allocator.destroy_image(result.0, &result.1);
+            return Err(Error::new(error, "ReservationToken::create_image"));
+        }
+        Ok(result)
+    }
+
+    /// Calls `Allocator::allocate_memory` and deducts the size of the new allocation from the
+    /// reservation if it landed in the reserved heap.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::allocate_memory`.
+    pub unsafe fn allocate_memory(
+        &mut self,
+        memory_requirements: &vk::MemoryRequirements,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(Allocation, AllocationInfo)> {
+        let result = self
+            .allocator
+            .allocate_memory(memory_requirements, allocation_info)?;
+        if let Err(error) = self.consume_allocation(&result.1) {
+            self.allocator.free_memory(&result.0);
+            return Err(Error::new(error, "ReservationToken::allocate_memory"));
+        }
+        Ok(result)
+    }
+
+    unsafe fn consume_allocation(&mut self, info: &AllocationInfo) -> VkResult<()> {
+        let properties = self.allocator.get_memory_properties()?;
+        let heap_index = properties.memory_types[info.get_memory_type() as usize].heap_index;
+        if heap_index == self.heap_index {
+            self.consume(info.get_size() as vk::DeviceSize);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for ReservationToken<'a> {
+    fn drop(&mut self) {
+        self.allocator
+            .reservations
+            .release(self.heap_index as usize, self.remaining);
+    }
+}
+
+/// What `Allocator::create_buffer_within_budget` does when the buffer doesn't fit in the budget of
+/// the memory it asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BudgetFallback {
+    /// Fail with a `BudgetError`.
+    Fail,
+
+    /// Retry in host memory, still within budget. The buffer ends up in memory the device reads
+    /// over the bus, which is slower but keeps the application running.
+    HostMemory,
+}
+
+/// Where `Allocator::create_buffer_within_budget` placed a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BudgetPlacement {
+    /// In memory chosen by the `AllocationCreateInfo` that was passed in.
+    Requested,
+
+    /// In host memory, after the requested memory was over budget.
+    HostMemory,
+}
+
+/// Usage and budget of one heap when a budget-aware allocation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapBudget {
+    pub heap_index: u32,
+
+    /// Estimated memory usage of the program in the heap, in bytes.
+    pub usage: vk::DeviceSize,
+
+    /// Estimated memory available to the program in the heap, in bytes.
+    pub budget: vk::DeviceSize,
+
+    /// Bytes reserved with `Allocator::reserve_budget`.
+    pub reserved: vk::DeviceSize,
+}
+
+impl HeapBudget {
+    /// Bytes neither used nor reserved: `budget - usage - reserved`.
+    pub fn available(&self) -> vk::DeviceSize {
+        self.budget
+            .saturating_sub(self.usage)
+            .saturating_sub(self.reserved)
+    }
+}
+
+/// Failure of `Allocator::create_buffer_within_budget`, with the budgets of all heaps at the time.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{error} ({})", BudgetList(budgets))]
+pub struct BudgetError {
+    /// Error of the last attempt.
+    pub error: Error,
+
+    /// Budgets of all heaps, ordered by heap index.
+    pub budgets: Vec<HeapBudget>,
+}
+
+impl BudgetError {
+    pub(crate) fn new(allocator: &Allocator, error: Error) -> Self {
+        let heap_count = allocator.memory_properties().memory_heap_count as usize;
+        let budgets = allocator
+            .get_heap_budgets(heap_count)
+            .iter()
+            .enumerate()
+            .map(|(heap_index, budget)| HeapBudget {
+                heap_index: heap_index as u32,
+                usage: budget.usage,
+                budget: budget.budget,
+                reserved: allocator.reserved_budget(heap_index as u32),
+            })
+            .collect();
+        BudgetError { error, budgets }
+    }
+}
+
+impl From<BudgetError> for Error {
+    fn from(error: BudgetError) -> Self {
+        error.error
+    }
+}
+
+impl From<BudgetError> for vk::Result {
+    fn from(error: BudgetError) -> Self {
+        error.error.result()
+    }
+}
+
+/// Formats the budgets of a `BudgetError`.
+struct BudgetList<'a>(&'a [HeapBudget]);
+
+impl fmt::Display for BudgetList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, heap) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(
+                f,
+                "heap {}: {} of {} used",
+                heap.heap_index,
+                crate::format_bytes(heap.usage),
+                crate::format_bytes(heap.budget)
+            )?;
+            if heap.reserved > 0 {
+                write!(f, ", {} reserved", crate::format_bytes(heap.reserved))?;
+            }
+        }
+        Ok(())
+    }
+}
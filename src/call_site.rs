@@ -0,0 +1,119 @@
+//! Allocations named after the code that made them.
+//!
+//! The `create_buffer!`, `create_image!` and `allocate_memory!` macros call the `Allocator`
+//! method of the same name and name the allocation with the file and line of the macro call, e.g.
+//! `src/terrain.rs:118`, or `heightmap @ src/terrain.rs:118` when given a label. The name shows
+//! up wherever allocation names do: `Allocator::build_stats_string`, leak reports and the tools
+//! built on them, so memory can be attributed to the code that allocated it.
+//!
+//! Buffers and images are created with `Allocator::create_buffer_named` and
+//! `Allocator::create_image_named`, so if the instance enabled `VK_EXT_debug_utils` they carry the
+//! name as their debug name too. The macros expand to calls of unsafe methods and must be used in
+//! an `unsafe` block.
+//!
+//! ```ignore
+//! let (buffer, allocation, _) =
+//!     unsafe { vk_mem::create_buffer!(allocator, &buffer_info, &allocation_info, "vertices")? };
+//! ```
+
+/// Separator between the label and the location in names made by the macros.
+pub const SEPARATOR: &str = " @ ";
+
+/// Splits a name made by the macros into its label, if it has one, and its `file:line` location.
+pub fn split(name: &str) -> (Option<&str>, &str) {
+    match name.rsplit_once(SEPARATOR) {
+        Some((label, location)) => (Some(label), location),
+        None => (None, name),
+    }
+}
+
+/// Name of an allocation made at `location`, with an optional label.
+#[doc(hidden)]
+pub fn name(label: Option<&str>, location: &str) -> String {
+    match label {
+        Some(label) => format!("{}{}{}", label, SEPARATOR, location),
+        None => location.to_owned(),
+    }
+}
+
+/// `Allocator::create_buffer`, naming the allocation and buffer after the call site.
+///
+/// `create_buffer!(allocator, buffer_info, allocation_info)` names them with the `file:line` of
+/// the call, `create_buffer!(allocator, buffer_info, allocation_info, label)` with
+/// `label @ file:line`. See `vk_mem::call_site`.
+#[macro_export]
+macro_rules! create_buffer {
+    (@named $allocator:expr, $buffer_info:expr, $allocation_info:expr, $label:expr) => {{
+        let allocator: &$crate::Allocator = &$allocator;
+        allocator.create_buffer_named(
+            $buffer_info,
+            $allocation_info,
+            &$crate::call_site::name($label, concat!(file!(), ":", line!())),
+        )
+    }};
+    ($allocator:expr, $buffer_info:expr, $allocation_info:expr $(,)?) => {
+        $crate::create_buffer!(@named $allocator, $buffer_info, $allocation_info, None)
+    };
+    ($allocator:expr, $buffer_info:expr, $allocation_info:expr, $label:expr $(,)?) => {
+        $crate::create_buffer!(
+            @named $allocator,
+            $buffer_info,
+            $allocation_info,
+            Some(AsRef::<str>::as_ref(&$label))
+        )
+    };
+}
+
+/// `Allocator::create_image`, naming the allocation and image after the call site.
+///
+/// Takes the same arguments as `create_buffer!`, with an `ash::vk::ImageCreateInfo`.
+#[macro_export]
+macro_rules! create_image {
+    (@named $allocator:expr, $image_info:expr, $allocation_info:expr, $label:expr) => {{
+        let allocator: &$crate::Allocator = &$allocator;
+        allocator.create_image_named(
+            $image_info,
+            $allocation_info,
+            &$crate::call_site::name($label, concat!(file!(), ":", line!())),
+        )
+    }};
+    ($allocator:expr, $image_info:expr, $allocation_info:expr $(,)?) => {
+        $crate::create_image!(@named $allocator, $image_info, $allocation_info, None)
+    };
+    ($allocator:expr, $image_info:expr, $allocation_info:expr, $label:expr $(,)?) => {
+        $crate::create_image!(
+            @named $allocator,
+            $image_info,
+            $allocation_info,
+            Some(AsRef::<str>::as_ref(&$label))
+        )
+    };
+}
+
+/// `Allocator::allocate_memory`, naming the allocation after the call site.
+///
+/// Takes the same arguments as `create_buffer!`, with an `ash::vk::MemoryRequirements`. A name
+/// rejected by the allocator's `NamePolicy` leaves the allocation unnamed.
+#[macro_export]
+macro_rules! allocate_memory {
+    (@named $allocator:expr, $memory_requirements:expr, $allocation_info:expr, $label:expr) => {{
+        let allocator: &$crate::Allocator = &$allocator;
+        let result = allocator.allocate_memory($memory_requirements, $allocation_info);
+        if let Ok((allocation, _)) = &result {
+            let name = $crate::call_site::name($label, concat!(file!(), ":", line!()));
+            let _ = allocator.set_allocation_name(allocation, &name);
+        }
+        result
+    }};
+    ($allocator:expr, $memory_requirements:expr, $allocation_info:expr $(,)?) => {
+        $crate::allocate_memory!(@named $allocator, $memory_requirements, $allocation_info, None)
+    };
+    ($allocator:expr, $memory_requirements:expr, $allocation_info:expr, $label:expr $(,)?) => {
+        $crate::allocate_memory!(
+            @named $allocator,
+            $memory_requirements,
+            $allocation_info,
+            Some(AsRef::<str>::as_ref(&$label))
+        )
+    };
+}
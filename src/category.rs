@@ -0,0 +1,162 @@
+//! Per-category accounting of allocations.
+//!
+//! Allocations created with `AllocationCreateInfo::category` set are counted towards that
+//! `Category` until they are freed, whatever memory type or pool they end up in.
+//! `Allocator::category_stats` returns the totals, e.g. to show how much memory textures take
+//! compared to meshes.
+
+use crate::{ffi, Allocation, AllocatorPool};
+use ash::vk;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// What an allocation is used for, as far as the application's accounting is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    Textures,
+    Meshes,
+    Ui,
+    Scratch,
+
+    /// A category named by the application, e.g. `Category::Other("shadow maps")`.
+    Other(&'static str),
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Category::Textures => f.write_str("textures"),
+            Category::Meshes => f.write_str("meshes"),
+            Category::Ui => f.write_str("ui"),
+            Category::Scratch => f.write_str("scratch"),
+            Category::Other(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Live allocations of one category.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CategoryStats {
+    /// Number of allocations.
+    pub allocation_count: u64,
+
+    /// Size of the allocations in bytes.
+    pub allocation_bytes: vk::DeviceSize,
+}
+
+/// A live allocation, as seen by `CategoryTracker`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LiveAllocation {
+    allocation: usize,
+    pub(crate) category: Option<Category>,
+    pool: usize,
+
+    /// Whether the allocation has its own `ash::vk::DeviceMemory`.
+    pub(crate) dedicated: bool,
+    size: vk::DeviceSize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    live: HashMap<usize, LiveAllocation>,
+    totals: HashMap<Category, CategoryStats>,
+    dedicated_count: u64,
+}
+
+impl LiveAllocation {
+    pub(crate) fn allocation(&self) -> Allocation {
+        self.allocation as Allocation
+    }
+
+    /// The custom pool the allocation was made from, or `None` for the default pools.
+    pub(crate) fn pool(&self) -> Option<AllocatorPool> {
+        Some(self.pool as AllocatorPool).filter(|pool| !pool.is_null())
+    }
+}
+
+/// Live allocations and their categories, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct CategoryTracker {
+    state: Mutex<State>,
+}
+
+impl CategoryTracker {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn on_create(
+        &self,
+        allocation: &Allocation,
+        category: Option<Category>,
+        create_info: &ffi::VmaAllocationCreateInfo,
+        size: vk::DeviceSize,
+        dedicated: bool,
+    ) {
+        let mut state = self.state();
+        if dedicated {
+            state.dedicated_count += 1;
+        }
+        state.live.insert(
+            *allocation as usize,
+            LiveAllocation {
+                allocation: *allocation as usize,
+                category,
+                pool: create_info.pool as usize,
+                dedicated,
+                size,
+            },
+        );
+        if let Some(category) = category {
+            let stats = state.totals.entry(category).or_default();
+            stats.allocation_count += 1;
+            stats.allocation_bytes += size;
+        }
+    }
+
+    pub(crate) fn on_free(&self, allocation: &Allocation) {
+        let mut state = self.state();
+        let live = match state.live.remove(&(*allocation as usize)) {
+            Some(live) => live,
+            None => return,
+        };
+        if live.dedicated {
+            state.dedicated_count -= 1;
+        }
+        let category = match live.category {
+            Some(category) => category,
+            None => return,
+        };
+        if let Some(stats) = state.totals.get_mut(&category) {
+            stats.allocation_count -= 1;
+            stats.allocation_bytes -= live.size;
+            if stats.allocation_count == 0 {
+                state.totals.remove(&category);
+            }
+        }
+    }
+
+    pub(crate) fn category(&self, allocation: &Allocation) -> Option<Category> {
+        self.state()
+            .live
+            .get(&(*allocation as usize))
+            .and_then(|live| live.category)
+    }
+
+    pub(crate) fn stats(&self) -> HashMap<Category, CategoryStats> {
+        self.state().totals.clone()
+    }
+
+    /// Number of live allocations with their own `ash::vk::DeviceMemory`.
+    pub(crate) fn dedicated_count(&self) -> u64 {
+        self.state().dedicated_count
+    }
+
+    /// All live allocations, in no particular order.
+    pub(crate) fn live(&self) -> Vec<LiveAllocation> {
+        self.state().live.values().copied().collect()
+    }
+}
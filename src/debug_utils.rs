@@ -0,0 +1,60 @@
+//! Forwarding of allocation and pool names to `VK_EXT_debug_utils` object names.
+//!
+//! When the instance was created with `VK_EXT_debug_utils`, names given to allocations and pools are
+//! also set on the underlying `ash::vk::DeviceMemory`, `ash::vk::Buffer` and `ash::vk::Image`
+//! objects, so tools like RenderDoc and the validation layers show them.
+
+use ash::vk;
+use std::ffi::CStr;
+
+/// `vkSetDebugUtilsObjectNameEXT` of the allocator's device.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DebugNames {
+    device: vk::Device,
+    set_object_name: vk::PFN_vkSetDebugUtilsObjectNameEXT,
+}
+
+impl DebugNames {
+    /// Looks up `vkSetDebugUtilsObjectNameEXT`, which is only available if the instance enabled
+    /// `VK_EXT_debug_utils`.
+    pub(crate) unsafe fn load(
+        get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+        instance: vk::Instance,
+        device: vk::Device,
+    ) -> Option<Self> {
+        let name = c"vkSetDebugUtilsObjectNameEXT";
+        let function = get_instance_proc_addr(instance, name.as_ptr())?;
+        Some(DebugNames {
+            device,
+            set_object_name: std::mem::transmute::<
+                unsafe extern "system" fn(),
+                vk::PFN_vkSetDebugUtilsObjectNameEXT,
+            >(function),
+        })
+    }
+
+    /// Names `object`. Null handles are ignored and failures are only logged, as names are a
+    /// debugging aid.
+    pub(crate) unsafe fn set_name<T: vk::Handle>(&self, object: T, name: &CStr) {
+        let object_type = T::TYPE;
+        let handle = object.as_raw();
+        if handle == 0 {
+            return;
+        }
+        let info = vk::DebugUtilsObjectNameInfoEXT {
+            object_type,
+            object_handle: handle,
+            p_object_name: name.as_ptr(),
+            ..Default::default()
+        };
+        let result = (self.set_object_name)(self.device, &info);
+        if result != vk::Result::SUCCESS {
+            log::warn!(
+                "vkSetDebugUtilsObjectNameEXT failed with {} for {:?} {:#x}",
+                result,
+                object_type,
+                handle
+            );
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Frees deferred until a fence is signaled.
+//!
+//! `Allocator::free_when`, `Allocator::destroy_buffer_when` and `Allocator::destroy_image_when`
+//! queue a resource behind the fence of the submission that last uses it, and
+//! `Allocator::poll_deferred` frees everything whose fence has been signaled since. Unlike the
+//! deferred frees of `frame::FrameGuard`, this doesn't need a frame loop, so it suits compute-only
+//! applications and submissions that don't line up with frames.
+//!
+//! ```ignore
+//! queue_submit(queue, &[submit], fence)?;
+//! unsafe { allocator.destroy_buffer_when(fence, scratch, &scratch_allocation) };
+//! // later, e.g. before the next submission
+//! unsafe { allocator.poll_deferred()? };
+//! ```
+//!
+//! Fences are only queried, never waited for or reset, so a fence must not be reset while
+//! resources are queued behind it. Whatever is still queued when the allocator is destroyed is
+//! freed then.
+//!
+//! Renderers synchronizing with timeline semaphores can use a `TimelineQueue` instead, which
+//! queues resources behind a semaphore value:
+//!
+//! ```ignore
+//! let deletion_queue = vk_mem::deferred::TimelineQueue::new(&allocator);
+//! unsafe { deletion_queue.defer(timeline, frame_value, Deferred::Buffer(buffer, allocation)) };
+//! // later
+//! unsafe { deletion_queue.collect(&device)? };
+//! ```
+
+pub use crate::frame::Deferred;
+use crate::Allocator;
+use ash::prelude::VkResult;
+use ash::vk;
+use std::sync::{Mutex, MutexGuard};
+
+/// Resources waiting for their fence, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct FenceQueue {
+    pending: Mutex<Vec<(vk::Fence, Deferred)>>,
+}
+
+impl FenceQueue {
+    fn pending(&self) -> MutexGuard<'_, Vec<(vk::Fence, Deferred)>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn defer(&self, fence: vk::Fence, resource: Deferred) {
+        self.pending().push((fence, resource));
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.pending().len()
+    }
+
+    /// Removes the resources whose fence `is_signaled` reports as signaled. Each fence is queried
+    /// once. If a query fails, nothing is removed and the error is returned.
+    pub(crate) fn take_signaled(
+        &self,
+        mut is_signaled: impl FnMut(vk::Fence) -> VkResult<bool>,
+    ) -> VkResult<Vec<Deferred>> {
+        let mut pending = self.pending();
+        let mut statuses: Vec<(vk::Fence, bool)> = Vec::new();
+        for &(fence, _) in pending.iter() {
+            if !statuses.iter().any(|&(queried, _)| queried == fence) {
+                statuses.push((fence, is_signaled(fence)?));
+            }
+        }
+
+        let mut signaled = Vec::new();
+        pending.retain(|&(fence, resource)| {
+            let done = statuses
+                .iter()
+                .any(|&(queried, status)| queried == fence && status);
+            if done {
+                signaled.push(resource);
+            }
+            !done
+        });
+        Ok(signaled)
+    }
+
+    /// Frees all queued resources regardless of their fences, when the allocator is destroyed.
+    pub(crate) unsafe fn free_all(&self, allocator: &Allocator) {
+        let pending = std::mem::take(&mut *self.pending());
+        for (_, resource) in pending {
+            resource.destroy(allocator);
+        }
+    }
+}
+
+/// Deletion queue keyed on timeline semaphore values.
+///
+/// Resources still queued when the `TimelineQueue` is dropped are freed then, so it must not be
+/// dropped while the device may still use them.
+#[derive(Debug)]
+pub struct TimelineQueue<'a> {
+    allocator: &'a Allocator,
+    pending: Mutex<Vec<(vk::Semaphore, u64, Deferred)>>,
+}
+
+impl<'a> TimelineQueue<'a> {
+    /// An empty queue of resources of `allocator`.
+    pub fn new(allocator: &'a Allocator) -> Self {
+        TimelineQueue {
+            allocator,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Vec<(vk::Semaphore, u64, Deferred)>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Queues `resource` to be freed once the counter of `semaphore` reaches `value`.
+    ///
+    /// # Safety
+    ///
+    /// The resource must not be used by the host afterwards, and the device must be done with it
+    /// once `semaphore` reaches `value`. `semaphore` must be a timeline semaphore that stays alive
+    /// until the resource is freed.
+    pub unsafe fn defer(&self, semaphore: vk::Semaphore, value: u64, resource: Deferred) {
+        self.pending().push((semaphore, value, resource));
+    }
+
+    /// Number of resources waiting for their semaphore value.
+    pub fn len(&self) -> usize {
+        self.pending().len()
+    }
+
+    /// Whether no resource is waiting.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Queries the counter of each semaphore with queued resources once and frees the resources
+    /// whose value has been reached. Returns how many were freed.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device the allocator was created for, with the `timelineSemaphore`
+    /// feature of Vulkan 1.2 enabled.
+    pub unsafe fn collect(&self, device: &ash::Device) -> VkResult<usize> {
+        let mut pending = self.pending();
+        let mut counters: Vec<(vk::Semaphore, u64)> = Vec::new();
+        for &(semaphore, _, _) in pending.iter() {
+            if !counters.iter().any(|&(queried, _)| queried == semaphore) {
+                counters.push((semaphore, device.get_semaphore_counter_value(semaphore)?));
+            }
+        }
+
+        let mut reached = Vec::new();
+        pending.retain(|&(semaphore, value, resource)| {
+            let done = counters
+                .iter()
+                .any(|&(queried, counter)| queried == semaphore && counter >= value);
+            if done {
+                reached.push(resource);
+            }
+            !done
+        });
+        drop(pending);
+
+        for resource in &reached {
+            resource.destroy(self.allocator);
+        }
+        Ok(reached.len())
+    }
+}
+
+impl Drop for TimelineQueue<'_> {
+    fn drop(&mut self) {
+        let pending = std::mem::take(&mut *self.pending());
+        for (_, _, resource) in pending {
+            unsafe { resource.destroy(self.allocator) };
+        }
+    }
+}
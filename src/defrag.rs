@@ -0,0 +1,581 @@
+//! Automatic defragmentation that drives the incremental pass API end to end.
+//!
+//! `Allocator::begin_defragmentation_pass` only tells which allocations should move where; the
+//! application has to recreate every buffer and image at its new place, copy the contents and update
+//! its own references before `Allocator::end_defragmentation_pass`. `run` does all of this for the
+//! resources a `DefragHandler` describes, recording the copies of each pass into one command buffer
+//! and waiting for it before committing the pass. `DefragScheduler` does the same a little every
+//! frame.
+
+use crate::ash_compat;
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::transfer::TransferQueue;
+use crate::{
+    ffi, ffi_to_result, Allocation, Allocator, AllocatorPool, DefragmentationContext,
+    DefragmentationFlags, DefragmentationInfo, DefragmentationMoveOperation,
+    DefragmentationPassMoveInfo, DefragmentationStats,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Parameters of `run`.
+#[derive(Debug, Clone)]
+pub struct DefragOptions {
+    /// Algorithm to use, see `DefragmentationInfo::flags`.
+    pub flags: DefragmentationFlags,
+
+    /// Custom pool to defragment, or `None` for the default pools.
+    pub pool: Option<AllocatorPool>,
+
+    /// Maximum number of bytes copied in one pass, see `DefragmentationInfo::max_bytes_per_pass`.
+    pub max_bytes_per_pass: vk::DeviceSize,
+
+    /// Maximum number of allocations moved in one pass, see
+    /// `DefragmentationInfo::max_allocations_per_pass`.
+    pub max_allocations_per_pass: u32,
+
+    /// Maximum number of passes, or 0 for no limit. Defragmentation stops early once it is reached,
+    /// keeping the moves made so far.
+    pub max_passes: u32,
+}
+
+impl Default for DefragOptions {
+    fn default() -> Self {
+        let info = DefragmentationInfo::default();
+        DefragOptions {
+            flags: info.flags,
+            pool: info.pool,
+            max_bytes_per_pass: info.max_bytes_per_pass,
+            max_allocations_per_pass: info.max_allocations_per_pass,
+            max_passes: 0,
+        }
+    }
+}
+
+/// A buffer or image bound to an allocation that may be moved, as described by a `DefragHandler`.
+///
+/// The create info is used to recreate the resource at its new place and must describe the current
+/// resource exactly. Pointers in it (`p_next`, `p_queue_family_indices`) must stay valid for the
+/// duration of `run`.
+#[derive(Debug, Clone, Copy)]
+pub enum MovableResource {
+    /// A buffer. Its usage must include `ash::vk::BufferUsageFlags::TRANSFER_SRC` and
+    /// `ash::vk::BufferUsageFlags::TRANSFER_DST`.
+    Buffer {
+        buffer: vk::Buffer,
+        create_info: ash_compat::BufferCreateInfo<'static>,
+    },
+
+    /// An image in `layout` for all of its subresources. Its usage must include
+    /// `ash::vk::ImageUsageFlags::TRANSFER_SRC` and `ash::vk::ImageUsageFlags::TRANSFER_DST`, and it
+    /// must not use `ash::vk::ImageLayout::UNDEFINED`. The new image is left in the same layout.
+    Image {
+        image: vk::Image,
+        create_info: ash_compat::ImageCreateInfo<'static>,
+        layout: vk::ImageLayout,
+        aspect_mask: vk::ImageAspectFlags,
+    },
+}
+
+/// Resource that replaced a moved one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovedResource {
+    Buffer(vk::Buffer),
+    Image(vk::Image),
+}
+
+/// Connects `run` to the application's resources.
+pub trait DefragHandler {
+    /// Resource bound to `allocation`, or `None` if the allocation must stay where it is, e.g.
+    /// because it is not bound to anything or is in use by the device.
+    fn resource(&mut self, allocation: &Allocation) -> Option<MovableResource>;
+
+    /// The resource bound to `allocation` has been replaced by `moved`, which holds a copy of its
+    /// contents at the allocation's new place. The old resource has already been destroyed, so every
+    /// reference to it (descriptors, views, framebuffers) must be updated.
+    fn moved(&mut self, allocation: &Allocation, moved: MovedResource);
+}
+
+/// Defragments the allocations selected by `options`, moving the resources described by `handler`.
+///
+/// Each pass recreates the resources at their new places, copies their contents with one command
+/// buffer allocated from `command_pool` and submitted to `queue`, waits for it, destroys the old
+/// resources and reports the new ones to `handler` before the pass is committed. Allocations for which
+/// `handler` has no resource, or whose resource could not be recreated, are left in place.
+///
+/// # Safety
+///
+/// The device must not use any resource that may be moved for the duration of the call, and `queue`
+/// must support transfer operations. Access to `queue` and `command_pool` must be externally
+/// synchronized for the duration of the call.
+pub unsafe fn run(
+    allocator: &Allocator,
+    options: &DefragOptions,
+    device: &ash::Device,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    handler: &mut impl DefragHandler,
+) -> VkResult<DefragmentationStats> {
+    let transfer = TransferQueue {
+        device,
+        queue,
+        command_pool,
+    };
+    let mut context = allocator.begin_defragmentation(&DefragmentationInfo {
+        flags: options.flags,
+        pool: options.pool,
+        max_bytes_per_pass: options.max_bytes_per_pass,
+        max_allocations_per_pass: options.max_allocations_per_pass,
+    })?;
+
+    let mut passes = 0;
+    let result = loop {
+        if options.max_passes != 0 && passes == options.max_passes {
+            break Ok(());
+        }
+        passes += 1;
+
+        match run_pass(allocator, &transfer, &mut context, handler) {
+            Ok(true) => continue,
+            Ok(false) => break Ok(()),
+            Err(error) => break Err(error),
+        }
+    };
+
+    let stats = allocator.end_defragmentation(&mut context)?;
+    result.map(|()| stats)
+}
+
+/// Runs one pass of `context`. Returns whether more passes are possible.
+unsafe fn run_pass(
+    allocator: &Allocator,
+    transfer: &TransferQueue,
+    context: &mut DefragmentationContext,
+    handler: &mut impl DefragHandler,
+) -> VkResult<bool> {
+    let (result, mut pass) = allocator.begin_defragmentation_pass(context);
+    match result {
+        Ok(()) => return Ok(false),
+        Err(vk::Result::INCOMPLETE) => {}
+        Err(error) => return Err(error),
+    }
+
+    let moved = perform_moves(allocator, transfer, &mut pass, handler);
+    let ended = allocator.end_defragmentation_pass(context, &mut pass);
+    moved?;
+    match ended {
+        Ok(()) => Ok(false),
+        Err(vk::Result::INCOMPLETE) => Ok(true),
+        Err(error) => Err(error),
+    }
+}
+
+/// Recreates and copies the resources of one pass. Moves that are not performed are set to
+/// `DefragmentationMoveOperation::Ignore`; if the copy fails, all of them are.
+unsafe fn perform_moves(
+    allocator: &Allocator,
+    transfer: &TransferQueue,
+    pass: &mut DefragmentationPassMoveInfo,
+    handler: &mut impl DefragHandler,
+) -> VkResult<()> {
+    let device = transfer.device;
+    let mut copies = Vec::with_capacity(pass.moves().len());
+    for index in 0..pass.moves().len() {
+        let defrag_move = pass.moves()[index];
+        let recreated = handler
+            .resource(&defrag_move.src_allocation)
+            .and_then(|resource| {
+                recreate(
+                    allocator,
+                    device,
+                    &resource,
+                    &defrag_move.dst_tmp_allocation,
+                )
+                .map(|moved| (resource, moved))
+            });
+        match recreated {
+            Some((resource, moved)) => copies.push((index, resource, moved)),
+            None => pass.set_operation(index, DefragmentationMoveOperation::Ignore),
+        }
+    }
+    if copies.is_empty() {
+        return Ok(());
+    }
+
+    let result = transfer.submit_and_wait(|command_buffer| {
+        memory_barrier(
+            device,
+            command_buffer,
+            (
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::AccessFlags::MEMORY_WRITE,
+            ),
+            (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+            ),
+        );
+        for (_, resource, moved) in &copies {
+            record_copy(device, command_buffer, resource, moved);
+        }
+        memory_barrier(
+            device,
+            command_buffer,
+            (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            ),
+            (
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            ),
+        );
+    });
+
+    for (index, resource, moved) in copies {
+        let src_allocation = pass.moves()[index].src_allocation;
+        match (result, resource, moved) {
+            (Ok(()), MovableResource::Buffer { buffer, .. }, _) => {
+                device.destroy_buffer(buffer, None);
+                handler.moved(&src_allocation, moved);
+            }
+            (Ok(()), MovableResource::Image { image, .. }, _) => {
+                device.destroy_image(image, None);
+                handler.moved(&src_allocation, moved);
+            }
+            (Err(_), _, MovedResource::Buffer(buffer)) => {
+                device.destroy_buffer(buffer, None);
+                pass.set_operation(index, DefragmentationMoveOperation::Ignore);
+            }
+            (Err(_), _, MovedResource::Image(image)) => {
+                device.destroy_image(image, None);
+                pass.set_operation(index, DefragmentationMoveOperation::Ignore);
+            }
+        }
+    }
+    result
+}
+
+/// Creates a copy of `resource` bound to `allocation`, or `None` if that fails.
+unsafe fn recreate(
+    allocator: &Allocator,
+    device: &ash::Device,
+    resource: &MovableResource,
+    allocation: &Allocation,
+) -> Option<MovedResource> {
+    match *resource {
+        MovableResource::Buffer { create_info, .. } => {
+            let buffer = device.create_buffer(&create_info, None).ok()?;
+            // The temporary allocation is not known to the validator, so bind through VMA directly.
+            match ffi_to_result(ffi::vmaBindBufferMemory(
+                allocator.handle(),
+                *allocation,
+                buffer,
+            )) {
+                Ok(()) => Some(MovedResource::Buffer(buffer)),
+                Err(_) => {
+                    device.destroy_buffer(buffer, None);
+                    None
+                }
+            }
+        }
+        MovableResource::Image { create_info, .. } => {
+            let image = device.create_image(&create_info, None).ok()?;
+            match ffi_to_result(ffi::vmaBindImageMemory(
+                allocator.handle(),
+                *allocation,
+                image,
+            )) {
+                Ok(()) => Some(MovedResource::Image(image)),
+                Err(_) => {
+                    device.destroy_image(image, None);
+                    None
+                }
+            }
+        }
+    }
+}
+
+unsafe fn memory_barrier(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    (src_stage, src_access): (vk::PipelineStageFlags, vk::AccessFlags),
+    (dst_stage, dst_access): (vk::PipelineStageFlags, vk::AccessFlags),
+) {
+    let barrier = vk::MemoryBarrier::builder()
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access)
+        .build();
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        src_stage,
+        dst_stage,
+        vk::DependencyFlags::empty(),
+        &[barrier],
+        &[],
+        &[],
+    );
+}
+
+unsafe fn record_copy(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    resource: &MovableResource,
+    moved: &MovedResource,
+) {
+    match (*resource, *moved) {
+        (
+            MovableResource::Buffer {
+                buffer,
+                create_info,
+            },
+            MovedResource::Buffer(new_buffer),
+        ) => {
+            let region = vk::BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size: create_info.size,
+            };
+            device.cmd_copy_buffer(command_buffer, buffer, new_buffer, &[region]);
+        }
+        (
+            MovableResource::Image {
+                image,
+                create_info,
+                layout,
+                aspect_mask,
+            },
+            MovedResource::Image(new_image),
+        ) => {
+            let range = vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: create_info.mip_levels,
+                base_array_layer: 0,
+                layer_count: create_info.array_layers,
+            };
+            let barrier = |image, src_access, dst_access, old_layout, new_layout| {
+                vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(src_access)
+                    .dst_access_mask(dst_access)
+                    .old_layout(old_layout)
+                    .new_layout(new_layout)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(image)
+                    .subresource_range(range)
+                    .build()
+            };
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[
+                    barrier(
+                        image,
+                        vk::AccessFlags::MEMORY_WRITE,
+                        vk::AccessFlags::TRANSFER_READ,
+                        layout,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    ),
+                    barrier(
+                        new_image,
+                        vk::AccessFlags::empty(),
+                        vk::AccessFlags::TRANSFER_WRITE,
+                        vk::ImageLayout::UNDEFINED,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    ),
+                ],
+            );
+
+            let regions: Vec<_> = (0..create_info.mip_levels)
+                .map(|mip_level| {
+                    let subresource = vk::ImageSubresourceLayers {
+                        aspect_mask,
+                        mip_level,
+                        base_array_layer: 0,
+                        layer_count: create_info.array_layers,
+                    };
+                    vk::ImageCopy {
+                        src_subresource: subresource,
+                        src_offset: vk::Offset3D::default(),
+                        dst_subresource: subresource,
+                        dst_offset: vk::Offset3D::default(),
+                        extent: vk::Extent3D {
+                            width: (create_info.extent.width >> mip_level).max(1),
+                            height: (create_info.extent.height >> mip_level).max(1),
+                            depth: (create_info.extent.depth >> mip_level).max(1),
+                        },
+                    }
+                })
+                .collect();
+            device.cmd_copy_image(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                new_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier(
+                    new_image,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    layout,
+                )],
+            );
+        }
+        // `recreate` always produces a resource of the same kind.
+        _ => unreachable!(),
+    }
+}
+
+/// Spreads the defragmentation of custom pools over many frames.
+///
+/// Every frame, `DefragScheduler::run_frame` performs one defragmentation pass limited to
+/// `max_bytes_per_frame` and `max_moves_per_frame`, so compaction never causes a hitch. A pool whose
+/// fragmentation (see `DefragScheduler::fragmentation`) reaches `min_fragmentation` is defragmented
+/// over consecutive frames until no more moves are possible; then the next most fragmented pool is
+/// picked.
+#[derive(Debug)]
+pub struct DefragScheduler {
+    /// Maximum number of bytes copied per frame.
+    pub max_bytes_per_frame: vk::DeviceSize,
+
+    /// Maximum number of allocations moved per frame.
+    pub max_moves_per_frame: u32,
+
+    /// Fragmentation from 0 to 1 a pool must reach before it is defragmented.
+    pub min_fragmentation: f32,
+
+    /// Algorithm to use, see `DefragmentationInfo::flags`.
+    pub flags: DefragmentationFlags,
+
+    pools: Vec<AllocatorPool>,
+    current: Option<(AllocatorPool, DefragmentationContext)>,
+}
+
+impl DefragScheduler {
+    /// Creates a scheduler without pools, starting pools at 25% fragmentation with
+    /// `DefragmentationFlags::ALGORITHM_FAST`.
+    ///
+    /// Changes to the limits apply from the next pool whose defragmentation starts.
+    pub fn new(max_bytes_per_frame: vk::DeviceSize, max_moves_per_frame: u32) -> Self {
+        DefragScheduler {
+            max_bytes_per_frame,
+            max_moves_per_frame,
+            min_fragmentation: 0.25,
+            flags: DefragmentationFlags::ALGORITHM_FAST,
+            pools: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// Adds `pool` to the pools considered for defragmentation.
+    pub fn register_pool(&mut self, pool: AllocatorPool) {
+        if !self.pools.contains(&pool) {
+            self.pools.push(pool);
+        }
+    }
+
+    /// Removes `pool`, ending its defragmentation if it is in progress.
+    ///
+    /// # Safety
+    ///
+    /// Must be called before the pool is destroyed, with the allocator the pool belongs to.
+    pub unsafe fn unregister_pool(
+        &mut self,
+        allocator: &Allocator,
+        pool: AllocatorPool,
+    ) -> VkResult<()> {
+        self.pools.retain(|&registered| registered != pool);
+        match self.current.take() {
+            Some((current, mut context)) if current == pool => {
+                allocator.end_defragmentation(&mut context).map(|_| ())
+            }
+            current => {
+                self.current = current;
+                Ok(())
+            }
+        }
+    }
+
+    /// Fraction of the memory blocks of `pool` not used by allocations, from 0 to 1. Pools with a
+    /// single block are never fragmented, as defragmentation couldn't release it.
+    pub fn fragmentation(allocator: &Allocator, pool: AllocatorPool) -> f32 {
+        let statistics = allocator.get_pool_statistics(&pool);
+        if statistics.block_count <= 1 || statistics.block_bytes == 0 {
+            return 0.0;
+        }
+        1.0 - statistics.allocation_bytes as f32 / statistics.block_bytes as f32
+    }
+
+    /// Pool whose defragmentation is in progress, if any.
+    pub fn current_pool(&self) -> Option<AllocatorPool> {
+        self.current.as_ref().map(|(pool, _)| *pool)
+    }
+
+    /// Performs this frame's share of defragmentation, moving the resources described by `handler`
+    /// like `run`. Returns the statistics of a pool's defragmentation when it finishes this frame.
+    ///
+    /// # Safety
+    ///
+    /// Same as `run`: the device must not use any resource that may be moved during the call.
+    pub unsafe fn run_frame(
+        &mut self,
+        allocator: &Allocator,
+        device: &ash::Device,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+        handler: &mut impl DefragHandler,
+    ) -> VkResult<Option<(AllocatorPool, DefragmentationStats)>> {
+        if self.current.is_none() {
+            let candidate = self
+                .pools
+                .iter()
+                .map(|&pool| (pool, Self::fragmentation(allocator, pool)))
+                .filter(|&(_, fragmentation)| fragmentation >= self.min_fragmentation)
+                .max_by(|(_, a), (_, b)| a.total_cmp(b));
+            let pool = match candidate {
+                Some((pool, _)) => pool,
+                None => return Ok(None),
+            };
+            let context = allocator.begin_defragmentation(&DefragmentationInfo {
+                flags: self.flags,
+                pool: Some(pool),
+                max_bytes_per_pass: self.max_bytes_per_frame,
+                max_allocations_per_pass: self.max_moves_per_frame,
+            })?;
+            self.current = Some((pool, context));
+        }
+
+        let transfer = TransferQueue {
+            device,
+            queue,
+            command_pool,
+        };
+        let (pool, context) = self.current.as_mut().unwrap();
+        let pool = *pool;
+        let more = run_pass(allocator, &transfer, context, handler);
+        if let Ok(true) = more {
+            return Ok(None);
+        }
+
+        let (_, mut context) = self.current.take().unwrap();
+        let stats = allocator.end_defragmentation(&mut context)?;
+        more.map(|_| Some((pool, stats)))
+    }
+}
@@ -0,0 +1,236 @@
+//! Backing memory for `VK_EXT_descriptor_buffer`.
+//!
+//! `DescriptorBuffer` creates a persistently mapped buffer with the descriptor buffer usage flags
+//! and the device address the extension binds it by, aligned to `descriptorBufferOffsetAlignment`.
+//! Descriptor sets are placed with `DescriptorBuffer::allocate_set`, which returns a
+//! `DescriptorSetWriter` to copy descriptors fetched with `vkGetDescriptorEXT` to the binding
+//! offsets reported by `vkGetDescriptorSetLayoutBindingOffsetEXT`.
+//!
+//! The usage flags are defined here, so the helpers work with versions of ash that predate the
+//! extension; with the `ash_0_37` or `ash_0_38` feature, `DescriptorBufferProperties` converts
+//! from `ash::vk::PhysicalDeviceDescriptorBufferPropertiesEXT`.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{
+    Allocation, AllocationCreateFlags, AllocationCreateInfo, Allocator, AllocatorCreateFlags,
+    Error, MemoryUsage, Result,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// `VK_BUFFER_USAGE_SAMPLER_DESCRIPTOR_BUFFER_BIT_EXT`: the buffer holds sampler and combined
+/// image sampler descriptors.
+pub const SAMPLER_DESCRIPTOR_BUFFER: vk::BufferUsageFlags =
+    vk::BufferUsageFlags::from_raw(0x0020_0000);
+
+/// `VK_BUFFER_USAGE_RESOURCE_DESCRIPTOR_BUFFER_BIT_EXT`: the buffer holds all other descriptors.
+pub const RESOURCE_DESCRIPTOR_BUFFER: vk::BufferUsageFlags =
+    vk::BufferUsageFlags::from_raw(0x0040_0000);
+
+/// `VK_BUFFER_USAGE_PUSH_DESCRIPTORS_DESCRIPTOR_BUFFER_BIT_EXT`: the buffer can be bound together
+/// with push descriptors.
+pub const PUSH_DESCRIPTORS_DESCRIPTOR_BUFFER: vk::BufferUsageFlags =
+    vk::BufferUsageFlags::from_raw(0x0400_0000);
+
+/// Limits of `VkPhysicalDeviceDescriptorBufferPropertiesEXT` the helpers need.
+#[derive(Debug, Clone, Copy)]
+pub struct DescriptorBufferProperties {
+    /// `descriptorBufferOffsetAlignment`: alignment of the buffer address and of set offsets.
+    pub offset_alignment: vk::DeviceSize,
+
+    /// `maxSamplerDescriptorBufferRange`.
+    pub max_sampler_range: vk::DeviceSize,
+
+    /// `maxResourceDescriptorBufferRange`.
+    pub max_resource_range: vk::DeviceSize,
+}
+
+#[cfg(any(feature = "ash_0_37", feature = "ash_0_38"))]
+impl<'a> From<&crate::ash_compat::PhysicalDeviceDescriptorBufferPropertiesEXT<'a>>
+    for DescriptorBufferProperties
+{
+    fn from(
+        properties: &crate::ash_compat::PhysicalDeviceDescriptorBufferPropertiesEXT<'a>,
+    ) -> Self {
+        DescriptorBufferProperties {
+            offset_alignment: properties.descriptor_buffer_offset_alignment,
+            max_sampler_range: properties.max_sampler_descriptor_buffer_range,
+            max_resource_range: properties.max_resource_descriptor_buffer_range,
+        }
+    }
+}
+
+/// A mapped buffer that descriptor sets are placed in linearly.
+pub struct DescriptorBuffer<'a> {
+    allocator: &'a Allocator,
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    address: vk::DeviceAddress,
+    mapped: *mut u8,
+    size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+    cursor: vk::DeviceSize,
+}
+
+impl<'a> DescriptorBuffer<'a> {
+    /// Creates a descriptor buffer of `size` bytes with `usage`, a combination of
+    /// `SAMPLER_DESCRIPTOR_BUFFER`, `RESOURCE_DESCRIPTOR_BUFFER` and
+    /// `PUSH_DESCRIPTORS_DESCRIPTOR_BUFFER`.
+    ///
+    /// The memory is host visible, preferably device local. The allocator must have been created
+    /// with `VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT`, otherwise
+    /// `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` is returned. A `size` beyond the range limit
+    /// of `properties` for `usage` fails with `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT`.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device `allocator` was created for, with the `descriptorBuffer` and
+    /// `bufferDeviceAddress` features enabled.
+    pub unsafe fn new(
+        allocator: &'a Allocator,
+        device: &ash::Device,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: &DescriptorBufferProperties,
+    ) -> Result<Self> {
+        const OPERATION: &str = "DescriptorBuffer::new";
+        if !allocator
+            .flags
+            .contains(AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT)
+        {
+            return Err(
+                Error::new(vk::Result::ERROR_FEATURE_NOT_PRESENT, OPERATION).with_size(size)
+            );
+        }
+        let max_range = if usage.contains(SAMPLER_DESCRIPTOR_BUFFER) {
+            properties.max_sampler_range
+        } else {
+            properties.max_resource_range
+        };
+        if size > max_range {
+            return Err(
+                Error::new(vk::Result::ERROR_VALIDATION_FAILED_EXT, OPERATION).with_size(size),
+            );
+        }
+
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(usage | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let allocation_info = AllocationCreateInfo {
+            flags: AllocationCreateFlags::MAPPED
+                | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            usage: MemoryUsage::AutoPreferDevice,
+            ..Default::default()
+        };
+        let alignment = properties.offset_alignment.max(1);
+        let (buffer, allocation, info) = allocator
+            .create_buffer_with_alignment(&buffer_info, &allocation_info, alignment)
+            .map_err(|result| Error::new(result, OPERATION).with_size(size))?;
+        let address = device
+            .get_buffer_device_address(&vk::BufferDeviceAddressInfo::builder().buffer(buffer));
+
+        Ok(DescriptorBuffer {
+            allocator,
+            buffer,
+            allocation,
+            address,
+            mapped: info.get_mapped_data(),
+            size,
+            alignment,
+            cursor: 0,
+        })
+    }
+
+    /// The buffer.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Device address to pass to `vkCmdBindDescriptorBuffersEXT`.
+    pub fn address(&self) -> vk::DeviceAddress {
+        self.address
+    }
+
+    /// Size of the buffer in bytes.
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// Number of bytes placed since the last `DescriptorBuffer::reset`, including alignment padding.
+    pub fn used(&self) -> vk::DeviceSize {
+        self.cursor
+    }
+
+    /// Reserves `set_size` bytes, as reported by `vkGetDescriptorSetLayoutSizeEXT`, at the next
+    /// aligned offset. Returns `None` if the buffer is full.
+    pub fn allocate_set(&mut self, set_size: vk::DeviceSize) -> Option<DescriptorSetWriter<'_>> {
+        let offset = self.cursor.div_ceil(self.alignment) * self.alignment;
+        if offset + set_size > self.size {
+            return None;
+        }
+        self.cursor = offset + set_size;
+        Some(DescriptorSetWriter {
+            mapped: unsafe {
+                std::slice::from_raw_parts_mut(self.mapped.add(offset as usize), set_size as usize)
+            },
+            offset,
+        })
+    }
+
+    /// Makes all sets placed so far available again, e.g. at the start of a frame.
+    ///
+    /// # Safety
+    ///
+    /// The device must be done with the descriptors placed so far.
+    pub unsafe fn reset(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// Flushes the descriptors placed since the last `DescriptorBuffer::reset` so the device sees
+    /// them. Does nothing if the memory is `ash::vk::MemoryPropertyFlags::HOST_COHERENT`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::flush_allocation`.
+    pub unsafe fn flush(&self) -> VkResult<()> {
+        self.allocator
+            .flush_allocation(&self.allocation, 0, self.cursor as usize)
+    }
+}
+
+impl<'a> Drop for DescriptorBuffer<'a> {
+    fn drop(&mut self) {
+        unsafe { self.allocator.destroy_buffer(self.buffer, &self.allocation) };
+    }
+}
+
+/// A descriptor set placed in a `DescriptorBuffer`.
+pub struct DescriptorSetWriter<'b> {
+    mapped: &'b mut [u8],
+    offset: vk::DeviceSize,
+}
+
+impl<'b> DescriptorSetWriter<'b> {
+    /// Offset of the set in the buffer, to pass to `vkCmdSetDescriptorBufferOffsetsEXT`.
+    pub fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    /// Copies `descriptor`, as written by `vkGetDescriptorEXT`, to `binding_offset` within the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the descriptor doesn't fit in the set.
+    pub fn write(&mut self, binding_offset: vk::DeviceSize, descriptor: &[u8]) {
+        let start = binding_offset as usize;
+        self.mapped[start..start + descriptor.len()].copy_from_slice(descriptor);
+    }
+
+    /// The memory of the set, for writing descriptors directly with `vkGetDescriptorEXT`.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.mapped
+    }
+}
@@ -0,0 +1,178 @@
+//! Per-frame uniform data bound with dynamic offsets.
+//!
+//! `DynamicUniformAllocator` owns one persistently mapped uniform buffer split into a region per
+//! frame in flight. Each `DynamicUniformAllocator::push` copies a value into the region of the
+//! current frame at the next `minUniformBufferOffsetAlignment` boundary and returns the offset to
+//! pass in `pDynamicOffsets` of `vkCmdBindDescriptorSets`, for a descriptor of type
+//! `UNIFORM_BUFFER_DYNAMIC` written with `DynamicUniformAllocator::descriptor_info`:
+//!
+//! ```ignore
+//! uniforms.begin_frame(frame_index);
+//! for object in &objects {
+//!     let offset = uniforms.push(&object.transform)?;
+//!     device.cmd_bind_descriptor_sets(cmd, GRAPHICS, layout, 0, &[set], &[offset]);
+//!     device.cmd_draw(cmd, ..);
+//! }
+//! uniforms.flush()?;
+//! ```
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::staging::align_up;
+use crate::{
+    Allocation, AllocationCreateFlags, AllocationCreateInfo, Allocator, Error, MemoryUsage, Result,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Suballocator of a mapped uniform buffer for dynamic offsets, with a region per frame in flight.
+///
+/// Data pushed during a frame stays valid until `DynamicUniformAllocator::begin_frame` comes
+/// back to the same region, `frames_in_flight` frames later. The caller must make sure the device
+/// is done with a frame by then, e.g. by waiting on that frame's fence.
+///
+/// The buffer is destroyed when the allocator is dropped.
+pub struct DynamicUniformAllocator<'a> {
+    allocator: &'a Allocator,
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    mapped: *mut u8,
+    alignment: vk::DeviceSize,
+    region_size: vk::DeviceSize,
+    frames_in_flight: u32,
+    region: u32,
+    cursor: vk::DeviceSize,
+}
+
+impl<'a> DynamicUniformAllocator<'a> {
+    /// Creates the buffer, with `bytes_per_frame` bytes for each of `frames_in_flight` frames.
+    ///
+    /// The memory is host visible, preferably device local. The first frame is frame 0.
+    pub fn new(
+        allocator: &'a Allocator,
+        frames_in_flight: u32,
+        bytes_per_frame: vk::DeviceSize,
+    ) -> Result<Self> {
+        const OPERATION: &str = "DynamicUniformAllocator::new";
+        let frames_in_flight = frames_in_flight.max(1);
+        let alignment = allocator.min_uniform_buffer_offset_alignment().max(1);
+        let region_size = align_up(bytes_per_frame.max(1), alignment);
+        let size = region_size * frames_in_flight as vk::DeviceSize;
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let allocation_info = AllocationCreateInfo {
+            flags: AllocationCreateFlags::MAPPED
+                | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            usage: MemoryUsage::AutoPreferDevice,
+            ..Default::default()
+        };
+        let (buffer, allocation, info) = allocator
+            .create_buffer_with_alignment(&buffer_info, &allocation_info, alignment)
+            .map_err(|result| Error::new(result, OPERATION).with_size(size))?;
+        Ok(DynamicUniformAllocator {
+            allocator,
+            buffer,
+            allocation,
+            mapped: info.get_mapped_data(),
+            alignment,
+            region_size,
+            frames_in_flight,
+            region: 0,
+            cursor: 0,
+        })
+    }
+
+    /// The uniform buffer.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Alignment of the returned offsets, `minUniformBufferOffsetAlignment` of the device.
+    pub fn alignment(&self) -> vk::DeviceSize {
+        self.alignment
+    }
+
+    /// Buffer info for a `UNIFORM_BUFFER_DYNAMIC` descriptor covering `range` bytes from each
+    /// dynamic offset, i.e. the size of the largest value bound through it.
+    pub fn descriptor_info(&self, range: vk::DeviceSize) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo {
+            buffer: self.buffer,
+            offset: 0,
+            range,
+        }
+    }
+
+    /// Starts pushing into the region of `frame_index`, discarding what was pushed into it
+    /// `frames_in_flight` frames ago.
+    pub fn begin_frame(&mut self, frame_index: u32) {
+        self.region = frame_index % self.frames_in_flight;
+        self.cursor = 0;
+    }
+
+    /// Number of bytes pushed in the current frame, alignment padding included.
+    pub fn used(&self) -> vk::DeviceSize {
+        self.cursor
+    }
+
+    /// Copies `value` into the current frame and returns its dynamic offset.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY` if the region of the frame is full.
+    pub fn push<T: Copy>(&mut self, value: &T) -> Result<u32> {
+        let size = std::mem::size_of::<T>();
+        let (offset, target) = self.allocate(size as vk::DeviceSize)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(value as *const T as *const u8, target.as_mut_ptr(), size)
+        };
+        Ok(offset)
+    }
+
+    /// Copies `bytes` into the current frame and returns their dynamic offset.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY` if the region of the frame is full.
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<u32> {
+        let (offset, target) = self.allocate(bytes.len() as vk::DeviceSize)?;
+        target.copy_from_slice(bytes);
+        Ok(offset)
+    }
+
+    /// Reserves `size` bytes in the current frame and returns their dynamic offset and the mapped
+    /// memory to write them to.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY` if the region of the frame is full.
+    pub fn allocate(&mut self, size: vk::DeviceSize) -> Result<(u32, &mut [u8])> {
+        let start = align_up(self.cursor, self.alignment);
+        if start + size > self.region_size {
+            return Err(Error::new(
+                vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+                "DynamicUniformAllocator::allocate",
+            )
+            .with_size(size));
+        }
+        self.cursor = start + size;
+        let offset = self.region as vk::DeviceSize * self.region_size + start;
+        let target = unsafe {
+            std::slice::from_raw_parts_mut(self.mapped.add(offset as usize), size as usize)
+        };
+        Ok((offset as u32, target))
+    }
+
+    /// Flushes what was pushed in the current frame so the device sees it. Does nothing if the
+    /// memory is `ash::vk::MemoryPropertyFlags::HOST_COHERENT`.
+    pub fn flush(&self) -> VkResult<()> {
+        let start = self.region as vk::DeviceSize * self.region_size;
+        unsafe {
+            self.allocator
+                .maybe_flush(&self.allocation, start..start + self.cursor)
+                .map(|_| ())
+        }
+    }
+}
+
+impl<'a> Drop for DynamicUniformAllocator<'a> {
+    fn drop(&mut self) {
+        unsafe { self.allocator.destroy_buffer(self.buffer, &self.allocation) };
+    }
+}
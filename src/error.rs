@@ -0,0 +1,99 @@
+//! Error type of the allocating functions.
+//!
+//! A bare `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY` says nothing about what was being allocated.
+//! `Error` keeps the `ash::vk::Result` returned by VMA together with the call that failed and, where it
+//! is known, the memory type and size of the request, and converts back into the `ash::vk::Result` so
+//! `?` keeps working in functions that return `ash::prelude::VkResult`.
+
+use ash::vk;
+use std::fmt;
+
+/// Result of the allocating functions of this crate.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Failure of an allocating function, with the context of the request.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("`{operation}` failed with {result}{}", Context(self))]
+pub struct Error {
+    result: vk::Result,
+    operation: &'static str,
+    memory_type_index: Option<u32>,
+    memory_type_bits: Option<u32>,
+    size: Option<vk::DeviceSize>,
+}
+
+impl Error {
+    pub(crate) fn new(result: vk::Result, operation: &'static str) -> Self {
+        Error {
+            result,
+            operation,
+            memory_type_index: None,
+            memory_type_bits: None,
+            size: None,
+        }
+    }
+
+    pub(crate) fn with_memory_type_index(mut self, memory_type_index: u32) -> Self {
+        self.memory_type_index = Some(memory_type_index);
+        self
+    }
+
+    pub(crate) fn with_memory_type_bits(mut self, memory_type_bits: u32) -> Self {
+        self.memory_type_bits = Some(memory_type_bits);
+        self
+    }
+
+    pub(crate) fn with_size(mut self, size: vk::DeviceSize) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Vulkan result returned by VMA.
+    pub fn result(&self) -> vk::Result {
+        self.result
+    }
+
+    /// Function that failed, e.g. `Allocator::create_buffer`.
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// Memory type the request was made in, if it was fixed by the request (e.g. for `Allocator::create_pool`).
+    pub fn memory_type_index(&self) -> Option<u32> {
+        self.memory_type_index
+    }
+
+    /// Memory types the request was allowed to use, if it was known.
+    pub fn memory_type_bits(&self) -> Option<u32> {
+        self.memory_type_bits
+    }
+
+    /// Requested size in bytes, if it was known.
+    pub fn size(&self) -> Option<vk::DeviceSize> {
+        self.size
+    }
+}
+
+impl From<Error> for vk::Result {
+    fn from(error: Error) -> Self {
+        error.result
+    }
+}
+
+/// Formats the optional fields of an `Error`.
+struct Context<'a>(&'a Error);
+
+impl fmt::Display for Context<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(size) = self.0.size {
+            write!(f, ", {} bytes", size)?;
+        }
+        if let Some(index) = self.0.memory_type_index {
+            write!(f, ", memory type {}", index)?;
+        }
+        if let Some(bits) = self.0.memory_type_bits {
+            write!(f, ", memory type bits {:#x}", bits)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+//! Reports explaining which memory type an allocation lands in, returned by
+//! `Allocator::explain_memory_type_choice`.
+//!
+//! The report mirrors how VMA picks a memory type: the usage and flags of the
+//! `AllocationCreateInfo` are turned into required, preferred and not preferred property flags,
+//! every memory type not excluded by a bitmask and having the required flags is a candidate, and
+//! the candidate missing the fewest preferred flags (and having the fewest not preferred ones) wins.
+//! Memory types whose heap is over budget are rejected too, since VMA falls back to the next
+//! candidate when an allocation there fails or, with `AllocationCreateFlags::WITHIN_BUDGET`, skips
+//! them right away.
+
+use crate::{format_bytes, AllocationCreateFlags, AllocationCreateInfo, MemoryUsage};
+use ash::vk;
+use std::fmt;
+
+/// Why a memory type can't be used for an allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The memory type bits of the resource don't include the type.
+    NotInResourceBits,
+
+    /// `AllocationCreateInfo::memory_type_bits` doesn't include the type.
+    ExcludedByMask,
+
+    /// The type uses `VK_AMD_device_coherent_memory`, which the allocator was not created with.
+    DeviceCoherentNotEnabled,
+
+    /// The type lacks these required property flags.
+    MissingRequiredFlags(vk::MemoryPropertyFlags),
+
+    /// The heap of the type has no budget left.
+    OverBudget {
+        /// Current usage of the heap in bytes.
+        usage: vk::DeviceSize,
+
+        /// Budget of the heap in bytes.
+        budget: vk::DeviceSize,
+    },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::NotInResourceBits => write!(f, "not allowed by the resource"),
+            Rejection::ExcludedByMask => write!(f, "excluded by memory_type_bits"),
+            Rejection::DeviceCoherentNotEnabled => {
+                write!(f, "device coherent memory is not enabled")
+            }
+            Rejection::MissingRequiredFlags(flags) => write!(f, "missing required {:?}", flags),
+            Rejection::OverBudget { usage, budget } => write!(
+                f,
+                "heap over budget ({} of {})",
+                format_bytes(*usage),
+                format_bytes(*budget)
+            ),
+        }
+    }
+}
+
+/// How a single memory type was judged.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryTypeVerdict {
+    /// Index of the memory type.
+    pub memory_type_index: u32,
+
+    /// Index of the heap the memory type belongs to.
+    pub heap_index: u32,
+
+    /// Property flags of the memory type.
+    pub property_flags: vk::MemoryPropertyFlags,
+
+    /// Why the type can't be used, or `None` if it is a candidate.
+    pub rejection: Option<Rejection>,
+
+    /// Preferred flags the type lacks.
+    pub missing_preferred_flags: vk::MemoryPropertyFlags,
+
+    /// Not preferred flags the type has.
+    pub unwanted_flags: vk::MemoryPropertyFlags,
+}
+
+impl MemoryTypeVerdict {
+    /// Number of preferred flags missing plus number of not preferred flags present. Among the
+    /// candidates, the one with the lowest cost is chosen.
+    pub fn cost(&self) -> u32 {
+        self.missing_preferred_flags.as_raw().count_ones()
+            + self.unwanted_flags.as_raw().count_ones()
+    }
+}
+
+/// Explanation of the memory type choice for an allocation.
+#[derive(Debug, Clone)]
+pub struct MemoryTypeReport {
+    /// Flags every candidate must have.
+    pub required_flags: vk::MemoryPropertyFlags,
+
+    /// Flags a candidate should have.
+    pub preferred_flags: vk::MemoryPropertyFlags,
+
+    /// Flags a candidate should not have.
+    pub not_preferred_flags: vk::MemoryPropertyFlags,
+
+    /// Verdict for every memory type, indexed by memory type index.
+    pub memory_types: Vec<MemoryTypeVerdict>,
+
+    /// The memory type the allocation is placed in first, or `None` if every type was rejected.
+    pub chosen: Option<u32>,
+}
+
+impl MemoryTypeReport {
+    /// Memory types that were rejected.
+    pub fn rejected(&self) -> impl Iterator<Item = &MemoryTypeVerdict> {
+        self.memory_types
+            .iter()
+            .filter(|verdict| verdict.rejection.is_some())
+    }
+}
+
+impl fmt::Display for MemoryTypeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "required {:?}, preferred {:?}, not preferred {:?}",
+            self.required_flags, self.preferred_flags, self.not_preferred_flags
+        )?;
+        for verdict in &self.memory_types {
+            write!(
+                f,
+                "memory type {} (heap {}, {:?}): ",
+                verdict.memory_type_index, verdict.heap_index, verdict.property_flags
+            )?;
+            match verdict.rejection {
+                Some(rejection) => write!(f, "rejected, {}", rejection)?,
+                None if Some(verdict.memory_type_index) == self.chosen => {
+                    write!(f, "chosen, cost {}", verdict.cost())?
+                }
+                None => write!(f, "candidate, cost {}", verdict.cost())?,
+            }
+            writeln!(f)?;
+        }
+        match self.chosen {
+            Some(index) => write!(f, "chosen memory type: {}", index),
+            None => write!(f, "no memory type is suitable"),
+        }
+    }
+}
+
+/// Required, preferred and not preferred flags VMA derives from `info`, for a resource whose usage
+/// is unknown. Follows `FindMemoryPreferences` of VMA.
+#[allow(deprecated)]
+pub(crate) fn memory_preferences(
+    info: &AllocationCreateInfo,
+    integrated_gpu: bool,
+) -> (
+    vk::MemoryPropertyFlags,
+    vk::MemoryPropertyFlags,
+    vk::MemoryPropertyFlags,
+) {
+    let mut required = info.required_flags;
+    let mut preferred = info.preferred_flags;
+    let mut not_preferred = vk::MemoryPropertyFlags::empty();
+    let prefer_device_local =
+        !integrated_gpu || !preferred.contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+
+    match info.usage {
+        MemoryUsage::GpuOnly => {
+            if prefer_device_local {
+                preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+            }
+        }
+        MemoryUsage::CpuOnly => {
+            required |=
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        }
+        MemoryUsage::CpuToGpu => {
+            required |= vk::MemoryPropertyFlags::HOST_VISIBLE;
+            if prefer_device_local {
+                preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+            }
+        }
+        MemoryUsage::GpuToCpu => {
+            required |= vk::MemoryPropertyFlags::HOST_VISIBLE;
+            preferred |= vk::MemoryPropertyFlags::HOST_CACHED;
+        }
+        MemoryUsage::CpuCopy => {
+            not_preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+        }
+        MemoryUsage::GpuLazilyAllocated => {
+            required |= vk::MemoryPropertyFlags::LAZILY_ALLOCATED;
+        }
+        MemoryUsage::Auto | MemoryUsage::AutoPreferDevice | MemoryUsage::AutoPreferHost => {
+            let prefer_host = info.usage == MemoryUsage::AutoPreferHost;
+            let allow_transfer_instead = info
+                .flags
+                .contains(AllocationCreateFlags::HOST_ACCESS_ALLOW_TRANSFER_INSTEAD);
+            if info
+                .flags
+                .contains(AllocationCreateFlags::HOST_ACCESS_RANDOM)
+            {
+                if !integrated_gpu && allow_transfer_instead && !prefer_host {
+                    preferred |= vk::MemoryPropertyFlags::HOST_VISIBLE
+                        | vk::MemoryPropertyFlags::HOST_CACHED;
+                } else {
+                    required |= vk::MemoryPropertyFlags::HOST_VISIBLE
+                        | vk::MemoryPropertyFlags::HOST_CACHED;
+                }
+            } else if info
+                .flags
+                .contains(AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE)
+            {
+                not_preferred |= vk::MemoryPropertyFlags::HOST_CACHED;
+                if !integrated_gpu && allow_transfer_instead && !prefer_host {
+                    preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL
+                        | vk::MemoryPropertyFlags::HOST_VISIBLE;
+                } else {
+                    // The resource is assumed to be accessed by the device.
+                    required |= vk::MemoryPropertyFlags::HOST_VISIBLE;
+                    if prefer_host {
+                        not_preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+                    } else {
+                        preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+                    }
+                }
+            } else if prefer_host {
+                not_preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+            } else {
+                preferred |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+            }
+        }
+        MemoryUsage::Unknown | MemoryUsage::MaxEnum => {}
+    }
+
+    // Device coherent memory is slow and only picked when asked for explicitly.
+    let amd_flags =
+        vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD | vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD;
+    if !(required | preferred).intersects(amd_flags) {
+        not_preferred |= vk::MemoryPropertyFlags::DEVICE_UNCACHED_AMD;
+    }
+    (required, preferred, not_preferred)
+}
+
+/// Heap usage and budget of a memory type, as `(usage, budget)`.
+pub(crate) type HeapBudget = (vk::DeviceSize, vk::DeviceSize);
+
+/// Judges every memory type of `properties` for an allocation with the given flags.
+pub(crate) fn explain(
+    properties: &vk::PhysicalDeviceMemoryProperties,
+    heap_budgets: &[HeapBudget],
+    device_coherent_enabled: bool,
+    resource_bits: u32,
+    mask_bits: u32,
+    (required, preferred, not_preferred): (
+        vk::MemoryPropertyFlags,
+        vk::MemoryPropertyFlags,
+        vk::MemoryPropertyFlags,
+    ),
+) -> MemoryTypeReport {
+    let memory_types: Vec<MemoryTypeVerdict> = properties.memory_types
+        [..properties.memory_type_count as usize]
+        .iter()
+        .enumerate()
+        .map(|(index, memory_type)| {
+            let bit = 1u32 << index;
+            let flags = memory_type.property_flags;
+            let (usage, budget) = heap_budgets
+                .get(memory_type.heap_index as usize)
+                .copied()
+                .unwrap_or((0, vk::DeviceSize::MAX));
+            let rejection = if resource_bits & bit == 0 {
+                Some(Rejection::NotInResourceBits)
+            } else if mask_bits != 0 && mask_bits & bit == 0 {
+                Some(Rejection::ExcludedByMask)
+            } else if !device_coherent_enabled
+                && flags.contains(vk::MemoryPropertyFlags::DEVICE_COHERENT_AMD)
+            {
+                Some(Rejection::DeviceCoherentNotEnabled)
+            } else if !flags.contains(required) {
+                Some(Rejection::MissingRequiredFlags(required & !flags))
+            } else if usage >= budget {
+                Some(Rejection::OverBudget { usage, budget })
+            } else {
+                None
+            };
+            MemoryTypeVerdict {
+                memory_type_index: index as u32,
+                heap_index: memory_type.heap_index,
+                property_flags: flags,
+                rejection,
+                missing_preferred_flags: preferred & !flags,
+                unwanted_flags: not_preferred & flags,
+            }
+        })
+        .collect();
+
+    // The first of the cheapest candidates wins, like in VMA.
+    let chosen = memory_types
+        .iter()
+        .filter(|verdict| verdict.rejection.is_none())
+        .min_by_key(|verdict| verdict.cost())
+        .map(|verdict| verdict.memory_type_index);
+
+    MemoryTypeReport {
+        required_flags: required,
+        preferred_flags: preferred,
+        not_preferred_flags: not_preferred,
+        memory_types,
+        chosen,
+    }
+}
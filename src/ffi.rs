@@ -1,6 +0,0 @@
-#![allow(non_upper_case_globals)]
-#![allow(non_camel_case_types)]
-#![allow(non_snake_case)]
-#![cfg_attr(feature = "cargo-clippy", allow(clippy::unreadable_literal))]
-
-include!("../gen/bindings.rs");
@@ -0,0 +1,16 @@
+//! Allocations, buffers and images: creation, mapping, binding and cache maintenance.
+
+pub use super::{
+    vmaAllocateMemory, vmaAllocateMemoryForBuffer, vmaAllocateMemoryForImage,
+    vmaAllocateMemoryPages, vmaBindBufferMemory, vmaBindBufferMemory2, vmaBindImageMemory,
+    vmaBindImageMemory2, vmaCopyAllocationToMemory, vmaCopyMemoryToAllocation,
+    vmaCreateAliasingBuffer, vmaCreateAliasingImage, vmaCreateBuffer, vmaCreateBufferWithAlignment,
+    vmaCreateImage, vmaDestroyBuffer, vmaDestroyImage, vmaFlushAllocation, vmaFlushAllocations,
+    vmaFreeMemory, vmaFreeMemoryPages, vmaGetAllocationInfo, vmaGetAllocationMemoryProperties,
+    vmaInvalidateAllocation, vmaInvalidateAllocations, vmaMapMemory, vmaSetAllocationName,
+    vmaSetAllocationUserData, vmaUnmapMemory, VmaAllocation, VmaAllocationCreateFlags,
+    VmaAllocationCreateInfo, VmaAllocationInfo, VmaMemoryUsage,
+};
+
+#[cfg(windows)]
+pub use super::vmaGetMemoryWin32Handle;
@@ -0,0 +1,11 @@
+//! Creation of the allocator, memory properties, statistics and budgets.
+
+pub use super::{
+    vmaBuildStatsString, vmaCalculateStatistics, vmaCheckCorruption, vmaCreateAllocator,
+    vmaDestroyAllocator, vmaFindMemoryTypeIndex, vmaFindMemoryTypeIndexForBufferInfo,
+    vmaFindMemoryTypeIndexForImageInfo, vmaFreeStatsString, vmaGetAllocatorInfo, vmaGetHeapBudgets,
+    vmaGetMemoryProperties, vmaGetMemoryTypeProperties, vmaGetPhysicalDeviceProperties,
+    vmaSetCurrentFrameIndex, VmaAllocator, VmaAllocatorCreateFlags, VmaAllocatorCreateInfo,
+    VmaAllocatorInfo, VmaBudget, VmaDetailedStatistics, VmaDeviceMemoryCallbacks, VmaStatistics,
+    VmaTotalStatistics, VmaVulkanFunctions,
+};
@@ -0,0 +1,8 @@
+//! Defragmentation.
+
+pub use super::{
+    vmaBeginDefragmentation, vmaBeginDefragmentationPass, vmaEndDefragmentation,
+    vmaEndDefragmentationPass, VmaDefragmentationContext, VmaDefragmentationFlags,
+    VmaDefragmentationInfo, VmaDefragmentationMove, VmaDefragmentationMoveOperation,
+    VmaDefragmentationPassMoveInfo, VmaDefragmentationStats,
+};
@@ -0,0 +1,18 @@
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![cfg_attr(feature = "cargo-clippy", allow(clippy::unreadable_literal))]
+
+//! Raw bindings to VMA.
+//!
+//! Everything is generated into this module. The submodules re-export the functions and types of
+//! one part of the API each, which is also how `tests/ffi_coverage.rs` checks that every VMA
+//! function is either wrapped or explicitly tracked as not wrapped yet.
+
+include!("../../gen/bindings.rs");
+
+pub mod allocation;
+pub mod allocator;
+pub mod defrag;
+pub mod pool;
+pub mod virtual_block;
@@ -0,0 +1,7 @@
+//! Custom pools.
+
+pub use super::{
+    vmaCalculatePoolStatistics, vmaCheckPoolCorruption, vmaCreatePool, vmaDestroyPool,
+    vmaGetPoolName, vmaGetPoolStatistics, vmaSetPoolName, VmaPool, VmaPoolCreateFlags,
+    VmaPoolCreateInfo,
+};
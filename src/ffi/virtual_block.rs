@@ -0,0 +1,10 @@
+//! Virtual blocks: VMA's allocation algorithms applied to a range that isn't backed by memory.
+
+pub use super::{
+    vmaBuildVirtualBlockStatsString, vmaCalculateVirtualBlockStatistics, vmaClearVirtualBlock,
+    vmaCreateVirtualBlock, vmaDestroyVirtualBlock, vmaFreeVirtualBlockStatsString,
+    vmaGetVirtualAllocationInfo, vmaGetVirtualBlockStatistics, vmaIsVirtualBlockEmpty,
+    vmaSetVirtualAllocationUserData, vmaVirtualAllocate, vmaVirtualFree, VmaVirtualAllocation,
+    VmaVirtualAllocationCreateFlags, VmaVirtualAllocationCreateInfo, VmaVirtualAllocationInfo,
+    VmaVirtualBlock, VmaVirtualBlockCreateFlags, VmaVirtualBlockCreateInfo,
+};
@@ -0,0 +1,236 @@
+//! Frame boundaries, started with `Allocator::begin_frame`.
+//!
+//! A `FrameGuard` sets the current frame index of the allocator when it is created and performs
+//! the end-of-frame bookkeeping when it is dropped:
+//!
+//! - `FrameHook`s registered with `Allocator::add_frame_hook` are notified at both ends of the
+//!   frame, so subsystems like transient pools can reset themselves at the frame boundary.
+//! - Resources passed to `FrameGuard::defer_free` and friends are freed once the frame that
+//!   deferred them and `Allocator::set_frames_in_flight` more frames have ended, when the device
+//!   can no longer use them.
+//! - With the `metrics` feature, the heap budget gauges are refreshed.
+//!
+//! ```ignore
+//! loop {
+//!     let frame = unsafe { allocator.begin_frame(frame_index) };
+//!     record_and_submit(&frame);
+//!     unsafe { frame.defer_destroy_buffer(old_buffer, &old_allocation) };
+//!     frame_index += 1;
+//! } // the guard is dropped here, ending the frame
+//! ```
+
+use crate::{Allocation, Allocator};
+use ash::vk;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Number of frames a deferred resource is kept alive for, unless changed with
+/// `Allocator::set_frames_in_flight`.
+pub const DEFAULT_FRAMES_IN_FLIGHT: u32 = 2;
+
+/// A subsystem that is notified of frame boundaries.
+pub trait FrameHook: Send + Sync {
+    /// Called by `Allocator::begin_frame`, after the current frame index was set.
+    fn begin_frame(&self, _allocator: &Allocator, _frame_index: u32) {}
+
+    /// Called when the `FrameGuard` of the frame is dropped, before deferred resources are freed.
+    fn end_frame(&self, _allocator: &Allocator, _frame_index: u32) {}
+}
+
+/// A resource whose destruction was deferred.
+#[derive(Debug, Clone, Copy)]
+pub enum Deferred {
+    /// Memory allocated with `Allocator::allocate_memory` and friends, freed with
+    /// `Allocator::free_memory`.
+    Memory(Allocation),
+
+    /// A buffer and its allocation, destroyed with `Allocator::destroy_buffer`.
+    Buffer(vk::Buffer, Allocation),
+
+    /// An image and its allocation, destroyed with `Allocator::destroy_image`.
+    Image(vk::Image, Allocation),
+}
+
+impl Deferred {
+    pub(crate) unsafe fn destroy(self, allocator: &Allocator) {
+        match self {
+            Deferred::Memory(allocation) => allocator.free_memory(&allocation),
+            Deferred::Buffer(buffer, allocation) => allocator.destroy_buffer(buffer, &allocation),
+            Deferred::Image(image, allocation) => allocator.destroy_image(image, &allocation),
+        }
+    }
+}
+
+struct State {
+    hooks: Vec<Arc<dyn FrameHook>>,
+    frames_in_flight: u32,
+    /// Number of frames ended so far.
+    ended_frames: u64,
+    /// Deferred resources with the value of `ended_frames` when they were queued.
+    deferred: Vec<(u64, Deferred)>,
+}
+
+/// Frame hooks and deferred resources, shared between clones of an `Allocator`.
+pub(crate) struct FrameState {
+    state: Mutex<State>,
+}
+
+impl Default for FrameState {
+    fn default() -> Self {
+        FrameState {
+            state: Mutex::new(State {
+                hooks: Vec::new(),
+                frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+                ended_frames: 0,
+                deferred: Vec::new(),
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for FrameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("FrameState")
+            .field("hooks", &state.hooks.len())
+            .field("frames_in_flight", &state.frames_in_flight)
+            .field("ended_frames", &state.ended_frames)
+            .field("deferred", &state.deferred.len())
+            .finish()
+    }
+}
+
+impl FrameState {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn add_hook(&self, hook: Arc<dyn FrameHook>) {
+        self.state().hooks.push(hook);
+    }
+
+    pub(crate) fn remove_hook(&self, hook: &Arc<dyn FrameHook>) {
+        self.state()
+            .hooks
+            .retain(|registered| !Arc::ptr_eq(registered, hook));
+    }
+
+    pub(crate) fn set_frames_in_flight(&self, frames: u32) {
+        self.state().frames_in_flight = frames;
+    }
+
+    /// The hooks, cloned so they can be called without holding the lock.
+    fn hooks(&self) -> Vec<Arc<dyn FrameHook>> {
+        self.state().hooks.clone()
+    }
+
+    fn defer(&self, resource: Deferred) {
+        let mut state = self.state();
+        let ended_frames = state.ended_frames;
+        state.deferred.push((ended_frames, resource));
+    }
+
+    /// Ends a frame and removes the deferred resources that are no longer in flight.
+    fn end_frame(&self) -> Vec<Deferred> {
+        let mut state = self.state();
+        state.ended_frames += 1;
+        let ended_frames = state.ended_frames;
+        let frames_in_flight = state.frames_in_flight as u64;
+        let mut expired = Vec::new();
+        // `queued` frames had ended when the resource was deferred, so the frame that deferred it
+        // is frame `queued + 1`. The device may use the resource until `frames_in_flight` frames
+        // after that one have ended too.
+        state.deferred.retain(|&(queued, resource)| {
+            let done = ended_frames - queued > frames_in_flight;
+            if done {
+                expired.push(resource);
+            }
+            !done
+        });
+        expired
+    }
+
+    /// Frees all deferred resources regardless of frames in flight, when the allocator is
+    /// destroyed.
+    pub(crate) unsafe fn free_all(&self, allocator: &Allocator) {
+        let deferred = std::mem::take(&mut self.state().deferred);
+        for (_, resource) in deferred {
+            resource.destroy(allocator);
+        }
+    }
+}
+
+/// A frame started with `Allocator::begin_frame`, which ends when this is dropped.
+pub struct FrameGuard<'a> {
+    allocator: &'a Allocator,
+    frame_index: u32,
+}
+
+impl<'a> FrameGuard<'a> {
+    pub(crate) fn new(allocator: &'a Allocator, frame_index: u32) -> Self {
+        for hook in allocator.frames.hooks() {
+            hook.begin_frame(allocator, frame_index);
+        }
+        FrameGuard {
+            allocator,
+            frame_index,
+        }
+    }
+
+    /// Index of the frame.
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    /// The allocator the frame belongs to.
+    pub fn allocator(&self) -> &'a Allocator {
+        self.allocator
+    }
+
+    /// Frees `allocation` once the frames in flight have ended.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must not be used by the host afterwards, and the device must be done with it
+    /// after `Allocator::set_frames_in_flight` more frames.
+    pub unsafe fn defer_free(&self, allocation: &Allocation) {
+        self.allocator.frames.defer(Deferred::Memory(*allocation));
+    }
+
+    /// Destroys `buffer` and frees `allocation` once the frames in flight have ended.
+    ///
+    /// # Safety
+    ///
+    /// See `FrameGuard::defer_free`.
+    pub unsafe fn defer_destroy_buffer(&self, buffer: vk::Buffer, allocation: &Allocation) {
+        self.allocator
+            .frames
+            .defer(Deferred::Buffer(buffer, *allocation));
+    }
+
+    /// Destroys `image` and frees `allocation` once the frames in flight have ended.
+    ///
+    /// # Safety
+    ///
+    /// See `FrameGuard::defer_free`.
+    pub unsafe fn defer_destroy_image(&self, image: vk::Image, allocation: &Allocation) {
+        self.allocator
+            .frames
+            .defer(Deferred::Image(image, *allocation));
+    }
+}
+
+impl<'a> Drop for FrameGuard<'a> {
+    fn drop(&mut self) {
+        for hook in self.allocator.frames.hooks() {
+            hook.end_frame(self.allocator, self.frame_index);
+        }
+        for resource in self.allocator.frames.end_frame() {
+            unsafe { resource.destroy(self.allocator) };
+        }
+        #[cfg(feature = "metrics")]
+        self.allocator.publish_metrics();
+    }
+}
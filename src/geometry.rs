@@ -0,0 +1,313 @@
+//! Vertex and index data of many meshes in one large buffer.
+//!
+//! `GeometryPool` owns a device-local buffer and hands out a range of it per mesh, keeping a
+//! coalesced list of the free ranges in between. As meshes come and go the free space fragments;
+//! `GeometryPool::compact` moves meshes from the end of the buffer into free ranges closer to the
+//! start, a few at a time, and describes the moves as copy regions to record with
+//! `vkCmdCopyBuffer` and as `Remap` events telling the application where its meshes went:
+//!
+//! ```ignore
+//! let compaction = geometry.compact(16 * 1024 * 1024);
+//! if !compaction.is_empty() {
+//!     device.cmd_copy_buffer(cmd, geometry.buffer(), geometry.buffer(), compaction.regions());
+//!     for remap in compaction.remaps() {
+//!         meshes[&remap.mesh].offset = remap.new_offset;
+//!     }
+//!     // once the copy has completed on the device:
+//!     geometry.finish_compaction(compaction);
+//! }
+//! ```
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::staging::align_up;
+use crate::{Allocation, AllocationCreateInfo, Allocator, Error, MemoryUsage, Result};
+use ash::vk;
+use std::collections::{BTreeMap, HashMap};
+
+/// Handle to the range of a mesh in a `GeometryPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MeshId(u64);
+
+/// Range of a mesh in the buffer of a `GeometryPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshRange {
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+}
+
+/// A mesh moved by `GeometryPool::compact`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Remap {
+    pub mesh: MeshId,
+    pub old_offset: vk::DeviceSize,
+    pub new_offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+}
+
+/// Moves planned by `GeometryPool::compact`.
+///
+/// The ranges the meshes were moved out of stay reserved until the compaction is handed back to
+/// `GeometryPool::finish_compaction`, so nothing overwrites them before the copy has read them.
+#[derive(Debug, Default)]
+pub struct Compaction {
+    regions: Vec<vk::BufferCopy>,
+    remaps: Vec<Remap>,
+}
+
+impl Compaction {
+    /// Copy regions to record with `vkCmdCopyBuffer` from the buffer of the pool to itself.
+    /// Sources and destinations never overlap.
+    pub fn regions(&self) -> &[vk::BufferCopy] {
+        &self.regions
+    }
+
+    /// Meshes that were moved.
+    pub fn remaps(&self) -> &[Remap] {
+        &self.remaps
+    }
+
+    /// Whether nothing was moved.
+    pub fn is_empty(&self) -> bool {
+        self.remaps.is_empty()
+    }
+
+    /// Number of bytes moved.
+    pub fn moved_bytes(&self) -> vk::DeviceSize {
+        self.remaps.iter().map(|remap| remap.size).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Mesh {
+    range: MeshRange,
+    alignment: vk::DeviceSize,
+}
+
+/// Large vertex and index buffer suballocated per mesh.
+///
+/// The buffer is destroyed when the pool is dropped.
+pub struct GeometryPool<'a> {
+    allocator: &'a Allocator,
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    capacity: vk::DeviceSize,
+
+    /// Free ranges by offset, never adjacent to each other.
+    free: BTreeMap<vk::DeviceSize, vk::DeviceSize>,
+    meshes: HashMap<MeshId, Mesh>,
+    next_id: u64,
+    used: vk::DeviceSize,
+}
+
+impl<'a> GeometryPool<'a> {
+    /// Creates the device-local buffer of `capacity` bytes with `usage`, typically
+    /// `VERTEX_BUFFER | INDEX_BUFFER`. `TRANSFER_SRC` and `TRANSFER_DST` are added for uploads
+    /// and compaction.
+    pub fn new(
+        allocator: &'a Allocator,
+        capacity: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(capacity)
+            .usage(usage | vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let allocation_info = AllocationCreateInfo {
+            usage: MemoryUsage::AutoPreferDevice,
+            ..Default::default()
+        };
+        let (buffer, allocation, _) = unsafe {
+            allocator
+                .create_buffer(&buffer_info, &allocation_info)
+                .map_err(|error| {
+                    Error::new(error.result(), "GeometryPool::new").with_size(capacity)
+                })?
+        };
+        let mut free = BTreeMap::new();
+        if capacity > 0 {
+            free.insert(0, capacity);
+        }
+        Ok(GeometryPool {
+            allocator,
+            buffer,
+            allocation,
+            capacity,
+            free,
+            meshes: HashMap::new(),
+            next_id: 0,
+            used: 0,
+        })
+    }
+
+    /// The buffer holding all meshes.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Size of the buffer.
+    pub fn capacity(&self) -> vk::DeviceSize {
+        self.capacity
+    }
+
+    /// Bytes used by meshes, alignment padding excluded.
+    pub fn used(&self) -> vk::DeviceSize {
+        self.used
+    }
+
+    /// Size of the largest free range; a mesh larger than this can't be placed.
+    pub fn largest_free_range(&self) -> vk::DeviceSize {
+        self.free.values().copied().max().unwrap_or(0)
+    }
+
+    /// Number of free ranges, a measure of fragmentation.
+    pub fn free_range_count(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Places a mesh of `size` bytes at an offset that is a multiple of `alignment`, in the first
+    /// free range it fits in.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY` if no free range is large enough,
+    /// even though compaction might make room.
+    pub fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Result<(MeshId, MeshRange)> {
+        let alignment = alignment.max(1);
+        let offset = self
+            .take_free(size, alignment, self.capacity)
+            .ok_or_else(|| {
+                Error::new(
+                    vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+                    "GeometryPool::allocate",
+                )
+                .with_size(size)
+            })?;
+        let id = MeshId(self.next_id);
+        self.next_id += 1;
+        let range = MeshRange { offset, size };
+        self.meshes.insert(id, Mesh { range, alignment });
+        self.used += size;
+        Ok((id, range))
+    }
+
+    /// Current range of `mesh`, or `None` if it was freed.
+    pub fn range(&self, mesh: MeshId) -> Option<MeshRange> {
+        self.meshes.get(&mesh).map(|mesh| mesh.range)
+    }
+
+    /// Releases the range of `mesh`. Returns whether it was allocated.
+    ///
+    /// The device must be done with the range.
+    pub fn free(&mut self, mesh: MeshId) -> bool {
+        match self.meshes.remove(&mesh) {
+            Some(mesh) => {
+                self.used -= mesh.range.size;
+                self.release(mesh.range.offset, mesh.range.size);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Plans moving meshes from the end of the buffer into free ranges closer to its start,
+    /// until about `max_bytes` have been moved.
+    ///
+    /// Meshes are moved last first, each into the first free range below it that fits. The new
+    /// ranges are effective immediately: `GeometryPool::range` returns them, and the copy must be
+    /// recorded before any command that reads a moved mesh at its new offset. Commands recorded
+    /// before the copy may still use the old offsets.
+    pub fn compact(&mut self, max_bytes: vk::DeviceSize) -> Compaction {
+        let mut compaction = Compaction::default();
+        let mut meshes: Vec<(MeshId, Mesh)> =
+            self.meshes.iter().map(|(&id, &mesh)| (id, mesh)).collect();
+        meshes.sort_by_key(|(_, mesh)| std::cmp::Reverse(mesh.range.offset));
+
+        let mut moved = 0;
+        for (id, mesh) in meshes {
+            if moved >= max_bytes {
+                break;
+            }
+            let old_offset = mesh.range.offset;
+            let size = mesh.range.size;
+            let new_offset = match self.take_free(size, mesh.alignment, old_offset) {
+                Some(new_offset) => new_offset,
+                None => continue,
+            };
+            compaction.regions.push(vk::BufferCopy {
+                src_offset: old_offset,
+                dst_offset: new_offset,
+                size,
+            });
+            compaction.remaps.push(Remap {
+                mesh: id,
+                old_offset,
+                new_offset,
+                size,
+            });
+            if let Some(mesh) = self.meshes.get_mut(&id) {
+                mesh.range.offset = new_offset;
+            }
+            moved += size;
+        }
+        compaction
+    }
+
+    /// Releases the ranges meshes were moved out of by `compaction`.
+    ///
+    /// The copy of the compaction must have completed on the device.
+    pub fn finish_compaction(&mut self, compaction: Compaction) {
+        for remap in compaction.remaps {
+            self.release(remap.old_offset, remap.size);
+        }
+    }
+
+    /// Takes `size` bytes aligned to `alignment` from the first free range that fits, ending at
+    /// or before `limit`.
+    fn take_free(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+        limit: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let size = size.max(1);
+        let (start, free_size, offset) = self.free.iter().find_map(|(&start, &free_size)| {
+            let offset = align_up(start, alignment);
+            (offset + size <= (start + free_size).min(limit)).then_some((start, free_size, offset))
+        })?;
+        self.free.remove(&start);
+        if offset > start {
+            self.free.insert(start, offset - start);
+        }
+        let end = offset + size;
+        if end < start + free_size {
+            self.free.insert(end, start + free_size - end);
+        }
+        Some(offset)
+    }
+
+    /// Returns a range to the free list, merging it with its neighbors.
+    fn release(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let mut start = offset;
+        let mut end = offset + size.max(1);
+        if let Some((&previous, &previous_size)) = self.free.range(..start).next_back() {
+            if previous + previous_size == start {
+                self.free.remove(&previous);
+                start = previous;
+            }
+        }
+        if let Some(next_size) = self.free.remove(&end) {
+            end += next_size;
+        }
+        self.free.insert(start, end - start);
+    }
+}
+
+impl<'a> Drop for GeometryPool<'a> {
+    fn drop(&mut self) {
+        unsafe { self.allocator.destroy_buffer(self.buffer, &self.allocation) };
+    }
+}
@@ -0,0 +1,212 @@
+//! One allocator per device, for renderers driving several GPUs explicitly.
+//!
+//! VMA allocators are bound to a single `ash::vk::Device`. `AllocatorGroup` creates one `Allocator`
+//! per device of an instance from a shared `GroupConfig`, routes allocations by device index and
+//! sums statistics and budgets over all devices.
+
+use crate::{
+    Allocation, AllocationCreateInfo, AllocationInfo, Allocator, AllocatorCreateFlags,
+    AllocatorCreateInfo, Budget, Result, Statistics,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Configuration shared by all allocators of an `AllocatorGroup`, the parameters of
+/// `AllocatorCreateInfo` that don't depend on the device.
+#[derive(Clone, Copy, Default)]
+pub struct GroupConfig {
+    /// Flags of every allocator.
+    pub flags: AllocatorCreateFlags,
+
+    /// Preferred size of a `ash::vk::DeviceMemory` block on large heaps, or 0 for the default.
+    pub preferred_large_heap_block_size: vk::DeviceSize,
+
+    /// Vulkan API version the instance and devices were created with.
+    pub vulkan_api_version: u32,
+
+    /// `vkGetInstanceProcAddr` of the loader the instance was created with. See
+    /// `AllocatorCreateInfo::get_instance_proc_addr`.
+    pub get_instance_proc_addr: Option<vk::PFN_vkGetInstanceProcAddr>,
+}
+
+/// A device of an `AllocatorGroup`.
+#[derive(Clone)]
+pub struct GroupDevice {
+    /// Physical device `device` was created from.
+    pub physical_device: vk::PhysicalDevice,
+
+    /// Logical device to allocate memory from.
+    pub device: ash::Device,
+}
+
+/// Usage of one device of an `AllocatorGroup`.
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceBudget {
+    /// Index of the device in the group.
+    pub device_index: usize,
+
+    /// Summed statistics of all memory heaps of the device.
+    pub statistics: Statistics,
+
+    /// Summed usage of all memory heaps of the device, in bytes.
+    pub usage: vk::DeviceSize,
+
+    /// Summed budget of all memory heaps of the device, in bytes.
+    pub budget: vk::DeviceSize,
+}
+
+/// One `Allocator` per device, created with the same configuration.
+///
+/// Devices are identified by their index in the slice passed to `AllocatorGroup::new`.
+pub struct AllocatorGroup {
+    allocators: Vec<Allocator>,
+}
+
+impl AllocatorGroup {
+    /// Creates an allocator for each of `devices`, in order.
+    ///
+    /// If creating any of them fails, the ones created before are destroyed and the error is
+    /// returned.
+    ///
+    /// # Safety
+    ///
+    /// Every device must have been created from its physical device of `instance`, and `instance`
+    /// and all devices must outlive the group.
+    pub unsafe fn new(
+        instance: &ash::Instance,
+        devices: &[GroupDevice],
+        config: &GroupConfig,
+    ) -> VkResult<AllocatorGroup> {
+        let allocators = devices
+            .iter()
+            .map(|device| {
+                Allocator::new(&AllocatorCreateInfo {
+                    flags: config.flags,
+                    physical_device: device.physical_device,
+                    device: device.device.clone(),
+                    preferred_large_heap_block_size: config.preferred_large_heap_block_size,
+                    allocation_callbacks: None,
+                    device_memory_callbacks: None,
+                    heap_size_limit: None,
+                    heap_size_limit_fractions: None,
+                    vulkan_functions: None,
+                    get_instance_proc_addr: config.get_instance_proc_addr,
+                    instance: instance.clone(),
+                    vulkan_api_version: config.vulkan_api_version,
+                    api_version_from_instance: false,
+                    external_memory_handle_type: std::ptr::null(),
+                })
+            })
+            .collect::<VkResult<Vec<_>>>()?;
+        Ok(AllocatorGroup { allocators })
+    }
+
+    /// Number of devices in the group.
+    pub fn len(&self) -> usize {
+        self.allocators.len()
+    }
+
+    /// Whether the group has no devices.
+    pub fn is_empty(&self) -> bool {
+        self.allocators.is_empty()
+    }
+
+    /// The allocator of device `device_index`.
+    ///
+    /// Panics if `device_index` is out of range.
+    pub fn allocator(&self, device_index: usize) -> &Allocator {
+        &self.allocators[device_index]
+    }
+
+    /// The allocators of all devices, in device order.
+    pub fn allocators(&self) -> &[Allocator] {
+        &self.allocators
+    }
+
+    /// `Allocator::allocate_memory` on device `device_index`.
+    ///
+    /// # Safety
+    ///
+    /// See `Allocator::allocate_memory`. The requirements must come from that device.
+    pub unsafe fn allocate_memory(
+        &self,
+        device_index: usize,
+        memory_requirements: &vk::MemoryRequirements,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(Allocation, AllocationInfo)> {
+        self.allocator(device_index)
+            .allocate_memory(memory_requirements, allocation_info)
+    }
+
+    /// `Allocator::create_buffer` on device `device_index`.
+    ///
+    /// # Safety
+    ///
+    /// See `Allocator::create_buffer`.
+    pub unsafe fn create_buffer(
+        &self,
+        device_index: usize,
+        buffer_info: &vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Buffer, Allocation, AllocationInfo)> {
+        self.allocator(device_index)
+            .create_buffer(buffer_info, allocation_info)
+    }
+
+    /// `Allocator::create_image` on device `device_index`.
+    ///
+    /// # Safety
+    ///
+    /// See `Allocator::create_image`.
+    pub unsafe fn create_image(
+        &self,
+        device_index: usize,
+        image_info: &vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Image, Allocation, AllocationInfo)> {
+        self.allocator(device_index)
+            .create_image(image_info, allocation_info)
+    }
+
+    /// Heap budgets of device `device_index`, one per memory heap.
+    pub fn get_heap_budgets(&self, device_index: usize) -> Vec<Budget> {
+        let allocator = self.allocator(device_index);
+        let heap_count = unsafe { allocator.get_memory_properties() }
+            .map_or(0, |properties| properties.memory_heap_count as usize);
+        allocator.get_heap_budgets(heap_count)
+    }
+
+    /// Usage and budget of every device, summed over its memory heaps.
+    pub fn device_budgets(&self) -> Vec<DeviceBudget> {
+        (0..self.len())
+            .map(|device_index| {
+                let budgets = self.get_heap_budgets(device_index);
+                DeviceBudget {
+                    device_index,
+                    statistics: budgets.iter().map(|budget| budget.statistics).sum(),
+                    usage: budgets.iter().map(|budget| budget.usage).sum(),
+                    budget: budgets.iter().map(|budget| budget.budget).sum(),
+                }
+            })
+            .collect()
+    }
+
+    /// Statistics summed over all devices.
+    ///
+    /// Like `Allocator::get_heap_budgets` this is cheap enough to call every frame.
+    pub fn statistics(&self) -> Statistics {
+        self.device_budgets()
+            .iter()
+            .map(|device| device.statistics)
+            .sum()
+    }
+
+    /// Usage and budget summed over all devices, in bytes.
+    pub fn total_budget(&self) -> (vk::DeviceSize, vk::DeviceSize) {
+        self.device_budgets()
+            .iter()
+            .fold((0, 0), |(usage, budget), device| {
+                (usage + device.usage, budget + device.budget)
+            })
+    }
+}
@@ -0,0 +1,199 @@
+//! Uploads to images from host memory without staging buffers (`VK_EXT_host_image_copy`).
+//!
+//! With the extension and its `hostImageCopy` feature enabled, images created with the
+//! `HOST_TRANSFER` usage can be filled directly from host memory by the CPU, with no staging
+//! buffer and no command buffer. `Allocator::create_image_host_copyable` and
+//! `Allocator::copy_host_to_image` use that path when a `HostImageCopy` says it is available and
+//! fall back to the staging path of `Allocator::create_image_init` otherwise.
+//!
+//! The extension is newer than the versions of ash this crate supports, so its definitions are
+//! declared here.
+
+use ash::prelude::VkResult;
+use ash::vk;
+use std::ffi::CStr;
+use std::os::raw::c_void;
+
+/// `VK_EXT_HOST_IMAGE_COPY_EXTENSION_NAME`.
+pub const EXTENSION_NAME: &CStr = c"VK_EXT_host_image_copy";
+
+/// `VK_IMAGE_USAGE_HOST_TRANSFER_BIT_EXT`: the image can be copied to and from by the host.
+pub const HOST_TRANSFER: vk::ImageUsageFlags = vk::ImageUsageFlags::from_raw(0x0040_0000);
+
+const STRUCTURE_TYPE_MEMORY_TO_IMAGE_COPY: vk::StructureType =
+    vk::StructureType::from_raw(1_000_270_002);
+const STRUCTURE_TYPE_COPY_MEMORY_TO_IMAGE_INFO: vk::StructureType =
+    vk::StructureType::from_raw(1_000_270_005);
+const STRUCTURE_TYPE_HOST_IMAGE_LAYOUT_TRANSITION_INFO: vk::StructureType =
+    vk::StructureType::from_raw(1_000_270_006);
+
+/// `VkMemoryToImageCopyEXT`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryToImageCopy {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub p_host_pointer: *const c_void,
+    pub memory_row_length: u32,
+    pub memory_image_height: u32,
+    pub image_subresource: vk::ImageSubresourceLayers,
+    pub image_offset: vk::Offset3D,
+    pub image_extent: vk::Extent3D,
+}
+
+/// `VkCopyMemoryToImageInfoEXT`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CopyMemoryToImageInfo {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub flags: vk::Flags,
+    pub dst_image: vk::Image,
+    pub dst_image_layout: vk::ImageLayout,
+    pub region_count: u32,
+    pub p_regions: *const MemoryToImageCopy,
+}
+
+/// `VkHostImageLayoutTransitionInfoEXT`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct HostImageLayoutTransitionInfo {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub image: vk::Image,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+    pub subresource_range: vk::ImageSubresourceRange,
+}
+
+type PfnCopyMemoryToImage =
+    unsafe extern "system" fn(device: vk::Device, info: *const CopyMemoryToImageInfo) -> vk::Result;
+
+type PfnTransitionImageLayout = unsafe extern "system" fn(
+    device: vk::Device,
+    transition_count: u32,
+    transitions: *const HostImageLayoutTransitionInfo,
+) -> vk::Result;
+
+/// Device functions of `VK_EXT_host_image_copy`, or nothing if it isn't enabled.
+#[derive(Clone, Copy)]
+pub struct HostImageCopy {
+    handle: vk::Device,
+    copy_memory_to_image: Option<PfnCopyMemoryToImage>,
+    transition_image_layout: Option<PfnTransitionImageLayout>,
+}
+
+impl std::fmt::Debug for HostImageCopy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostImageCopy")
+            .field("available", &self.is_available())
+            .finish()
+    }
+}
+
+impl HostImageCopy {
+    /// Loads the extension functions if `enabled` is set, which the application must only do if
+    /// it enabled the extension and the `hostImageCopy` feature on `device`.
+    pub fn new(instance: &ash::Instance, device: &ash::Device, enabled: bool) -> Self {
+        let mut host_copy = Self::unavailable(device.handle());
+        if enabled {
+            let mut load = crate::interop::device_proc_loader(instance, device);
+            let copy_memory_to_image = load(c"vkCopyMemoryToImageEXT");
+            let transition_image_layout = load(c"vkTransitionImageLayoutEXT");
+            unsafe {
+                host_copy.copy_memory_to_image = std::mem::transmute::<
+                    *const c_void,
+                    Option<PfnCopyMemoryToImage>,
+                >(copy_memory_to_image);
+                host_copy.transition_image_layout = std::mem::transmute::<
+                    *const c_void,
+                    Option<PfnTransitionImageLayout>,
+                >(transition_image_layout);
+            }
+        }
+        host_copy
+    }
+
+    /// Functions for a device without the extension, so that uploads always go through staging
+    /// buffers.
+    pub fn unavailable(device: vk::Device) -> Self {
+        HostImageCopy {
+            handle: device,
+            copy_memory_to_image: None,
+            transition_image_layout: None,
+        }
+    }
+
+    /// Whether host copies are used.
+    pub fn is_available(&self) -> bool {
+        self.copy_memory_to_image.is_some() && self.transition_image_layout.is_some()
+    }
+
+    /// Transitions all subresources of `image` from `UNDEFINED` to `layout` and copies `mips`
+    /// into it, as `Allocator::create_image_init` lays them out.
+    pub(crate) unsafe fn copy_mips(
+        &self,
+        image: vk::Image,
+        image_info: &vk::ImageCreateInfo,
+        mips: &[&[u8]],
+        layout: vk::ImageLayout,
+    ) -> VkResult<()> {
+        let (copy_memory_to_image, transition_image_layout) =
+            match (self.copy_memory_to_image, self.transition_image_layout) {
+                (Some(copy), Some(transition)) => (copy, transition),
+                _ => return Err(vk::Result::ERROR_FEATURE_NOT_PRESENT),
+            };
+        let transition = HostImageLayoutTransitionInfo {
+            s_type: STRUCTURE_TYPE_HOST_IMAGE_LAYOUT_TRANSITION_INFO,
+            p_next: std::ptr::null(),
+            image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: layout,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: image_info.mip_levels,
+                base_array_layer: 0,
+                layer_count: image_info.array_layers,
+            },
+        };
+        transition_image_layout(self.handle, 1, &transition).result()?;
+
+        let regions: Vec<MemoryToImageCopy> = mips
+            .iter()
+            .enumerate()
+            .map(|(level, mip)| MemoryToImageCopy {
+                s_type: STRUCTURE_TYPE_MEMORY_TO_IMAGE_COPY,
+                p_next: std::ptr::null(),
+                p_host_pointer: mip.as_ptr() as *const c_void,
+                memory_row_length: 0,
+                memory_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: level as u32,
+                    base_array_layer: 0,
+                    layer_count: image_info.array_layers,
+                },
+                image_offset: vk::Offset3D::default(),
+                image_extent: vk::Extent3D {
+                    width: (image_info.extent.width >> level).max(1),
+                    height: (image_info.extent.height >> level).max(1),
+                    depth: (image_info.extent.depth >> level).max(1),
+                },
+            })
+            .collect();
+        if regions.is_empty() {
+            return Ok(());
+        }
+        let copy = CopyMemoryToImageInfo {
+            s_type: STRUCTURE_TYPE_COPY_MEMORY_TO_IMAGE_INFO,
+            p_next: std::ptr::null(),
+            flags: 0,
+            dst_image: image,
+            dst_image_layout: layout,
+            region_count: regions.len() as u32,
+            p_regions: regions.as_ptr(),
+        };
+        copy_memory_to_image(self.handle, &copy).result()
+    }
+}
@@ -0,0 +1,152 @@
+//! Import of existing host memory as device memory (`VK_EXT_external_memory_host`).
+//!
+//! `Allocator::import_host_memory` wraps a range of host memory the application already owns, e.g.
+//! a memory-mapped file or a large data set, in an `ash::vk::DeviceMemory` the device reads and
+//! writes directly, so it can be used without copying it into a staging buffer first. The range
+//! must start at a multiple of `minImportedHostPointerAlignment` and have a size that is a
+//! multiple of it, see `Allocator::min_imported_host_pointer_alignment`.
+//!
+//! Buffers bound to imported memory must be created with an `ash::vk::ExternalMemoryBufferCreateInfo`
+//! listing `HANDLE_TYPE`. The imported memory is not managed by VMA and doesn't count towards its
+//! statistics or budget.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use ash::prelude::VkResult;
+use ash::vk;
+use std::os::raw::c_void;
+
+/// Handle type to list in `ash::vk::ExternalMemoryBufferCreateInfo::handle_types` of buffers bound
+/// to imported host memory.
+pub const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::HOST_ALLOCATION_EXT;
+
+/// `vkGetMemoryHostPointerPropertiesEXT` of the allocator's device and the import alignment of its
+/// physical device.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HostPointerImport {
+    pub(crate) get_properties: vk::PFN_vkGetMemoryHostPointerPropertiesEXT,
+    pub(crate) alignment: vk::DeviceSize,
+}
+
+impl HostPointerImport {
+    /// Looks up the extension function, which is only available if the device enabled
+    /// `VK_EXT_external_memory_host`, and queries `minImportedHostPointerAlignment`.
+    pub(crate) unsafe fn load(
+        get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+        get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr,
+        instance: vk::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: vk::Device,
+    ) -> Option<Self> {
+        let get_properties =
+            get_device_proc_addr(device, c"vkGetMemoryHostPointerPropertiesEXT".as_ptr())?;
+        // The extension requires Vulkan 1.1 or `VK_KHR_get_physical_device_properties2`.
+        let get_physical_device_properties2 =
+            get_instance_proc_addr(instance, c"vkGetPhysicalDeviceProperties2".as_ptr()).or_else(
+                || get_instance_proc_addr(instance, c"vkGetPhysicalDeviceProperties2KHR".as_ptr()),
+            )?;
+        let get_physical_device_properties2 = std::mem::transmute::<
+            unsafe extern "system" fn(),
+            vk::PFN_vkGetPhysicalDeviceProperties2,
+        >(get_physical_device_properties2);
+
+        let mut host_properties = vk::PhysicalDeviceExternalMemoryHostPropertiesEXT::default();
+        let mut properties = vk::PhysicalDeviceProperties2::builder()
+            .push_next(&mut host_properties)
+            .build();
+        get_physical_device_properties2(physical_device, &mut properties);
+
+        Some(HostPointerImport {
+            get_properties: std::mem::transmute::<
+                unsafe extern "system" fn(),
+                vk::PFN_vkGetMemoryHostPointerPropertiesEXT,
+            >(get_properties),
+            alignment: host_properties.min_imported_host_pointer_alignment.max(1),
+        })
+    }
+}
+
+/// Host memory imported with `Allocator::import_host_memory`. The device memory is freed when this
+/// is dropped; the host memory stays owned by the application.
+///
+/// Buffers and images bound to it must be destroyed before it is dropped, and the host memory
+/// must outlive it.
+pub struct HostMemory {
+    pub(crate) device: vk::Device,
+    pub(crate) memory: vk::DeviceMemory,
+    pub(crate) pointer: *mut c_void,
+    pub(crate) size: vk::DeviceSize,
+    pub(crate) memory_type_index: u32,
+    pub(crate) free_memory: vk::PFN_vkFreeMemory,
+    pub(crate) bind_buffer_memory: vk::PFN_vkBindBufferMemory,
+    pub(crate) bind_image_memory: vk::PFN_vkBindImageMemory,
+}
+
+impl std::fmt::Debug for HostMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostMemory")
+            .field("memory", &self.memory)
+            .field("pointer", &self.pointer)
+            .field("size", &self.size)
+            .field("memory_type_index", &self.memory_type_index)
+            .finish()
+    }
+}
+
+impl HostMemory {
+    /// The imported memory object.
+    pub fn device_memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    /// The imported host pointer. Writes through it are visible to the device, after a flush if
+    /// the memory type isn't `ash::vk::MemoryPropertyFlags::HOST_COHERENT`.
+    pub fn host_pointer(&self) -> *mut c_void {
+        self.pointer
+    }
+
+    /// Size of the imported memory in bytes.
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// Index of the memory type the memory was imported into.
+    pub fn memory_type_index(&self) -> u32 {
+        self.memory_type_index
+    }
+
+    /// Binds `buffer` to the imported memory at `offset`.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must have been created with `HANDLE_TYPE` in its external memory info, not be
+    /// bound yet, and fit in the memory at `offset`.
+    pub unsafe fn bind_buffer_memory(
+        &self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+    ) -> VkResult<()> {
+        (self.bind_buffer_memory)(self.device, buffer, self.memory, offset).result()
+    }
+
+    /// Binds `image` to the imported memory at `offset`.
+    ///
+    /// # Safety
+    ///
+    /// `image` must not be bound yet and must fit in the memory at `offset`. Images backed by
+    /// host memory are seldom supported, check `vkGetPhysicalDeviceImageFormatProperties2` first.
+    pub unsafe fn bind_image_memory(
+        &self,
+        image: vk::Image,
+        offset: vk::DeviceSize,
+    ) -> VkResult<()> {
+        (self.bind_image_memory)(self.device, image, self.memory, offset).result()
+    }
+}
+
+impl Drop for HostMemory {
+    fn drop(&mut self) {
+        unsafe { (self.free_memory)(self.device, self.memory, std::ptr::null()) };
+    }
+}
@@ -0,0 +1,59 @@
+//! Export of allocations as `AHardwareBuffer` objects on Android (`VK_ANDROID_external_memory_android_hardware_buffer`).
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{Allocation, Allocator};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Whether `AHardwareBuffer` export is available on the current target.
+pub const SUPPORTED: bool = cfg!(target_os = "android");
+
+/// Handle type to request in `ash::vk::ExportMemoryAllocateInfo::handle_types` for memory that will be exported.
+pub const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::ANDROID_HARDWARE_BUFFER_ANDROID;
+
+/// Device functions of `VK_ANDROID_external_memory_android_hardware_buffer`.
+pub struct HardwareBufferExport {
+    handle: vk::Device,
+    fp: crate::ash_compat::AndroidExternalMemoryAndroidHardwareBufferFn,
+}
+
+impl HardwareBufferExport {
+    /// Loads the extension functions. The extension must have been enabled on `device`.
+    pub fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        HardwareBufferExport {
+            handle: device.handle(),
+            fp: crate::ash_compat::AndroidExternalMemoryAndroidHardwareBufferFn::load(
+                super::device_proc_loader(instance, device),
+            ),
+        }
+    }
+
+    /// Returns the `AHardwareBuffer` backing the memory of `allocation`.
+    ///
+    /// A reference to the buffer is acquired for the caller, who must release it with
+    /// `AHardwareBuffer_release`.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live dedicated allocation of `allocator`, made from memory exported
+    /// with `HANDLE_TYPE`.
+    pub unsafe fn get_hardware_buffer(
+        &self,
+        allocator: &Allocator,
+        allocation: &Allocation,
+    ) -> VkResult<*mut vk::AHardwareBuffer> {
+        if !SUPPORTED {
+            return super::unsupported();
+        }
+
+        let info = allocator.get_allocation_info(allocation)?;
+        let get_info = vk::MemoryGetAndroidHardwareBufferInfoANDROID::builder()
+            .memory(info.get_device_memory())
+            .build();
+        let mut buffer = std::ptr::null_mut();
+        (self.fp.get_memory_android_hardware_buffer_android)(self.handle, &get_info, &mut buffer)
+            .result_with_success(buffer)
+    }
+}
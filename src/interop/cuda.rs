@@ -0,0 +1,199 @@
+//! Exporting buffers to CUDA through `cudaImportExternalMemory` for zero-copy Vulkan/CUDA pipelines.
+//!
+//! Memory is exported as an opaque file descriptor on Linux and as an opaque NT handle on Windows,
+//! matching `cudaExternalMemoryHandleTypeOpaqueFd` and `cudaExternalMemoryHandleTypeOpaqueWin32`.
+//! On Windows the allocator must have been created with `AllocatorCreateFlags::KHR_EXTERNAL_MEMORY_WIN32`.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::pool_next::PoolAllocateNext;
+use crate::{
+    Allocation, AllocationCreateFlags, AllocationCreateInfo, AllocationInfo, Allocator,
+    AllocatorPool, AllocatorPoolCreateInfo, MemoryUsage,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Whether CUDA export is available on the current target.
+pub const SUPPORTED: bool = cfg!(any(target_os = "linux", windows));
+
+/// Handle type the memory is exported with on the current target.
+#[cfg(windows)]
+pub const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
+/// Handle type the memory is exported with on the current target.
+#[cfg(not(windows))]
+pub const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+
+/// Platform handle to pass in `cudaExternalMemoryHandleDesc::handle`.
+#[derive(Debug)]
+pub enum ExternalHandle {
+    /// Opaque file descriptor, owned by the caller until it is imported by CUDA, which takes ownership of it.
+    Fd(std::os::raw::c_int),
+
+    /// Opaque NT handle, closed when dropped. CUDA does not take ownership of it.
+    #[cfg(windows)]
+    Win32(super::win32::OwnedHandle),
+}
+
+/// Everything needed to fill `cudaExternalMemoryHandleDesc` and `cudaExternalMemoryBufferDesc`.
+#[derive(Debug)]
+pub struct CudaExternalMemory {
+    /// Handle to the whole `ash::vk::DeviceMemory` object.
+    pub handle: ExternalHandle,
+
+    /// Size of the whole `ash::vk::DeviceMemory` object, for `cudaExternalMemoryHandleDesc::size`.
+    pub memory_size: vk::DeviceSize,
+
+    /// Offset of the buffer inside the memory object, for `cudaExternalMemoryBufferDesc::offset`.
+    pub offset: vk::DeviceSize,
+
+    /// Size of the buffer's allocation, for `cudaExternalMemoryBufferDesc::size`.
+    pub size: vk::DeviceSize,
+}
+
+/// Custom pool whose memory is allocated with `ash::vk::ExportMemoryAllocateInfo` so it can be
+/// imported by CUDA.
+///
+/// Every buffer created through the pool gets a dedicated `ash::vk::DeviceMemory`, as required for
+/// CUDA to map it with the right size. All buffers must be destroyed before the pool is dropped.
+pub struct CudaExportPool<'a> {
+    allocator: &'a Allocator,
+    device: vk::Device,
+    #[cfg_attr(windows, allow(dead_code))]
+    fd_fp: crate::ash_compat::KhrExternalMemoryFdFn,
+    pool: AllocatorPool,
+}
+
+impl<'a> CudaExportPool<'a> {
+    /// Creates a pool in the device-local memory type suitable for buffers like `buffer_info`.
+    ///
+    /// # Safety
+    ///
+    /// `instance` and `device` must be the ones `allocator` was created with, and
+    /// `VK_KHR_external_memory_fd` (Linux) or `VK_KHR_external_memory_win32` (Windows) must have
+    /// been enabled on `device`.
+    pub unsafe fn new(
+        allocator: &'a Allocator,
+        instance: &ash::Instance,
+        device: &ash::Device,
+        buffer_info: &vk::BufferCreateInfo,
+    ) -> VkResult<Self> {
+        if !SUPPORTED {
+            return super::unsupported();
+        }
+
+        let mut external_info = external_buffer_info(buffer_info);
+        let mut export_buffer_info = *buffer_info;
+        export_buffer_info.p_next = &mut external_info as *mut _ as *const _;
+        let pool = allocator.create_pool_for_buffer_info(
+            &export_buffer_info,
+            &AllocationCreateInfo {
+                usage: MemoryUsage::AutoPreferDevice,
+                ..Default::default()
+            },
+            &AllocatorPoolCreateInfo {
+                memory_allocate_next: Some(PoolAllocateNext::ExportMemory(HANDLE_TYPE)),
+                ..Default::default()
+            },
+        )?;
+
+        Ok(CudaExportPool {
+            allocator,
+            device: device.handle(),
+            fd_fp: crate::ash_compat::KhrExternalMemoryFdFn::load(super::device_proc_loader(
+                instance, device,
+            )),
+            pool,
+        })
+    }
+
+    /// The underlying pool.
+    pub fn pool(&self) -> AllocatorPool {
+        self.pool
+    }
+
+    /// Creates a buffer in exportable memory and returns it together with the data CUDA needs to import it.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`. The buffer must be destroyed with
+    /// `Allocator::destroy_buffer` before the pool is dropped.
+    pub unsafe fn create_buffer(
+        &self,
+        buffer_info: &vk::BufferCreateInfo,
+    ) -> VkResult<(vk::Buffer, Allocation, AllocationInfo, CudaExternalMemory)> {
+        let mut external_info = external_buffer_info(buffer_info);
+        let mut export_buffer_info = *buffer_info;
+        export_buffer_info.p_next = &mut external_info as *mut _ as *const _;
+        let (buffer, allocation, allocation_info) = self.allocator.create_buffer(
+            &export_buffer_info,
+            &AllocationCreateInfo {
+                flags: AllocationCreateFlags::DEDICATED_MEMORY,
+                pool: Some(self.pool),
+                ..Default::default()
+            },
+        )?;
+
+        match self.export_handle(&allocation, &allocation_info) {
+            Ok(handle) => {
+                let size = allocation_info.get_size() as vk::DeviceSize;
+                let offset = allocation_info.get_offset() as vk::DeviceSize;
+                let memory = CudaExternalMemory {
+                    handle,
+                    memory_size: offset + size,
+                    offset,
+                    size,
+                };
+                Ok((buffer, allocation, allocation_info, memory))
+            }
+            Err(err) => {
+                self.allocator.destroy_buffer(buffer, &allocation);
+                Err(err)
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    unsafe fn export_handle(
+        &self,
+        allocation: &Allocation,
+        _allocation_info: &AllocationInfo,
+    ) -> VkResult<ExternalHandle> {
+        super::win32::get_memory_win32_handle(self.allocator, allocation, None)
+            .map(ExternalHandle::Win32)
+    }
+
+    #[cfg(not(windows))]
+    unsafe fn export_handle(
+        &self,
+        _allocation: &Allocation,
+        allocation_info: &AllocationInfo,
+    ) -> VkResult<ExternalHandle> {
+        let get_info = vk::MemoryGetFdInfoKHR::builder()
+            .memory(allocation_info.get_device_memory())
+            .handle_type(HANDLE_TYPE)
+            .build();
+        let mut fd = -1;
+        (self.fd_fp.get_memory_fd_khr)(self.device, &get_info, &mut fd)
+            .result_with_success(ExternalHandle::Fd(fd))
+    }
+}
+
+impl<'a> Drop for CudaExportPool<'a> {
+    fn drop(&mut self) {
+        unsafe { self.allocator.destroy_pool(self.pool) };
+    }
+}
+
+/// `ash::vk::ExternalMemoryBufferCreateInfo` chained in front of the existing `p_next` of `buffer_info`.
+fn external_buffer_info(
+    buffer_info: &vk::BufferCreateInfo,
+) -> crate::ash_compat::ExternalMemoryBufferCreateInfo<'static> {
+    vk::ExternalMemoryBufferCreateInfo {
+        p_next: buffer_info.p_next,
+        handle_types: HANDLE_TYPE,
+        ..Default::default()
+    }
+}
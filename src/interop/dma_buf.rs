@@ -0,0 +1,221 @@
+//! Export and import of device memory as DMA-BUF file descriptors (`VK_EXT_external_memory_dma_buf`),
+//! e.g. for Wayland compositors and video decoders.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{Allocation, AllocationCreateInfo, Allocator};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Whether DMA-BUF export and import are available on the current target.
+pub const SUPPORTED: bool = cfg!(any(target_os = "linux", target_os = "android"));
+
+/// Handle type to request in `ash::vk::ExportMemoryAllocateInfo::handle_types` for memory that will be exported.
+pub const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT;
+
+/// File descriptor referring to a DMA-BUF.
+pub type Fd = std::os::raw::c_int;
+
+/// Resource an imported DMA-BUF is dedicated to.
+#[derive(Debug, Clone, Copy)]
+pub enum DedicatedResource {
+    Buffer(vk::Buffer),
+    Image(vk::Image),
+}
+
+/// Device functions of `VK_KHR_external_memory_fd`, used to export and import DMA-BUF descriptors.
+pub struct DmaBuf {
+    device: ash::Device,
+    fp: crate::ash_compat::KhrExternalMemoryFdFn,
+}
+
+impl DmaBuf {
+    /// Loads the extension functions. `VK_KHR_external_memory_fd` and `VK_EXT_external_memory_dma_buf`
+    /// must have been enabled on `device`.
+    pub fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        DmaBuf {
+            device: device.clone(),
+            fp: crate::ash_compat::KhrExternalMemoryFdFn::load(super::device_proc_loader(
+                instance, device,
+            )),
+        }
+    }
+
+    /// Returns a new DMA-BUF file descriptor referring to the `ash::vk::DeviceMemory` block of `allocation`.
+    ///
+    /// The caller owns the descriptor and must close it.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of `allocator`, whose memory was allocated with
+    /// `HANDLE_TYPE` in its export info.
+    pub unsafe fn get_fd(&self, allocator: &Allocator, allocation: &Allocation) -> VkResult<Fd> {
+        if !SUPPORTED {
+            return super::unsupported();
+        }
+
+        let info = allocator.get_allocation_info(allocation)?;
+        let get_info = vk::MemoryGetFdInfoKHR::builder()
+            .memory(info.get_device_memory())
+            .handle_type(HANDLE_TYPE)
+            .build();
+        let mut fd = -1;
+        (self.fp.get_memory_fd_khr)(self.device.handle(), &get_info, &mut fd)
+            .result_with_success(fd)
+    }
+
+    /// Imports `fd` as a new `ash::vk::DeviceMemory` of `requirements.size` bytes.
+    ///
+    /// The memory type is chosen with `Allocator::find_memory_type_index` among the types that both
+    /// the DMA-BUF and `requirements.memory_type_bits` allow, preferring `preferred_flags`. Pass
+    /// the buffer or image the memory will be bound to as `dedicated` if the exporter or the driver
+    /// requires a dedicated allocation, which is usually the case for images.
+    ///
+    /// On success Vulkan takes ownership of `fd`; on failure the caller still owns it.
+    /// The imported memory is not managed by VMA and doesn't count towards its statistics.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open DMA-BUF file descriptor of at least `requirements.size` bytes, and
+    /// `dedicated`, if any, a live resource created on the device of `allocator`.
+    pub unsafe fn import(
+        &self,
+        allocator: &Allocator,
+        fd: Fd,
+        requirements: &vk::MemoryRequirements,
+        preferred_flags: vk::MemoryPropertyFlags,
+        dedicated: Option<DedicatedResource>,
+    ) -> VkResult<ImportedMemory> {
+        if !SUPPORTED {
+            return super::unsupported();
+        }
+
+        let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+        (self.fp.get_memory_fd_properties_khr)(
+            self.device.handle(),
+            HANDLE_TYPE,
+            fd,
+            &mut fd_properties,
+        )
+        .result()?;
+
+        let memory_type_bits = fd_properties.memory_type_bits & requirements.memory_type_bits;
+        let allocation_info = AllocationCreateInfo {
+            preferred_flags,
+            ..Default::default()
+        };
+        let memory_type_index =
+            allocator.find_memory_type_index(memory_type_bits, &allocation_info)?;
+
+        let mut import_info = vk::ImportMemoryFdInfoKHR::builder()
+            .handle_type(HANDLE_TYPE)
+            .fd(fd);
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default();
+        match dedicated {
+            Some(DedicatedResource::Buffer(buffer)) => dedicated_info.buffer = buffer,
+            Some(DedicatedResource::Image(image)) => dedicated_info.image = image,
+            None => {}
+        }
+        let mut allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut import_info);
+        if dedicated.is_some() {
+            allocate_info = allocate_info.push_next(&mut dedicated_info);
+        }
+        let memory = self.device.allocate_memory(&allocate_info, None)?;
+
+        Ok(ImportedMemory {
+            device: self.device.clone(),
+            memory,
+            size: requirements.size,
+            memory_type_index,
+        })
+    }
+}
+
+/// Device memory imported from a DMA-BUF with `DmaBuf::import`. The memory is freed when this is dropped.
+///
+/// Buffers and images bound to it must be destroyed before it is dropped.
+pub struct ImportedMemory {
+    device: ash::Device,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    memory_type_index: u32,
+}
+
+impl ImportedMemory {
+    /// The imported memory object.
+    pub fn device_memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    /// Size of the imported memory in bytes.
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// Index of the memory type the memory was imported into.
+    pub fn memory_type_index(&self) -> u32 {
+        self.memory_type_index
+    }
+
+    /// Binds `buffer` to the imported memory at `offset`.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be an unbound buffer of the same device whose requirements fit the memory at
+    /// `offset`.
+    pub unsafe fn bind_buffer_memory(
+        &self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+    ) -> VkResult<()> {
+        self.device.bind_buffer_memory(buffer, self.memory, offset)
+    }
+
+    /// Binds `image` to the imported memory at `offset`.
+    ///
+    /// # Safety
+    ///
+    /// `image` must be an unbound image of the same device whose requirements fit the memory at
+    /// `offset`.
+    pub unsafe fn bind_image_memory(
+        &self,
+        image: vk::Image,
+        offset: vk::DeviceSize,
+    ) -> VkResult<()> {
+        self.device.bind_image_memory(image, self.memory, offset)
+    }
+
+    /// Maps the whole imported memory and returns a pointer to its first byte.
+    ///
+    /// Unlike `Allocator::map_memory`, mapping is not reference counted.
+    ///
+    /// # Safety
+    ///
+    /// The memory type must be `ash::vk::MemoryPropertyFlags::HOST_VISIBLE`, and the memory must be
+    /// unmapped with `ImportedMemory::unmap_memory` before it is mapped again.
+    pub unsafe fn map_memory(&self) -> VkResult<*mut u8> {
+        let data =
+            self.device
+                .map_memory(self.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?;
+        Ok(data as *mut u8)
+    }
+
+    /// Unmaps memory mapped with `ImportedMemory::map_memory`.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be mapped, and pointers into the mapping must not be used afterwards.
+    pub unsafe fn unmap_memory(&self) {
+        self.device.unmap_memory(self.memory);
+    }
+}
+
+impl Drop for ImportedMemory {
+    fn drop(&mut self) {
+        unsafe { self.device.free_memory(self.memory, None) };
+    }
+}
@@ -0,0 +1,120 @@
+//! Export of allocations as `MTLBuffer` objects on Apple platforms (`VK_EXT_metal_objects`).
+//!
+//! The extension is newer than the Vulkan headers of the `ash` version this crate depends on,
+//! so the structures it needs are declared here.
+
+use crate::{Allocation, Allocator};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::os::raw::c_void;
+
+/// Whether `MTLBuffer` export is available on the current target.
+pub const SUPPORTED: bool = cfg!(any(target_os = "macos", target_os = "ios"));
+
+/// Opaque `id<MTLBuffer>` pointer.
+pub type MtlBuffer = *mut c_void;
+
+/// `VK_EXPORT_METAL_OBJECT_TYPE_METAL_BUFFER_BIT_EXT`, to request in
+/// `ExportMetalObjectCreateInfo::export_object_type` for memory that will be exported.
+pub const EXPORT_OBJECT_TYPE_METAL_BUFFER: u32 = 0x0000_0004;
+
+const STRUCTURE_TYPE_EXPORT_METAL_OBJECT_CREATE_INFO_EXT: vk::StructureType =
+    vk::StructureType::from_raw(1_000_311_000);
+const STRUCTURE_TYPE_EXPORT_METAL_OBJECTS_INFO_EXT: vk::StructureType =
+    vk::StructureType::from_raw(1_000_311_001);
+const STRUCTURE_TYPE_EXPORT_METAL_BUFFER_INFO_EXT: vk::StructureType =
+    vk::StructureType::from_raw(1_000_311_004);
+
+/// `VkExportMetalObjectCreateInfoEXT`. Chain it into the `pNext` of the memory allocation (e.g. through
+/// `pool_next::PoolAllocateNext::Raw`) to make the memory exportable as an `MTLBuffer`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExportMetalObjectCreateInfo {
+    pub s_type: vk::StructureType,
+    pub p_next: *const c_void,
+    pub export_object_type: u32,
+}
+
+impl Default for ExportMetalObjectCreateInfo {
+    fn default() -> Self {
+        ExportMetalObjectCreateInfo {
+            s_type: STRUCTURE_TYPE_EXPORT_METAL_OBJECT_CREATE_INFO_EXT,
+            p_next: std::ptr::null(),
+            export_object_type: EXPORT_OBJECT_TYPE_METAL_BUFFER,
+        }
+    }
+}
+
+#[repr(C)]
+struct ExportMetalObjectsInfo {
+    s_type: vk::StructureType,
+    p_next: *const c_void,
+}
+
+#[repr(C)]
+struct ExportMetalBufferInfo {
+    s_type: vk::StructureType,
+    p_next: *const c_void,
+    memory: vk::DeviceMemory,
+    mtl_buffer: MtlBuffer,
+}
+
+type PfnExportMetalObjects =
+    unsafe extern "system" fn(device: vk::Device, metal_objects_info: *mut ExportMetalObjectsInfo);
+
+/// Device function of `VK_EXT_metal_objects`.
+pub struct MetalObjectsExport {
+    handle: vk::Device,
+    fp: Option<PfnExportMetalObjects>,
+}
+
+impl MetalObjectsExport {
+    /// Loads the extension function. The extension must have been enabled on `device`.
+    pub fn new(instance: &ash::Instance, device: &ash::Device) -> Self {
+        let mut load = super::device_proc_loader(instance, device);
+        let fp = load(c"vkExportMetalObjectsEXT");
+        MetalObjectsExport {
+            handle: device.handle(),
+            fp: unsafe { std::mem::transmute::<*const c_void, Option<PfnExportMetalObjects>>(fp) },
+        }
+    }
+
+    /// Returns the `MTLBuffer` backing the `ash::vk::DeviceMemory` block of `allocation`.
+    ///
+    /// The buffer is not retained for the caller.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of `allocator`, whose memory was allocated with an
+    /// `ExportMetalObjectCreateInfo` requesting `EXPORT_OBJECT_TYPE_METAL_BUFFER`. The returned
+    /// buffer must not be used after the memory is freed.
+    pub unsafe fn get_metal_buffer(
+        &self,
+        allocator: &Allocator,
+        allocation: &Allocation,
+    ) -> VkResult<MtlBuffer> {
+        let fp = match self.fp {
+            Some(fp) if SUPPORTED => fp,
+            _ => return super::unsupported(),
+        };
+
+        let info = allocator.get_allocation_info(allocation)?;
+        let mut buffer_info = ExportMetalBufferInfo {
+            s_type: STRUCTURE_TYPE_EXPORT_METAL_BUFFER_INFO_EXT,
+            p_next: std::ptr::null(),
+            memory: info.get_device_memory(),
+            mtl_buffer: std::ptr::null_mut(),
+        };
+        let mut objects_info = ExportMetalObjectsInfo {
+            s_type: STRUCTURE_TYPE_EXPORT_METAL_OBJECTS_INFO_EXT,
+            p_next: &mut buffer_info as *mut ExportMetalBufferInfo as *const c_void,
+        };
+        fp(self.handle, &mut objects_info);
+
+        if buffer_info.mtl_buffer.is_null() {
+            Err(vk::Result::ERROR_FEATURE_NOT_PRESENT)
+        } else {
+            Ok(buffer_info.mtl_buffer)
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Sharing allocations with other graphics APIs and other processes.
+//!
+//! Each platform mechanism lives in its own module behind its own cargo feature:
+//!
+//! | Module     | Feature           | Mechanism                     | Supported targets      |
+//! |------------|-------------------|-------------------------------|------------------------|
+//! | `android`  | `android_interop` | `AHardwareBuffer` export      | Android                |
+//! | `cuda`     | `cuda_interop`    | CUDA external memory export   | Linux, Windows         |
+//! | `win32`    | `win32_interop`   | Win32 `HANDLE` export         | Windows                |
+//! | `metal`    | `metal_interop`   | `MTLBuffer` export            | macOS, iOS             |
+//! | `dma_buf`  | `dma_buf_interop` | DMA-BUF file descriptors      | Linux, Android         |
+//!
+//! The modules compile on every target, so code using them doesn't need its own `cfg` attributes.
+//! On targets without the underlying mechanism, `SUPPORTED` is `false` and every function returns
+//! `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` without calling into Vulkan.
+//!
+//! Memory can only be exported if it was allocated with a matching `ash::vk::ExportMemoryAllocateInfo`,
+//! either for whole memory types through `AllocatorCreateInfo::external_memory_handle_type`, or
+//! for a custom pool through `AllocatorPoolCreateInfo::memory_allocate_next`. The exported object
+//! always refers to the whole `ash::vk::DeviceMemory` block, so allocations meant to be shared
+//! should usually be created with `AllocationCreateFlags::DEDICATED_MEMORY`; otherwise the receiver
+//! must apply `AllocationInfo::get_offset` itself.
+
+#[cfg(feature = "android_interop")]
+pub mod android;
+#[cfg(feature = "cuda_interop")]
+pub mod cuda;
+#[cfg(feature = "dma_buf_interop")]
+pub mod dma_buf;
+#[cfg(feature = "metal_interop")]
+pub mod metal;
+#[cfg(feature = "win32_interop")]
+pub mod win32;
+
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Result returned by interop functions on targets that don't support them.
+#[allow(dead_code)]
+pub(crate) fn unsupported<T>() -> VkResult<T> {
+    Err(vk::Result::ERROR_FEATURE_NOT_PRESENT)
+}
+
+/// Loads a device-level function through `vkGetDeviceProcAddr`, for use with the `load` functions of ash.
+#[allow(dead_code)]
+pub(crate) fn device_proc_loader<'a>(
+    instance: &'a ash::Instance,
+    device: &'a ash::Device,
+) -> impl FnMut(&std::ffi::CStr) -> *const std::os::raw::c_void + 'a {
+    move |name| unsafe {
+        std::mem::transmute(instance.get_device_proc_addr(device.handle(), name.as_ptr()))
+    }
+}
@@ -0,0 +1,102 @@
+//! Export of allocations as Win32 handles (`VK_KHR_external_memory_win32`), e.g. for D3D11/D3D12 interop.
+//!
+//! The allocator must have been created with `AllocatorCreateFlags::KHR_EXTERNAL_MEMORY_WIN32`.
+
+use crate::{Allocation, Allocator};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Whether Win32 handle export is available on the current target.
+pub const SUPPORTED: bool = cfg!(windows);
+
+/// NT handle type, to request in `ash::vk::ExportMemoryAllocateInfo::handle_types` for memory that will be exported.
+pub const HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
+
+/// Legacy KMT handle type. Handles of this type are not reference counted and cannot be closed.
+pub const HANDLE_TYPE_KMT: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32_KMT;
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn CloseHandle(object: vk::HANDLE) -> i32;
+}
+
+/// Win32 handle owned by the caller, closed with `CloseHandle` when dropped.
+#[derive(Debug)]
+pub struct OwnedHandle {
+    handle: vk::HANDLE,
+}
+
+impl OwnedHandle {
+    /// Takes ownership of `handle`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be a handle that can be closed with `CloseHandle`, and must not be closed
+    /// elsewhere.
+    pub unsafe fn from_raw(handle: vk::HANDLE) -> Self {
+        OwnedHandle { handle }
+    }
+
+    /// Returns the handle without giving up ownership, e.g. to pass it to
+    /// `ID3D12Device::OpenSharedHandle` in the current process.
+    pub fn as_raw(&self) -> vk::HANDLE {
+        self.handle
+    }
+
+    /// Gives up ownership of the handle. The caller becomes responsible for closing it.
+    pub fn into_raw(self) -> vk::HANDLE {
+        let handle = self.handle;
+        std::mem::forget(self);
+        handle
+    }
+}
+
+impl Drop for OwnedHandle {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        if !self.handle.is_null() {
+            unsafe { CloseHandle(self.handle) };
+        }
+    }
+}
+
+unsafe impl Send for OwnedHandle {}
+unsafe impl Sync for OwnedHandle {}
+
+/// Returns a handle to the `ash::vk::DeviceMemory` block of `allocation` that can be imported by
+/// another API or process.
+///
+/// `target_process` is a handle to the process that will use the returned handle, or `None` for the
+/// current process. The handle is fetched once by VMA and duplicated on every call, so each returned `OwnedHandle`
+/// is independent and closes only its own copy.
+///
+/// # Safety
+///
+/// `allocation` must be a live allocation of `allocator`, whose memory was allocated with
+/// `HANDLE_TYPE` in its export info. `target_process` must be a valid process handle.
+pub unsafe fn get_memory_win32_handle(
+    allocator: &Allocator,
+    allocation: &Allocation,
+    target_process: Option<vk::HANDLE>,
+) -> VkResult<OwnedHandle> {
+    #[cfg(windows)]
+    {
+        let mut handle: vk::HANDLE = std::ptr::null_mut();
+        crate::ffi_to_result(crate::ffi::vmaGetMemoryWin32Handle(
+            allocator.handle(),
+            *allocation,
+            target_process.unwrap_or(std::ptr::null_mut()),
+            &mut handle,
+        ))?;
+        Ok(OwnedHandle::from_raw(handle))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (allocator, allocation, target_process);
+        super::unsupported()
+    }
+}
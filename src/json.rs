@@ -0,0 +1,200 @@
+//! Minimal JSON reader for the documents produced by `vmaBuildStatsString`.
+//!
+//! Only what is needed to walk VMA's statistics dumps is supported; numbers are kept as their text so
+//! byte counts beyond the precision of `f64` survive.
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub(crate) fn parse(text: &str) -> Option<Value> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position == parser.bytes.len() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Member `key` of an object.
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        self.entries()
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Members of an object, or nothing for other values.
+    pub(crate) fn entries(&self) -> &[(String, Value)] {
+        match self {
+            Value::Object(entries) => entries,
+            _ => &[],
+        }
+    }
+
+    /// Elements of an array, or nothing for other values.
+    pub(crate) fn elements(&self) -> &[Value] {
+        match self {
+            Value::Array(elements) => elements,
+            _ => &[],
+        }
+    }
+
+    pub(crate) fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.skip_whitespace();
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn literal(&mut self, text: &str, value: Value) -> Option<Value> {
+        if self.bytes[self.position..].starts_with(text.as_bytes()) {
+            self.position += text.len();
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => self.object(),
+            b'[' => self.array(),
+            b'"' => self.string().map(Value::String),
+            b't' => self.literal("true", Value::Bool(true)),
+            b'f' => self.literal("false", Value::Bool(false)),
+            b'n' => self.literal("null", Value::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn object(&mut self) -> Option<Value> {
+        self.expect(b'{')?;
+        let mut entries = Vec::new();
+        if self.expect(b'}').is_some() {
+            return Some(Value::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(b':')?;
+            entries.push((key, self.value()?));
+            if self.expect(b',').is_none() {
+                self.expect(b'}')?;
+                return Some(Value::Object(entries));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Option<Value> {
+        self.expect(b'[')?;
+        let mut elements = Vec::new();
+        if self.expect(b']').is_some() {
+            return Some(Value::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+            if self.expect(b',').is_none() {
+                self.expect(b']')?;
+                return Some(Value::Array(elements));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.peek() != Some(b'"') {
+            return None;
+        }
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let byte = self.peek()?;
+            self.position += 1;
+            match byte {
+                b'"' => return String::from_utf8(bytes).ok(),
+                b'\\' => {
+                    let escaped = self.peek()?;
+                    self.position += 1;
+                    let unescaped = match escaped {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let hex = std::str::from_utf8(
+                                self.bytes.get(self.position..self.position + 4)?,
+                            )
+                            .ok()?;
+                            self.position += 4;
+                            char::from_u32(u32::from_str_radix(hex, 16).ok()?).unwrap_or('\u{FFFD}')
+                        }
+                        other => other as char,
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(unescaped.encode_utf8(&mut buffer).as_bytes());
+                }
+                _ => bytes.push(byte),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let start = self.position;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.peek() {
+            self.position += 1;
+        }
+        if self.position == start {
+            return None;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).ok()?;
+        Some(Value::Number(text.to_owned()))
+    }
+}
@@ -0,0 +1,129 @@
+//! Leak tracking, enabled with the `leak_track` feature.
+//!
+//! VMA only asserts that some allocation was not freed when the allocator is destroyed. With this
+//! feature the allocator remembers, for every live allocation, the call that created it, its size,
+//! its name and a backtrace of where it was created. `Allocator::report_leaks` returns the
+//! allocations that are still alive, and `Allocator::destroy` logs them as errors before destroying
+//! VMA.
+//!
+//! Backtraces are captured unconditionally (regardless of `RUST_BACKTRACE`) and only resolved to
+//! symbols when they are printed, but capturing still walks the stack on every allocation, so the
+//! feature is meant for debug builds.
+
+use crate::Allocation;
+use ash::vk;
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// An allocation that is still alive, with the context of its creation.
+#[derive(Debug, Clone)]
+pub struct LeakedAllocation {
+    /// The leaked allocation.
+    pub allocation: Allocation,
+
+    /// Function that created the allocation, e.g. `Allocator::create_buffer`.
+    pub operation: &'static str,
+
+    /// Size of the allocation in bytes.
+    pub size: vk::DeviceSize,
+
+    /// Name last set with `Allocator::set_allocation_name`, if any.
+    pub name: Option<String>,
+
+    /// Where the allocation was created.
+    pub backtrace: Arc<Backtrace>,
+}
+
+impl fmt::Display for LeakedAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "allocation {:?} of {} bytes", self.allocation, self.size)?;
+        if let Some(name) = &self.name {
+            write!(f, " named {:?}", name)?;
+        }
+        write!(
+            f,
+            " created by `{}` was not freed, created at:\n{}",
+            self.operation, self.backtrace
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Record {
+    operation: &'static str,
+    size: vk::DeviceSize,
+    name: Option<String>,
+    backtrace: Arc<Backtrace>,
+    /// Creation order, so leaks are reported oldest first.
+    sequence: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    allocations: HashMap<usize, Record>,
+    next_sequence: u64,
+}
+
+/// Live allocations, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct LeakTracker {
+    state: Mutex<State>,
+}
+
+impl LeakTracker {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn on_create(
+        &self,
+        operation: &'static str,
+        allocation: &Allocation,
+        size: vk::DeviceSize,
+    ) {
+        let backtrace = Arc::new(Backtrace::force_capture());
+        let mut state = self.state();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.allocations.insert(
+            *allocation as usize,
+            Record {
+                operation,
+                size,
+                name: None,
+                backtrace,
+                sequence,
+            },
+        );
+    }
+
+    pub(crate) fn on_name(&self, allocation: &Allocation, name: &str) {
+        if let Some(record) = self.state().allocations.get_mut(&(*allocation as usize)) {
+            record.name = Some(name.to_owned());
+        }
+    }
+
+    pub(crate) fn on_free(&self, allocation: &Allocation) {
+        self.state().allocations.remove(&(*allocation as usize));
+    }
+
+    pub(crate) fn live(&self) -> Vec<LeakedAllocation> {
+        let state = self.state();
+        let mut records: Vec<_> = state.allocations.iter().collect();
+        records.sort_by_key(|(_, record)| record.sequence);
+        records
+            .into_iter()
+            .map(|(&allocation, record)| LeakedAllocation {
+                allocation: allocation as Allocation,
+                operation: record.operation,
+                size: record.size,
+                name: record.name.clone(),
+                backtrace: record.backtrace.clone(),
+            })
+            .collect()
+    }
+}
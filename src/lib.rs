@@ -7,10 +7,97 @@
 
 use bitflags::bitflags;
 
+/// The version of ash the crate is built against, selected with the `ash_0_36` (default),
+/// `ash_0_37` or `ash_0_38` feature. If several are enabled, the newest one is used.
+#[cfg(all(
+    feature = "ash_0_36",
+    not(any(feature = "ash_0_37", feature = "ash_0_38"))
+))]
+pub extern crate ash_0_36 as ash;
+#[cfg(all(feature = "ash_0_37", not(feature = "ash_0_38")))]
+pub extern crate ash_0_37 as ash;
+#[cfg(feature = "ash_0_38")]
+pub extern crate ash_0_38 as ash;
+#[cfg(not(any(feature = "ash_0_36", feature = "ash_0_37", feature = "ash_0_38")))]
+compile_error!(
+    "vk-mem needs the `ash_0_36`, `ash_0_37` or `ash_0_38` feature to select a version of ash"
+);
+#[cfg(all(
+    feature = "ash_0_38",
+    any(feature = "gpu_allocator", feature = "vulkano", feature = "wgpu_hal")
+))]
+compile_error!(
+    "the `gpu_allocator`, `vulkano` and `wgpu_hal` features need ash 0.37, not `ash_0_38`"
+);
+
+pub mod aliasing;
+#[cfg(feature = "alloc_log")]
+pub mod alloc_log;
+pub mod architecture;
+mod ash_compat;
+#[cfg(feature = "async_allocator")]
+pub mod async_allocator;
+pub mod bindless;
+pub mod budget;
+pub mod call_site;
+pub mod category;
+mod debug_utils;
+pub mod deferred;
+pub mod defrag;
+pub mod descriptor_buffer;
+pub mod dynamic_uniform;
+mod error;
+pub mod explain;
 pub mod ffi;
+pub mod frame;
+pub mod geometry;
+pub mod group;
+pub mod host_image_copy;
+pub mod host_memory;
+pub mod interop;
+mod json;
+#[cfg(feature = "leak_track")]
+pub mod leak;
+#[cfg(feature = "lifetime_stats")]
+pub mod lifetime;
+pub mod memory_allocator;
+pub mod memory_type_mask;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod oom;
+pub mod pool_growth;
+pub mod pool_next;
+pub mod priority;
+pub mod raytracing;
+pub mod readback;
+pub mod registry;
+pub mod report;
+pub mod routing;
+pub mod single_thread;
+pub mod small_buffer;
+pub mod snapshot;
+pub mod sparse;
+pub mod staging;
+pub mod streaming;
+#[cfg(feature = "timing")]
+pub mod timing;
+#[cfg(feature = "tracy")]
+pub mod tracy;
+pub mod transfer;
+pub mod upload;
+mod user_data;
+#[cfg(feature = "validation")]
+mod validation;
+pub mod viz;
+mod vma_log;
 use ash::prelude::VkResult;
 use ash::vk;
+#[cfg(feature = "ash_0_38")]
+use ash_compat::Builder;
+pub use error::{Error, Result};
 use std::mem;
+#[cfg(feature = "validation")]
+pub use validation::ViolationPolicy;
 
 /* #region BITFLAGS & ENUMS */
 
@@ -24,6 +111,9 @@ bitflags! {
         /// so you must guarantee they are used from only one thread at a time or synchronized
         /// externally by you. Using this flag may increase performance because internal
         /// mutexes are not used.
+        ///
+        /// `Allocator` is `Sync`, so `Allocator::new` rejects this flag. Create a
+        /// `single_thread::SingleThreadAllocator` instead, which is not.
         const EXTERNALLY_SYNCHRONIZED = 0x0000_0001;
 
         /// Enables usage of `VK_KHR_dedicated_allocation` extension.
@@ -121,10 +211,101 @@ bitflags! {
         /// For more details, see the documentation of the VK_EXT_memory_priority extension.
         const VMA_ALLOCATOR_CREATE_EXT_MEMORY_PRIORITY_BIT = 0x00000040;
 
+        /// Enables usage of VK_KHR_maintenance4 extension in the library.
+        ///
+        /// You may set this flag only if you found available and enabled this device extension,
+        /// while creating Vulkan device passed as VmaAllocatorCreateInfo::device.
+        ///
+        /// When this flag is used on Vulkan 1.1 or 1.2, `vkGetDeviceBufferMemoryRequirementsKHR` and
+        /// `vkGetDeviceImageMemoryRequirementsKHR` are fetched from the device and used to query memory
+        /// requirements without creating a temporary resource. On Vulkan 1.3 the core functions are used regardless.
+        const KHR_MAINTENANCE4 = 0x00000080;
+
+        /// Enables usage of VK_KHR_maintenance5 extension in the library.
+        ///
+        /// You should set this flag if you found available and enabled this device extension,
+        /// while creating Vulkan device passed as VmaAllocatorCreateInfo::device.
+        ///
+        /// With it, buffer usage can be passed through `VkBufferUsageFlags2CreateInfoKHR` in the `pNext`
+        /// chain of `ash::vk::BufferCreateInfo` and will be taken into account by `MemoryUsage::Auto`.
+        const KHR_MAINTENANCE5 = 0x00000100;
+
+        /// Enables usage of VK_KHR_external_memory_win32 extension in the library.
+        ///
+        /// You should set this flag if you found available and enabled this device extension,
+        /// while creating Vulkan device passed as VmaAllocatorCreateInfo::device.
+        /// It is required to export the memory of an allocation as a Win32 `HANDLE`.
+        /// The flag only has an effect when the crate is built for Windows.
+        const KHR_EXTERNAL_MEMORY_WIN32 = 0x00000200;
+
         const VMA_ALLOCATOR_CREATE_FLAG_BITS_MAX_ENUM = 0x7FFFFFFF;
     }
 }
 
+/// Optional Vulkan paths the allocator actually uses, as returned by `Allocator::enabled_features`.
+///
+/// Paths promoted to core Vulkan are active from the API version they were promoted in, whether
+/// or not the corresponding `AllocatorCreateFlags` were set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct EnabledFeatures {
+    /// Dedicated allocations recommended by the driver (`VK_KHR_dedicated_allocation`, core in
+    /// 1.1).
+    pub dedicated_allocation: bool,
+
+    /// `vkBindBufferMemory2` and `vkBindImageMemory2` (`VK_KHR_bind_memory2`, core in 1.1).
+    pub bind_memory2: bool,
+
+    /// Budget queried from the driver (`VK_EXT_memory_budget`) instead of estimated.
+    pub memory_budget: bool,
+
+    /// Memory allocated with `VK_MEMORY_ALLOCATE_DEVICE_ADDRESS_BIT`, required by
+    /// `Allocator::create_buffer_with_address`.
+    pub buffer_device_address: bool,
+
+    /// Allocation priorities passed to the driver (`VK_EXT_memory_priority`).
+    pub memory_priority: bool,
+
+    /// Memory types with `DEVICE_COHERENT_AMD` (`VK_AMD_device_coherent_memory`).
+    pub amd_device_coherent_memory: bool,
+
+    /// Memory requirements queried without temporary resources (`VK_KHR_maintenance4`, core in
+    /// 1.3).
+    pub maintenance4: bool,
+
+    /// Buffer usage taken from `VkBufferUsageFlags2CreateInfoKHR` (`VK_KHR_maintenance5`).
+    pub maintenance5: bool,
+
+    /// Export of allocations as Win32 handles (`VK_KHR_external_memory_win32`), only on Windows.
+    pub external_memory_win32: bool,
+}
+
+impl EnabledFeatures {
+    /// The paths VMA takes for an allocator created with `flags` for `vulkan_api_version`.
+    pub fn new(flags: AllocatorCreateFlags, vulkan_api_version: u32) -> Self {
+        let vulkan_1_1 = vulkan_api_version >= vk::API_VERSION_1_1;
+        EnabledFeatures {
+            dedicated_allocation: vulkan_1_1
+                || flags.contains(AllocatorCreateFlags::KHR_DEDICATED_ALLOCATION),
+            bind_memory2: vulkan_1_1
+                || flags.contains(AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_KHR_BIND_MEMORY2_BIT),
+            memory_budget: flags
+                .contains(AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_EXT_MEMORY_BUDGET_BIT),
+            buffer_device_address: flags
+                .contains(AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT),
+            memory_priority: flags
+                .contains(AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_EXT_MEMORY_PRIORITY_BIT),
+            amd_device_coherent_memory: flags.contains(
+                AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_AMD_DEVICE_COHERENT_MEMORY_BIT,
+            ),
+            maintenance4: vulkan_api_version >= vk::API_VERSION_1_3
+                || flags.contains(AllocatorCreateFlags::KHR_MAINTENANCE4),
+            maintenance5: flags.contains(AllocatorCreateFlags::KHR_MAINTENANCE5),
+            external_memory_win32: cfg!(windows)
+                && flags.contains(AllocatorCreateFlags::KHR_EXTERNAL_MEMORY_WIN32),
+        }
+    }
+}
+
 bitflags! {
     /// Flags for configuring `AllocatorPool` construction.
     pub struct AllocatorPoolCreateFlags: u32 {
@@ -472,7 +653,8 @@ pub enum MemoryUsage {
 }
 
 /// Operation performed on single defragmentation move. See structure #DefragmentationMove.
-#[derive(Debug, Copy, Clone)]
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DefragmentationMoveOperation {
     /// Buffer/image has been recreated at `dstTmpAllocation`, data has been copied, old buffer/image has been destroyed. `srcAllocation` should be changed to point to the new place. This is the default value set by vmaBeginDefragmentationPass().
     Copy = 0,
@@ -490,11 +672,116 @@ pub enum DefragmentationMoveOperation {
 
 /* #region STRUCTURES */
 
+/// Size of the buffer used to pick the memory type of the pool presets, like
+/// `Allocator::create_staging_pool`.
+const PRESET_PROBE_SIZE: vk::DeviceSize = 1024;
+
+/// Device-level Vulkan functions the wrapper calls itself, taken from the functions given to VMA.
+#[derive(Debug, Clone, Copy)]
+struct DeviceFunctions {
+    device: vk::Device,
+    create_buffer: vk::PFN_vkCreateBuffer,
+    destroy_buffer: vk::PFN_vkDestroyBuffer,
+    create_image: vk::PFN_vkCreateImage,
+    destroy_image: vk::PFN_vkDestroyImage,
+
+    /// `None` unless the device is Vulkan 1.1 or `VK_KHR_dedicated_allocation` is enabled
+    get_buffer_memory_requirements2: Option<vk::PFN_vkGetBufferMemoryRequirements2>,
+    get_image_memory_requirements2: Option<vk::PFN_vkGetImageMemoryRequirements2>,
+
+    allocate_memory: vk::PFN_vkAllocateMemory,
+    free_memory: vk::PFN_vkFreeMemory,
+    bind_buffer_memory: vk::PFN_vkBindBufferMemory,
+    bind_image_memory: vk::PFN_vkBindImageMemory,
+    get_fence_status: vk::PFN_vkGetFenceStatus,
+
+    /// `None` unless the device enabled `VK_EXT_external_memory_host`
+    host_pointer_import: Option<host_memory::HostPointerImport>,
+}
+
 /// Main allocator object
 #[derive(Debug, Clone)]
 pub struct Allocator {
     /// Pointer to internal VmaAllocator instance
     internal: ffi::VmaAllocator,
+
+    /// Set by `Allocator::destroy`, shared with clones, whose `internal` is left dangling
+    destroyed: std::sync::Arc<std::sync::atomic::AtomicBool>,
+
+    /// Flags the allocator was created with
+    flags: AllocatorCreateFlags,
+
+    /// `AllocatorCreateInfo::vulkan_api_version` the allocator was created with
+    vulkan_api_version: u32,
+
+    /// Properties of the physical device, fetched once at creation
+    physical_device_properties: vk::PhysicalDeviceProperties,
+
+    /// Memory properties of the physical device, fetched once at creation
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+
+    /// Vulkan functions used outside of VMA
+    device_functions: DeviceFunctions,
+
+    /// How names passed to `Allocator::set_allocation_name` and `Allocator::set_pool_name` are treated
+    name_policy: NamePolicy,
+
+    /// Where diagnostics of out-of-memory failures go, see `Allocator::set_oom_dump`
+    oom_dump: Option<oom::OomDumpTarget>,
+
+    /// Budget reserved with `Allocator::reserve_budget`, per heap
+    reservations: std::sync::Arc<budget::Reservations>,
+
+    /// Custom pools that have not been destroyed yet
+    pools: std::sync::Arc<snapshot::PoolRegistry>,
+
+    /// `pNext` chains of custom pools, see `AllocatorPoolCreateInfo::memory_allocate_next`
+    pool_next: std::sync::Arc<pool_next::PoolNextStore>,
+
+    /// Configuration and pools of `Allocator::set_smart_routing`
+    routing: std::sync::Arc<routing::Router>,
+
+    /// Frame hooks and resources whose destruction was deferred with `frame::FrameGuard`
+    frames: std::sync::Arc<frame::FrameState>,
+
+    /// Resources whose destruction waits for a fence, see `Allocator::free_when`
+    fences: std::sync::Arc<deferred::FenceQueue>,
+
+    /// `vkSetDebugUtilsObjectNameEXT`, if the instance enabled `VK_EXT_debug_utils`
+    debug_names: Option<debug_utils::DebugNames>,
+
+    /// Data attached with `Allocator::set_user_data`, per allocation
+    user_data: std::sync::Arc<user_data::UserDataStore>,
+
+    /// Tiers and eviction callbacks set with `Allocator::set_memory_priority`, per allocation
+    priorities: std::sync::Arc<priority::PriorityStore>,
+
+    /// Live allocations and their categories, see `AllocationCreateInfo::category`
+    categories: std::sync::Arc<category::CategoryTracker>,
+
+    /// Shadow state used to validate API usage
+    #[cfg(feature = "validation")]
+    validator: std::sync::Arc<validation::Validator>,
+
+    /// Creation timestamps and lifetime histograms of allocations
+    #[cfg(feature = "lifetime_stats")]
+    lifetimes: std::sync::Arc<lifetime::LifetimeTracker>,
+
+    /// Pool names live allocations were reported to Tracy under
+    #[cfg(feature = "tracy")]
+    tracy: std::sync::Arc<tracy::TracyMemory>,
+
+    /// Creation context of live allocations, reported as leaks
+    #[cfg(feature = "leak_track")]
+    leaks: std::sync::Arc<leak::LeakTracker>,
+
+    /// Latency histograms of allocator calls
+    #[cfg(feature = "timing")]
+    timings: std::sync::Arc<timing::Timings>,
+
+    /// Writer of the event log set with `Allocator::set_allocation_log`
+    #[cfg(feature = "alloc_log")]
+    event_log: std::sync::Arc<alloc_log::AllocationLog>,
 }
 
 /// Represents custom memory pool handle.
@@ -521,6 +808,7 @@ pub type AllocatorPool = ffi::VmaPool;
 /// Some kinds allocations can be in lost state.
 pub type Allocation = ffi::VmaAllocation;
 
+#[derive(Debug)]
 pub struct DefragmentationContext {
     internal: ffi::VmaDefragmentationContext,
 }
@@ -567,7 +855,12 @@ pub struct DeviceMemoryCallbacks {
     pub p_user_data: *mut ::std::os::raw::c_void,
 }
 
-// pub struct VmaVulkanFunctions ... // this structure is not needed for this wrapper
+/// Pointers to the Vulkan functions used by VMA.
+///
+/// By default they are taken from the `ash::Instance` and `ash::Device` in `AllocatorCreateInfo`.
+/// Set `AllocatorCreateInfo::vulkan_functions` to route VMA's calls through other pointers, e.g.
+/// an interception layer or a headless stub.
+pub type VulkanFunctions = ffi::VmaVulkanFunctions;
 
 /// Description of an `Allocator` to be created.
 pub struct AllocatorCreateInfo<'a> {
@@ -582,10 +875,14 @@ pub struct AllocatorCreateInfo<'a> {
 
     /// Preferred size of a single `ash::vk::DeviceMemory` block to be allocated from large heaps > 1 GiB.
     /// Set to 0 to use default, which is currently 256 MiB.
+    ///
+    /// Set to `AllocatorCreateInfo::AUTO_BLOCK_SIZE` to derive it from the size of the device
+    /// local heaps with `AllocatorCreateInfo::auto_block_size`, so cards with little memory don't
+    /// lose a large share of it to partly used 256 MiB blocks.
     pub preferred_large_heap_block_size: ash::vk::DeviceSize,
 
     /// Custom CPU memory allocation callbacks.
-    pub allocation_callbacks: Option<vk::AllocationCallbacks>,
+    pub allocation_callbacks: Option<ash_compat::AllocationCallbacks<'a>>,
 
     /// Custom CPU memory allocation callbacks.
     pub device_memory_callbacks: Option<DeviceMemoryCallbacks>,
@@ -603,10 +900,10 @@ pub struct AllocatorCreateInfo<'a> {
     /// If there is a limit defined for a heap:
     ///
     /// * If user tries to allocate more memory from that heap using this allocator, the allocation
-    /// fails with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY`.
+    ///   fails with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY`.
     ///
     /// * If the limit is smaller than heap size reported in `ash::vk::MemoryHeap::size`, the value of this
-    /// limit will be reported instead when using `Allocator::get_memory_properties`.
+    ///   limit will be reported instead when using `Allocator::get_memory_properties`.
     ///
     /// Warning! Using this feature may not be equivalent to installing a GPU with smaller amount of
     /// memory, because graphics driver doesn't necessary fail new allocations with
@@ -615,9 +912,29 @@ pub struct AllocatorCreateInfo<'a> {
     /// also be controlled using the `VK_AMD_memory_overallocation_behavior` extension.
     pub heap_size_limit: Option<&'a [ash::vk::DeviceSize]>,
 
-    // /// Pointers to Vulkan functions. Can be null.
-    // /// For details see [Pointers to Vulkan functions](@ref config_Vulkan_functions)."]
-    // pub pVulkanFunctions: *const VmaVulkanFunctions,
+    /// Either empty or an array of limits like `heap_size_limit`, each a fraction of the size of
+    /// the heap reported by the driver, e.g. 0.8 to leave a fifth of the VRAM to other applications.
+    ///
+    /// If not empty, it must contain `ash::vk::PhysicalDeviceMemoryProperties::memory_heap_count`
+    /// elements, each greater than 0. Elements of 1.0 or more mean no limit on that heap. If both
+    /// arrays are given, the smaller limit of each heap applies.
+    pub heap_size_limit_fractions: Option<&'a [f32]>,
+
+    /// Vulkan functions VMA calls instead of the ones of `instance` and `device`.
+    ///
+    /// The table is used as given, so every function in it must be valid for `device` (or
+    /// `instance`). Optional entries VMA only uses with certain `flags`, like
+    /// `vkGetMemoryWin32HandleKHR`, are not filled in from the device either.
+    pub vulkan_functions: Option<VulkanFunctions>,
+
+    /// `vkGetInstanceProcAddr` of the loader `instance` was created with, e.g.
+    /// `entry.static_fn().get_instance_proc_addr`.
+    ///
+    /// If `None`, `Allocator::new` links or loads the Vulkan library itself, depending on the
+    /// `link_vulkan` and `load_vulkan` features. Providing it avoids loading the library a second
+    /// time, and is required when neither feature is enabled.
+    pub get_instance_proc_addr: Option<vk::PFN_vkGetInstanceProcAddr>,
+
     /// Handle to Vulkan instance object.
     /// It must be valid throughout whole lifetime of created allocator.
     pub instance: ash::Instance,
@@ -626,11 +943,24 @@ pub struct AllocatorCreateInfo<'a> {
     /// It must be a value in the format as created by macro `VK_MAKE_VERSION` or a constant like:
     /// `VK_API_VERSION_1_1`, `VK_API_VERSION_1_0`. The patch version number specified is ignored.
     /// Only the major and minor versions are considered. It must be less or equal (preferably equal)
-    /// to value as passed to `vkCreateInstance` as `VkApplicationInfo::apiVersion`. Only versions
-    /// 1.0, 1.1, 1.2 are supported by the current implementation.
-    /// Leaving it initialized to zero is equivalent to `VK_API_VERSION_1_0`.
+    /// to value as passed to `vkCreateInstance` as `VkApplicationInfo::apiVersion`. Versions 1.0 to
+    /// 1.4 are supported.
+    /// Leaving it initialized to zero is equivalent to `VK_API_VERSION_1_0`, unless
+    /// `api_version_from_instance` is set.
+    ///
+    /// `Allocator::new` fails with `ash::vk::Result::ERROR_INITIALIZATION_FAILED` if the version
+    /// isn't supported or is higher than the `apiVersion` of the physical device.
     pub vulkan_api_version: u32,
 
+    /// If set and `vulkan_api_version` is zero, the allocator uses the highest version supported
+    /// by both the Vulkan loader (`vkEnumerateInstanceVersion`) and the physical device, capped at
+    /// 1.4.
+    ///
+    /// Only set this if the instance was created with at least that version as
+    /// `VkApplicationInfo::apiVersion`, e.g. because it asked for the loader's version. The version
+    /// the allocator ended up with is reported by `Allocator::vulkan_api_version`.
+    pub api_version_from_instance: bool,
+
     /// Either null or a pointer to an array of external memory handle types for each Vulkan memory type.
     ///
     /// If not NULL, it must be a pointer to an array of `VkPhysicalDeviceMemoryProperties::memoryTypeCount`
@@ -642,6 +972,92 @@ pub struct AllocatorCreateInfo<'a> {
     pub external_memory_handle_type: *const vk::ExternalMemoryHandleTypeFlagsKHR,
 }
 
+impl AllocatorCreateInfo<'_> {
+    /// Value of `AllocatorCreateInfo::preferred_large_heap_block_size` that derives the block size
+    /// from the heap sizes of the device.
+    pub const AUTO_BLOCK_SIZE: vk::DeviceSize = vk::WHOLE_SIZE;
+
+    /// Block size for large heaps suited to the device local heaps of a device: a sixteenth of the
+    /// smallest device local heap larger than 1 GiB, rounded down to a power of two, between 32 MiB
+    /// and VMA's default of 256 MiB.
+    ///
+    /// A 2 GiB card gets 128 MiB blocks, cards with 4 GiB or more get the default. Returns 0, i.e.
+    /// VMA's default, if no device local heap is larger than 1 GiB, as VMA already uses an eighth
+    /// of the heap size for blocks of smaller heaps.
+    pub fn auto_block_size(
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    ) -> vk::DeviceSize {
+        const SMALL_HEAP_MAX_SIZE: vk::DeviceSize = 1024 * 1024 * 1024;
+        const MIN_BLOCK_SIZE: vk::DeviceSize = 32 * 1024 * 1024;
+        const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 256 * 1024 * 1024;
+
+        memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| {
+                heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+                    && heap.size > SMALL_HEAP_MAX_SIZE
+            })
+            .map(|heap| {
+                let size = heap.size / 16;
+                let power_of_two = 1 << (63 - size.leading_zeros());
+                power_of_two.clamp(MIN_BLOCK_SIZE, DEFAULT_BLOCK_SIZE)
+            })
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// How `Allocator::set_allocation_name` and `Allocator::set_pool_name` treat names that can't be
+/// stored as given. Set it with `Allocator::set_name_policy`.
+#[derive(Debug, Clone, Copy)]
+pub struct NamePolicy {
+    /// Maximum length of a stored name in bytes, or 0 for no limit.
+    ///
+    /// Longer names are truncated at a character boundary and a warning is logged, to keep
+    /// `Allocator::build_stats_string` dumps manageable.
+    pub max_length: usize,
+
+    /// Replace interior NUL characters with U+FFFD instead of rejecting the name.
+    pub replace_interior_nul: bool,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        NamePolicy {
+            max_length: 256,
+            replace_interior_nul: false,
+        }
+    }
+}
+
+impl NamePolicy {
+    fn make_c_string(
+        &self,
+        kind: &str,
+        name: &str,
+    ) -> Result<std::ffi::CString, std::ffi::NulError> {
+        let mut name = std::borrow::Cow::Borrowed(name);
+        if self.replace_interior_nul && name.contains('\0') {
+            name = std::borrow::Cow::Owned(name.replace('\0', "\u{FFFD}"));
+        }
+        if self.max_length != 0 && name.len() > self.max_length {
+            let mut end = self.max_length;
+            while !name.is_char_boundary(end) {
+                end -= 1;
+            }
+            log::warn!(
+                "{} name of {} bytes truncated to {} bytes: {:?}",
+                kind,
+                name.len(),
+                end,
+                &name[..end]
+            );
+            name = std::borrow::Cow::Owned(name[..end].to_owned());
+        }
+        std::ffi::CString::new(name.into_owned())
+    }
+}
+
 /// Information about existing #Allocator object.
 pub struct AllocatorInfo {
     /// Handle to Vulkan instance object.
@@ -664,7 +1080,7 @@ pub struct AllocatorInfo {
 ///
 /// These are fast to calculate.
 /// See functions: vmaGetHeapBudgets(), vmaGetPoolStatistics().
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Statistics {
     /// Number of `VkDeviceMemory` objects - Vulkan memory blocks allocated.
     pub block_count: u32,
@@ -731,6 +1147,12 @@ pub struct TotalStatistics {
     pub memory_type: [DetailedStatistics; 32usize],
     pub memory_heap: [DetailedStatistics; 16usize],
     pub total: DetailedStatistics,
+
+    /// Number of valid entries in `memory_type`, i.e. the number of memory types of the physical device.
+    pub memory_type_count: u32,
+
+    /// Number of valid entries in `memory_heap`, i.e. the number of memory heaps of the physical device.
+    pub memory_heap_count: u32,
 }
 
 /// Statistics of current memory usage and available budget for a specific memory heap.
@@ -765,6 +1187,7 @@ pub struct Budget {
 /// Parameters of new #Allocation.
 ///
 /// To be used with functions like vmaCreateBuffer(), vmaCreateImage(), and many others.
+#[derive(Debug, Clone)]
 pub struct AllocationCreateInfo {
     /// Use #AllocationCreateFlagBits enum.
     pub flags: AllocationCreateFlags,
@@ -814,6 +1237,45 @@ pub struct AllocationCreateInfo {
     /// and this allocation ends up as dedicated or is explicitly forced as dedicated using #VMA_ALLOCATION_CREATE_DEDICATED_MEMORY_BIT.
     /// Otherwise, it has the priority of a memory block where it is placed and this variable is ignored.
     pub priority: f32,
+
+    /// Category the allocation is counted towards in `Allocator::category_stats`, if any.
+    pub category: Option<category::Category>,
+
+    /// Required flags tried in order by `Allocator::allocate_with_fallback` and
+    /// `Allocator::create_buffer_with_fallback` when allocating with `required_flags` fails, e.g.
+    /// `DEVICE_LOCAL | HOST_VISIBLE` and then `HOST_VISIBLE` after `DEVICE_LOCAL`.
+    ///
+    /// Ignored by all other functions.
+    pub fallback_required_flags: Vec<vk::MemoryPropertyFlags>,
+}
+
+impl AllocationCreateInfo {
+    /// The parameters of each tier of the fallback chain: this info, then a copy with
+    /// `required_flags` replaced by each entry of `fallback_required_flags`.
+    fn fallback_tiers(&self) -> impl Iterator<Item = std::borrow::Cow<'_, AllocationCreateInfo>> {
+        std::iter::once(std::borrow::Cow::Borrowed(self)).chain(
+            self.fallback_required_flags
+                .iter()
+                .map(move |&required_flags| {
+                    std::borrow::Cow::Owned(AllocationCreateInfo {
+                        required_flags,
+                        fallback_required_flags: Vec::new(),
+                        ..self.clone()
+                    })
+                }),
+        )
+    }
+}
+
+/// Whether a failed tier of a fallback chain should be followed by the next one: the memory ran
+/// out, or no memory type has the required flags.
+fn is_fallback_error(result: vk::Result) -> bool {
+    matches!(
+        result,
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY
+            | vk::Result::ERROR_OUT_OF_HOST_MEMORY
+            | vk::Result::ERROR_FEATURE_NOT_PRESENT
+    )
 }
 
 /// Description of an `AllocationPool` to be created.
@@ -861,15 +1323,15 @@ pub struct AllocatorPoolCreateInfo {
     /// e.g. when doing interop with OpenGL.
     pub min_allocation_alignment: vk::DeviceSize,
 
-    /// Additional `pNext` chain to be attached to `VkMemoryAllocateInfo` used for every allocation made by this pool. Optional.
+    /// Additional structure to be attached to `VkMemoryAllocateInfo` used for every allocation made by this pool. Optional.
     ///
-    /// Optional, can be null. If not null, it must point to a `pNext` chain of structures that can be attached to `VkMemoryAllocateInfo`.
-    /// It can be useful for special needs such as adding `VkExportMemoryAllocateInfoKHR`.
-    /// Structures pointed by this member must remain alive and unchanged for the whole lifetime of the custom pool.
+    /// It can be useful for special needs such as adding `VkExportMemoryAllocateInfoKHR`. Except for
+    /// `pool_next::PoolAllocateNext::Raw`, the structure is built by `Allocator::create_pool` and
+    /// kept alive until `Allocator::destroy_pool`.
     ///
     /// Please note that some structures, e.g. `VkMemoryPriorityAllocateInfoEXT`, `VkMemoryDedicatedAllocateInfoKHR`,
     /// can be attached automatically by this library when using other, more convenient of its features.
-    pub p_memory_allocate_next: *mut ::std::os::raw::c_void,
+    pub memory_allocate_next: Option<pool_next::PoolAllocateNext>,
 }
 
 /// Parameters of `Allocation` objects, that can be retrieved using `Allocator::get_allocation_info`.
@@ -904,6 +1366,10 @@ pub struct DefragmentationInfo {
 }
 
 /// Single move of an allocation to be done for defragmentation.
+///
+/// Laid out like `ffi::VmaDefragmentationMove`, so `DefragmentationPassMoveInfo::moves` can view the
+/// array filled by VMA directly.
+#[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct DefragmentationMove {
     /// Operation to be performed on the allocation by vmaEndDefragmentationPass(). Default value is #VMA_DEFRAGMENTATION_MOVE_OPERATION_COPY. You can modify it."]
@@ -957,7 +1423,7 @@ pub struct VirtualBlockCreateInfo {
     /// Custom CPU memory allocation callbacks. Optional.
     ///
     /// Optional, can be null. When specified, they will be used for all CPU-side memory allocations.
-    pub allocation_callbacks: Option<vk::AllocationCallbacks>,
+    pub allocation_callbacks: Option<ash_compat::AllocationCallbacks<'static>>,
 }
 
 /// Parameters of created virtual allocation to be passed to vmaVirtualAllocate().
@@ -999,11 +1465,18 @@ pub struct VirtualAllocationInfo {
     pub p_user_data: *mut ::std::os::raw::c_void,
 }
 
+/// Resource an allocation has been created for or bound to.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum BoundResource {
+    Buffer(vk::Buffer),
+    Image(vk::Image),
+}
+
 /* #endregion STRUCTURES */
 
 /* #region FUNCTIONS & IMPLS */
 
-// Allocator is internally thread safe unless AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED is used (then you need to add synchronization!)
+// Allocator is internally thread safe; AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED is only allowed for the !Sync SingleThreadAllocator
 unsafe impl Send for Allocator {}
 unsafe impl Sync for Allocator {}
 
@@ -1061,12 +1534,15 @@ impl Into<ffi::VmaDetailedStatistics> for DetailedStatistics {
     }
 }
 
+/// Counts are set to the array lengths, as the raw struct does not record them.
 impl From<ffi::VmaTotalStatistics> for TotalStatistics {
     fn from(vma_statistics: ffi::VmaTotalStatistics) -> Self {
         TotalStatistics {
             memory_type: vma_statistics.memoryType.map(|value| value.into()),
             memory_heap: vma_statistics.memoryHeap.map(|value| value.into()),
             total: vma_statistics.total.into(),
+            memory_type_count: vk::MAX_MEMORY_TYPES as u32,
+            memory_heap_count: vk::MAX_MEMORY_HEAPS as u32,
         }
     }
 }
@@ -1081,6 +1557,34 @@ impl Into<ffi::VmaTotalStatistics> for TotalStatistics {
     }
 }
 
+impl TotalStatistics {
+    /// Statistics of the memory types of the physical device that have at least one block or
+    /// allocation, with their memory type index.
+    pub fn iter_types(&self) -> impl Iterator<Item = (u32, &DetailedStatistics)> {
+        non_empty_statistics(&self.memory_type, self.memory_type_count)
+    }
+
+    /// Statistics of the memory heaps of the physical device that have at least one block or
+    /// allocation, with their memory heap index.
+    pub fn iter_heaps(&self) -> impl Iterator<Item = (u32, &DetailedStatistics)> {
+        non_empty_statistics(&self.memory_heap, self.memory_heap_count)
+    }
+}
+
+fn non_empty_statistics(
+    statistics: &[DetailedStatistics],
+    count: u32,
+) -> impl Iterator<Item = (u32, &DetailedStatistics)> {
+    statistics
+        .iter()
+        .take(count as usize)
+        .enumerate()
+        .filter(|(_, value)| {
+            value.statistics.block_count > 0 || value.statistics.allocation_count > 0
+        })
+        .map(|(index, value)| (index as u32, value))
+}
+
 impl AllocationInfo {
     #[inline(always)]
     // Gets the memory type index that this allocation was allocated from. (Never changes)
@@ -1132,14 +1636,57 @@ impl AllocationInfo {
         self.internal.pMappedData as *mut u8
     }
 
-    /*#[inline(always)]
-    pub fn get_mapped_slice(&self) -> Option<&mut &[u8]> {
+    /// Mapped memory of this allocation as a byte slice, or `None` if it wasn't mapped when this
+    /// `AllocationInfo` was retrieved.
+    ///
+    /// # Safety
+    ///
+    /// The slice points into memory owned by the allocation, not by this `AllocationInfo`, which is
+    /// only a snapshot of its parameters. While the slice is alive:
+    ///
+    /// - the allocation must stay mapped and must not be freed or moved by defragmentation. An
+    ///   allocation created with `AllocationCreateFlags::MAPPED` stays mapped for its whole lifetime;
+    ///   otherwise every `Allocator::map_memory` must be matched by `Allocator::unmap_memory` only after
+    ///   the slice is gone.
+    /// - nothing may write to the same bytes, neither through a slice from another `AllocationInfo` of
+    ///   the same allocation, nor through `Allocator::copy_to_allocation`, nor the device.
+    ///
+    /// If the memory type is not `HOST_COHERENT`, call `Allocator::invalidate_allocation` before
+    /// reading data written by the device.
+    #[inline(always)]
+    pub unsafe fn mapped_slice(&self) -> Option<&[u8]> {
+        if self.internal.pMappedData.is_null() {
+            None
+        } else {
+            Some(std::slice::from_raw_parts(
+                self.internal.pMappedData as *const u8,
+                self.internal.size as usize,
+            ))
+        }
+    }
+
+    /// Mapped memory of this allocation as a mutable byte slice, or `None` if it wasn't mapped when
+    /// this `AllocationInfo` was retrieved.
+    ///
+    /// # Safety
+    ///
+    /// Same as `AllocationInfo::mapped_slice`, and additionally nothing else may read or write the
+    /// same bytes while the slice is alive, including slices from other `AllocationInfo`s of the same
+    /// allocation and the device.
+    ///
+    /// If the memory type is not `HOST_COHERENT`, call `Allocator::flush_allocation` after writing
+    /// data the device should see.
+    #[inline(always)]
+    pub unsafe fn mapped_slice_mut(&mut self) -> Option<&mut [u8]> {
         if self.internal.pMappedData.is_null() {
             None
         } else {
-            Some(unsafe { &mut ::std::slice::from_raw_parts(self.internal.pMappedData as *mut u8, self.get_size()) })
+            Some(std::slice::from_raw_parts_mut(
+                self.internal.pMappedData as *mut u8,
+                self.internal.size as usize,
+            ))
         }
-    }*/
+    }
 
     /// Custom general-purpose pointer that was passed as `AllocationCreateInfo::user_data` or set using `Allocator::set_allocation_user_data`.
     ///
@@ -1148,6 +1695,318 @@ impl AllocationInfo {
     pub fn get_user_data(&self) -> *mut ::std::os::raw::c_void {
         self.internal.pUserData
     }
+
+    /// Name set with `Allocator::set_allocation_name`, or `None` if the allocation has no name.
+    ///
+    /// `Allocator::get_allocation_name` returns a copy of the current name instead.
+    ///
+    /// # Safety
+    ///
+    /// The string is owned by the allocation. The allocation must not be freed or renamed while
+    /// the returned `&str` is in use.
+    #[inline(always)]
+    pub unsafe fn get_name(&self) -> Option<&str> {
+        if self.internal.pName.is_null() {
+            None
+        } else {
+            std::ffi::CStr::from_ptr(self.internal.pName).to_str().ok()
+        }
+    }
+}
+
+impl std::fmt::Display for BoundResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoundResource::Buffer(buffer) => write!(f, "buffer {:?}", buffer),
+            BoundResource::Image(image) => write!(f, "image {:?}", image),
+        }
+    }
+}
+
+impl std::ops::Add for Statistics {
+    type Output = Statistics;
+
+    fn add(self, other: Statistics) -> Statistics {
+        Statistics {
+            block_count: self.block_count + other.block_count,
+            allocation_count: self.allocation_count + other.allocation_count,
+            block_bytes: self.block_bytes + other.block_bytes,
+            allocation_bytes: self.allocation_bytes + other.allocation_bytes,
+        }
+    }
+}
+
+impl std::iter::Sum for Statistics {
+    fn sum<I: Iterator<Item = Statistics>>(iter: I) -> Statistics {
+        iter.fold(Statistics::default(), |total, statistics| {
+            total + statistics
+        })
+    }
+}
+
+impl std::fmt::Display for Statistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} allocations ({}) in {} blocks ({}",
+            self.allocation_count,
+            format_bytes(self.allocation_bytes),
+            self.block_count,
+            format_bytes(self.block_bytes)
+        )?;
+        if self.block_bytes > 0 {
+            write!(
+                f,
+                ", {:.1}% used",
+                percentage(self.allocation_bytes, self.block_bytes)
+            )?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Display for DetailedStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.statistics)?;
+        if self.statistics.allocation_count > 0 {
+            write!(
+                f,
+                ", allocations {}..{}",
+                format_bytes(self.allocation_size_min),
+                format_bytes(self.allocation_size_max)
+            )?;
+        }
+        write!(f, ", {} unused ranges", self.unused_range_count)?;
+        if self.unused_range_count > 0 {
+            write!(
+                f,
+                " {}..{}",
+                format_bytes(self.unused_range_size_min),
+                format_bytes(self.unused_range_size_max)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Budget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "usage {} of {} budget ({:.1}%), {}",
+            format_bytes(self.usage),
+            format_bytes(self.budget),
+            percentage(self.usage, self.budget),
+            self.statistics
+        )
+    }
+}
+
+impl std::fmt::Display for DefragmentationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "moved {} allocations ({}), freed {} blocks ({})",
+            self.allocations_moved,
+            format_bytes(self.bytes_moved),
+            self.device_memory_blocks_freed,
+            format_bytes(self.bytes_freed)
+        )
+    }
+}
+
+impl MemoryUsage {
+    /// Whether this is one of the `Auto*` usages, which let VMA pick the memory type from the
+    /// buffer or image the memory is for and only work with functions that know that resource.
+    pub fn is_auto(&self) -> bool {
+        matches!(
+            self,
+            MemoryUsage::Auto | MemoryUsage::AutoPreferDevice | MemoryUsage::AutoPreferHost
+        )
+    }
+}
+
+/// Formats `usage/budget` in GiB with one decimal, or in whole MiB for budgets below 1 GiB.
+fn format_usage(usage: vk::DeviceSize, budget: vk::DeviceSize) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    const GIB: f64 = 1024.0 * MIB;
+    if budget as f64 >= GIB {
+        format!("{:.1}/{:.1} GiB", usage as f64 / GIB, budget as f64 / GIB)
+    } else {
+        format!("{:.0}/{:.0} MiB", usage as f64 / MIB, budget as f64 / MIB)
+    }
+}
+
+/// Formats a size in bytes, KiB, MiB or GiB with one decimal.
+pub(crate) fn format_bytes(bytes: vk::DeviceSize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// The heap size limits to pass to VMA: `AllocatorCreateInfo::heap_size_limit` combined with
+/// `AllocatorCreateInfo::heap_size_limit_fractions` of the heap sizes in `memory_properties`.
+fn heap_size_limits(
+    create_info: &AllocatorCreateInfo,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+) -> VkResult<Option<Vec<vk::DeviceSize>>> {
+    let fractions = match create_info.heap_size_limit_fractions {
+        Some(fractions) if !fractions.is_empty() => fractions,
+        _ => return Ok(create_info.heap_size_limit.map(<[vk::DeviceSize]>::to_vec)),
+    };
+    let heap_count = memory_properties.memory_heap_count as usize;
+    let invalid = |fraction: &f32| fraction.is_nan() || *fraction <= 0.0;
+    if fractions.len() != heap_count || fractions.iter().any(invalid) {
+        log::error!(
+            "AllocatorCreateInfo::heap_size_limit_fractions must have {} elements greater than 0, \
+             got {:?}",
+            heap_count,
+            fractions
+        );
+        return Err(vk::Result::ERROR_INITIALIZATION_FAILED);
+    }
+
+    let limits = memory_properties.memory_heaps[..heap_count]
+        .iter()
+        .zip(fractions)
+        .enumerate()
+        .map(|(heap_index, (heap, &fraction))| {
+            let from_fraction = if fraction >= 1.0 {
+                vk::WHOLE_SIZE
+            } else {
+                (heap.size as f64 * fraction as f64) as vk::DeviceSize
+            };
+            let from_bytes = create_info
+                .heap_size_limit
+                .and_then(|limits| limits.get(heap_index).copied())
+                .unwrap_or(vk::WHOLE_SIZE);
+            from_fraction.min(from_bytes)
+        })
+        .collect();
+    Ok(Some(limits))
+}
+
+/// Highest Vulkan API version supported by the bundled VMA.
+const MAX_API_VERSION: u32 = vk::make_api_version(0, 1, 4, 0);
+
+/// `version` without its patch number.
+fn api_version_major_minor(version: u32) -> u32 {
+    vk::make_api_version(
+        vk::api_version_variant(version),
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        0,
+    )
+}
+
+/// The Vulkan API version to create the allocator with: `AllocatorCreateInfo::vulkan_api_version`
+/// or the detected one, checked against what VMA and the physical device support.
+unsafe fn vulkan_api_version(
+    create_info: &AllocatorCreateInfo,
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    device_api_version: u32,
+) -> VkResult<u32> {
+    let device_api_version = api_version_major_minor(device_api_version);
+    let version = match create_info.vulkan_api_version {
+        0 if create_info.api_version_from_instance => {
+            let enumerate_instance_version = get_instance_proc_addr(
+                vk::Instance::null(),
+                c"vkEnumerateInstanceVersion".as_ptr(),
+            );
+            let mut loader_api_version = vk::API_VERSION_1_0;
+            if let Some(enumerate_instance_version) = enumerate_instance_version {
+                let enumerate_instance_version = mem::transmute::<
+                    unsafe extern "system" fn(),
+                    vk::PFN_vkEnumerateInstanceVersion,
+                >(enumerate_instance_version);
+                let _ = enumerate_instance_version(&mut loader_api_version);
+            }
+            let version = api_version_major_minor(loader_api_version)
+                .min(device_api_version)
+                .min(MAX_API_VERSION);
+            log::debug!(
+                "Using Vulkan {}.{} for the allocator",
+                vk::api_version_major(version),
+                vk::api_version_minor(version)
+            );
+            return Ok(version);
+        }
+        0 => vk::API_VERSION_1_0,
+        version => api_version_major_minor(version),
+    };
+
+    if vk::api_version_variant(version) != 0
+        || vk::api_version_major(version) != 1
+        || version > MAX_API_VERSION
+    {
+        log::error!(
+            "AllocatorCreateInfo::vulkan_api_version {}.{} (variant {}) is not supported, only \
+             Vulkan 1.0 to 1.4 are",
+            vk::api_version_major(version),
+            vk::api_version_minor(version),
+            vk::api_version_variant(version)
+        );
+        return Err(vk::Result::ERROR_INITIALIZATION_FAILED);
+    }
+    if version > device_api_version {
+        log::error!(
+            "AllocatorCreateInfo::vulkan_api_version is {}.{}, but the physical device only \
+             supports Vulkan {}.{}",
+            vk::api_version_major(version),
+            vk::api_version_minor(version),
+            vk::api_version_major(device_api_version),
+            vk::api_version_minor(device_api_version)
+        );
+        return Err(vk::Result::ERROR_INITIALIZATION_FAILED);
+    }
+    Ok(version)
+}
+
+/// `part` as a percentage of `whole`, 0 if `whole` is 0.
+fn percentage(part: vk::DeviceSize, whole: vk::DeviceSize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+/// `vkGetInstanceProcAddr` of the Vulkan library linked or loaded by ash.
+#[cfg(feature = "link_vulkan")]
+fn library_get_instance_proc_addr() -> VkResult<vk::PFN_vkGetInstanceProcAddr> {
+    Ok(ash::Entry::linked().static_fn().get_instance_proc_addr)
+}
+
+/// `vkGetInstanceProcAddr` of the Vulkan library linked or loaded by ash.
+#[cfg(all(feature = "load_vulkan", not(feature = "link_vulkan")))]
+fn library_get_instance_proc_addr() -> VkResult<vk::PFN_vkGetInstanceProcAddr> {
+    // The library is never unloaded, as the function pointers fetched through it are used for the
+    // whole lifetime of the allocator.
+    let entry = unsafe { ash::Entry::load() }.map_err(|error| {
+        log::error!("Failed to load the Vulkan library: {}", error);
+        vk::Result::ERROR_INITIALIZATION_FAILED
+    })?;
+    let get_instance_proc_addr = entry.static_fn().get_instance_proc_addr;
+    mem::forget(entry);
+    Ok(get_instance_proc_addr)
+}
+
+/// `vkGetInstanceProcAddr` of the Vulkan library linked or loaded by ash.
+#[cfg(not(any(feature = "link_vulkan", feature = "load_vulkan")))]
+fn library_get_instance_proc_addr() -> VkResult<vk::PFN_vkGetInstanceProcAddr> {
+    log::error!(
+        "AllocatorCreateInfo::get_instance_proc_addr must be set without the link_vulkan or load_vulkan feature"
+    );
+    Err(vk::Result::ERROR_INITIALIZATION_FAILED)
 }
 
 /// Converts a raw result into an ash result.
@@ -1159,6 +2018,50 @@ fn ffi_to_result(result: vk::Result) -> VkResult<()> {
     }
 }
 
+/// Converts byte range bounds into the offset and size VMA takes, `ash::vk::WHOLE_SIZE` for an
+/// unbounded end.
+fn offset_and_size(
+    range: impl std::ops::RangeBounds<vk::DeviceSize>,
+) -> (vk::DeviceSize, vk::DeviceSize) {
+    use std::ops::Bound;
+    let offset = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let size = match range.end_bound() {
+        Bound::Included(&end) => (end + 1).saturating_sub(offset),
+        Bound::Excluded(&end) => end.saturating_sub(offset),
+        Bound::Unbounded => vk::WHOLE_SIZE,
+    };
+    (offset, size)
+}
+
+/// Checks that the optional `offsets` and `sizes` of a batch flush or invalidation have one
+/// element per allocation.
+fn check_batch_lengths(
+    operation: &str,
+    allocation_count: usize,
+    offsets: Option<&[vk::DeviceSize]>,
+    sizes: Option<&[vk::DeviceSize]>,
+) -> VkResult<()> {
+    for (name, slice) in [("offsets", offsets), ("sizes", sizes)] {
+        if let Some(slice) = slice {
+            if slice.len() != allocation_count {
+                log::error!(
+                    "`{}`: {} {} for {} allocations",
+                    operation,
+                    slice.len(),
+                    name,
+                    allocation_count
+                );
+                return Err(vk::Result::ERROR_VALIDATION_FAILED_EXT);
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Converts an `AllocationCreateInfo` struct into the raw representation.
 #[allow(deprecated)]
 fn allocation_create_info_to_ffi(info: &AllocationCreateInfo) -> ffi::VmaAllocationCreateInfo {
@@ -1189,7 +2092,7 @@ fn allocation_create_info_to_ffi(info: &AllocationCreateInfo) -> ffi::VmaAllocat
             None => ::std::ptr::null_mut(), // TODO // unsafe { mem::zeroed() },
         },
         pUserData: info.p_user_data,
-        priority: 0.0,
+        priority: info.priority,
     }
 }
 
@@ -1201,24 +2104,175 @@ fn pool_create_info_to_ffi(info: &AllocatorPoolCreateInfo) -> ffi::VmaPoolCreate
         blockSize: info.block_size as vk::DeviceSize,
         minBlockCount: info.min_block_count,
         maxBlockCount: info.max_block_count,
-        priority: 0.0,
-        minAllocationAlignment: 0,
-        pMemoryAllocateNext: ::std::ptr::null_mut(),
+        priority: info.priority,
+        minAllocationAlignment: info.min_allocation_alignment,
+        pMemoryAllocateNext: std::ptr::null_mut(),
     }
 }
 
 impl Allocator {
     /// Constructor a new `Allocator` using the provided options.
+    ///
+    /// If `AllocatorCreateInfo::get_instance_proc_addr` is `None`, `vkGetInstanceProcAddr` is taken
+    /// from the Vulkan library, which is linked with the `link_vulkan` feature or loaded at runtime
+    /// with the `load_vulkan` feature. Without either feature (or if loading fails), it must be
+    /// provided and `ash::vk::Result::ERROR_INITIALIZATION_FAILED` is returned otherwise.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn new(create_info: &AllocatorCreateInfo) -> VkResult<Self> {
-        let instance = create_info.instance.clone();
-        let device = create_info.device.clone();
+        let get_instance_proc_addr = match create_info.get_instance_proc_addr {
+            Some(get_instance_proc_addr) => get_instance_proc_addr,
+            None => library_get_instance_proc_addr()?,
+        };
+        Self::create(create_info, get_instance_proc_addr)
+    }
+
+    /// Creates an allocator from raw Vulkan handles, for applications that don't create their
+    /// instance and device through ash.
+    ///
+    /// All Vulkan functions the allocator needs are fetched through `get_instance_proc_addr` and
+    /// `vkGetDeviceProcAddr`, so the Vulkan library isn't loaded or linked again. `api_version` is
+    /// the `vulkan_api_version` of `AllocatorCreateInfo` and `flags` its `flags`; all other
+    /// parameters are left at their defaults.
+    ///
+    /// # Safety
+    ///
+    /// `instance`, `device` and `physical_device` must be valid handles, with `device` created from
+    /// `physical_device` of `instance`, and must outlive the allocator. `get_instance_proc_addr`
+    /// must be the `vkGetInstanceProcAddr` of the loader `instance` was created with.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn new_from_raw(
+        instance: vk::Instance,
+        device: vk::Device,
+        physical_device: vk::PhysicalDevice,
+        get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+        api_version: u32,
+        flags: AllocatorCreateFlags,
+    ) -> VkResult<Self> {
+        let instance = ash::Instance::load(
+            &ash_compat::StaticFn {
+                get_instance_proc_addr,
+            },
+            instance,
+        );
+        let device = ash::Device::load(instance.fp_v1_0(), device);
+        let create_info = AllocatorCreateInfo {
+            flags,
+            physical_device,
+            device,
+            preferred_large_heap_block_size: 0,
+            allocation_callbacks: None,
+            device_memory_callbacks: None,
+            heap_size_limit: None,
+            heap_size_limit_fractions: None,
+            vulkan_functions: None,
+            get_instance_proc_addr: Some(get_instance_proc_addr),
+            instance,
+            vulkan_api_version: api_version,
+            api_version_from_instance: false,
+            external_memory_handle_type: std::ptr::null(),
+        };
+        Self::create(&create_info, get_instance_proc_addr)
+    }
+
+    /// Creates an allocator for the Vulkan device behind a wgpu-hal device, enabled with the
+    /// `wgpu_hal` feature, e.g. to manage the memory of native Vulkan passes in a wgpu application.
+    ///
+    /// The Vulkan API version is the one wgpu-hal created the instance with. `flags` must only
+    /// enable extensions wgpu-hal enabled on the device, see
+    /// `wgpu_hal::vulkan::Device::enabled_device_extensions`.
+    ///
+    /// # Safety
+    ///
+    /// The allocator must be dropped before `device`.
+    #[cfg(feature = "wgpu_hal")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn from_wgpu_hal(
+        device: &wgpu_hal::vulkan::Device,
+        flags: AllocatorCreateFlags,
+    ) -> VkResult<Self> {
+        let instance = device.shared_instance();
+        let create_info = AllocatorCreateInfo {
+            flags,
+            physical_device: device.raw_physical_device(),
+            device: device.raw_device().clone(),
+            preferred_large_heap_block_size: 0,
+            allocation_callbacks: None,
+            device_memory_callbacks: None,
+            heap_size_limit: None,
+            heap_size_limit_fractions: None,
+            vulkan_functions: None,
+            get_instance_proc_addr: Some(instance.entry().static_fn().get_instance_proc_addr),
+            instance: instance.raw_instance().clone(),
+            vulkan_api_version: instance.instance_api_version(),
+            api_version_from_instance: false,
+            external_memory_handle_type: std::ptr::null(),
+        };
+        Self::new(&create_info)
+    }
+
+    /// Creates an allocator for a vulkano device, enabled with the `vulkano` feature, e.g. to
+    /// manage the memory of native Vulkan code in a vulkano application.
+    ///
+    /// vulkano does not expose its `vkGetInstanceProcAddr`, so the one of the Vulkan library linked
+    /// or loaded with the `link_vulkan` or `load_vulkan` feature is used. The Vulkan API version is
+    /// the device's. `flags` must only enable extensions enabled on the device.
+    ///
+    /// # Safety
+    ///
+    /// The allocator must be dropped before `device`.
+    #[cfg(feature = "vulkano")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn from_vulkano(
+        device: &vulkano::device::Device,
+        flags: AllocatorCreateFlags,
+    ) -> VkResult<Self> {
+        use vulkano::VulkanObject;
+
+        let version = device.api_version();
+        Self::new_from_raw(
+            device.instance().handle(),
+            device.handle(),
+            device.physical_device().handle(),
+            library_get_instance_proc_addr()?,
+            vk::make_api_version(0, version.major, version.minor, version.patch),
+            flags,
+        )
+    }
 
-        #[cfg(feature = "load_vulkan")]
-        let entry = unsafe { ash::Entry::load().unwrap() };
-        #[cfg(feature = "link_vulkan")]
-        let entry = ash::Entry::linked();
+    unsafe fn create(
+        create_info: &AllocatorCreateInfo,
+        get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    ) -> VkResult<Self> {
+        if create_info
+            .flags
+            .contains(AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED)
+        {
+            log::error!(
+                "AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED would make the allocator unsound to share \
+                 between threads; create a single_thread::SingleThreadAllocator instead"
+            );
+            return Err(vk::Result::ERROR_INITIALIZATION_FAILED);
+        }
+        Self::create_with_flags(create_info, get_instance_proc_addr, create_info.flags)
+    }
 
-        let routed_functions = ffi::VmaVulkanFunctions {
+    /// Creates the allocator with `flags` instead of `AllocatorCreateInfo::flags`.
+    pub(crate) unsafe fn create_with_flags(
+        create_info: &AllocatorCreateInfo,
+        get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+        flags: AllocatorCreateFlags,
+    ) -> VkResult<Self> {
+        let instance = create_info.instance.clone();
+        let device = create_info.device.clone();
+        let vulkan_api_version = vulkan_api_version(
+            create_info,
+            get_instance_proc_addr,
+            instance
+                .get_physical_device_properties(create_info.physical_device)
+                .api_version,
+        )?;
+
+        let mut routed_functions = ffi::VmaVulkanFunctions {
             vkGetPhysicalDeviceProperties: instance.fp_v1_0().get_physical_device_properties,
             vkGetPhysicalDeviceMemoryProperties: instance
                 .fp_v1_0()
@@ -1245,33 +2299,105 @@ impl Allocator {
             vkGetPhysicalDeviceMemoryProperties2KHR: instance
                 .fp_v1_1()
                 .get_physical_device_memory_properties2,
-            vkGetInstanceProcAddr: entry.static_fn().get_instance_proc_addr,
+            vkGetInstanceProcAddr: get_instance_proc_addr,
             vkGetDeviceProcAddr: instance.fp_v1_0().get_device_proc_addr,
             vkGetDeviceBufferMemoryRequirements: device.fp_v1_3().get_device_buffer_memory_requirements,
             vkGetDeviceImageMemoryRequirements: device.fp_v1_3().get_device_image_memory_requirements,
+            #[cfg(windows)]
+            vkGetMemoryWin32HandleKHR: None,
+        };
+
+        let get_device_proc_addr = instance.fp_v1_0().get_device_proc_addr;
+        let load_device_fn = |name: &std::ffi::CStr| -> *const std::os::raw::c_void {
+            mem::transmute(get_device_proc_addr(device.handle(), name.as_ptr()))
+        };
+
+        if flags.contains(AllocatorCreateFlags::KHR_MAINTENANCE4)
+            && vulkan_api_version < vk::API_VERSION_1_3
+        {
+            let maintenance4 = ash_compat::KhrMaintenance4Fn::load(load_device_fn);
+            routed_functions.vkGetDeviceBufferMemoryRequirements =
+                maintenance4.get_device_buffer_memory_requirements_khr;
+            routed_functions.vkGetDeviceImageMemoryRequirements =
+                maintenance4.get_device_image_memory_requirements_khr;
+        }
+
+        #[cfg(windows)]
+        if flags.contains(AllocatorCreateFlags::KHR_EXTERNAL_MEMORY_WIN32) {
+            let external_memory_win32 = ash_compat::KhrExternalMemoryWin32Fn::load(load_device_fn);
+            routed_functions.vkGetMemoryWin32HandleKHR =
+                Some(external_memory_win32.get_memory_win32_handle_khr);
+        }
+
+        let vulkan_functions = match &create_info.vulkan_functions {
+            Some(functions) => functions,
+            None => &routed_functions,
         };
 
         let allocation_callbacks = match create_info.allocation_callbacks {
             None => std::ptr::null(),
-            Some(ref cb) => cb as *const _,
+            Some(ref cb) => (cb as *const ash_compat::AllocationCallbacks).cast(),
+        };
+
+        let memory_properties =
+            instance.get_physical_device_memory_properties(create_info.physical_device);
+        let heap_size_limit = heap_size_limits(create_info, &memory_properties)?;
+
+        let preferred_large_heap_block_size = if create_info.preferred_large_heap_block_size
+            == AllocatorCreateInfo::AUTO_BLOCK_SIZE
+        {
+            let block_size = AllocatorCreateInfo::auto_block_size(&memory_properties);
+            log::debug!(
+                "Derived preferred large heap block size {} from the device local heaps",
+                format_bytes(block_size)
+            );
+            block_size
+        } else {
+            create_info.preferred_large_heap_block_size
         };
 
         let ffi_create_info = ffi::VmaAllocatorCreateInfo {
             physicalDevice: create_info.physical_device,
             device: create_info.device.handle(),
             instance: instance.handle(),
-            flags: create_info.flags.bits(),
+            flags: flags.bits(),
             // frameInUseCount: create_info.frame_in_use_count,
-            preferredLargeHeapBlockSize: create_info.preferred_large_heap_block_size as u64,
-            pHeapSizeLimit: match &create_info.heap_size_limit {
+            preferredLargeHeapBlockSize: preferred_large_heap_block_size as u64,
+            pHeapSizeLimit: match &heap_size_limit {
                 None => ::std::ptr::null(),
                 Some(limits) => limits.as_ptr(),
             },
-            pVulkanFunctions: &routed_functions,
+            pVulkanFunctions: vulkan_functions,
             pAllocationCallbacks: allocation_callbacks,
             pDeviceMemoryCallbacks: ::std::ptr::null(), // TODO: Add support
-            vulkanApiVersion: create_info.vulkan_api_version,
-            pTypeExternalMemoryHandleTypes: ::std::ptr::null(),
+            vulkanApiVersion: vulkan_api_version,
+            pTypeExternalMemoryHandleTypes: create_info.external_memory_handle_type,
+        };
+
+        let requirements2 = vulkan_api_version >= vk::API_VERSION_1_1
+            || flags.contains(AllocatorCreateFlags::KHR_DEDICATED_ALLOCATION);
+        let device_functions = DeviceFunctions {
+            device: create_info.device.handle(),
+            create_buffer: vulkan_functions.vkCreateBuffer,
+            destroy_buffer: vulkan_functions.vkDestroyBuffer,
+            create_image: vulkan_functions.vkCreateImage,
+            destroy_image: vulkan_functions.vkDestroyImage,
+            get_buffer_memory_requirements2: requirements2
+                .then_some(vulkan_functions.vkGetBufferMemoryRequirements2KHR),
+            get_image_memory_requirements2: requirements2
+                .then_some(vulkan_functions.vkGetImageMemoryRequirements2KHR),
+            allocate_memory: vulkan_functions.vkAllocateMemory,
+            free_memory: vulkan_functions.vkFreeMemory,
+            bind_buffer_memory: vulkan_functions.vkBindBufferMemory,
+            bind_image_memory: vulkan_functions.vkBindImageMemory,
+            get_fence_status: device.fp_v1_0().get_fence_status,
+            host_pointer_import: host_memory::HostPointerImport::load(
+                get_instance_proc_addr,
+                get_device_proc_addr,
+                instance.handle(),
+                create_info.physical_device,
+                device.handle(),
+            ),
         };
 
         let mut internal: ffi::VmaAllocator = mem::zeroed();
@@ -1280,27 +2406,106 @@ impl Allocator {
             &mut internal,
         ))?;
 
-        Ok(Allocator { internal })
+        let mut physical_device_properties: *const vk::PhysicalDeviceProperties = std::ptr::null();
+        ffi::vmaGetPhysicalDeviceProperties(internal, &mut physical_device_properties);
+        let mut memory_properties: *const vk::PhysicalDeviceMemoryProperties = std::ptr::null();
+        ffi::vmaGetMemoryProperties(internal, &mut memory_properties);
+
+        Ok(Allocator {
+            internal,
+            destroyed: Default::default(),
+            flags,
+            vulkan_api_version,
+            physical_device_properties: *physical_device_properties,
+            memory_properties: *memory_properties,
+            device_functions,
+            name_policy: NamePolicy::default(),
+            oom_dump: None,
+            reservations: Default::default(),
+            pools: Default::default(),
+            pool_next: Default::default(),
+            routing: Default::default(),
+            frames: Default::default(),
+            fences: Default::default(),
+            debug_names: debug_utils::DebugNames::load(
+                get_instance_proc_addr,
+                instance.handle(),
+                device.handle(),
+            ),
+            user_data: Default::default(),
+            priorities: Default::default(),
+            categories: Default::default(),
+            #[cfg(feature = "validation")]
+            validator: Default::default(),
+            #[cfg(feature = "lifetime_stats")]
+            lifetimes: Default::default(),
+            #[cfg(feature = "tracy")]
+            tracy: Default::default(),
+            #[cfg(feature = "leak_track")]
+            leaks: Default::default(),
+            #[cfg(feature = "timing")]
+            timings: Default::default(),
+            #[cfg(feature = "alloc_log")]
+            event_log: Default::default(),
+        })
     }
 
-    /// Destroys the internal allocator instance. After this has been called,
-    /// no other functions may be called. Useful for ensuring a specific destruction
+    /// Destroys the internal allocator instance. Useful for ensuring a specific destruction
     /// order (for example, if an Allocator is a member of something that owns the Vulkan
     /// instance and destroys it in its own Drop).
+    ///
+    /// The allocator must be destroyed after every resource created through it has been destroyed
+    /// and its memory freed, and before the `ash::vk::Device` and `ash::vk::Instance` it was
+    /// created with. Frame arenas and smart-routing pools are released here. Clones of the
+    /// allocator share its state and are destroyed with it.
+    ///
+    /// After this has been called, `Allocator::is_destroyed` returns `true`, any other method
+    /// that reaches VMA panics, and further calls to `destroy` (including the one made on drop) do nothing.
+    ///
+    /// With the `leak_track` feature, allocations that are still alive are logged as errors
+    /// together with where they were created.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn destroy(&mut self) {
-        if !self.internal.is_null() {
+        if !self.is_destroyed() {
+            self.frames.free_all(self);
+            self.fences.free_all(self);
+            self.routing.destroy_all(self);
+            #[cfg(feature = "leak_track")]
+            for leak in self.leaks.live() {
+                log::error!("{}", leak);
+            }
+            #[cfg(feature = "validation")]
+            self.validator.check_destroy();
             ffi::vmaDestroyAllocator(self.internal);
-            self.internal = std::ptr::null_mut();
+            self.destroyed
+                .store(true, std::sync::atomic::Ordering::Release);
         }
+        self.internal = std::ptr::null_mut();
+    }
+
+    /// Whether `Allocator::destroy` has been called on this allocator or one of its clones.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn is_destroyed(&self) -> bool {
+        self.internal.is_null() || self.destroyed.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// The VMA handle, after checking that the allocator has not been destroyed.
+    pub(crate) fn handle(&self) -> ffi::VmaAllocator {
+        assert!(
+            !self.is_destroyed(),
+            "vk_mem::Allocator used after Allocator::destroy"
+        );
+        self.internal
     }
 
     /// Returns information about existing #VmaAllocator object - handle to Vulkan device etc.
     ///
     /// It might be useful if you want to keep just the #Allocator handle and fetch other required handles to
     /// `vk::PhysicalDevice`, `vk::Device` etc. every time using this function.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn get_info(&self) -> AllocatorInfo {
         let mut allocator_info: ffi::VmaAllocatorInfo = mem::zeroed();
-        ffi::vmaGetAllocatorInfo(self.internal, &mut allocator_info);
+        ffi::vmaGetAllocatorInfo(self.handle(), &mut allocator_info);
 
         AllocatorInfo {
             instance: allocator_info.instance as vk::Instance,
@@ -1311,35 +2516,327 @@ impl Allocator {
 
     /// The allocator fetches `ash::vk::PhysicalDeviceProperties` from the physical device.
     /// You can get it here, without fetching it again on your own.
+    ///
+    /// Same as `Allocator::physical_device_properties`, kept for compatibility.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn get_physical_device_properties(&self) -> VkResult<vk::PhysicalDeviceProperties> {
-        let mut properties = vk::PhysicalDeviceProperties::default();
-        ffi::vmaGetPhysicalDeviceProperties(
-            self.internal,
-            &mut properties as *mut _ as *mut *const _,
-        );
-
-        Ok(properties)
+        Ok(self.physical_device_properties)
     }
 
     /// The allocator fetches `ash::vk::PhysicalDeviceMemoryProperties` from the physical device.
     /// You can get it here, without fetching it again on your own.
+    ///
+    /// Same as `Allocator::memory_properties`, kept for compatibility.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn get_memory_properties(&self) -> VkResult<vk::PhysicalDeviceMemoryProperties> {
-        let mut properties = vk::PhysicalDeviceMemoryProperties::default();
-        ffi::vmaGetMemoryProperties(self.internal, &mut properties as *mut _ as *mut *const _);
+        Ok(self.memory_properties)
+    }
+
+    /// `ash::vk::PhysicalDeviceProperties` of the physical device, fetched when the allocator was
+    /// created.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn physical_device_properties(&self) -> &vk::PhysicalDeviceProperties {
+        &self.physical_device_properties
+    }
+
+    /// `ash::vk::PhysicalDeviceMemoryProperties` of the physical device, fetched when the
+    /// allocator was created.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    /// Vulkan API version the allocator was created with, without the patch number. If
+    /// `AllocatorCreateInfo::api_version_from_instance` was set, this is the detected version.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn vulkan_api_version(&self) -> u32 {
+        self.vulkan_api_version
+    }
+
+    /// Optional Vulkan paths the allocator uses, derived from the flags and Vulkan API version it
+    /// was created with.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn enabled_features(&self) -> EnabledFeatures {
+        EnabledFeatures::new(self.flags, self.vulkan_api_version)
+    }
+
+    /// All memory types of the device, as a `memory_type_mask::MemoryTypeMask` to narrow down to
+    /// a `memory_type_bits` value.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn memory_type_mask(&self) -> memory_type_mask::MemoryTypeMask<'_> {
+        memory_type_mask::MemoryTypeMask::new(&self.memory_properties)
+    }
+
+    /// Limits of the physical device.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        &self.physical_device_properties.limits
+    }
+
+    /// `nonCoherentAtomSize`: granularity of flushes and invalidations of memory that is not
+    /// `ash::vk::MemoryPropertyFlags::HOST_COHERENT`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn non_coherent_atom_size(&self) -> vk::DeviceSize {
+        self.limits().non_coherent_atom_size
+    }
+
+    /// `minUniformBufferOffsetAlignment`: alignment of uniform buffer offsets in descriptors
+    /// and dynamic offsets.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn min_uniform_buffer_offset_alignment(&self) -> vk::DeviceSize {
+        self.limits().min_uniform_buffer_offset_alignment
+    }
+
+    /// `minStorageBufferOffsetAlignment`: alignment of storage buffer offsets in descriptors
+    /// and dynamic offsets.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn min_storage_buffer_offset_alignment(&self) -> vk::DeviceSize {
+        self.limits().min_storage_buffer_offset_alignment
+    }
+
+    /// `bufferImageGranularity`: granularity at which linear and optimal resources may share
+    /// memory without aliasing.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn buffer_image_granularity(&self) -> vk::DeviceSize {
+        self.limits().buffer_image_granularity
+    }
 
-        Ok(properties)
+    /// Whether host and device memory are separate, classified from the memory heaps and types of
+    /// the physical device.
+    pub fn memory_architecture(&self) -> architecture::MemoryArchitecture {
+        architecture::MemoryArchitecture::classify(
+            &self.physical_device_properties,
+            &self.memory_properties,
+        )
+    }
+
+    /// Whether the device exposes a device local heap larger than
+    /// `architecture::REBAR_MIN_HEAP_SIZE` through host visible memory, i.e. resizable BAR is
+    /// enabled. Also true on unified memory devices with a large heap.
+    pub fn has_rebar(&self) -> bool {
+        architecture::rebar_heap_index(&self.memory_properties).is_some()
+    }
+
+    /// Suggests how to fill a buffer of `size` bytes with `usage` from the host.
+    ///
+    /// On integrated and unified memory devices that have device local, host visible memory for
+    /// such a buffer, it is `architecture::UploadStrategy::Mapped`: the buffer is written in place
+    /// and no staging copy is needed. On discrete devices with `Allocator::has_rebar` it is
+    /// `architecture::UploadStrategy::MappedOrStaging`. Otherwise it is
+    /// `architecture::UploadStrategy::Staging`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn suggest_upload_strategy(
+        &self,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+    ) -> architecture::UploadStrategy {
+        if !self.memory_architecture().is_unified() {
+            return if self.has_rebar() {
+                architecture::UploadStrategy::MappedOrStaging
+            } else {
+                architecture::UploadStrategy::Staging
+            };
+        }
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size.max(1))
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let mapped = architecture::UploadStrategy::Mapped.allocation_create_info();
+        match unsafe { self.find_memory_type_index_for_buffer_info(&buffer_info, &mapped) } {
+            Ok(_) => architecture::UploadStrategy::Mapped,
+            Err(_) => architecture::UploadStrategy::Staging,
+        }
+    }
+
+    /// Whether the driver prefers or requires a dedicated `ash::vk::DeviceMemory` for a buffer
+    /// created with `buffer_info`, according to `VkMemoryDedicatedRequirements`.
+    ///
+    /// A temporary buffer is created to query its requirements, so this is best done once per kind
+    /// of resource, e.g. to decide between pooling it and `AllocationCreateFlags::DEDICATED_MEMORY`
+    /// before committing to a create path. Returns `false` when the allocator was created for
+    /// Vulkan 1.0 without `AllocatorCreateFlags::KHR_DEDICATED_ALLOCATION`, since the driver can't
+    /// be asked then.
+    ///
+    /// # Safety
+    ///
+    /// `buffer_info` must be a valid `VkBufferCreateInfo` for the device of the allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn prefers_dedicated_for_buffer(
+        &self,
+        buffer_info: &vk::BufferCreateInfo,
+    ) -> VkResult<bool> {
+        let functions = self.device_functions;
+        let get_requirements = match functions.get_buffer_memory_requirements2 {
+            Some(get_requirements) => get_requirements,
+            None => return Ok(false),
+        };
+        let mut buffer = vk::Buffer::null();
+        ffi_to_result((functions.create_buffer)(
+            functions.device,
+            buffer_info,
+            std::ptr::null(),
+            &mut buffer,
+        ))?;
+        let mut dedicated = vk::MemoryDedicatedRequirements::default();
+        let mut requirements = vk::MemoryRequirements2 {
+            p_next: &mut dedicated as *mut _ as *mut std::os::raw::c_void,
+            ..Default::default()
+        };
+        let requirements_info = vk::BufferMemoryRequirementsInfo2 {
+            buffer,
+            ..Default::default()
+        };
+        get_requirements(functions.device, &requirements_info, &mut requirements);
+        (functions.destroy_buffer)(functions.device, buffer, std::ptr::null());
+        Ok(dedicated.prefers_dedicated_allocation == vk::TRUE
+            || dedicated.requires_dedicated_allocation == vk::TRUE)
+    }
+
+    /// Whether the driver prefers or requires a dedicated `ash::vk::DeviceMemory` for an image
+    /// created with `image_info`, see `Allocator::prefers_dedicated_for_buffer`.
+    ///
+    /// # Safety
+    ///
+    /// `image_info` must be a valid `VkImageCreateInfo` for the device of the allocator, without
+    /// `ash::vk::ImageCreateFlags::DISJOINT`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn prefers_dedicated_for_image(
+        &self,
+        image_info: &vk::ImageCreateInfo,
+    ) -> VkResult<bool> {
+        let functions = self.device_functions;
+        let get_requirements = match functions.get_image_memory_requirements2 {
+            Some(get_requirements) => get_requirements,
+            None => return Ok(false),
+        };
+        let mut image = vk::Image::null();
+        ffi_to_result((functions.create_image)(
+            functions.device,
+            image_info,
+            std::ptr::null(),
+            &mut image,
+        ))?;
+        let mut dedicated = vk::MemoryDedicatedRequirements::default();
+        let mut requirements = vk::MemoryRequirements2 {
+            p_next: &mut dedicated as *mut _ as *mut std::os::raw::c_void,
+            ..Default::default()
+        };
+        let requirements_info = vk::ImageMemoryRequirementsInfo2 {
+            image,
+            ..Default::default()
+        };
+        get_requirements(functions.device, &requirements_info, &mut requirements);
+        (functions.destroy_image)(functions.device, image, std::ptr::null());
+        Ok(dedicated.prefers_dedicated_allocation == vk::TRUE
+            || dedicated.requires_dedicated_allocation == vk::TRUE)
+    }
+
+    /// `minImportedHostPointerAlignment` of the physical device, or `None` if the device didn't
+    /// enable `VK_EXT_external_memory_host`.
+    pub fn min_imported_host_pointer_alignment(&self) -> Option<vk::DeviceSize> {
+        self.device_functions
+            .host_pointer_import
+            .map(|import| import.alignment)
+    }
+
+    /// Imports `size` bytes of host memory starting at `pointer` as device memory, without
+    /// copying them.
+    ///
+    /// The memory type is chosen with `Allocator::find_memory_type_index` among the types the
+    /// driver allows for the pointer. See `host_memory` for how to bind buffers to the result.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if the device didn't enable
+    /// `VK_EXT_external_memory_host`, and with `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` if
+    /// `pointer` or `size` isn't a multiple of `Allocator::min_imported_host_pointer_alignment`.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` must point to `size` bytes of host memory that stay allocated, and aren't
+    /// imported elsewhere, until the returned `host_memory::HostMemory` is dropped.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn import_host_memory(
+        &self,
+        pointer: *mut std::os::raw::c_void,
+        size: vk::DeviceSize,
+    ) -> Result<host_memory::HostMemory> {
+        const OPERATION: &str = "Allocator::import_host_memory";
+        let functions = &self.device_functions;
+        let import = functions.host_pointer_import.ok_or_else(|| {
+            Error::new(vk::Result::ERROR_FEATURE_NOT_PRESENT, OPERATION).with_size(size)
+        })?;
+        if pointer.is_null()
+            || size == 0
+            || !(pointer as vk::DeviceSize).is_multiple_of(import.alignment)
+            || !size.is_multiple_of(import.alignment)
+        {
+            log::error!(
+                "{}: pointer {:p} and size {} must be non-zero multiples of \
+                 minImportedHostPointerAlignment ({})",
+                OPERATION,
+                pointer,
+                size,
+                import.alignment
+            );
+            return Err(
+                Error::new(vk::Result::ERROR_VALIDATION_FAILED_EXT, OPERATION).with_size(size),
+            );
+        }
+
+        let mut pointer_properties = vk::MemoryHostPointerPropertiesEXT::default();
+        (import.get_properties)(
+            functions.device,
+            host_memory::HANDLE_TYPE,
+            pointer,
+            &mut pointer_properties,
+        )
+        .result()
+        .map_err(|result| Error::new(result, OPERATION).with_size(size))?;
+        let memory_type_index = self
+            .find_memory_type_index(pointer_properties.memory_type_bits, &Default::default())
+            .map_err(|error| Error::new(error.result(), OPERATION).with_size(size))?;
+
+        let mut import_info = vk::ImportMemoryHostPointerInfoEXT::builder()
+            .handle_type(host_memory::HANDLE_TYPE)
+            .host_pointer(pointer);
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut import_info)
+            .build();
+        let mut memory = vk::DeviceMemory::null();
+        (functions.allocate_memory)(
+            functions.device,
+            &allocate_info,
+            std::ptr::null(),
+            &mut memory,
+        )
+        .result()
+        .map_err(|result| Error::new(result, OPERATION).with_size(size))?;
+
+        Ok(host_memory::HostMemory {
+            device: functions.device,
+            memory,
+            pointer,
+            size,
+            memory_type_index,
+            free_memory: functions.free_memory,
+            bind_buffer_memory: functions.bind_buffer_memory,
+            bind_image_memory: functions.bind_image_memory,
+        })
     }
 
     /// Given a memory type index, returns `ash::vk::MemoryPropertyFlags` of this memory type.
     ///
     /// This is just a convenience function; the same information can be obtained using
     /// `Allocator::get_memory_properties`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn get_memory_type_properties(
         &self,
         memory_type_index: u32,
         flags: &mut vk::MemoryPropertyFlags,
     ) -> VkResult<vk::MemoryPropertyFlags> {
-        ffi::vmaGetMemoryTypeProperties(self.internal, memory_type_index, flags);
+        ffi::vmaGetMemoryTypeProperties(self.handle(), memory_type_index, flags);
 
         Ok(*flags)
     }
@@ -1350,18 +2847,138 @@ impl Allocator {
     /// `AllocationCreateFlags::CAN_MAKE_OTHER_LOST` flags to inform the allocator when a new frame begins.
     /// Allocations queried using `Allocator::get_allocation_info` cannot become lost
     /// in the current frame.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn set_current_frame_index(&self, frame_index: u32) {
-        ffi::vmaSetCurrentFrameIndex(self.internal, frame_index);
+        ffi::vmaSetCurrentFrameIndex(self.handle(), frame_index);
     }
 
-    /// Retrieves statistics from current state of the `Allocator`.
-    pub unsafe fn calculate_statistics(
+    /// Starts frame `frame_index`: sets it as the current frame index and notifies the hooks added
+    /// with `Allocator::add_frame_hook`. The frame ends when the returned guard is dropped, which
+    /// frees the resources deferred `Allocator::set_frames_in_flight` frames ago.
+    ///
+    /// See `frame` for details.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::set_current_frame_index`. Frames must not overlap: the previous guard
+    /// must be dropped before the next frame begins.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn begin_frame(&self, frame_index: u32) -> frame::FrameGuard<'_> {
+        self.set_current_frame_index(frame_index);
+        frame::FrameGuard::new(self, frame_index)
+    }
+
+    /// Registers `hook` to be notified when frames begin and end.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn add_frame_hook(&self, hook: std::sync::Arc<dyn frame::FrameHook>) {
+        self.frames.add_hook(hook);
+    }
+
+    /// Unregisters a hook added with `Allocator::add_frame_hook`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn remove_frame_hook(&self, hook: &std::sync::Arc<dyn frame::FrameHook>) {
+        self.frames.remove_hook(hook);
+    }
+
+    /// Sets how many frames resources deferred with `frame::FrameGuard` are kept alive for,
+    /// `frame::DEFAULT_FRAMES_IN_FLIGHT` by default. It must be at least the number of frames the
+    /// device can lag behind the host.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_frames_in_flight(&self, frames: u32) {
+        self.frames.set_frames_in_flight(frames);
+    }
+
+    /// Frees `allocation` once `fence` is signaled, on a later call to `Allocator::poll_deferred`.
+    ///
+    /// See `deferred` for details.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must not be used by the host afterwards, and the device must be done with it
+    /// once `fence` is signaled. `fence` must stay alive and not be reset until the allocation is
+    /// freed.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn free_when(&self, fence: vk::Fence, allocation: &Allocation) {
+        self.fences
+            .defer(fence, frame::Deferred::Memory(*allocation));
+    }
+
+    /// Destroys `buffer` and frees `allocation` once `fence` is signaled, on a later call to
+    /// `Allocator::poll_deferred`.
+    ///
+    /// # Safety
+    ///
+    /// See `Allocator::free_when`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn destroy_buffer_when(
         &self,
-        total_statistics: TotalStatistics,
-    ) -> VkResult<ffi::VmaTotalStatistics> {
-        let mut vma_stats: ffi::VmaTotalStatistics = total_statistics.into();
-        ffi::vmaCalculateStatistics(self.internal, &mut vma_stats);
-        Ok(vma_stats)
+        fence: vk::Fence,
+        buffer: vk::Buffer,
+        allocation: &Allocation,
+    ) {
+        self.fences
+            .defer(fence, frame::Deferred::Buffer(buffer, *allocation));
+    }
+
+    /// Destroys `image` and frees `allocation` once `fence` is signaled, on a later call to
+    /// `Allocator::poll_deferred`.
+    ///
+    /// # Safety
+    ///
+    /// See `Allocator::free_when`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn destroy_image_when(
+        &self,
+        fence: vk::Fence,
+        image: vk::Image,
+        allocation: &Allocation,
+    ) {
+        self.fences
+            .defer(fence, frame::Deferred::Image(image, *allocation));
+    }
+
+    /// Frees the resources queued with `Allocator::free_when`, `Allocator::destroy_buffer_when`
+    /// and `Allocator::destroy_image_when` whose fence is signaled, and returns how many were
+    /// freed. Fences that aren't signaled yet are left for a later call.
+    ///
+    /// # Safety
+    ///
+    /// The fences of the queued resources must still be alive.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn poll_deferred(&self) -> VkResult<usize> {
+        let functions = &self.device_functions;
+        let signaled = self.fences.take_signaled(|fence| {
+            match (functions.get_fence_status)(functions.device, fence) {
+                vk::Result::SUCCESS => Ok(true),
+                vk::Result::NOT_READY => Ok(false),
+                result => Err(result),
+            }
+        })?;
+        for resource in &signaled {
+            resource.destroy(self);
+        }
+        Ok(signaled.len())
+    }
+
+    /// Number of resources still waiting for their fence.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn deferred_count(&self) -> usize {
+        self.fences.len()
+    }
+
+    /// Retrieves statistics from current state of the `Allocator`.
+    ///
+    /// This function is slow to call. Use for debugging purposes.
+    /// For less detailed statistics, see `Allocator::get_heap_budgets`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn calculate_statistics(&self) -> VkResult<TotalStatistics> {
+        let mut vma_stats: ffi::VmaTotalStatistics = mem::zeroed();
+        ffi::vmaCalculateStatistics(self.handle(), &mut vma_stats);
+        let memory_properties = self.get_memory_properties()?;
+        let mut statistics: TotalStatistics = vma_stats.into();
+        statistics.memory_type_count = memory_properties.memory_type_count;
+        statistics.memory_heap_count = memory_properties.memory_heap_count;
+        Ok(statistics)
     }
 
     /// Retrieves information about current memory usage and budget for all memory heaps.
@@ -1374,13 +2991,19 @@ impl Allocator {
     ///
     /// Note that when using allocator from multiple threads, returned information may immediately
     /// become outdated.
+    ///
+    /// Only the first `budget_count` heaps are returned. Budget reserved with
+    /// `Allocator::reserve_budget` is not included; see `Allocator::available_budget`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn get_heap_budgets(&self, budget_count: usize) -> Vec<Budget> {
         unsafe {
-            let mut budgets = Vec::<ffi::VmaBudget>::with_capacity(budget_count);
-            budgets.resize_with(budget_count, || mem::zeroed());
-            ffi::vmaGetHeapBudgets(self.internal, budgets.as_mut_ptr());
+            // VMA writes one entry per memory heap, regardless of how many the caller asked for.
+            let mut budgets = Vec::<ffi::VmaBudget>::with_capacity(vk::MAX_MEMORY_HEAPS);
+            budgets.resize_with(vk::MAX_MEMORY_HEAPS, || mem::zeroed());
+            ffi::vmaGetHeapBudgets(self.handle(), budgets.as_mut_ptr());
             budgets
                 .iter()
+                .take(budget_count)
                 .map(|value| Budget {
                     statistics: Statistics {
                         block_count: value.statistics.blockCount,
@@ -1395,6 +3018,204 @@ impl Allocator {
         }
     }
 
+    /// Refreshes the per-heap gauges of `vk_mem::metrics` from the current heap budgets.
+    ///
+    /// Only calls `vmaGetHeapBudgets`, so it is cheap enough to call once per frame or whenever the
+    /// metrics are scraped. Defragmentation counters are updated by `Allocator::end_defragmentation`
+    /// and don't need this call.
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn publish_metrics(&self) {
+        let heap_count = unsafe {
+            let mut properties: *const vk::PhysicalDeviceMemoryProperties = std::ptr::null();
+            ffi::vmaGetMemoryProperties(self.handle(), &mut properties);
+            (*properties).memory_heap_count as usize
+        };
+        metrics::publish_budgets(&self.get_heap_budgets(heap_count));
+    }
+
+    /// Soft-reserves `bytes` of the budget of heap `heap_index` for an upcoming load.
+    ///
+    /// The reservation succeeds only if `budget - usage` of the heap, minus everything already
+    /// reserved, covers `bytes`; otherwise it fails with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY`.
+    /// While the token is alive the reserved bytes are subtracted from `Allocator::available_budget`,
+    /// which systems that size themselves from the budget (like `staging::StagingManager`) use, so two
+    /// systems can't both plan against the same free space.
+    ///
+    /// Allocations made through the token are deducted from the reservation as they are created.
+    /// Whatever is left is released when the token is dropped.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` if `heap_index` isn't below the
+    /// `memory_heap_count` of the physical device.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn reserve_budget(
+        &self,
+        heap_index: u32,
+        bytes: vk::DeviceSize,
+    ) -> VkResult<budget::ReservationToken<'_>> {
+        let heap_count = self.memory_properties().memory_heap_count;
+        if heap_index >= heap_count {
+            log::error!(
+                "`Allocator::reserve_budget`: heap {} out of {} heaps",
+                heap_index,
+                heap_count
+            );
+            return Err(vk::Result::ERROR_VALIDATION_FAILED_EXT);
+        }
+        let heap = heap_index as usize;
+        let budgets = self.get_heap_budgets(heap + 1);
+        let free = budgets[heap].budget.saturating_sub(budgets[heap].usage);
+        self.reservations.reserve(heap, bytes, free)?;
+        Ok(budget::ReservationToken::new(self, heap_index, bytes))
+    }
+
+    /// Creates a buffer like `Allocator::create_buffer`, but only if it fits in the budget of the
+    /// heap it would be placed in: `AllocationCreateFlags::WITHIN_BUDGET` is added to the flags of
+    /// `allocation_info`.
+    ///
+    /// If it doesn't fit, `fallback` decides whether to fail or to retry in host memory, also
+    /// within budget. The returned `budget::BudgetPlacement` tells where the buffer ended up. On
+    /// failure the `budget::BudgetError` carries the budgets of all heaps, so the caller can decide
+    /// what to evict or whether to try again later.
+    ///
+    /// Budget reserved with `Allocator::reserve_budget` is not taken into account by VMA, only
+    /// reported in the error.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_buffer_within_budget(
+        &self,
+        buffer_info: &ash::vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+        fallback: budget::BudgetFallback,
+    ) -> Result<
+        (
+            ash::vk::Buffer,
+            Allocation,
+            AllocationInfo,
+            budget::BudgetPlacement,
+        ),
+        budget::BudgetError,
+    > {
+        let mut allocation_info = allocation_info.clone();
+        allocation_info.flags |= AllocationCreateFlags::WITHIN_BUDGET;
+        let error = match self.create_buffer(buffer_info, &allocation_info) {
+            Ok((buffer, allocation, info)) => {
+                return Ok((buffer, allocation, info, budget::BudgetPlacement::Requested))
+            }
+            Err(error) if error.result() == vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => error,
+            Err(error) => return Err(budget::BudgetError::new(self, error)),
+        };
+        if fallback == budget::BudgetFallback::Fail {
+            return Err(budget::BudgetError::new(self, error));
+        }
+
+        log::debug!(
+            "Buffer of {} is over budget, retrying in host memory",
+            format_bytes(buffer_info.size)
+        );
+        let host_info = AllocationCreateInfo {
+            usage: MemoryUsage::AutoPreferHost,
+            required_flags: vk::MemoryPropertyFlags::empty(),
+            preferred_flags: vk::MemoryPropertyFlags::empty(),
+            pool: None,
+            fallback_required_flags: Vec::new(),
+            ..allocation_info
+        };
+        match self.create_buffer(buffer_info, &host_info) {
+            Ok((buffer, allocation, info)) => Ok((
+                buffer,
+                allocation,
+                info,
+                budget::BudgetPlacement::HostMemory,
+            )),
+            Err(error) => Err(budget::BudgetError::new(self, error)),
+        }
+    }
+
+    /// Bytes of heap `heap_index` currently reserved with `Allocator::reserve_budget`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn reserved_budget(&self, heap_index: u32) -> vk::DeviceSize {
+        self.reservations.reserved(heap_index as usize)
+    }
+
+    /// Budget of heap `heap_index` that is neither used nor reserved: `budget - usage - reserved`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn available_budget(&self, heap_index: u32) -> vk::DeviceSize {
+        let heap = heap_index as usize;
+        if heap >= vk::MAX_MEMORY_HEAPS {
+            return 0;
+        }
+        let budgets = self.get_heap_budgets(heap + 1);
+        budgets[heap]
+            .budget
+            .saturating_sub(budgets[heap].usage)
+            .saturating_sub(self.reservations.reserved(heap))
+    }
+
+    /// Number and size of the live allocations of each category set with
+    /// `AllocationCreateInfo::category`. Allocations without a category are not included, and
+    /// categories without live allocations are left out.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn category_stats(
+        &self,
+    ) -> std::collections::HashMap<category::Category, category::CategoryStats> {
+        self.categories.stats()
+    }
+
+    /// Category the allocation was created with, if any.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn get_allocation_category(&self, allocation: &Allocation) -> Option<category::Category> {
+        self.categories.category(allocation)
+    }
+
+    /// Returns a short, single-line summary of current memory usage, for example
+    /// `DL 2.1/8.0 GiB | HV 310/16384 MiB | blocks 23 | allocs 1842 | dedicated 12`.
+    ///
+    /// `DL` is the usage and budget summed over device-local heaps and `HV` over all other,
+    /// host-visible heaps, followed by the number of `ash::vk::DeviceMemory` blocks, of allocations,
+    /// and of allocations with dedicated memory. It is computed from `Allocator::get_heap_budgets`
+    /// and counters kept up to date as allocations are created and freed, so it is cheap enough to
+    /// be appended to a per-frame log line or overlay.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn one_line_summary(&self) -> String {
+        let properties = unsafe { self.get_memory_properties() }.unwrap_or_default();
+        let heap_count = properties.memory_heap_count as usize;
+        let budgets = self.get_heap_budgets(heap_count);
+
+        let mut device_local = (0, 0);
+        let mut host = (0, 0);
+        let mut block_count = 0;
+        let mut allocation_count = 0;
+        let mut has_host_heap = false;
+        for (heap, budget) in properties.memory_heaps[..heap_count].iter().zip(&budgets) {
+            let totals = if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) {
+                &mut device_local
+            } else {
+                has_host_heap = true;
+                &mut host
+            };
+            totals.0 += budget.usage;
+            totals.1 += budget.budget;
+            block_count += budget.statistics.block_count;
+            allocation_count += budget.statistics.allocation_count;
+        }
+
+        let mut summary = format!("DL {}", format_usage(device_local.0, device_local.1));
+        if has_host_heap {
+            summary += &format!(" | HV {}", format_usage(host.0, host.1));
+        }
+        summary += &format!(
+            " | blocks {} | allocs {} | dedicated {}",
+            block_count,
+            allocation_count,
+            self.categories.dedicated_count()
+        );
+        summary
+    }
+
     /// Helps to find memory type index, given memory type bits and allocation info.
     ///
     /// This algorithm tries to find a memory type that:
@@ -1409,23 +3230,95 @@ impl Allocator {
     /// device doesn't support any memory type with requested features for the specific
     /// type of resource you want to use it for. Please check parameters of your
     /// resource, like image layout (OPTIMAL versus LINEAR) or mip level count.
+    ///
+    /// `MemoryUsage::Auto`, `MemoryUsage::AutoPreferDevice` and `MemoryUsage::AutoPreferHost` need to
+    /// know the resource the memory is meant for and are rejected with
+    /// ash::vk::Result::ERROR_FEATURE_NOT_PRESENT, logging an error that points to
+    /// `Allocator::find_memory_type_index_for_buffer_info` and
+    /// `Allocator::find_memory_type_index_for_image_info` instead.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn find_memory_type_index(
         &self,
         memory_type_bits: u32,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<u32> {
+    ) -> Result<u32> {
+        if allocation_info.usage.is_auto() {
+            log::error!(
+                "Allocator::find_memory_type_index called with MemoryUsage::{:?}, which needs the buffer or \
+                 image the memory is for; use Allocator::find_memory_type_index_for_buffer_info or \
+                 Allocator::find_memory_type_index_for_image_info, or pass explicit required/preferred flags",
+                allocation_info.usage
+            );
+            return Err(Error::new(
+                vk::Result::ERROR_FEATURE_NOT_PRESENT,
+                "Allocator::find_memory_type_index",
+            )
+            .with_memory_type_bits(memory_type_bits));
+        }
+
         let create_info = allocation_create_info_to_ffi(&allocation_info);
         let mut memory_type_index: u32 = 0;
         ffi_to_result(ffi::vmaFindMemoryTypeIndex(
-            self.internal,
+            self.handle(),
             memory_type_bits,
             &create_info,
             &mut memory_type_index,
-        ))?;
+        ))
+        .map_err(|result| {
+            Error::new(result, "Allocator::find_memory_type_index")
+                .with_memory_type_bits(memory_type_bits)
+        })?;
 
         Ok(memory_type_index)
     }
 
+    /// Explains which memory type an allocation with `allocation_info` lands in, for memory
+    /// acceptable to a resource with `memory_type_bits`.
+    ///
+    /// The report lists every memory type with the reason it was rejected (not allowed by the
+    /// resource, excluded by `AllocationCreateInfo::memory_type_bits`, missing required flags or
+    /// over budget, counting `Allocator::reserve_budget` reservations as used) or, for candidates,
+    /// the preferred flags it lacks. It is meant for debugging allocations that end up in system
+    /// RAM instead of VRAM, and is built on the Rust side, so it can differ from VMA in corner
+    /// cases. `MemoryUsage::Auto*` usages are explained as for a
+    /// resource the device accesses. `AllocationCreateInfo::pool` is ignored.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn explain_memory_type_choice(
+        &self,
+        allocation_info: &AllocationCreateInfo,
+        memory_type_bits: u32,
+    ) -> VkResult<explain::MemoryTypeReport> {
+        let (properties, device_properties) = unsafe {
+            (
+                self.get_memory_properties()?,
+                self.get_physical_device_properties()?,
+            )
+        };
+        let heap_budgets: Vec<explain::HeapBudget> = self
+            .get_heap_budgets(properties.memory_heap_count as usize)
+            .iter()
+            .enumerate()
+            .map(|(heap, budget)| {
+                (
+                    budget.usage + self.reservations.reserved(heap),
+                    budget.budget,
+                )
+            })
+            .collect();
+        let integrated_gpu =
+            device_properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU;
+        Ok(explain::explain(
+            &properties,
+            &heap_budgets,
+            self.flags.contains(
+                AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_AMD_DEVICE_COHERENT_MEMORY_BIT,
+            ),
+            memory_type_bits,
+            allocation_info.memory_type_bits,
+            explain::memory_preferences(allocation_info, integrated_gpu),
+        ))
+    }
+
     /// Helps to find memory type index, given buffer info and allocation info.
     ///
     /// It can be useful e.g. to determine value to be used as `AllocatorPoolCreateInfo::memory_type_index`.
@@ -1436,19 +3329,28 @@ impl Allocator {
     /// - `ash::vk::Device::get_buffer_memory_requirements`
     /// - `Allocator::find_memory_type_index`
     /// - `ash::vk::Device::destroy_buffer`
+    ///
+    /// The `p_next` chain of `buffer_info` is passed on to the dummy buffer, so structures like
+    /// `ash::vk::ExternalMemoryBufferCreateInfo` are taken into account. A builder can be passed
+    /// as `&builder`, which keeps the structures added with `push_next` borrowed for the call.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn find_memory_type_index_for_buffer_info(
         &self,
-        buffer_info: ash::vk::BufferCreateInfo,
+        buffer_info: &ash::vk::BufferCreateInfo,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<u32> {
+    ) -> Result<u32> {
         let allocation_create_info = allocation_create_info_to_ffi(&allocation_info);
         let mut memory_type_index: u32 = 0;
         ffi_to_result(ffi::vmaFindMemoryTypeIndexForBufferInfo(
-            self.internal,
-            &buffer_info,
+            self.handle(),
+            buffer_info,
             &allocation_create_info,
             &mut memory_type_index,
-        ))?;
+        ))
+        .map_err(|result| {
+            Error::new(result, "Allocator::find_memory_type_index_for_buffer_info")
+                .with_size(buffer_info.size)
+        })?;
 
         Ok(memory_type_index)
     }
@@ -1463,62 +3365,335 @@ impl Allocator {
     /// - `ash::vk::Device::get_image_memory_requirements`
     /// - `Allocator::find_memory_type_index`
     /// - `ash::vk::Device::destroy_image`
+    ///
+    /// The `p_next` chain of `image_info` is passed on to the dummy image, so structures like
+    /// `ash::vk::ExternalMemoryImageCreateInfo` are taken into account. A builder can be passed
+    /// as `&builder`, which keeps the structures added with `push_next` borrowed for the call.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn find_memory_type_index_for_image_info(
         &self,
-        image_info: ash::vk::ImageCreateInfo,
+        image_info: &ash::vk::ImageCreateInfo,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<u32> {
+    ) -> Result<u32> {
         let allocation_create_info = allocation_create_info_to_ffi(&allocation_info);
         let mut memory_type_index: u32 = 0;
         ffi_to_result(ffi::vmaFindMemoryTypeIndexForImageInfo(
-            self.internal,
-            &image_info,
+            self.handle(),
+            image_info,
             &allocation_create_info,
             &mut memory_type_index,
-        ))?;
+        ))
+        .map_err(|result| Error::new(result, "Allocator::find_memory_type_index_for_image_info"))?;
 
         Ok(memory_type_index)
     }
 
     /// Allocates Vulkan device memory and creates `AllocatorPool` object.
-    pub unsafe fn create_pool(
-        &self,
-        pool_info: &AllocatorPoolCreateInfo,
-    ) -> VkResult<AllocatorPool> {
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_pool(&self, pool_info: &AllocatorPoolCreateInfo) -> Result<AllocatorPool> {
         let mut ffi_pool: ffi::VmaPool = mem::zeroed();
-        let create_info = pool_create_info_to_ffi(&pool_info);
+        let mut create_info = pool_create_info_to_ffi(&pool_info);
+        let mut next = pool_info
+            .memory_allocate_next
+            .as_ref()
+            .map(pool_next::PendingNext::new);
+        if let Some(next) = &mut next {
+            create_info.pMemoryAllocateNext = next.as_ptr();
+        }
         ffi_to_result(ffi::vmaCreatePool(
-            self.internal,
+            self.handle(),
             &create_info,
             &mut ffi_pool,
-        ))?;
+        ))
+        .map_err(|result| {
+            self.allocation_failed(
+                Error::new(result, "Allocator::create_pool")
+                    .with_memory_type_index(pool_info.memory_type_index)
+                    .with_size(pool_info.block_size),
+            )
+        })?;
+        self.pools.register(&ffi_pool);
+        if let Some(next) = next {
+            self.pool_next.keep(&ffi_pool, next);
+        }
+        #[cfg(feature = "validation")]
+        self.validator.register_pool(&ffi_pool);
+        #[cfg(feature = "lifetime_stats")]
+        self.lifetimes.register_pool(&ffi_pool, pool_info.flags);
         Ok(ffi_pool)
     }
 
+    /// Creates a pool in the memory type `Allocator::find_memory_type_index_for_buffer_info` selects
+    /// for `buffer_info` and `allocation_info`.
+    ///
+    /// All parameters of the pool except `memory_type_index` are taken from `pool_info`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::find_memory_type_index_for_buffer_info` and `Allocator::create_pool`.
+    /// The pool must be destroyed with `Allocator::destroy_pool` before the allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_pool_for_buffer_info(
+        &self,
+        buffer_info: &ash::vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+        pool_info: &AllocatorPoolCreateInfo,
+    ) -> Result<AllocatorPool> {
+        let memory_type_index =
+            self.find_memory_type_index_for_buffer_info(buffer_info, allocation_info)?;
+        self.create_pool(&AllocatorPoolCreateInfo {
+            memory_type_index,
+            ..pool_info.clone()
+        })
+    }
+
+    /// Creates a pool in the memory type `Allocator::find_memory_type_index_for_image_info` selects
+    /// for `image_info` and `allocation_info`.
+    ///
+    /// All parameters of the pool except `memory_type_index` are taken from `pool_info`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::find_memory_type_index_for_image_info` and `Allocator::create_pool`.
+    /// The pool must be destroyed with `Allocator::destroy_pool` before the allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_pool_for_image_info(
+        &self,
+        image_info: &ash::vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+        pool_info: &AllocatorPoolCreateInfo,
+    ) -> Result<AllocatorPool> {
+        let memory_type_index =
+            self.find_memory_type_index_for_image_info(image_info, allocation_info)?;
+        self.create_pool(&AllocatorPoolCreateInfo {
+            memory_type_index,
+            ..pool_info.clone()
+        })
+    }
+
+    /// Creates a pool of `size` bytes for staging buffers the host writes and the device reads from.
+    ///
+    /// The memory type is the one `Allocator::find_memory_type_index_for_buffer_info` picks for a
+    /// `ash::vk::BufferUsageFlags::TRANSFER_SRC` buffer with
+    /// `AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE`, so allocations from the pool must be made
+    /// with that flag (and `AllocationCreateFlags::MAPPED` to map them persistently). The whole pool
+    /// is allocated up front as a single block.
+    ///
+    /// # Safety
+    ///
+    /// The pool must be destroyed with `Allocator::destroy_pool`, after all its allocations were
+    /// freed and before the allocator is dropped.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_staging_pool(&self, size: vk::DeviceSize) -> Result<AllocatorPool> {
+        self.create_host_pool(
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+        )
+    }
+
+    /// Creates a pool of `size` bytes for readback buffers the device writes and the host reads.
+    ///
+    /// Like `Allocator::create_staging_pool`, for a `ash::vk::BufferUsageFlags::TRANSFER_DST` buffer
+    /// with `AllocationCreateFlags::HOST_ACCESS_RANDOM`, which prefers cached memory.
+    ///
+    /// # Safety
+    ///
+    /// See `Allocator::create_staging_pool`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_readback_pool(&self, size: vk::DeviceSize) -> Result<AllocatorPool> {
+        self.create_host_pool(
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            AllocationCreateFlags::HOST_ACCESS_RANDOM,
+        )
+    }
+
+    /// Creates a pool in the device local memory type preferred for buffers with `usage_hint`, e.g.
+    /// `ash::vk::BufferUsageFlags::VERTEX_BUFFER | ash::vk::BufferUsageFlags::INDEX_BUFFER`.
+    ///
+    /// The pool grows block by block with VMA's default block size and has no size limit.
+    ///
+    /// If `Allocator::suggest_upload_strategy` suggests `architecture::UploadStrategy::Mapped` for
+    /// `usage_hint`, the pool uses memory that is also host visible, so its allocations can be
+    /// mapped and written in place instead of staged.
+    ///
+    /// # Safety
+    ///
+    /// See `Allocator::create_staging_pool`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_device_pool(
+        &self,
+        usage_hint: vk::BufferUsageFlags,
+    ) -> Result<AllocatorPool> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(PRESET_PROBE_SIZE)
+            .usage(usage_hint)
+            .build();
+        let strategy = match self.suggest_upload_strategy(PRESET_PROBE_SIZE, usage_hint) {
+            architecture::UploadStrategy::Mapped => architecture::UploadStrategy::Mapped,
+            _ => architecture::UploadStrategy::Staging,
+        };
+        let allocation_info = strategy.allocation_create_info();
+        self.create_pool_for_buffer_info(&buffer_info, &allocation_info, &Default::default())
+    }
+
+    fn create_host_pool(
+        &self,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        flags: AllocationCreateFlags,
+    ) -> Result<AllocatorPool> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size.max(PRESET_PROBE_SIZE))
+            .usage(usage)
+            .build();
+        let allocation_info = AllocationCreateInfo {
+            usage: MemoryUsage::Auto,
+            flags,
+            ..Default::default()
+        };
+        let pool_info = AllocatorPoolCreateInfo {
+            block_size: size,
+            min_block_count: 1,
+            max_block_count: 1,
+            ..Default::default()
+        };
+        unsafe { self.create_pool_for_buffer_info(&buffer_info, &allocation_info, &pool_info) }
+    }
+
     /// Destroys `AllocatorPool` object and frees Vulkan device memory.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn destroy_pool(&self, pool: AllocatorPool) {
-        ffi::vmaDestroyPool(self.internal, pool);
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .unregister_pool("Allocator::destroy_pool", &pool)
+            .is_err()
+        {
+            return;
+        }
+        #[cfg(feature = "lifetime_stats")]
+        self.lifetimes.unregister_pool(&pool);
+        self.pools.unregister(&pool);
+        ffi::vmaDestroyPool(self.handle(), pool);
+        self.pool_next.release(&pool);
     }
 
-    /// Retrieves statistics of existing `AllocatorPool` object.
-    pub unsafe fn get_pool_statistics(
+    /// Encourages VMA to release the empty `ash::vk::DeviceMemory` block it keeps in `pool`, and
+    /// returns the number of bytes given back to the driver.
+    ///
+    /// VMA keeps one empty block per pool so that freeing and allocating again doesn't hit the
+    /// driver every time, and only releases it when another allocation is freed from a block that
+    /// stays in use. This makes such a free happen: it places a tiny allocation in the fullest block
+    /// that has room, without creating a block, and frees it again. Calling this after freeing a
+    /// large part of a pool, e.g. after unloading a level, actually lowers its memory usage.
+    ///
+    /// Nothing is released if the pool has no other block with free space, in particular if the
+    /// empty block is its only block; destroy the pool to release that one.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn trim_pool(&self, pool: &AllocatorPool) -> vk::DeviceSize {
+        self.trim_block_vector(Some(*pool), 0, || {
+            let statistics = self.get_pool_statistics(pool);
+            (statistics.block_count, statistics.block_bytes)
+        })
+    }
+
+    /// `Allocator::trim_pool` for the default pool of every memory type and every custom pool.
+    /// Returns the total number of bytes given back to the driver.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn trim_all(&self) -> vk::DeviceSize {
+        let mut freed = 0;
+        for memory_type_index in 0..self.memory_properties.memory_type_count {
+            freed += self.trim_block_vector(None, memory_type_index, || {
+                let statistics = unsafe { self.calculate_statistics() }
+                    .map(|statistics| statistics.memory_type[memory_type_index as usize].statistics)
+                    .unwrap_or_default();
+                (statistics.block_count, statistics.block_bytes)
+            });
+        }
+        for pool in self.pools() {
+            freed += self.trim_pool(&pool);
+        }
+        freed
+    }
+
+    /// Allocates and frees a tiny allocation in the blocks of `pool`, or of the default pool of
+    /// `memory_type_index`, until `block_statistics` reports fewer blocks. Every allocation and
+    /// free moves the empty block one step towards the end of the list VMA releases it from, so
+    /// this is tried once per block.
+    fn trim_block_vector(
         &self,
-        pool: AllocatorPool,
-    ) -> VkResult<ffi::VmaDetailedStatistics> {
-        let mut pool_stats: ffi::VmaDetailedStatistics = mem::zeroed();
-        ffi::vmaCalculatePoolStatistics(self.internal, pool, &mut pool_stats);
-        Ok(pool_stats)
+        pool: Option<AllocatorPool>,
+        memory_type_index: u32,
+        block_statistics: impl Fn() -> (u32, vk::DeviceSize),
+    ) -> vk::DeviceSize {
+        let (block_count, block_bytes) = block_statistics();
+        let requirements = vk::MemoryRequirements {
+            size: 1,
+            alignment: 1,
+            memory_type_bits: 1 << memory_type_index,
+        };
+        // Straight to VMA: the probe is freed right away, so it needs none of the bookkeeping of
+        // `Allocator::allocate_memory`, and running out of room is expected, not an error to report.
+        let create_info = allocation_create_info_to_ffi(&AllocationCreateInfo {
+            flags: AllocationCreateFlags::NEVER_ALLOCATE
+                | AllocationCreateFlags::STRATEGY_MIN_MEMORY,
+            pool,
+            ..Default::default()
+        });
+        for _ in 0..block_count {
+            unsafe {
+                let mut allocation: Allocation = mem::zeroed();
+                let result = ffi::vmaAllocateMemory(
+                    self.handle(),
+                    &requirements,
+                    &create_info,
+                    &mut allocation,
+                    std::ptr::null_mut(),
+                );
+                if ffi_to_result(result).is_err() {
+                    break;
+                }
+                ffi::vmaFreeMemory(self.handle(), allocation);
+            }
+            let (trimmed_count, trimmed_bytes) = block_statistics();
+            if trimmed_count < block_count {
+                return block_bytes.saturating_sub(trimmed_bytes);
+            }
+        }
+        0
+    }
+
+    /// Custom pools created with this allocator (or one of its clones) that have not been destroyed
+    /// yet, in creation order.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn pools(&self) -> Vec<AllocatorPool> {
+        self.pools.live()
     }
 
-    /// Retrieves detailed statistics of existing #VmaPool object.
+    /// Retrieves statistics of existing `AllocatorPool` object.
     ///
-    /// allocator Allocator object.
-    /// pool Pool object.
-    /// pPoolStats Statistics of specified pool.
+    /// This function is fast to call, e.g. once per frame. For more detailed statistics, see
+    /// `Allocator::calculate_pool_statistics`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn get_pool_statistics(&self, pool: &AllocatorPool) -> Statistics {
+        unsafe {
+            let mut vma_stats: ffi::VmaStatistics = mem::zeroed();
+            ffi::vmaGetPoolStatistics(self.handle(), *pool, &mut vma_stats);
+            vma_stats.into()
+        }
+    }
+
+    /// Retrieves detailed statistics of existing `AllocatorPool` object.
+    ///
+    /// This function is slow to call. Use for debugging purposes.
+    /// For less detailed statistics, see `Allocator::get_pool_statistics`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn calculate_pool_statistics(&self, pool: &AllocatorPool) -> DetailedStatistics {
         unsafe {
             let mut vma_detailed_stats: ffi::VmaDetailedStatistics = mem::zeroed();
-            ffi::vmaCalculatePoolStatistics(self.internal, *pool, &mut vma_detailed_stats);
+            ffi::vmaCalculatePoolStatistics(self.handle(), *pool, &mut vma_detailed_stats);
             vma_detailed_stats.into()
         }
     }
@@ -1528,15 +3703,17 @@ impl Allocator {
     /// Corruption detection is enabled only when `VMA_DEBUG_DETECT_CORRUPTION` macro is defined to nonzero,
     /// `VMA_DEBUG_MARGIN` is defined to nonzero and the pool is created in memory type that is
     /// `ash::vk::MemoryPropertyFlags::HOST_VISIBLE` and `ash::vk::MemoryPropertyFlags::HOST_COHERENT`.
+    /// Both macros are defined by the crate's `detect_corruption` feature.
     ///
     /// Possible error values:
     ///
     /// - `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` - corruption detection is not enabled for specified pool.
     /// - `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` - corruption detection has been performed and found memory corruptions around one of the allocations.
-    ///  `VMA_ASSERT` is also fired in that case.
+    ///   `VMA_ASSERT` is also fired in that case.
     /// - Other value: Error returned by Vulkan, e.g. memory mapping failure.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn check_pool_corruption(&self, pool: AllocatorPool) -> VkResult<()> {
-        ffi_to_result(ffi::vmaCheckPoolCorruption(self.internal, pool))
+        ffi_to_result(ffi::vmaCheckPoolCorruption(self.handle(), pool))
     }
 
     /// Retrieves name of a custom pool.
@@ -1544,23 +3721,36 @@ impl Allocator {
     /// After the call `ppName` is either null or points to an internally-owned null-terminated string
     /// containing name of the pool that was previously set. The pointer becomes invalid when the pool is
     /// destroyed or its name is changed using vmaSetPoolName().
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn get_pool_name(&self, pool: &AllocatorPool) -> &str {
         unsafe {
-            let c_name: *mut *const ::std::os::raw::c_char = mem::zeroed();
-            ffi::vmaGetPoolName(self.internal, *pool, c_name);
-            std::ffi::CStr::from_ptr(*c_name).to_str().unwrap()
+            let mut c_name: *const ::std::os::raw::c_char = std::ptr::null();
+            ffi::vmaGetPoolName(self.handle(), *pool, &mut c_name);
+            if c_name.is_null() {
+                return "";
+            }
+            std::ffi::CStr::from_ptr(c_name).to_str().unwrap()
         }
     }
 
     /// Sets name of a custom pool.
     ///
-    /// `pName` can be either null or pointer to a null-terminated string with new name for the pool.
     /// Function makes internal copy of the string, so it can be changed or freed immediately after this call.
-    pub fn set_pool_name(&self, pool: &AllocatorPool, name: String) {
-        unsafe {
-            let c_name = std::ffi::CString::new(name).unwrap();
-            ffi::vmaSetPoolName(self.internal, *pool, c_name.as_ptr())
-        };
+    ///
+    /// The name is adjusted according to the allocator's `NamePolicy`. Fails if it contains an
+    /// interior NUL character and `NamePolicy::replace_interior_nul` is not set.
+    ///
+    /// If the instance enabled `VK_EXT_debug_utils`, the `ash::vk::DeviceMemory` of every
+    /// allocation created in the pool from now on is given the pool's name as its debug name.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_pool_name(
+        &self,
+        pool: &AllocatorPool,
+        name: &str,
+    ) -> Result<(), std::ffi::NulError> {
+        let c_name = self.name_policy.make_c_string("pool", name)?;
+        unsafe { ffi::vmaSetPoolName(self.handle(), *pool, c_name.as_ptr()) };
+        Ok(())
     }
 
     /// General purpose memory allocation.
@@ -1569,25 +3759,72 @@ impl Allocator {
     ///
     /// It is recommended to use `Allocator::allocate_memory_for_buffer`, `Allocator::allocate_memory_for_image`,
     /// `Allocator::create_buffer`, `Allocator::create_image` instead whenever possible.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn allocate_memory(
         &self,
         memory_requirements: &ash::vk::MemoryRequirements,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<(Allocation, AllocationInfo)> {
+    ) -> Result<(Allocation, AllocationInfo)> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Allocate);
         let create_info = allocation_create_info_to_ffi(&allocation_info);
+        let category = allocation_info.category;
         let mut allocation: Allocation = mem::zeroed();
         let mut allocation_info: AllocationInfo = mem::zeroed();
         ffi_to_result(ffi::vmaAllocateMemory(
-            self.internal,
+            self.handle(),
             memory_requirements,
             &create_info,
             &mut allocation,
             &mut allocation_info.internal,
-        ))?;
+        ))
+        .map_err(|result| {
+            self.allocation_failed(
+                Error::new(result, "Allocator::allocate_memory")
+                    .with_memory_type_bits(memory_requirements.memory_type_bits)
+                    .with_size(memory_requirements.size),
+            )
+        })?;
+        self.allocation_created(
+            "Allocator::allocate_memory",
+            &allocation,
+            &create_info,
+            &allocation_info.internal,
+            None,
+            category,
+        );
 
         Ok((allocation, allocation_info))
     }
 
+    /// Like `Allocator::allocate_memory`, but when the allocation fails because memory ran out or no
+    /// memory type has `AllocationCreateInfo::required_flags`, it is retried with each entry of
+    /// `AllocationCreateInfo::fallback_required_flags` in turn.
+    ///
+    /// Returns the tier that succeeded along with the allocation: 0 for `required_flags`, `n` for
+    /// the `n`-th fallback. If every tier fails, the error of the last one is returned. Every tier
+    /// that runs out of memory is reported to `Allocator::set_oom_dump`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::allocate_memory`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_with_fallback(
+        &self,
+        memory_requirements: &ash::vk::MemoryRequirements,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(Allocation, AllocationInfo, usize)> {
+        let mut last_error = None;
+        for (tier, tier_info) in allocation_info.fallback_tiers().enumerate() {
+            match self.allocate_memory(memory_requirements, &tier_info) {
+                Ok((allocation, info)) => return Ok((allocation, info, tier)),
+                Err(error) if is_fallback_error(error.result()) => last_error = Some(error),
+                Err(error) => return Err(error),
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
     /// General purpose memory allocation for multiple allocation objects at once.
     ///
     /// You should free the memory using `Allocator::free_memory` or `Allocator::free_memory_pages`.
@@ -1597,24 +3834,46 @@ impl Allocator {
     /// It may be internally optimized to be more efficient than calling `Allocator::allocate_memory` `allocations.len()` times.
     ///
     /// All allocations are made using same parameters. All of them are created out of the same memory pool and type.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn allocate_memory_pages(
         &self,
         memory_requirements: &ash::vk::MemoryRequirements,
         allocation_info: &AllocationCreateInfo,
         allocation_count: usize,
-    ) -> VkResult<Vec<(Allocation, AllocationInfo)>> {
+    ) -> Result<Vec<(Allocation, AllocationInfo)>> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Allocate);
         let create_info = allocation_create_info_to_ffi(&allocation_info);
+        let category = allocation_info.category;
         let mut allocations: Vec<ffi::VmaAllocation> = vec![mem::zeroed(); allocation_count];
         let mut allocation_info: Vec<ffi::VmaAllocationInfo> =
             vec![mem::zeroed(); allocation_count];
         ffi_to_result(ffi::vmaAllocateMemoryPages(
-            self.internal,
+            self.handle(),
             memory_requirements,
             &create_info,
             allocation_count,
             allocations.as_mut_ptr(),
             allocation_info.as_mut_ptr(),
-        ))?;
+        ))
+        .map_err(|result| {
+            self.allocation_failed(
+                Error::new(result, "Allocator::allocate_memory_pages")
+                    .with_memory_type_bits(memory_requirements.memory_type_bits)
+                    .with_size(memory_requirements.size * allocation_count as vk::DeviceSize),
+            )
+        })?;
+
+        for (allocation, info) in allocations.iter().zip(allocation_info.iter()) {
+            self.allocation_created(
+                "Allocator::allocate_memory_pages",
+                allocation,
+                &create_info,
+                info,
+                None,
+                category,
+            );
+        }
 
         let it = allocations.iter().zip(allocation_info.iter());
         let allocations: Vec<(Allocation, AllocationInfo)> = it
@@ -1627,21 +3886,37 @@ impl Allocator {
     /// Buffer specialized memory allocation.
     ///
     /// You should free the memory using `Allocator::free_memory` or 'Allocator::free_memory_pages'.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn allocate_memory_for_buffer(
         &self,
         buffer: ash::vk::Buffer,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<(Allocation, AllocationInfo)> {
+    ) -> Result<(Allocation, AllocationInfo)> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Allocate);
         let create_info = allocation_create_info_to_ffi(&allocation_info);
+        let category = allocation_info.category;
         let mut allocation: Allocation = mem::zeroed();
         let mut allocation_info: AllocationInfo = mem::zeroed();
         ffi_to_result(ffi::vmaAllocateMemoryForBuffer(
-            self.internal,
+            self.handle(),
             buffer,
             &create_info,
             &mut allocation,
             &mut allocation_info.internal,
-        ))?;
+        ))
+        .map_err(|result| {
+            self.allocation_failed(Error::new(result, "Allocator::allocate_memory_for_buffer"))
+        })?;
+
+        self.allocation_created(
+            "Allocator::allocate_memory_for_buffer",
+            &allocation,
+            &create_info,
+            &allocation_info.internal,
+            None,
+            category,
+        );
 
         Ok((allocation, allocation_info))
     }
@@ -1649,29 +3924,55 @@ impl Allocator {
     /// Image specialized memory allocation.
     ///
     /// You should free the memory using `Allocator::free_memory` or 'Allocator::free_memory_pages'.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn allocate_memory_for_image(
         &self,
         image: ash::vk::Image,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<(Allocation, AllocationInfo)> {
+    ) -> Result<(Allocation, AllocationInfo)> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Allocate);
         let create_info = allocation_create_info_to_ffi(&allocation_info);
+        let category = allocation_info.category;
         let mut allocation: Allocation = mem::zeroed();
         let mut allocation_info: AllocationInfo = mem::zeroed();
         ffi_to_result(ffi::vmaAllocateMemoryForImage(
-            self.internal,
+            self.handle(),
             image,
             &create_info,
             &mut allocation,
             &mut allocation_info.internal,
-        ))?;
+        ))
+        .map_err(|result| {
+            self.allocation_failed(Error::new(result, "Allocator::allocate_memory_for_image"))
+        })?;
+
+        self.allocation_created(
+            "Allocator::allocate_memory_for_image",
+            &allocation,
+            &create_info,
+            &allocation_info.internal,
+            None,
+            category,
+        );
 
         Ok((allocation, allocation_info))
     }
 
     /// Frees memory previously allocated using `Allocator::allocate_memory`,
     /// `Allocator::allocate_memory_for_buffer`, or `Allocator::allocate_memory_for_image`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn free_memory(&self, allocation: &Allocation) {
-        ffi::vmaFreeMemory(self.internal, *allocation);
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Free);
+        if self
+            .allocation_freed("Allocator::free_memory", allocation)
+            .is_err()
+        {
+            return;
+        }
+
+        ffi::vmaFreeMemory(self.handle(), *allocation);
     }
 
     /// Frees memory and destroys multiple allocations.
@@ -1683,12 +3984,17 @@ impl Allocator {
     /// It may be internally optimized to be more efficient than calling 'Allocator::free_memory` `allocations.len()` times.
     ///
     /// Allocations in 'allocations' slice can come from any memory pools and types.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn free_memory_pages(&self, allocations: &[Allocation]) {
-        ffi::vmaFreeMemoryPages(
-            self.internal,
-            allocations.len(),
-            allocations.as_ptr() as *mut _,
-        );
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Free);
+        let mut allocations = allocations.to_vec();
+        allocations.retain(|allocation| {
+            self.allocation_freed("Allocator::free_memory_pages", allocation)
+                .is_ok()
+        });
+
+        ffi::vmaFreeMemoryPages(self.handle(), allocations.len(), allocations.as_mut_ptr());
     }
 
     /// Returns current information about specified allocation and atomically marks it as used in current frame.
@@ -1704,14 +4010,31 @@ impl Allocator {
     /// you can avoid calling it too often.
     ///
     /// If you just want to check if allocation is not lost, `Allocator::touch_allocation` will work faster.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn get_allocation_info(&self, allocation: &Allocation) -> VkResult<AllocationInfo> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::get_allocation_info", allocation)?;
+
         let mut allocation_info: AllocationInfo = mem::zeroed();
-        ffi::vmaGetAllocationInfo(self.internal, *allocation, &mut allocation_info.internal);
+        ffi::vmaGetAllocationInfo(self.handle(), *allocation, &mut allocation_info.internal);
         Ok(allocation_info)
     }
 
+    /// Copy of the name set with `Allocator::set_allocation_name`, or `None` if the allocation has
+    /// no name.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn get_allocation_name(&self, allocation: &Allocation) -> VkResult<Option<String>> {
+        unsafe {
+            let allocation_info = self.get_allocation_info(allocation)?;
+            Ok(allocation_info.get_name().map(str::to_owned))
+        }
+    }
+
     /// Sets user data in given allocation to new value.
     ///
+    /// Prefer `Allocator::set_user_data`, which owns the data and frees it with the allocation.
+    ///
     /// If the allocation was created with `AllocationCreateFlags::USER_DATA_COPY_STRING`,
     /// `user_data` must be either null, or pointer to a null-terminated string. The function
     /// makes local copy of the string and sets it as allocation's user data. String
@@ -1722,38 +4045,266 @@ impl Allocator {
     /// If the flag was not used, the value of pointer `user_data` is just copied to
     /// allocation's user data. It is opaque, so you can use it however you want - e.g.
     /// as a pointer, ordinal number or some handle to you own data.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn set_allocation_user_data(
         &self,
         allocation: &Allocation,
         p_user_data: *mut ::std::os::raw::c_void,
     ) {
-        ffi::vmaSetAllocationUserData(self.internal, *allocation, p_user_data);
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .check_allocation("Allocator::set_allocation_user_data", allocation)
+            .is_err()
+        {
+            return;
+        }
+
+        ffi::vmaSetAllocationUserData(self.handle(), *allocation, p_user_data);
+    }
+
+    /// Attaches `data` to the allocation, replacing and returning what was attached before.
+    ///
+    /// Unlike `Allocator::set_allocation_user_data`, the allocator owns the data and drops it when
+    /// the allocation is freed. Read it back with `Allocator::get_user_data` or
+    /// `Allocator::with_user_data`, or detach it with `Allocator::take_user_data`. The raw
+    /// `pUserData` pointer of the allocation is left untouched.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_user_data<T: std::any::Any + Send>(
+        &self,
+        allocation: &Allocation,
+        data: Box<T>,
+    ) -> Option<Box<dyn std::any::Any + Send>> {
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .check_allocation("Allocator::set_user_data", allocation)
+            .is_err()
+        {
+            return None;
+        }
+
+        self.user_data.set(allocation, data)
+    }
+
+    /// Copy of the data attached with `Allocator::set_user_data`, or `None` if there is none or it
+    /// is not a `T`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn get_user_data<T: std::any::Any + Clone>(&self, allocation: &Allocation) -> Option<T> {
+        self.with_user_data(allocation, |data: &mut T| data.clone())
+    }
+
+    /// Calls `f` with the data attached with `Allocator::set_user_data`, or returns `None` if there
+    /// is none or it is not a `T`.
+    ///
+    /// The data of all allocations is behind one lock, which is held while `f` runs, so `f` must not
+    /// call back into the user data functions of this allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn with_user_data<T: std::any::Any, R>(
+        &self,
+        allocation: &Allocation,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .check_allocation("Allocator::with_user_data", allocation)
+            .is_err()
+        {
+            return None;
+        }
+
+        self.user_data.with(allocation, f)
+    }
+
+    /// Records the priority tier of the allocation and, optionally, a callback that evicts it for
+    /// `Allocator::reclaim`. Setting it again replaces both. The record is dropped when the
+    /// allocation is freed.
+    ///
+    /// The tier is only bookkeeping: the priority VMA passes to `VK_EXT_memory_priority` is chosen
+    /// at creation with `AllocationCreateInfo::priority`, e.g. from `MemoryPriority::value`.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of this allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn set_memory_priority(
+        &self,
+        allocation: &Allocation,
+        priority: priority::MemoryPriority,
+        evict: Option<priority::EvictCallback>,
+    ) -> VkResult<()> {
+        let info = self.get_allocation_info(allocation)?;
+        self.priorities.set(
+            allocation,
+            priority,
+            info.get_size() as vk::DeviceSize,
+            info.get_memory_type(),
+            evict,
+        );
+        Ok(())
+    }
+
+    /// Tier recorded with `Allocator::set_memory_priority`, if any.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn memory_priority(&self, allocation: &Allocation) -> Option<priority::MemoryPriority> {
+        self.priorities.priority(allocation)
+    }
+
+    /// Makes room for `bytes_needed` more bytes by evicting allocations of heaps whose usage plus
+    /// reservations (see `Allocator::reserve_budget`) plus `bytes_needed` exceeds their budget. Returns the number of bytes freed.
+    ///
+    /// The eviction callbacks of allocations in those heaps are called lowest tier first, larger
+    /// allocations first within a tier, until `bytes_needed` bytes are freed. `MemoryPriority::Critical`
+    /// allocations and allocations without a callback are never evicted. Nothing is evicted if
+    /// every heap has enough budget left.
+    ///
+    /// # Safety
+    ///
+    /// The callbacks must only free allocations the device no longer uses.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn reclaim(&self, bytes_needed: vk::DeviceSize) -> vk::DeviceSize {
+        let properties = match self.get_memory_properties() {
+            Ok(properties) => properties,
+            Err(_) => return 0,
+        };
+        let pressured: Vec<bool> = self
+            .get_heap_budgets(properties.memory_heap_count as usize)
+            .iter()
+            .enumerate()
+            .map(|(heap, budget)| {
+                budget
+                    .usage
+                    .saturating_add(self.reservations.reserved(heap))
+                    .saturating_add(bytes_needed)
+                    > budget.budget
+            })
+            .collect();
+        if !pressured.contains(&true) {
+            return 0;
+        }
+
+        let candidates = self.priorities.candidates(|memory_type_index| {
+            let heap_index = properties.memory_types[memory_type_index as usize].heap_index;
+            pressured[heap_index as usize]
+        });
+        let mut reclaimed = 0;
+        for (allocation, size) in candidates {
+            if reclaimed >= bytes_needed {
+                break;
+            }
+            if self.priorities.evict(self, &allocation) {
+                reclaimed += size;
+            }
+        }
+        if reclaimed < bytes_needed {
+            log::warn!(
+                "Allocator::reclaim freed {} of the {} requested",
+                format_bytes(reclaimed),
+                format_bytes(bytes_needed)
+            );
+        }
+        reclaimed
+    }
+
+    /// Detaches and returns the data attached with `Allocator::set_user_data`, if it is a `T`.
+    /// Data of another type stays attached.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn take_user_data<T: std::any::Any>(&self, allocation: &Allocation) -> Option<Box<T>> {
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .check_allocation("Allocator::take_user_data", allocation)
+            .is_err()
+        {
+            return None;
+        }
+
+        self.user_data.take(allocation)
     }
 
     /// Sets pName in given allocation to new value.
     ///
-    /// `pName` must be either null, or pointer to a null-terminated string. The function
-    /// makes local copy of the string and sets it as allocation's `pName`. String
+    /// The function makes local copy of the string and sets it as allocation's `pName`. String
     /// passed as pName doesn't need to be valid for whole lifetime of the allocation -
     /// you can free it after this call. String previously pointed by allocation's
     /// `pName` is freed from memory.
-    pub fn set_allocation_name(&self, allocation: &Allocation, name: String) {
-        let c_name = std::ffi::CString::new(name).unwrap();
+    ///
+    /// The name is adjusted according to the allocator's `NamePolicy`. Fails if it contains an
+    /// interior NUL character and `NamePolicy::replace_interior_nul` is not set.
+    ///
+    /// If the instance enabled `VK_EXT_debug_utils`, the name is also set as the debug name of the
+    /// allocation's `ash::vk::DeviceMemory`. Memory blocks shared by several allocations carry the
+    /// name set last.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_allocation_name(
+        &self,
+        allocation: &Allocation,
+        name: &str,
+    ) -> Result<(), std::ffi::NulError> {
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .check_allocation("Allocator::set_allocation_name", allocation)
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        let c_name = self.name_policy.make_c_string("allocation", name)?;
         unsafe {
-            ffi::vmaSetAllocationName(self.internal, *allocation, c_name.as_ptr());
+            ffi::vmaSetAllocationName(self.handle(), *allocation, c_name.as_ptr());
+            if let Some(debug_names) = &self.debug_names {
+                let mut info: ffi::VmaAllocationInfo = mem::zeroed();
+                ffi::vmaGetAllocationInfo(self.handle(), *allocation, &mut info);
+                debug_names.set_name(info.deviceMemory, &c_name);
+            }
         };
+        #[cfg(feature = "leak_track")]
+        self.leaks.on_name(allocation, &c_name.to_string_lossy());
+        #[cfg(feature = "alloc_log")]
+        self.event_log
+            .on_name(allocation, &c_name.to_string_lossy());
+        Ok(())
+    }
+
+    /// Sets how `Allocator::set_allocation_name` and `Allocator::set_pool_name` treat names
+    /// that are too long or contain interior NUL characters.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_name_policy(&mut self, policy: NamePolicy) {
+        self.name_policy = policy;
+    }
+
+    /// Makes the allocating functions capture an `oom::OomDump` whenever they fail with
+    /// `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY`, and hand it to `target` before returning the
+    /// error. `None` turns it off, which is the default.
+    ///
+    /// Building the dump walks every allocation, so it is slow, but only happens on failure.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_oom_dump(&mut self, target: Option<oom::OomDumpTarget>) {
+        self.oom_dump = target;
     }
 
     /// Given an allocation, returns Property Flags of its memory type.
     ///
     /// This is just a convenience function. Same information can be obtained using
     /// vmaGetAllocationInfo() + vmaGetMemoryProperties().
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn get_allocation_memory_properties(
         &self,
         allocation: &Allocation,
     ) -> vk::MemoryPropertyFlags {
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .check_allocation("Allocator::get_allocation_memory_properties", allocation)
+            .is_err()
+        {
+            return vk::MemoryPropertyFlags::empty();
+        }
+
         let mut p_flags: vk::MemoryPropertyFlags = unsafe { mem::zeroed() };
-        unsafe { ffi::vmaGetAllocationMemoryProperties(self.internal, *allocation, &mut p_flags) };
+        unsafe { ffi::vmaGetAllocationMemoryProperties(self.handle(), *allocation, &mut p_flags) };
         p_flags
     }
 
@@ -1791,20 +4342,44 @@ impl Allocator {
     ///
     /// This function always fails when called for allocation that was created with
     /// `AllocationCreateFlags::CAN_BECOME_LOST` flag. Such allocations cannot be mapped.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn map_memory(&self, allocation: &Allocation) -> VkResult<*mut u8> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Map);
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::map_memory", allocation)?;
+
         let mut mapped_data: *mut ::std::os::raw::c_void = ::std::ptr::null_mut();
         ffi_to_result(ffi::vmaMapMemory(
-            self.internal,
+            self.handle(),
             *allocation,
             &mut mapped_data,
         ))?;
 
+        #[cfg(feature = "validation")]
+        self.validator.on_map(allocation);
+        #[cfg(feature = "alloc_log")]
+        self.event_log.on_map(allocation);
+
         Ok(mapped_data as *mut u8)
     }
 
     /// Unmaps memory represented by given allocation, mapped previously using `Allocator::map_memory`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn unmap_memory(&self, allocation: &Allocation) {
-        ffi::vmaUnmapMemory(self.internal, *allocation);
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Unmap);
+        #[cfg(feature = "validation")]
+        if self
+            .validator
+            .on_unmap("Allocator::unmap_memory", allocation)
+            .is_err()
+        {
+            return;
+        }
+
+        ffi::vmaUnmapMemory(self.handle(), *allocation);
     }
 
     /// Flushes memory of given allocation.
@@ -1816,14 +4391,19 @@ impl Allocator {
     /// - `offset` and `size` don't have to be aligned; hey are internally rounded down/up to multiple of `nonCoherentAtomSize`.
     /// - If `size` is 0, this call is ignored.
     /// - If memory type that the `allocation` belongs to is not `ash::vk::MemoryPropertyFlags::HOST_VISIBLE` or it is `ash::vk::MemoryPropertyFlags::HOST_COHERENT`, this call is ignored.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn flush_allocation(
         &self,
         allocation: &Allocation,
         offset: usize,
         size: usize,
     ) -> VkResult<()> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::flush_allocation", allocation)?;
+
         ffi_to_result(ffi::vmaFlushAllocation(
-            self.internal,
+            self.handle(),
             *allocation,
             offset as vk::DeviceSize,
             size as vk::DeviceSize,
@@ -1839,46 +4419,167 @@ impl Allocator {
     /// - `offset` and `size` don't have to be aligned. They are internally rounded down/up to multiple of `nonCoherentAtomSize`.
     /// - If `size` is 0, this call is ignored.
     /// - If memory type that the `allocation` belongs to is not `ash::vk::MemoryPropertyFlags::HOST_VISIBLE` or it is `ash::vk::MemoryPropertyFlags::HOST_COHERENT`, this call is ignored.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn invalidate_allocation(
         &self,
         allocation: &Allocation,
         offset: usize,
         size: usize,
     ) -> VkResult<()> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::invalidate_allocation", allocation)?;
+
         ffi_to_result(ffi::vmaInvalidateAllocation(
-            self.internal,
+            self.handle(),
             *allocation,
             offset as vk::DeviceSize,
             size as vk::DeviceSize,
         ))
     }
 
+    /// Flushes `range` of the allocation, in bytes relative to its beginning.
+    ///
+    /// Like `Allocator::flush_allocation`, but the range can be any `RangeBounds`, e.g. `16..80`
+    /// or `..`, with an unbounded end flushing to the end of the allocation. It doesn't have to be
+    /// aligned: it is rounded to `nonCoherentAtomSize` of the memory block internally. The call is
+    /// ignored for memory that is not `ash::vk::MemoryPropertyFlags::HOST_VISIBLE` or that is
+    /// `ash::vk::MemoryPropertyFlags::HOST_COHERENT`.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of this allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn flush_range(
+        &self,
+        allocation: &Allocation,
+        range: impl std::ops::RangeBounds<vk::DeviceSize>,
+    ) -> VkResult<()> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::flush_range", allocation)?;
+
+        let (offset, size) = offset_and_size(range);
+        ffi_to_result(ffi::vmaFlushAllocation(
+            self.handle(),
+            *allocation,
+            offset,
+            size,
+        ))
+    }
+
+    /// Invalidates `range` of the allocation, in bytes relative to its beginning.
+    ///
+    /// Like `Allocator::invalidate_allocation`, with the range handled as in
+    /// `Allocator::flush_range`.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of this allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn invalidate_range(
+        &self,
+        allocation: &Allocation,
+        range: impl std::ops::RangeBounds<vk::DeviceSize>,
+    ) -> VkResult<()> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::invalidate_range", allocation)?;
+
+        let (offset, size) = offset_and_size(range);
+        ffi_to_result(ffi::vmaInvalidateAllocation(
+            self.handle(),
+            *allocation,
+            offset,
+            size,
+        ))
+    }
+
+    /// Flushes `range` of the allocation with `Allocator::flush_range` if its memory type needs it,
+    /// i.e. is `ash::vk::MemoryPropertyFlags::HOST_VISIBLE` but not
+    /// `ash::vk::MemoryPropertyFlags::HOST_COHERENT`. Returns whether it did.
+    ///
+    /// The memory type is checked against the properties cached at creation, so coherent memory
+    /// costs no call into Vulkan.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of this allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn maybe_flush(
+        &self,
+        allocation: &Allocation,
+        range: impl std::ops::RangeBounds<vk::DeviceSize>,
+    ) -> VkResult<bool> {
+        let memory_type = self.get_allocation_info(allocation)?.get_memory_type();
+        let flags = self.memory_properties.memory_types[memory_type as usize].property_flags;
+        if !flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+            || flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+        {
+            return Ok(false);
+        }
+        self.flush_range(allocation, range)?;
+        Ok(true)
+    }
+
+    /// Rounds `range` of a `ash::vk::DeviceMemory` out to multiples of `nonCoherentAtomSize`, as
+    /// `ash::vk::MappedMemoryRange` requires, for memory flushed or invalidated without the
+    /// allocator. The end must still be clamped to the size of the memory by the caller.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn non_coherent_range(
+        &self,
+        range: std::ops::Range<vk::DeviceSize>,
+    ) -> std::ops::Range<vk::DeviceSize> {
+        let atom = self.non_coherent_atom_size().max(1);
+        let start = range.start / atom * atom;
+        let end = range
+            .end
+            .checked_add(atom - 1)
+            .map_or(vk::DeviceSize::MAX, |end| end / atom * atom);
+        start..end.max(start)
+    }
+
     /// Flushes memory of given set of allocations.
     ///
     /// Calls `vkFlushMappedMemoryRanges()` for memory associated with given ranges of given allocations.
     /// For more information, see documentation of vmaFlushAllocation().
     ///
-    /// allocator
-    /// allocationCount
-    /// allocations
-    /// offsets If not null, it must point to an array of offsets of regions to flush, relative to the beginning of respective allocations. Null means all offsets are zero.
-    /// sizes If not null, it must point to an array of sizes of regions to flush in respective allocations. Null means `VK_WHOLE_SIZE` for all allocations.
+    /// - `offsets`: offsets of the regions to flush, relative to the beginning of the respective
+    ///   allocations, or `None` for all zero.
+    /// - `sizes`: sizes of the regions to flush, or `None` for `ash::vk::WHOLE_SIZE` for all
+    ///   allocations.
+    ///
+    /// Slices must have one element per allocation, otherwise the call fails with
+    /// `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` without flushing anything.
     ///
     /// This function returns the `VkResult` from `vkFlushMappedMemoryRanges` if it is
     /// called, otherwise `VK_SUCCESS`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn flush_allocations(
         &self,
         allocations: &mut [Allocation],
-        offsets: &[vk::DeviceSize],
-        sizes: &[vk::DeviceSize],
+        offsets: Option<&[vk::DeviceSize]>,
+        sizes: Option<&[vk::DeviceSize]>,
     ) -> VkResult<()> {
+        check_batch_lengths(
+            "Allocator::flush_allocations",
+            allocations.len(),
+            offsets,
+            sizes,
+        )?;
+        #[cfg(feature = "validation")]
+        for allocation in allocations.iter() {
+            self.validator
+                .check_allocation("Allocator::flush_allocations", allocation)?;
+        }
+
         unsafe {
             ffi_to_result(ffi::vmaFlushAllocations(
-                self.internal,
+                self.handle(),
                 allocations.len() as u32,
                 allocations.as_mut_ptr(),
-                offsets.as_ptr(),
-                sizes.as_ptr(),
+                offsets.map_or(std::ptr::null(), <[_]>::as_ptr),
+                sizes.map_or(std::ptr::null(), <[_]>::as_ptr),
             ))
         }
     }
@@ -1888,52 +4589,162 @@ impl Allocator {
     /// Calls `vkInvalidateMappedMemoryRanges()` for memory associated with given ranges of given allocations.
     /// For more information, see documentation of vmaInvalidateAllocation().
     ///
-    /// allocator
-    /// allocationCount
-    /// allocations
-    /// offsets If not null, it must point to an array of offsets of regions to flush, relative to the beginning of respective allocations. Null means all ofsets are zero.
-    /// sizes If not null, it must point to an array of sizes of regions to flush in respective allocations. Null means `VK_WHOLE_SIZE` for all allocations.
+    /// - `offsets`: offsets of the regions to invalidate, relative to the beginning of the
+    ///   respective allocations, or `None` for all zero.
+    /// - `sizes`: sizes of the regions to invalidate, or `None` for `ash::vk::WHOLE_SIZE` for all
+    ///   allocations.
+    ///
+    /// Slices must have one element per allocation, otherwise the call fails with
+    /// `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` without invalidating anything.
     ///
     /// This function returns the `VkResult` from `vkInvalidateMappedMemoryRanges` if it is
     /// called, otherwise `VK_SUCCESS`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn invalidate_allocations(
         &self,
         allocations: &mut [Allocation],
-        offsets: &[vk::DeviceSize],
-        sizes: &[vk::DeviceSize],
+        offsets: Option<&[vk::DeviceSize]>,
+        sizes: Option<&[vk::DeviceSize]>,
     ) -> VkResult<()> {
+        check_batch_lengths(
+            "Allocator::invalidate_allocations",
+            allocations.len(),
+            offsets,
+            sizes,
+        )?;
+        #[cfg(feature = "validation")]
+        for allocation in allocations.iter() {
+            self.validator
+                .check_allocation("Allocator::invalidate_allocations", allocation)?;
+        }
+
         unsafe {
             ffi_to_result(ffi::vmaInvalidateAllocations(
-                self.internal,
+                self.handle(),
                 allocations.len() as u32,
                 allocations.as_mut_ptr(),
-                offsets.as_ptr(),
-                sizes.as_ptr(),
+                offsets.map_or(std::ptr::null(), <[_]>::as_ptr),
+                sizes.map_or(std::ptr::null(), <[_]>::as_ptr),
             ))
         }
     }
 
+    /// Maps the allocation temporarily if needed, copies `data` into it at `offset`, and flushes the
+    /// memory from the host caches if needed.
+    ///
+    /// Same behavior can be achieved by calling `Allocator::map_memory`, copying the bytes,
+    /// `Allocator::unmap_memory` and `Allocator::flush_allocation`.
+    ///
+    /// - `offset` is relative to the beginning of the allocation, not to the `ash::vk::DeviceMemory` block.
+    /// - The allocation must have been created in a memory type that is `ash::vk::MemoryPropertyFlags::HOST_VISIBLE`,
+    ///   e.g. by using `MemoryUsage::Auto` together with `AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE`
+    ///   or `AllocationCreateFlags::HOST_ACCESS_RANDOM`. Otherwise the call fails.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of this allocator in host-visible memory, and the
+    /// device must not be using the range being written.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn copy_to_allocation(
+        &self,
+        data: &[u8],
+        allocation: &Allocation,
+        offset: vk::DeviceSize,
+    ) -> VkResult<()> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::copy_to_allocation", allocation)?;
+
+        ffi_to_result(ffi::vmaCopyMemoryToAllocation(
+            self.handle(),
+            data.as_ptr() as *const ::std::os::raw::c_void,
+            *allocation,
+            offset,
+            data.len() as vk::DeviceSize,
+        ))
+    }
+
+    /// Invalidates memory from the host caches if needed, maps the allocation temporarily if needed,
+    /// and copies `data.len()` bytes starting at `offset` out of it.
+    ///
+    /// Same behavior can be achieved by calling `Allocator::invalidate_allocation`,
+    /// `Allocator::map_memory`, copying the bytes and `Allocator::unmap_memory`.
+    ///
+    /// - `offset` is relative to the beginning of the allocation, not to the `ash::vk::DeviceMemory` block.
+    /// - The allocation should have been created in a memory type that is `ash::vk::MemoryPropertyFlags::HOST_VISIBLE`
+    ///   and `ash::vk::MemoryPropertyFlags::HOST_CACHED`, e.g. by using `MemoryUsage::Auto` together with
+    ///   `AllocationCreateFlags::HOST_ACCESS_RANDOM`. Reading from uncached memory works, but may be very slow.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be a live allocation of this allocator in host-visible memory, and the
+    /// device must not be writing the range being read.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn copy_from_allocation(
+        &self,
+        allocation: &Allocation,
+        offset: vk::DeviceSize,
+        data: &mut [u8],
+    ) -> VkResult<()> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::copy_from_allocation", allocation)?;
+
+        ffi_to_result(ffi::vmaCopyAllocationToMemory(
+            self.handle(),
+            *allocation,
+            offset,
+            data.as_mut_ptr() as *mut ::std::os::raw::c_void,
+            data.len() as vk::DeviceSize,
+        ))
+    }
+
     /// Checks magic number in margins around all allocations in given memory types (in both default and custom pools) in search for corruptions.
     ///
     /// `memory_type_bits` bit mask, where each bit set means that a memory type with that index should be checked.
     ///
     /// Corruption detection is enabled only when `VMA_DEBUG_DETECT_CORRUPTION` macro is defined to nonzero,
     /// `VMA_DEBUG_MARGIN` is defined to nonzero and only for memory types that are `HOST_VISIBLE` and `HOST_COHERENT`.
+    /// Both macros are defined by the crate's `detect_corruption` feature.
     ///
     /// Possible error values:
     ///
-    /// - `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` - corruption detection is not enabled for any of specified memory types.
-    /// - `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` - corruption detection has been performed and found memory corruptions around one of the allocations.
-    ///  `VMA_ASSERT` is also fired in that case.
-    /// - Other value: Error returned by Vulkan, e.g. memory mapping failure.
-    pub unsafe fn check_corruption(
+    /// - `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` - corruption detection is not enabled for any of specified memory types.
+    /// - `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` - corruption detection has been performed and found memory corruptions around one of the allocations.
+    ///   `VMA_ASSERT` is also fired in that case.
+    /// - Other value: Error returned by Vulkan, e.g. memory mapping failure.
+    ///
+    /// # Safety
+    ///
+    /// The memory of the checked types is mapped and its margins are read, so no allocation in it
+    /// may be freed during the call.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn check_corruption(&self, memory_type_bits: u32) -> VkResult<()> {
+        ffi_to_result(ffi::vmaCheckCorruption(self.handle(), memory_type_bits))
+    }
+
+    /// Checks for corruptions like `Allocator::check_corruption`, in all memory types that have all of `property_flags`.
+    ///
+    /// Returns `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if no memory type has the flags.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::check_corruption`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn check_corruption_for_property_flags(
         &self,
-        memory_types: ash::vk::MemoryPropertyFlags,
+        property_flags: ash::vk::MemoryPropertyFlags,
     ) -> VkResult<()> {
-        ffi_to_result(ffi::vmaCheckCorruption(
-            self.internal,
-            memory_types.as_raw(),
-        ))
+        let properties = self.get_memory_properties()?;
+        let memory_type_bits = properties.memory_types[..properties.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .filter(|(_, memory_type)| memory_type.property_flags.contains(property_flags))
+            .fold(0u32, |bits, (index, _)| bits | (1 << index));
+        if memory_type_bits == 0 {
+            return Err(vk::Result::ERROR_FEATURE_NOT_PRESENT);
+        }
+        self.check_corruption(memory_type_bits)
     }
 
     /// Begins defragmentation process.
@@ -1958,6 +4769,7 @@ impl Allocator {
     ///
     /// - If `info.command_buffer` is not null, you must submit that command buffer
     /// and make sure it finished execution before calling `Allocator::defragmentation_end`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn begin_defragmentation(
         &self,
         info: &DefragmentationInfo,
@@ -1974,7 +4786,7 @@ impl Allocator {
         };
 
         ffi_to_result(ffi::vmaBeginDefragmentation(
-            self.internal,
+            self.handle(),
             &ffi_info,
             &mut context.internal,
         ))?;
@@ -1985,12 +4797,13 @@ impl Allocator {
     /// Ends defragmentation process.
     ///
     /// Use this function to finish defragmentation started by `Allocator::defragmentation_begin`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn end_defragmentation(
         &self,
         context: &mut DefragmentationContext,
     ) -> VkResult<DefragmentationStats> {
         let mut vma_defrag_stats: ffi::VmaDefragmentationStats = mem::zeroed();
-        ffi::vmaEndDefragmentation(self.internal, context.internal, &mut vma_defrag_stats);
+        ffi::vmaEndDefragmentation(self.handle(), context.internal, &mut vma_defrag_stats);
 
         let stats = DefragmentationStats {
             bytes_moved: vma_defrag_stats.bytesMoved,
@@ -1999,6 +4812,9 @@ impl Allocator {
             device_memory_blocks_freed: vma_defrag_stats.deviceMemoryBlocksFreed,
         };
 
+        #[cfg(feature = "metrics")]
+        metrics::record_defragmentation(&stats);
+
         Ok(stats)
     }
 
@@ -2011,6 +4827,7 @@ impl Allocator {
     /// - `VK_SUCCESS` if no more moves are possible. Then you can omit call to vmaEndDefragmentationPass() and simply end whole defragmentation.
     /// - `VK_INCOMPLETE` if there are pending moves returned in `pPassInfo`. You need to perform them, call vmaEndDefragmentationPass(),
     /// and then preferably try another pass with vmaBeginDefragmentationPass().
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn begin_defragmentation_pass(
         &self,
         context: &mut DefragmentationContext,
@@ -2018,7 +4835,7 @@ impl Allocator {
         let mut pass_info: ffi::VmaDefragmentationPassMoveInfo = unsafe { mem::zeroed() };
         unsafe {
             let result = ffi_to_result(ffi::vmaBeginDefragmentationPass(
-                self.internal,
+                self.handle(),
                 context.internal,
                 &mut pass_info,
             ));
@@ -2049,14 +4866,42 @@ impl Allocator {
     /// will be freed.
     ///
     /// If no more moves are possible you can end whole defragmentation.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn end_defragmentation_pass(
         &self,
         context: &mut DefragmentationContext,
         move_pass_info: &mut DefragmentationPassMoveInfo,
     ) -> VkResult<()> {
+        for move_info in move_pass_info.moves() {
+            if move_info.operation == DefragmentationMoveOperation::Destroy {
+                // VMA frees the allocation regardless, so a violation is only reported.
+                let _ = self.allocation_freed(
+                    "Allocator::end_defragmentation_pass",
+                    &move_info.src_allocation,
+                );
+            }
+            #[cfg(feature = "alloc_log")]
+            if move_info.operation == DefragmentationMoveOperation::Copy {
+                // The temporary allocation isn't tracked, so it is queried from VMA directly.
+                let mut dst_info: ffi::VmaAllocationInfo = unsafe { mem::zeroed() };
+                unsafe {
+                    ffi::vmaGetAllocationInfo(
+                        self.handle(),
+                        move_info.dst_tmp_allocation,
+                        &mut dst_info,
+                    )
+                };
+                self.event_log.on_move(
+                    &move_info.src_allocation,
+                    dst_info.memoryType,
+                    self.memory_properties.memory_types[dst_info.memoryType as usize].heap_index,
+                );
+            }
+        }
+
         unsafe {
             ffi_to_result(ffi::vmaEndDefragmentationPass(
-                self.internal,
+                self.handle(),
                 context.internal,
                 &mut move_pass_info.internal,
             ))
@@ -2076,12 +4921,26 @@ impl Allocator {
     /// (which is illegal in Vulkan).
     ///
     /// It is recommended to use function `Allocator::create_buffer` instead of this one.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn bind_buffer_memory(
         &self,
         buffer: ash::vk::Buffer,
         allocation: &Allocation,
     ) -> VkResult<()> {
-        ffi_to_result(ffi::vmaBindBufferMemory(self.internal, *allocation, buffer))
+        #[cfg(feature = "validation")]
+        self.validator.check_bind(
+            "Allocator::bind_buffer_memory",
+            allocation,
+            BoundResource::Buffer(buffer),
+        )?;
+
+        ffi_to_result(ffi::vmaBindBufferMemory(self.handle(), *allocation, buffer))?;
+
+        #[cfg(feature = "validation")]
+        self.validator
+            .on_bind(allocation, BoundResource::Buffer(buffer));
+
+        Ok(())
     }
 
     /// Binds buffer to allocation with additional parameters.
@@ -2097,6 +4956,7 @@ impl Allocator {
     /// If `pNext` is not null, #VmaAllocator object must have been created with #VMA_ALLOCATOR_CREATE_KHR_BIND_MEMORY2_BIT flag
     /// or with VmaAllocatorCreateInfo::vulkanApiVersion `>= VK_API_VERSION_1_1`. Otherwise the call fails.
 
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn bind_buffer_memory2<T>(
         &self,
         buffer: ash::vk::Buffer,
@@ -2107,8 +4967,15 @@ impl Allocator {
     where
         T: Into<Option<*mut ::std::os::raw::c_void>>,
     {
+        #[cfg(feature = "validation")]
+        self.validator.check_bind(
+            "Allocator::bind_buffer_memory2",
+            allocation,
+            BoundResource::Buffer(buffer),
+        )?;
+
         ffi_to_result(ffi::vmaBindBufferMemory2(
-            self.internal,
+            self.handle(),
             *allocation,
             allocation_local_offset,
             buffer,
@@ -2117,7 +4984,13 @@ impl Allocator {
             } else {
                 std::ptr::null_mut()
             },
-        ))
+        ))?;
+
+        #[cfg(feature = "validation")]
+        self.validator
+            .on_bind(allocation, BoundResource::Buffer(buffer));
+
+        Ok(())
     }
 
     /// Binds image to allocation.
@@ -2133,12 +5006,26 @@ impl Allocator {
     /// (which is illegal in Vulkan).
     ///
     /// It is recommended to use function `Allocator::create_image` instead of this one.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn bind_image_memory(
         &self,
         image: ash::vk::Image,
         allocation: &Allocation,
     ) -> VkResult<()> {
-        ffi_to_result(ffi::vmaBindImageMemory(self.internal, *allocation, image))
+        #[cfg(feature = "validation")]
+        self.validator.check_bind(
+            "Allocator::bind_image_memory",
+            allocation,
+            BoundResource::Image(image),
+        )?;
+
+        ffi_to_result(ffi::vmaBindImageMemory(self.handle(), *allocation, image))?;
+
+        #[cfg(feature = "validation")]
+        self.validator
+            .on_bind(allocation, BoundResource::Image(image));
+
+        Ok(())
     }
 
     /// Binds image to allocation with additional parameters.
@@ -2153,6 +5040,7 @@ impl Allocator {
     ///
     /// If `pNext` is not null, #VmaAllocator object must have been created with #VMA_ALLOCATOR_CREATE_KHR_BIND_MEMORY2_BIT flag
     /// or with VmaAllocatorCreateInfo::vulkanApiVersion `>= VK_API_VERSION_1_1`. Otherwise the call fails.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn bind_image_memory2<T>(
         &self,
         image: ash::vk::Image,
@@ -2163,8 +5051,15 @@ impl Allocator {
     where
         T: Into<Option<*mut ::std::os::raw::c_void>>,
     {
+        #[cfg(feature = "validation")]
+        self.validator.check_bind(
+            "Allocator::bind_image_memory2",
+            allocation,
+            BoundResource::Image(image),
+        )?;
+
         ffi_to_result(ffi::vmaBindImageMemory2(
-            self.internal,
+            self.handle(),
             *allocation,
             allocation_local_offset,
             image,
@@ -2173,7 +5068,13 @@ impl Allocator {
             } else {
                 std::ptr::null_mut()
             },
-        ))
+        ))?;
+
+        #[cfg(feature = "validation")]
+        self.validator
+            .on_bind(allocation, BoundResource::Image(image));
+
+        Ok(())
     }
 
     /// Creates a new `VkBuffer`, allocates and binds memory for it.
@@ -2223,57 +5124,272 @@ impl Allocator {
     /// and if dedicated allocation is possible (AllocationCreateInfo::pool is null
     /// and `AllocationCreateFlags::NEVER_ALLOCATE` is not used), it creates dedicated
     /// allocation for this buffer, just like when using `AllocationCreateFlags::DEDICATED_MEMORY`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn create_buffer(
         &self,
         buffer_info: &ash::vk::BufferCreateInfo,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<(ash::vk::Buffer, Allocation, AllocationInfo)> {
-        let allocation_create_info = allocation_create_info_to_ffi(&allocation_info);
+    ) -> Result<(ash::vk::Buffer, Allocation, AllocationInfo)> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Allocate);
+        let mut allocation_create_info = allocation_create_info_to_ffi(&allocation_info);
+        let category = allocation_info.category;
+        let routed_pool = self.routing.buffer_pool(self, buffer_info, allocation_info);
         let mut buffer = vk::Buffer::null();
         let mut allocation: Allocation = mem::zeroed();
         let mut allocation_info: AllocationInfo = mem::zeroed();
-        ffi_to_result(ffi::vmaCreateBuffer(
-            self.internal,
-            &*buffer_info,
+        let mut create = |create_info: &ffi::VmaAllocationCreateInfo| {
+            ffi_to_result(ffi::vmaCreateBuffer(
+                self.handle(),
+                &*buffer_info,
+                create_info,
+                &mut buffer,
+                &mut allocation,
+                &mut allocation_info.internal,
+            ))
+        };
+        match routed_pool {
+            Some(pool) => {
+                allocation_create_info.pool = pool;
+                create(&allocation_create_info).or_else(|_| {
+                    allocation_create_info.pool = std::ptr::null_mut();
+                    create(&allocation_create_info)
+                })
+            }
+            None => create(&allocation_create_info),
+        }
+        .map_err(|result| {
+            self.allocation_failed(
+                Error::new(result, "Allocator::create_buffer").with_size(buffer_info.size),
+            )
+        })?;
+
+        self.allocation_created(
+            "Allocator::create_buffer",
+            &allocation,
             &allocation_create_info,
-            &mut buffer,
-            &mut allocation,
-            &mut allocation_info.internal,
-        ))?;
+            &allocation_info.internal,
+            Some(BoundResource::Buffer(buffer)),
+            category,
+        );
+
+        Ok((buffer, allocation, allocation_info))
+    }
 
+    /// Same as `Allocator::create_buffer`, then names the allocation with
+    /// `Allocator::set_allocation_name` and, if the instance enabled `VK_EXT_debug_utils`, sets
+    /// `name` as the debug name of the buffer.
+    ///
+    /// A name rejected by the allocator's `NamePolicy` is logged and the buffer is left unnamed.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_buffer_named(
+        &self,
+        buffer_info: &ash::vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+        name: &str,
+    ) -> Result<(ash::vk::Buffer, Allocation, AllocationInfo)> {
+        let (buffer, allocation, allocation_info) =
+            self.create_buffer(buffer_info, allocation_info)?;
+        self.name_resource(&allocation, BoundResource::Buffer(buffer), name);
         Ok((buffer, allocation, allocation_info))
     }
 
+    /// Like `Allocator::create_buffer`, but retries with the fallback chain of `allocation_info`.
+    /// See `Allocator::allocate_with_fallback`.
+    ///
+    /// # Safety
+    ///
+    /// `buffer_info` must be valid for `vkCreateBuffer` on the allocator's device.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_buffer_with_fallback(
+        &self,
+        buffer_info: &ash::vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(ash::vk::Buffer, Allocation, AllocationInfo, usize)> {
+        let mut last_error = None;
+        for (tier, tier_info) in allocation_info.fallback_tiers().enumerate() {
+            match self.create_buffer(buffer_info, &tier_info) {
+                Ok((buffer, allocation, info)) => return Ok((buffer, allocation, info, tier)),
+                Err(error) if is_fallback_error(error.result()) => last_error = Some(error),
+                Err(error) => return Err(error),
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
+    /// Turns smart routing on with `config`, or off with `None`. See the `routing` module.
+    ///
+    /// Pools created under a previous configuration are kept until the allocator is destroyed, so
+    /// their allocations stay valid.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_smart_routing(&self, config: Option<routing::RoutingConfig>) {
+        self.routing.set_config(config);
+    }
+
+    /// Pools created by smart routing so far, ordered by memory type, usage class and bucket.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn smart_pools(&self) -> Vec<routing::RoutedPool> {
+        self.routing.pools()
+    }
+
+    /// Creates many buffers at once, e.g. the thousands of small buffers of a loading screen.
+    ///
+    /// Equivalent to calling `Allocator::create_buffer` for every entry of `infos`, in order, but
+    /// all parameters are converted up front and the results are collected into a single vector.
+    /// Creation is all or nothing: if any buffer fails, the ones created before it are destroyed
+    /// and the error of the failing one is returned.
+    ///
+    /// # Safety
+    ///
+    /// Every create info must be valid for `vkCreateBuffer` on the allocator's device.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_buffers(
+        &self,
+        infos: &[(ash::vk::BufferCreateInfo, AllocationCreateInfo)],
+    ) -> Result<Vec<(ash::vk::Buffer, Allocation, AllocationInfo)>> {
+        let allocation_create_infos: Vec<ffi::VmaAllocationCreateInfo> = infos
+            .iter()
+            .map(|(_, allocation_info)| allocation_create_info_to_ffi(allocation_info))
+            .collect();
+        let mut created = Vec::with_capacity(infos.len());
+        for ((buffer_info, allocation_info), allocation_create_info) in
+            infos.iter().zip(&allocation_create_infos)
+        {
+            #[cfg(feature = "timing")]
+            let _timer = self.timings.start(timing::Operation::Allocate);
+            let mut buffer = vk::Buffer::null();
+            let mut allocation: Allocation = mem::zeroed();
+            let category = allocation_info.category;
+            let mut allocation_info: AllocationInfo = mem::zeroed();
+            let result = ffi_to_result(ffi::vmaCreateBuffer(
+                self.handle(),
+                buffer_info,
+                allocation_create_info,
+                &mut buffer,
+                &mut allocation,
+                &mut allocation_info.internal,
+            ));
+            if let Err(result) = result {
+                for (buffer, allocation, _) in created.iter().rev() {
+                    self.destroy_buffer(*buffer, allocation);
+                }
+                return Err(self.allocation_failed(
+                    Error::new(result, "Allocator::create_buffers").with_size(buffer_info.size),
+                ));
+            }
+
+            self.allocation_created(
+                "Allocator::create_buffers",
+                &allocation,
+                allocation_create_info,
+                &allocation_info.internal,
+                Some(BoundResource::Buffer(buffer)),
+                category,
+            );
+            created.push((buffer, allocation, allocation_info));
+        }
+        Ok(created)
+    }
+
     /// Creates a buffer with additional minimum alignment.
     ///
     /// Similar to vmaCreateBuffer() but provides additional parameter `minAlignment` which allows to specify custom,
     /// minimum alignment to be used when placing the buffer inside a larger memory block, which may be needed e.g.
     /// for interop with OpenGL.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn create_buffer_with_alignment(
         &self,
         buffer_info: &ash::vk::BufferCreateInfo,
         allocation_info: &AllocationCreateInfo,
         min_alignment: vk::DeviceSize,
     ) -> VkResult<(ash::vk::Buffer, Allocation, AllocationInfo)> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Allocate);
         let allocation_create_info = allocation_create_info_to_ffi(&allocation_info);
+        let category = allocation_info.category;
         let mut buffer = vk::Buffer::null();
         unsafe {
             let mut allocation: Allocation = mem::zeroed();
             let mut allocation_info: AllocationInfo = mem::zeroed();
             ffi_to_result(ffi::vmaCreateBufferWithAlignment(
-                self.internal,
+                self.handle(),
                 &*buffer_info,
                 &allocation_create_info,
                 min_alignment,
                 &mut buffer,
                 &mut allocation,
                 &mut allocation_info.internal,
-            ))?;
+            ))
+            .map_err(|result| {
+                self.allocation_failed(
+                    Error::new(result, "Allocator::create_buffer_with_alignment")
+                        .with_size(buffer_info.size),
+                )
+                .result()
+            })?;
+
+            self.allocation_created(
+                "Allocator::create_buffer_with_alignment",
+                &allocation,
+                &allocation_create_info,
+                &allocation_info.internal,
+                Some(BoundResource::Buffer(buffer)),
+                category,
+            );
 
             Ok((buffer, allocation, allocation_info))
         }
     }
 
+    /// Creates a buffer that shaders can access through its device address, and returns the
+    /// address along with it.
+    ///
+    /// `ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS` is added to the usage of `buffer_info`,
+    /// and VMA allocates the memory with `ash::vk::MemoryAllocateFlags::DEVICE_ADDRESS`. The
+    /// allocator must have been created with `VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT`,
+    /// otherwise `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` is returned.
+    ///
+    /// The address is queried with `vkGetBufferDeviceAddress` of `device`, which requires
+    /// Vulkan 1.2 and the `bufferDeviceAddress` feature.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device the allocator was created for.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_buffer_with_address(
+        &self,
+        device: &ash::Device,
+        buffer_info: &ash::vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(
+        ash::vk::Buffer,
+        Allocation,
+        AllocationInfo,
+        ash::vk::DeviceAddress,
+    )> {
+        if !self
+            .flags
+            .contains(AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT)
+        {
+            return Err(Error::new(
+                vk::Result::ERROR_FEATURE_NOT_PRESENT,
+                "Allocator::create_buffer_with_address",
+            )
+            .with_size(buffer_info.size));
+        }
+        let mut buffer_info = *buffer_info;
+        buffer_info.usage |= vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+        let (buffer, allocation, allocation_info) =
+            self.create_buffer(&buffer_info, allocation_info)?;
+        let address = device
+            .get_buffer_device_address(&vk::BufferDeviceAddressInfo::builder().buffer(buffer));
+        Ok((buffer, allocation, allocation_info, address))
+    }
+
     /// Creates a new `VkBuffer`, binds already created memory for it.
     ///
     /// allocator
@@ -2292,15 +5408,20 @@ impl Allocator {
     /// If the function succeeded, you must destroy the buffer when you
     /// no longer need it using `vkDestroyBuffer()`. If you want to also destroy the corresponding
     /// allocation you can use convenience function vmaDestroyBuffer().
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn create_aliasing_buffer(
         &self,
         allocation: &Allocation,
         buffer_info: &ash::vk::BufferCreateInfo,
     ) -> VkResult<vk::Buffer> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::create_aliasing_buffer", allocation)?;
+
         let mut buffer = vk::Buffer::null();
         unsafe {
             ffi_to_result(ffi::vmaCreateAliasingBuffer(
-                self.internal,
+                self.handle(),
                 *allocation,
                 &*buffer_info,
                 &mut buffer,
@@ -2320,8 +5441,18 @@ impl Allocator {
     /// ```
     ///
     /// It it safe to pass null as `buffer` and/or `allocation`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn destroy_buffer(&self, buffer: ash::vk::Buffer, allocation: &Allocation) {
-        ffi::vmaDestroyBuffer(self.internal, buffer, *allocation);
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Free);
+        if self
+            .allocation_freed("Allocator::destroy_buffer", allocation)
+            .is_err()
+        {
+            return;
+        }
+
+        ffi::vmaDestroyBuffer(self.handle(), buffer, *allocation);
     }
 
     /// This function automatically creates an image, allocates appropriate memory
@@ -2341,37 +5472,273 @@ impl Allocator {
     /// If `VK_ERROR_VALIDAITON_FAILED_EXT` is returned, VMA may have encountered a problem
     /// that is not caught by the validation layers. One example is if you try to create a 0x0
     /// image, a panic will occur and `VK_ERROR_VALIDAITON_FAILED_EXT` is thrown.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub unsafe fn create_image(
         &self,
         image_info: &ash::vk::ImageCreateInfo,
         allocation_info: &AllocationCreateInfo,
-    ) -> VkResult<(ash::vk::Image, Allocation, AllocationInfo)> {
-        let allocation_create_info = allocation_create_info_to_ffi(&allocation_info);
+    ) -> Result<(ash::vk::Image, Allocation, AllocationInfo)> {
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Allocate);
+        let mut allocation_create_info = allocation_create_info_to_ffi(&allocation_info);
+        let category = allocation_info.category;
+        let routed_pool = self.routing.image_pool(self, image_info, allocation_info);
         let mut image = vk::Image::null();
         let mut allocation: Allocation = mem::zeroed();
         let mut allocation_info: AllocationInfo = mem::zeroed();
-        ffi_to_result(ffi::vmaCreateImage(
-            self.internal,
-            &*image_info,
+        let mut create = |create_info: &ffi::VmaAllocationCreateInfo| {
+            ffi_to_result(ffi::vmaCreateImage(
+                self.handle(),
+                &*image_info,
+                create_info,
+                &mut image,
+                &mut allocation,
+                &mut allocation_info.internal,
+            ))
+        };
+        match routed_pool {
+            Some(pool) => {
+                allocation_create_info.pool = pool;
+                create(&allocation_create_info).or_else(|_| {
+                    allocation_create_info.pool = std::ptr::null_mut();
+                    create(&allocation_create_info)
+                })
+            }
+            None => create(&allocation_create_info),
+        }
+        .map_err(|result| self.allocation_failed(Error::new(result, "Allocator::create_image")))?;
+
+        self.allocation_created(
+            "Allocator::create_image",
+            &allocation,
             &allocation_create_info,
-            &mut image,
-            &mut allocation,
-            &mut allocation_info.internal,
-        ))?;
+            &allocation_info.internal,
+            Some(BoundResource::Image(image)),
+            category,
+        );
+
+        Ok((image, allocation, allocation_info))
+    }
 
+    /// Same as `Allocator::create_image`, then names the allocation with
+    /// `Allocator::set_allocation_name` and, if the instance enabled `VK_EXT_debug_utils`, sets
+    /// `name` as the debug name of the image.
+    ///
+    /// A name rejected by the allocator's `NamePolicy` is logged and the image is left unnamed.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_image`. `name` only has to live for the call: the allocator and
+    /// `VK_EXT_debug_utils` both keep their own copy of it.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_image_named(
+        &self,
+        image_info: &ash::vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+        name: &str,
+    ) -> Result<(ash::vk::Image, Allocation, AllocationInfo)> {
+        let (image, allocation, allocation_info) =
+            self.create_image(image_info, allocation_info)?;
+        self.name_resource(&allocation, BoundResource::Image(image), name);
         Ok((image, allocation, allocation_info))
     }
 
+    /// Creates an image like `Allocator::create_image` and records commands into `command_buffer`
+    /// that fill it with `mips`, returning the staging buffer the data is read from along with it.
+    ///
+    /// `mips[i]` holds the texels of mip level `i` for all array layers, tightly packed layer after
+    /// layer. Levels beyond `mips.len()` are left uninitialized. All levels and layers end up in
+    /// `final_layout`. `ash::vk::ImageUsageFlags::TRANSFER_DST` is added to the usage of
+    /// `image_info`. Only color formats are supported.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` if there are more entries in
+    /// `mips` than mip levels in `image_info`.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device the allocator was created for and `command_buffer` must be in
+    /// the recording state. The returned `upload::InitStaging` must be destroyed once the command
+    /// buffer completed.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_image_init(
+        &self,
+        device: &ash::Device,
+        image_info: &ash::vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+        mips: &[&[u8]],
+        command_buffer: vk::CommandBuffer,
+        final_layout: vk::ImageLayout,
+    ) -> Result<(
+        ash::vk::Image,
+        Allocation,
+        AllocationInfo,
+        upload::InitStaging,
+    )> {
+        const OPERATION: &str = "Allocator::create_image_init";
+        if mips.len() > image_info.mip_levels as usize {
+            return Err(Error::new(
+                vk::Result::ERROR_VALIDATION_FAILED_EXT,
+                OPERATION,
+            ));
+        }
+
+        let (staging, offsets) = self.stage_mips(mips, OPERATION)?;
+
+        let mut image_info = *image_info;
+        image_info.usage |= vk::ImageUsageFlags::TRANSFER_DST;
+        let (image, allocation, allocation_info) =
+            match self.create_image(&image_info, allocation_info) {
+                Ok(created) => created,
+                Err(err) => {
+                    staging.destroy(self);
+                    return Err(err);
+                }
+            };
+
+        upload::record_image_init(
+            device,
+            command_buffer,
+            image,
+            &image_info,
+            staging.buffer(),
+            &offsets,
+            final_layout,
+        );
+        Ok((image, allocation, allocation_info, staging))
+    }
+
+    /// Copies `mips` into a new mapped staging buffer, one after the other at the offsets
+    /// `upload::record_image_init` expects, and returns it with the offsets.
+    unsafe fn stage_mips(
+        &self,
+        mips: &[&[u8]],
+        operation: &'static str,
+    ) -> Result<(upload::InitStaging, Vec<vk::DeviceSize>)> {
+        let (offsets, staging_size) = upload::mip_offsets(mips);
+        let staging_info = vk::BufferCreateInfo::builder()
+            .size(staging_size.max(1))
+            .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let (staging_buffer, staging_allocation, staging_allocation_info) = self.create_buffer(
+            &staging_info,
+            &AllocationCreateInfo {
+                flags: AllocationCreateFlags::MAPPED
+                    | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+                usage: MemoryUsage::Auto,
+                ..Default::default()
+            },
+        )?;
+        let staging = upload::InitStaging::new(staging_buffer, staging_allocation);
+
+        let mapped = staging_allocation_info.get_mapped_data();
+        for (mip, &offset) in mips.iter().zip(&offsets) {
+            std::ptr::copy_nonoverlapping(mip.as_ptr(), mapped.add(offset as usize), mip.len());
+        }
+        if let Err(result) = self.flush_allocation(&staging_allocation, 0, staging_size as usize) {
+            staging.destroy(self);
+            return Err(Error::new(result, operation));
+        }
+        Ok((staging, offsets))
+    }
+
+    /// Creates an image like `Allocator::create_image` that `Allocator::copy_host_to_image` can
+    /// fill: with `host_image_copy::HOST_TRANSFER` added to the usage of `image_info` if
+    /// `host_copy` is available, with `ash::vk::ImageUsageFlags::TRANSFER_DST` otherwise.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_image`. The format and usage of `image_info` must support
+    /// host copies on the device if `host_copy` is available.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn create_image_host_copyable(
+        &self,
+        host_copy: &host_image_copy::HostImageCopy,
+        image_info: &ash::vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(ash::vk::Image, Allocation, AllocationInfo)> {
+        let mut image_info = *image_info;
+        image_info.usage |= if host_copy.is_available() {
+            host_image_copy::HOST_TRANSFER
+        } else {
+            vk::ImageUsageFlags::TRANSFER_DST
+        };
+        self.create_image(&image_info, allocation_info)
+    }
+
+    /// Fills a new image created with `Allocator::create_image_host_copyable` with `mips`, laid
+    /// out as for `Allocator::create_image_init`, leaving all levels and layers in `final_layout`.
+    ///
+    /// If `host_copy` is available the texels are copied by the host right away, nothing is
+    /// recorded into `command_buffer` and `None` is returned. Otherwise the copy goes through a
+    /// staging buffer and commands recorded into `command_buffer`, and the staging buffer is
+    /// returned.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` if there are more entries in
+    /// `mips` than mip levels in `image_info`.
+    ///
+    /// # Safety
+    ///
+    /// `image` must have been created from `image_info` with
+    /// `Allocator::create_image_host_copyable` and the same `host_copy`, and never used.
+    /// `device` must be the device the allocator was created for and `command_buffer` must be in
+    /// the recording state. The returned `upload::InitStaging` must be destroyed once the command
+    /// buffer completed.
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn copy_host_to_image(
+        &self,
+        host_copy: &host_image_copy::HostImageCopy,
+        device: &ash::Device,
+        image: vk::Image,
+        image_info: &ash::vk::ImageCreateInfo,
+        mips: &[&[u8]],
+        command_buffer: vk::CommandBuffer,
+        final_layout: vk::ImageLayout,
+    ) -> Result<Option<upload::InitStaging>> {
+        const OPERATION: &str = "Allocator::copy_host_to_image";
+        if mips.len() > image_info.mip_levels as usize {
+            return Err(Error::new(
+                vk::Result::ERROR_VALIDATION_FAILED_EXT,
+                OPERATION,
+            ));
+        }
+
+        if host_copy.is_available() {
+            host_copy
+                .copy_mips(image, image_info, mips, final_layout)
+                .map_err(|result| Error::new(result, OPERATION))?;
+            return Ok(None);
+        }
+
+        let (staging, offsets) = self.stage_mips(mips, OPERATION)?;
+        upload::record_image_init(
+            device,
+            command_buffer,
+            image,
+            image_info,
+            staging.buffer(),
+            &offsets,
+            final_layout,
+        );
+        Ok(Some(staging))
+    }
+
     /// Function similar to vmaCreateAliasingBuffer().
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn create_aliasing_image(
         &self,
         allocation: &Allocation,
         image_info: &ash::vk::ImageCreateInfo,
     ) -> VkResult<vk::Image> {
+        #[cfg(feature = "validation")]
+        self.validator
+            .check_allocation("Allocator::create_aliasing_image", allocation)?;
+
         let mut image = vk::Image::null();
         unsafe {
             ffi_to_result(ffi::vmaCreateAliasingImage(
-                self.internal,
+                self.handle(),
                 *allocation,
                 &*image_info,
                 &mut image,
@@ -2391,17 +5758,32 @@ impl Allocator {
     /// ```
     ///
     /// It it safe to pass null as `image` and/or `allocation`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn destroy_image(&self, image: ash::vk::Image, allocation: &Allocation) {
-        unsafe { ffi::vmaDestroyImage(self.internal, image, *allocation) };
+        #[cfg(feature = "timing")]
+        let _timer = self.timings.start(timing::Operation::Free);
+        if self
+            .allocation_freed("Allocator::destroy_image", allocation)
+            .is_err()
+        {
+            return;
+        }
+
+        unsafe { ffi::vmaDestroyImage(self.handle(), image, *allocation) };
     }
 
     /// Builds and returns statistics as a String in JSON format.
     /// detailed_map
+    ///
+    /// Returns `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if the crate was built with the
+    /// `stats_string_disabled` feature.
+    #[cfg(not(feature = "stats_string_disabled"))]
+    #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn build_stats_string(&self, detailed_map: bool) -> VkResult<String> {
         let mut stats_string: *mut ::std::os::raw::c_char = ::std::ptr::null_mut();
         unsafe {
             ffi::vmaBuildStatsString(
-                self.internal,
+                self.handle(),
                 &mut stats_string,
                 if detailed_map { 1 } else { 0 },
             );
@@ -2414,11 +5796,234 @@ impl Allocator {
                 let result = std::ffi::CStr::from_ptr(stats_string)
                     .to_string_lossy()
                     .into_owned();
-                ffi::vmaFreeStatsString(self.internal, stats_string);
+                ffi::vmaFreeStatsString(self.handle(), stats_string);
                 result
             }
         })
     }
+
+    /// Builds and returns statistics as a String in JSON format.
+    /// detailed_map
+    ///
+    /// Returns `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if the crate was built with the
+    /// `stats_string_disabled` feature.
+    #[cfg(feature = "stats_string_disabled")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn build_stats_string(&self, _detailed_map: bool) -> VkResult<String> {
+        Err(vk::Result::ERROR_FEATURE_NOT_PRESENT)
+    }
+
+    /// Chooses how misuse detected by the `validation` feature is reported. The policy is shared by
+    /// all clones of this allocator and defaults to `ViolationPolicy::Panic`.
+    #[cfg(feature = "validation")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_violation_policy(&self, policy: ViolationPolicy) {
+        self.validator.set_policy(policy);
+    }
+
+    /// Policy set with `Allocator::set_violation_policy`.
+    #[cfg(feature = "validation")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn violation_policy(&self) -> ViolationPolicy {
+        self.validator.policy()
+    }
+
+    /// Allocations that have not been freed yet, oldest first, with the call that created them, their
+    /// size, name and a backtrace of where they were created.
+    ///
+    /// Called right before `Allocator::destroy`, this lists the allocations VMA would complain about.
+    #[cfg(feature = "leak_track")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn report_leaks(&self) -> Vec<leak::LeakedAllocation> {
+        self.leaks.live()
+    }
+
+    /// Starts appending an event to `writer` for every allocation, free, map, name change and
+    /// defragmentation move, one JSON object per line, see `vk_mem::alloc_log` for the format.
+    /// `None` stops the log and flushes the previous writer. The log is shared by all clones of
+    /// this allocator.
+    ///
+    /// Allocations made before the writer was set are not logged when freed or mapped.
+    #[cfg(feature = "alloc_log")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_allocation_log(&self, writer: Option<Box<dyn std::io::Write + Send>>) {
+        self.event_log.set_writer(writer);
+    }
+
+    /// Latency distributions of the allocate, free, map and unmap calls made so far.
+    #[cfg(feature = "timing")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn timing_report(&self) -> timing::TimingReport {
+        self.timings.report()
+    }
+
+    /// Clears the recorded timings, e.g. to measure one loading phase on its own.
+    #[cfg(feature = "timing")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn reset_timings(&self) {
+        self.timings.reset();
+    }
+
+    /// Lifetime distributions of the allocations freed so far, one entry per custom pool (or the
+    /// default pools) and memory type, ordered by pool and memory type index.
+    ///
+    /// Statistics of a custom pool are discarded when the pool is destroyed.
+    #[cfg(feature = "lifetime_stats")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn lifetime_statistics(&self) -> Vec<lifetime::LifetimeStatistics> {
+        self.lifetimes.statistics()
+    }
+
+    /// Categories that look like they should be served by a ring or transient allocator instead:
+    /// allocations from the default pools or from custom pools not created with
+    /// `AllocatorPoolCreateFlags::LINEAR_ALGORITHM`, of which at least `min_fraction` (0 to 1) lived
+    /// shorter than `threshold`, e.g. the duration of two or three frames.
+    ///
+    /// The result is ordered by the number of short-lived allocations, most first.
+    #[cfg(feature = "lifetime_stats")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn short_lived_categories(
+        &self,
+        threshold: std::time::Duration,
+        min_fraction: f64,
+    ) -> Vec<lifetime::LifetimeStatistics> {
+        let mut candidates: Vec<_> = self
+            .lifetimes
+            .statistics()
+            .into_iter()
+            .filter(|statistics| {
+                !statistics.linear
+                    && statistics.count > 0
+                    && statistics.fraction_shorter_than(threshold) >= min_fraction
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            let short_a = a.fraction_shorter_than(threshold) * a.count as f64;
+            let short_b = b.fraction_shorter_than(threshold) * b.count as f64;
+            short_b.total_cmp(&short_a)
+        });
+        candidates
+    }
+
+    /// Names a freshly created allocation and the resource created with it.
+    fn name_resource(&self, allocation: &Allocation, resource: BoundResource, name: &str) {
+        let c_name = match self.name_policy.make_c_string("allocation", name) {
+            Ok(c_name) => c_name,
+            Err(error) => {
+                log::warn!("{} left unnamed: {}", resource, error);
+                return;
+            }
+        };
+        // Already adjusted to the policy, so this can't fail or truncate again.
+        let _ = self.set_allocation_name(allocation, c_name.to_str().unwrap_or_default());
+        if let Some(debug_names) = &self.debug_names {
+            unsafe {
+                match resource {
+                    BoundResource::Buffer(buffer) => debug_names.set_name(buffer, &c_name),
+                    BoundResource::Image(image) => debug_names.set_name(image, &c_name),
+                }
+            }
+        }
+    }
+
+    /// Records a new allocation in the enabled bookkeeping features.
+    #[allow(unused_variables)]
+    fn allocation_created(
+        &self,
+        operation: &'static str,
+        allocation: &Allocation,
+        create_info: &ffi::VmaAllocationCreateInfo,
+        info: &ffi::VmaAllocationInfo,
+        resource: Option<BoundResource>,
+        category: Option<category::Category>,
+    ) {
+        #[cfg(feature = "validation")]
+        self.validator
+            .register_allocation(operation, allocation, create_info, resource);
+        #[cfg(feature = "lifetime_stats")]
+        self.lifetimes.on_create(allocation, create_info, info);
+        #[cfg(feature = "tracy")]
+        self.tracy.on_create(
+            allocation,
+            info.size,
+            Some(&create_info.pool)
+                .filter(|pool| !pool.is_null())
+                .map(|pool| self.get_pool_name(pool)),
+        );
+        #[cfg(feature = "leak_track")]
+        self.leaks.on_create(operation, allocation, info.size);
+        #[cfg(feature = "alloc_log")]
+        self.event_log.on_create(
+            operation,
+            allocation,
+            info.size,
+            info.memoryType,
+            self.memory_properties.memory_types[info.memoryType as usize].heap_index,
+            Some(&create_info.pool)
+                .filter(|pool| !pool.is_null())
+                .map(|pool| alloc_log::pool_label(pool, self.get_pool_name(pool))),
+        );
+        let mut info2: ffi::VmaAllocationInfo2 = unsafe { mem::zeroed() };
+        unsafe { ffi::vmaGetAllocationInfo2(self.internal, *allocation, &mut info2) };
+        self.categories.on_create(
+            allocation,
+            category,
+            create_info,
+            info.size,
+            info2.dedicatedMemory == vk::TRUE,
+        );
+        if let Some(debug_names) = &self.debug_names {
+            if !create_info.pool.is_null() {
+                unsafe {
+                    let mut c_name: *const ::std::os::raw::c_char = std::ptr::null();
+                    ffi::vmaGetPoolName(self.handle(), create_info.pool, &mut c_name);
+                    if !c_name.is_null() {
+                        debug_names.set_name(info.deviceMemory, std::ffi::CStr::from_ptr(c_name));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Hands an `oom::OomDump` to the target set with `Allocator::set_oom_dump` if `error` is
+    /// `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY`, then returns `error`.
+    fn allocation_failed(&self, error: Error) -> Error {
+        let target = match &self.oom_dump {
+            Some(target) if error.result() == vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => target,
+            _ => return error,
+        };
+        let heap_count = unsafe { self.get_memory_properties() }
+            .map_or(0, |properties| properties.memory_heap_count as usize);
+        let dump = oom::OomDump {
+            error,
+            budgets: self.get_heap_budgets(heap_count),
+            stats_json: self.build_stats_string(true).unwrap_or_default(),
+        };
+        target.deliver(&dump);
+        dump.error
+    }
+
+    /// Records that an allocation is about to be freed in the enabled bookkeeping features.
+    ///
+    /// Fails if validation rejects the call under `ViolationPolicy::Error`, in which case the
+    /// allocation must not be freed.
+    #[allow(unused_variables)]
+    fn allocation_freed(&self, operation: &'static str, allocation: &Allocation) -> VkResult<()> {
+        #[cfg(feature = "validation")]
+        self.validator.release_allocation(operation, allocation)?;
+        #[cfg(feature = "lifetime_stats")]
+        self.lifetimes.on_free(allocation);
+        #[cfg(feature = "tracy")]
+        self.tracy.on_free(allocation);
+        #[cfg(feature = "leak_track")]
+        self.leaks.on_free(allocation);
+        #[cfg(feature = "alloc_log")]
+        self.event_log.on_free(allocation);
+        drop(self.user_data.remove(allocation));
+        drop(self.priorities.remove(allocation));
+        self.categories.on_free(allocation);
+        Ok(())
+    }
 }
 
 impl VirtualBlock {
@@ -2429,7 +6034,7 @@ impl VirtualBlock {
     pub fn new(create_info: VirtualBlockCreateInfo) -> VkResult<Self> {
         let allocation_callbacks = match create_info.allocation_callbacks {
             None => std::ptr::null(),
-            Some(ref cb) => cb as *const _,
+            Some(ref cb) => (cb as *const ash_compat::AllocationCallbacks).cast(),
         };
 
         let ffi_create_info = ffi::VmaVirtualBlockCreateInfo {
@@ -2586,6 +6191,10 @@ impl VirtualBlock {
     /// virtualBlock Virtual block.
     /// ppStatsString Returned string.
     /// detailedMap Pass `VK_FALSE` to only obtain statistics as returned by vmaCalculateVirtualBlockStatistics(). Pass `VK_TRUE` to also obtain full list of allocations and free spaces.
+    ///
+    /// Returns `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if the crate was built with the
+    /// `stats_string_disabled` feature.
+    #[cfg(not(feature = "stats_string_disabled"))]
     pub fn build_stats_string(&self, detailed_map: bool) -> VkResult<String> {
         let mut stats_string: *mut ::std::os::raw::c_char = ::std::ptr::null_mut();
         unsafe {
@@ -2608,6 +6217,18 @@ impl VirtualBlock {
             }
         })
     }
+
+    /// Builds and returns a String in JSON format with information about given #VmaVirtualBlock.
+    /// virtualBlock Virtual block.
+    /// ppStatsString Returned string.
+    /// detailedMap Pass `VK_FALSE` to only obtain statistics as returned by vmaCalculateVirtualBlockStatistics(). Pass `VK_TRUE` to also obtain full list of allocations and free spaces.
+    ///
+    /// Returns `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if the crate was built with the
+    /// `stats_string_disabled` feature.
+    #[cfg(feature = "stats_string_disabled")]
+    pub fn build_stats_string(&self, _detailed_map: bool) -> VkResult<String> {
+        Err(vk::Result::ERROR_FEATURE_NOT_PRESENT)
+    }
 }
 
 /// Construct `AllocatorCreateFlags` with default values
@@ -2629,6 +6250,8 @@ impl Default for AllocationCreateInfo {
             pool: None,
             p_user_data: ::std::ptr::null_mut(),
             priority: 0.0,
+            category: None,
+            fallback_required_flags: Vec::new(),
         }
     }
 }
@@ -2644,7 +6267,7 @@ impl Default for AllocatorPoolCreateInfo {
             max_block_count: 0,
             priority: 0.0,
             min_allocation_alignment: 0,
-            p_memory_allocate_next: ::std::ptr::null_mut(),
+            memory_allocate_next: None,
         }
     }
 }
@@ -2661,6 +6284,51 @@ impl Default for DefragmentationInfo {
     }
 }
 
+const _: () = assert!(
+    mem::size_of::<DefragmentationMove>() == mem::size_of::<ffi::VmaDefragmentationMove>()
+        && mem::align_of::<DefragmentationMove>() == mem::align_of::<ffi::VmaDefragmentationMove>()
+        && mem::size_of::<DefragmentationMoveOperation>()
+            == mem::size_of::<ffi::VmaDefragmentationMoveOperation>()
+);
+
+impl DefragmentationPassMoveInfo {
+    /// Moves to be performed in the current pass, empty if the pass has none.
+    ///
+    /// For each move, either recreate the buffer or image at `dst_tmp_allocation`, bind it and copy
+    /// the data (the default `DefragmentationMoveOperation::Copy`), or change the operation with
+    /// `DefragmentationPassMoveInfo::set_operation` before calling
+    /// `Allocator::end_defragmentation_pass`.
+    pub fn moves(&self) -> &[DefragmentationMove] {
+        if self.internal.pMoves.is_null() {
+            &[]
+        } else {
+            unsafe {
+                std::slice::from_raw_parts(
+                    self.internal.pMoves as *const DefragmentationMove,
+                    self.internal.moveCount as usize,
+                )
+            }
+        }
+    }
+
+    /// Sets the operation `Allocator::end_defragmentation_pass` performs for move `index`.
+    ///
+    /// Panics if `index` is out of bounds of `DefragmentationPassMoveInfo::moves`.
+    pub fn set_operation(&mut self, index: usize, operation: DefragmentationMoveOperation) {
+        let count = self.internal.moveCount as usize;
+        assert!(
+            index < count,
+            "move index {} out of bounds for a pass of {} moves",
+            index,
+            count
+        );
+        unsafe {
+            (*self.internal.pMoves.add(index)).operation =
+                operation as ffi::VmaDefragmentationMoveOperation;
+        }
+    }
+}
+
 /// Custom `Drop` implementation to clean up internal allocation instance
 impl Drop for Allocator {
     fn drop(&mut self) {
@@ -0,0 +1,237 @@
+//! Allocation lifetime statistics, enabled with the `lifetime_stats` feature.
+//!
+//! The allocator timestamps every allocation when it is created and, when it is freed, records how long
+//! it lived in a histogram of its category: the custom pool it came from (or the default pools) and
+//! its memory type. Categories whose allocations mostly live for a few frames but are served by the
+//! general-purpose allocation algorithm are good candidates for a pool created with
+//! `AllocatorPoolCreateFlags::LINEAR_ALGORITHM` used as a ring buffer, or for a per-frame transient
+//! allocator, which avoids the fragmentation and bookkeeping cost of short-lived allocations.
+
+use crate::{ffi, Allocation, AllocatorPool, AllocatorPoolCreateFlags};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Number of histogram buckets in `LifetimeStatistics::histogram`.
+///
+/// Bucket `i` counts lifetimes in `[2^i, 2^(i+1))` microseconds; the first bucket also counts
+/// lifetimes below one microsecond and the last one everything from `2^(BUCKET_COUNT-1)` microseconds
+/// (about 18 minutes) up.
+pub const BUCKET_COUNT: usize = 31;
+
+/// Distribution of the lifetimes of the allocations of one category that have been freed.
+#[derive(Debug, Clone)]
+pub struct LifetimeStatistics {
+    /// Custom pool the allocations were made from, or `None` for the default pools.
+    pub pool: Option<AllocatorPool>,
+
+    /// Whether `pool` was created with `AllocatorPoolCreateFlags::LINEAR_ALGORITHM`.
+    pub linear: bool,
+
+    /// Memory type index of the allocations.
+    pub memory_type_index: u32,
+
+    /// Number of allocations of this category that have been freed.
+    pub count: u64,
+
+    /// Number of allocations of this category that are still alive.
+    pub live_count: u64,
+
+    /// Total number of bytes of the freed allocations.
+    pub total_bytes: u64,
+
+    /// Shortest recorded lifetime.
+    pub min: Duration,
+
+    /// Longest recorded lifetime.
+    pub max: Duration,
+
+    /// Sum of all recorded lifetimes.
+    pub total: Duration,
+
+    /// Number of lifetimes per power-of-two bucket of microseconds. See `BUCKET_COUNT`.
+    pub histogram: [u64; BUCKET_COUNT],
+}
+
+impl LifetimeStatistics {
+    fn new(pool: Option<AllocatorPool>, linear: bool, memory_type_index: u32) -> Self {
+        LifetimeStatistics {
+            pool,
+            linear,
+            memory_type_index,
+            count: 0,
+            live_count: 0,
+            total_bytes: 0,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            total: Duration::ZERO,
+            histogram: [0; BUCKET_COUNT],
+        }
+    }
+
+    /// Average lifetime, or zero if no allocation has been freed yet.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+        }
+    }
+
+    /// Range of lifetimes counted by bucket `index` of `LifetimeStatistics::histogram`.
+    pub fn bucket_range(index: usize) -> (Duration, Duration) {
+        let lower = if index == 0 { 0 } else { 1u64 << index };
+        let upper = if index + 1 >= BUCKET_COUNT {
+            Duration::MAX
+        } else {
+            Duration::from_micros(1u64 << (index + 1))
+        };
+        (Duration::from_micros(lower), upper)
+    }
+
+    /// Upper bound of the lifetime below which a `fraction` (0 to 1) of the allocations were freed,
+    /// at the resolution of the histogram buckets.
+    pub fn percentile(&self, fraction: f64) -> Duration {
+        let target = (self.count as f64 * fraction.clamp(0.0, 1.0)).ceil() as u64;
+        let mut seen = 0;
+        for (index, &count) in self.histogram.iter().enumerate() {
+            seen += count;
+            if count > 0 && seen >= target {
+                return Self::bucket_range(index).1.min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Fraction (0 to 1) of the allocations that lived shorter than `threshold`, at the resolution
+    /// of the histogram buckets: a bucket only counts if it lies entirely below `threshold`.
+    pub fn fraction_shorter_than(&self, threshold: Duration) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let shorter: u64 = self
+            .histogram
+            .iter()
+            .enumerate()
+            .take_while(|&(index, _)| Self::bucket_range(index).1 <= threshold)
+            .map(|(_, &count)| count)
+            .sum();
+        shorter as f64 / self.count as f64
+    }
+
+    fn record(&mut self, lifetime: Duration, size: u64) {
+        self.count += 1;
+        self.live_count = self.live_count.saturating_sub(1);
+        self.total_bytes += size;
+        self.min = self.min.min(lifetime);
+        self.max = self.max.max(lifetime);
+        self.total += lifetime;
+        self.histogram[bucket_index(lifetime)] += 1;
+    }
+}
+
+fn bucket_index(lifetime: Duration) -> usize {
+    let micros = lifetime.as_micros().max(1);
+    ((127 - micros.leading_zeros()) as usize).min(BUCKET_COUNT - 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Category {
+    pool: usize,
+    memory_type_index: u32,
+}
+
+#[derive(Debug)]
+struct LiveAllocation {
+    created: Instant,
+    category: Category,
+    size: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    linear_pools: HashMap<usize, bool>,
+    allocations: HashMap<usize, LiveAllocation>,
+    categories: HashMap<Category, LifetimeStatistics>,
+}
+
+/// Per-allocator lifetime records, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct LifetimeTracker {
+    state: Mutex<State>,
+}
+
+impl LifetimeTracker {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn register_pool(&self, pool: &AllocatorPool, flags: AllocatorPoolCreateFlags) {
+        self.state().linear_pools.insert(
+            *pool as usize,
+            flags.contains(AllocatorPoolCreateFlags::LINEAR_ALGORITHM),
+        );
+    }
+
+    pub(crate) fn unregister_pool(&self, pool: &AllocatorPool) {
+        let mut state = self.state();
+        let key = *pool as usize;
+        state.linear_pools.remove(&key);
+        state.categories.retain(|category, _| category.pool != key);
+    }
+
+    pub(crate) fn on_create(
+        &self,
+        allocation: &Allocation,
+        create_info: &ffi::VmaAllocationCreateInfo,
+        info: &ffi::VmaAllocationInfo,
+    ) {
+        let category = Category {
+            pool: create_info.pool as usize,
+            memory_type_index: info.memoryType,
+        };
+        let mut state = self.state();
+        let linear = state
+            .linear_pools
+            .get(&category.pool)
+            .copied()
+            .unwrap_or(false);
+        state
+            .categories
+            .entry(category)
+            .or_insert_with(|| {
+                LifetimeStatistics::new(
+                    Some(create_info.pool).filter(|pool| !pool.is_null()),
+                    linear,
+                    category.memory_type_index,
+                )
+            })
+            .live_count += 1;
+        state.allocations.insert(
+            *allocation as usize,
+            LiveAllocation {
+                created: Instant::now(),
+                category,
+                size: info.size,
+            },
+        );
+    }
+
+    pub(crate) fn on_free(&self, allocation: &Allocation) {
+        let now = Instant::now();
+        let mut state = self.state();
+        if let Some(live) = state.allocations.remove(&(*allocation as usize)) {
+            if let Some(statistics) = state.categories.get_mut(&live.category) {
+                statistics.record(now - live.created, live.size);
+            }
+        }
+    }
+
+    pub(crate) fn statistics(&self) -> Vec<LifetimeStatistics> {
+        let mut statistics: Vec<_> = self.state().categories.values().cloned().collect();
+        statistics.sort_by_key(|s| (s.pool.map_or(0, |pool| pool as usize), s.memory_type_index));
+        statistics
+    }
+}
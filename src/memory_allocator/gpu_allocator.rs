@@ -0,0 +1,113 @@
+//! `MemoryAllocator` over [gpu-allocator](https://crates.io/crates/gpu-allocator), enabled with the
+//! `gpu_allocator` feature.
+//!
+//! gpu-allocator supports ash up to 0.37, so the feature also enables `ash_0_37` to make both crates
+//! use the same ash types.
+
+use super::{AllocationRequest, MemoryAllocator, MemoryLocation, MemoryRange};
+use crate::{Error, Result, Statistics};
+use ::gpu_allocator::vulkan;
+use ::gpu_allocator::AllocationError;
+use ash::vk;
+use std::ptr::NonNull;
+
+/// A gpu-allocator `Allocator` behind the `MemoryAllocator` trait.
+///
+/// gpu-allocator does not report its memory blocks, so only the allocation counters of
+/// `MemoryAllocator::statistics` are filled, from the allocations made through this adapter.
+pub struct GpuAllocator {
+    inner: vulkan::Allocator,
+    statistics: Statistics,
+}
+
+impl GpuAllocator {
+    /// Wraps an allocator created with `gpu_allocator::vulkan::Allocator::new`.
+    pub fn new(inner: vulkan::Allocator) -> Self {
+        GpuAllocator {
+            inner,
+            statistics: Statistics::default(),
+        }
+    }
+
+    /// The wrapped allocator, for functionality outside of `MemoryAllocator`.
+    pub fn inner(&mut self) -> &mut vulkan::Allocator {
+        &mut self.inner
+    }
+
+    /// Unwraps the allocator.
+    pub fn into_inner(self) -> vulkan::Allocator {
+        self.inner
+    }
+}
+
+/// Converts a gpu-allocator error into the closest `ash::vk::Result`.
+fn to_error(error: AllocationError, operation: &'static str) -> Error {
+    let result = match error {
+        AllocationError::OutOfMemory => vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+        AllocationError::FailedToMap(_) => vk::Result::ERROR_MEMORY_MAP_FAILED,
+        AllocationError::NoCompatibleMemoryTypeFound => vk::Result::ERROR_FEATURE_NOT_PRESENT,
+        _ => vk::Result::ERROR_UNKNOWN,
+    };
+    log::debug!("`{}` failed: {}", operation, error);
+    Error::new(result, operation)
+}
+
+impl MemoryAllocator for GpuAllocator {
+    type Allocation = vulkan::Allocation;
+
+    unsafe fn allocate(&mut self, request: &AllocationRequest<'_>) -> Result<vulkan::Allocation> {
+        let location = match request.location {
+            MemoryLocation::Unknown => ::gpu_allocator::MemoryLocation::Unknown,
+            MemoryLocation::GpuOnly => ::gpu_allocator::MemoryLocation::GpuOnly,
+            MemoryLocation::CpuToGpu => ::gpu_allocator::MemoryLocation::CpuToGpu,
+            MemoryLocation::GpuToCpu => ::gpu_allocator::MemoryLocation::GpuToCpu,
+        };
+        let allocation = self
+            .inner
+            .allocate(&vulkan::AllocationCreateDesc {
+                name: request.name,
+                requirements: request.requirements,
+                location,
+                linear: request.linear,
+                allocation_scheme: vulkan::AllocationScheme::GpuAllocatorManaged,
+            })
+            .map_err(|error| {
+                to_error(error, "GpuAllocator::allocate").with_size(request.requirements.size)
+            })?;
+        self.statistics.allocation_count += 1;
+        self.statistics.allocation_bytes += allocation.size();
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: vulkan::Allocation) -> Result<()> {
+        let size = allocation.size();
+        self.inner
+            .free(allocation)
+            .map_err(|error| to_error(error, "GpuAllocator::free"))?;
+        self.statistics.allocation_count -= 1;
+        self.statistics.allocation_bytes -= size;
+        Ok(())
+    }
+
+    unsafe fn memory(&self, allocation: &vulkan::Allocation) -> Result<MemoryRange> {
+        Ok(MemoryRange {
+            memory: allocation.memory(),
+            offset: allocation.offset(),
+            size: allocation.size(),
+        })
+    }
+
+    unsafe fn map(&mut self, allocation: &mut vulkan::Allocation) -> Result<NonNull<u8>> {
+        // gpu-allocator keeps host visible memory persistently mapped.
+        allocation
+            .mapped_ptr()
+            .map(NonNull::cast)
+            .ok_or_else(|| Error::new(vk::Result::ERROR_MEMORY_MAP_FAILED, "GpuAllocator::map"))
+    }
+
+    unsafe fn unmap(&mut self, _allocation: &mut vulkan::Allocation) {}
+
+    fn statistics(&self) -> Statistics {
+        self.statistics
+    }
+}
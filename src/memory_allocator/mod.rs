@@ -0,0 +1,173 @@
+//! An allocator-agnostic interface for raw memory allocations.
+//!
+//! `MemoryAllocator` covers the subset of functionality engines typically build on: allocating
+//! memory for given `ash::vk::MemoryRequirements`, freeing it, mapping it and reading usage
+//! statistics. It is implemented by `Allocator` and, with the `gpu_allocator` feature, by
+//! `gpu_allocator::GpuAllocator` over the [gpu-allocator](https://crates.io/crates/gpu-allocator)
+//! crate, so call sites written against the trait can switch between the two for A/B tests or an
+//! incremental migration.
+
+#[cfg(feature = "gpu_allocator")]
+pub mod gpu_allocator;
+
+use crate::{Allocation, AllocationCreateInfo, Allocator, Error, MemoryUsage, Result, Statistics};
+use ash::vk;
+use std::ptr::NonNull;
+
+/// Where an allocation should live, mirroring gpu-allocator's `MemoryLocation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryLocation {
+    /// Let the allocator pick any memory type allowed by the requirements.
+    Unknown,
+
+    /// Device local memory that the host does not access.
+    GpuOnly,
+
+    /// Host visible memory the host writes and the device reads, preferably device local.
+    CpuToGpu,
+
+    /// Host visible memory the device writes and the host reads, preferably cached.
+    GpuToCpu,
+}
+
+/// A request for memory passed to `MemoryAllocator::allocate`.
+#[derive(Debug, Clone, Copy)]
+pub struct AllocationRequest<'a> {
+    /// Requirements of the buffer or image the memory is for.
+    pub requirements: vk::MemoryRequirements,
+
+    /// Where the memory should live.
+    pub location: MemoryLocation,
+
+    /// Whether the resource is linear (a buffer or a linear image) rather than an optimally tiled
+    /// image. Allocators that track `bufferImageGranularity` use it to place the allocation.
+    pub linear: bool,
+
+    /// Name of the allocation for debugging, may be empty.
+    pub name: &'a str,
+}
+
+/// The `ash::vk::DeviceMemory` range backing an allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRange {
+    /// Memory block the allocation is part of.
+    pub memory: vk::DeviceMemory,
+
+    /// Offset of the allocation in `memory`, to pass to `vkBindBufferMemory`/`vkBindImageMemory`.
+    pub offset: vk::DeviceSize,
+
+    /// Size of the allocation in bytes.
+    pub size: vk::DeviceSize,
+}
+
+/// Allocates, frees and maps raw memory, independently of the allocator implementation.
+pub trait MemoryAllocator {
+    /// Handle of an allocation made by this allocator.
+    type Allocation;
+
+    /// Allocates memory for `request`.
+    ///
+    /// # Safety
+    ///
+    /// The requirements must come from the device the allocator was created for.
+    unsafe fn allocate(&mut self, request: &AllocationRequest<'_>) -> Result<Self::Allocation>;
+
+    /// Frees `allocation`, which must not be used afterwards.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must have been made by this allocator, and the device must no longer use it.
+    unsafe fn free(&mut self, allocation: Self::Allocation) -> Result<()>;
+
+    /// The memory range backing `allocation`.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must have been made by this allocator and not freed yet.
+    unsafe fn memory(&self, allocation: &Self::Allocation) -> Result<MemoryRange>;
+
+    /// Pointer to the start of `allocation` in host memory. Fails if its memory is not host visible.
+    ///
+    /// Every call must be paired with `MemoryAllocator::unmap`.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must have been made by this allocator and not freed yet.
+    unsafe fn map(&mut self, allocation: &mut Self::Allocation) -> Result<NonNull<u8>>;
+
+    /// Releases a pointer returned by `MemoryAllocator::map`.
+    ///
+    /// # Safety
+    ///
+    /// `allocation` must be mapped, and the pointer must not be used afterwards.
+    unsafe fn unmap(&mut self, allocation: &mut Self::Allocation);
+
+    /// Usage of the allocator. `Statistics::block_count` and `Statistics::block_bytes` are 0 if
+    /// the implementation does not expose its memory blocks.
+    fn statistics(&self) -> Statistics;
+}
+
+impl MemoryAllocator for Allocator {
+    type Allocation = Allocation;
+
+    unsafe fn allocate(&mut self, request: &AllocationRequest<'_>) -> Result<Allocation> {
+        let coherent =
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let (required_flags, preferred_flags) = match request.location {
+            MemoryLocation::Unknown => Default::default(),
+            MemoryLocation::GpuOnly => (
+                vk::MemoryPropertyFlags::empty(),
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ),
+            MemoryLocation::CpuToGpu => (coherent, vk::MemoryPropertyFlags::DEVICE_LOCAL),
+            MemoryLocation::GpuToCpu => (coherent, vk::MemoryPropertyFlags::HOST_CACHED),
+        };
+        let allocation_info = AllocationCreateInfo {
+            usage: MemoryUsage::Unknown,
+            required_flags,
+            preferred_flags,
+            ..Default::default()
+        };
+        let (allocation, _) = self.allocate_memory(&request.requirements, &allocation_info)?;
+        if !request.name.is_empty() {
+            // Names are a debugging aid, an unusable one is not worth failing the allocation.
+            let _ = self.set_allocation_name(&allocation, request.name);
+        }
+        Ok(allocation)
+    }
+
+    unsafe fn free(&mut self, allocation: Allocation) -> Result<()> {
+        self.free_memory(&allocation);
+        Ok(())
+    }
+
+    unsafe fn memory(&self, allocation: &Allocation) -> Result<MemoryRange> {
+        let info = self
+            .get_allocation_info(allocation)
+            .map_err(|result| Error::new(result, "Allocator::get_allocation_info"))?;
+        Ok(MemoryRange {
+            memory: info.get_device_memory(),
+            offset: info.get_offset() as vk::DeviceSize,
+            size: info.get_size() as vk::DeviceSize,
+        })
+    }
+
+    unsafe fn map(&mut self, allocation: &mut Allocation) -> Result<NonNull<u8>> {
+        let pointer = self
+            .map_memory(allocation)
+            .map_err(|result| Error::new(result, "Allocator::map_memory"))?;
+        NonNull::new(pointer)
+            .ok_or_else(|| Error::new(vk::Result::ERROR_MEMORY_MAP_FAILED, "Allocator::map_memory"))
+    }
+
+    unsafe fn unmap(&mut self, allocation: &mut Allocation) {
+        self.unmap_memory(allocation);
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.get_heap_budgets(vk::MAX_MEMORY_HEAPS)
+            .iter()
+            .map(|budget| budget.statistics)
+            .sum()
+    }
+}
@@ -0,0 +1,110 @@
+//! Builder of `memory_type_bits` masks.
+//!
+//! `AllocationCreateInfo::memory_type_bits` and `AllocatorPoolCreateInfo` restrict allocations to
+//! a set of memory types given as a bit mask. Computing it by hand means walking the memory types
+//! of the device and shifting bits, which is easy to get wrong. `MemoryTypeMask` starts from all
+//! memory types of the device and narrows them down by property flags and heaps.
+//!
+//! ```ignore
+//! let allocation_info = vk_mem::AllocationCreateInfo {
+//!     memory_type_bits: allocator
+//!         .memory_type_mask()
+//!         .require(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+//!         .exclude_host_visible_device_local()
+//!         .bits(),
+//!     ..Default::default()
+//! };
+//! ```
+
+use ash::vk;
+
+/// Set of memory types of a device, see `Allocator::memory_type_mask`.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryTypeMask<'a> {
+    memory_properties: &'a vk::PhysicalDeviceMemoryProperties,
+    bits: u32,
+}
+
+impl<'a> MemoryTypeMask<'a> {
+    /// All memory types in `memory_properties`.
+    pub fn new(memory_properties: &'a vk::PhysicalDeviceMemoryProperties) -> Self {
+        let count = memory_properties
+            .memory_type_count
+            .min(vk::MAX_MEMORY_TYPES as u32);
+        MemoryTypeMask {
+            memory_properties,
+            bits: if count >= 32 {
+                u32::MAX
+            } else {
+                (1 << count) - 1
+            },
+        }
+    }
+
+    /// Keeps the memory types for which `keep` returns `true`.
+    pub fn retain(mut self, mut keep: impl FnMut(u32, &vk::MemoryType) -> bool) -> Self {
+        for index in self.indices().collect::<Vec<_>>() {
+            if !keep(index, &self.memory_properties.memory_types[index as usize]) {
+                self.bits &= !(1 << index);
+            }
+        }
+        self
+    }
+
+    /// Keeps the memory types that have all of `flags`.
+    pub fn require(self, flags: vk::MemoryPropertyFlags) -> Self {
+        self.retain(|_, memory_type| memory_type.property_flags.contains(flags))
+    }
+
+    /// Removes the memory types that have any of `flags`.
+    pub fn forbid(self, flags: vk::MemoryPropertyFlags) -> Self {
+        self.retain(|_, memory_type| !memory_type.property_flags.intersects(flags))
+    }
+
+    /// Removes the memory types that are both device local and host visible, like the 256 MiB
+    /// BAR window of discrete GPUs or resizable BAR, so that large resources don't use them up.
+    pub fn exclude_host_visible_device_local(self) -> Self {
+        self.retain(|_, memory_type| {
+            !memory_type.property_flags.contains(
+                vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )
+        })
+    }
+
+    /// Keeps the memory types of heap `heap_index`.
+    pub fn only_heap(self, heap_index: u32) -> Self {
+        self.retain(|_, memory_type| memory_type.heap_index == heap_index)
+    }
+
+    /// Keeps the memory types that are also in `bits`, e.g.
+    /// `ash::vk::MemoryRequirements::memory_type_bits` of a resource.
+    pub fn intersect(mut self, bits: u32) -> Self {
+        self.bits &= bits;
+        self
+    }
+
+    /// The mask, for `AllocationCreateInfo::memory_type_bits`.
+    ///
+    /// Note that VMA treats 0 as "no restriction", so check `MemoryTypeMask::is_empty` before
+    /// passing a mask that may have lost all its memory types.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Whether no memory type is left.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Indices of the memory types in the mask, in increasing order.
+    pub fn indices(&self) -> impl Iterator<Item = u32> {
+        let bits = self.bits;
+        (0..32).filter(move |index| bits & (1 << index) != 0)
+    }
+}
+
+impl From<MemoryTypeMask<'_>> for u32 {
+    fn from(mask: MemoryTypeMask<'_>) -> Self {
+        mask.bits
+    }
+}
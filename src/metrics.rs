@@ -0,0 +1,104 @@
+//! Allocator health metrics, enabled with the `metrics` feature.
+//!
+//! Values are recorded through the [`metrics`](https://docs.rs/metrics) facade, so they end up in
+//! whichever recorder the application installed (for example a Prometheus exporter). Gauges are
+//! refreshed by `Allocator::publish_metrics`, which only reads the heap budgets and is cheap enough to
+//! call once per frame or on every scrape. Counters are updated as the events happen.
+//!
+//! Per-heap gauges carry a `heap` label with the index of the memory heap.
+
+use crate::{Budget, DefragmentationStats};
+use ::metrics::{counter, describe_counter, describe_gauge, gauge, Unit};
+
+/// Bytes occupied by allocations in the heap.
+pub const HEAP_ALLOCATION_BYTES: &str = "vk_mem_heap_allocation_bytes";
+
+/// Bytes of `ash::vk::DeviceMemory` blocks allocated from the heap.
+pub const HEAP_BLOCK_BYTES: &str = "vk_mem_heap_block_bytes";
+
+/// Number of allocations in the heap.
+pub const HEAP_ALLOCATIONS: &str = "vk_mem_heap_allocations";
+
+/// Number of `ash::vk::DeviceMemory` blocks allocated from the heap.
+pub const HEAP_BLOCKS: &str = "vk_mem_heap_blocks";
+
+/// Memory usage of the heap, as reported by `VK_EXT_memory_budget` or estimated by VMA.
+pub const HEAP_USAGE_BYTES: &str = "vk_mem_heap_usage_bytes";
+
+/// Budget of the heap, as reported by `VK_EXT_memory_budget` or estimated by VMA.
+pub const HEAP_BUDGET_BYTES: &str = "vk_mem_heap_budget_bytes";
+
+/// Bytes copied by finished defragmentations.
+pub const DEFRAGMENTATION_BYTES_MOVED: &str = "vk_mem_defragmentation_bytes_moved_total";
+
+/// Allocations moved by finished defragmentations.
+pub const DEFRAGMENTATION_ALLOCATIONS_MOVED: &str =
+    "vk_mem_defragmentation_allocations_moved_total";
+
+/// Bytes released to the system by finished defragmentations.
+pub const DEFRAGMENTATION_BYTES_FREED: &str = "vk_mem_defragmentation_bytes_freed_total";
+
+const HEAP_LABELS: [&str; ash::vk::MAX_MEMORY_HEAPS] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15",
+];
+
+/// Registers units and descriptions of all metrics with the installed recorder.
+///
+/// Optional; recorders that export descriptions (like Prometheus' `# HELP` lines) use them.
+pub fn describe() {
+    describe_gauge!(
+        HEAP_ALLOCATION_BYTES,
+        Unit::Bytes,
+        "Bytes occupied by allocations in the heap."
+    );
+    describe_gauge!(
+        HEAP_BLOCK_BYTES,
+        Unit::Bytes,
+        "Bytes of device memory blocks allocated from the heap."
+    );
+    describe_gauge!(
+        HEAP_ALLOCATIONS,
+        Unit::Count,
+        "Number of allocations in the heap."
+    );
+    describe_gauge!(
+        HEAP_BLOCKS,
+        Unit::Count,
+        "Number of device memory blocks allocated from the heap."
+    );
+    describe_gauge!(HEAP_USAGE_BYTES, Unit::Bytes, "Memory usage of the heap.");
+    describe_gauge!(HEAP_BUDGET_BYTES, Unit::Bytes, "Memory budget of the heap.");
+    describe_counter!(
+        DEFRAGMENTATION_BYTES_MOVED,
+        Unit::Bytes,
+        "Bytes copied by defragmentation."
+    );
+    describe_counter!(
+        DEFRAGMENTATION_ALLOCATIONS_MOVED,
+        Unit::Count,
+        "Allocations moved by defragmentation."
+    );
+    describe_counter!(
+        DEFRAGMENTATION_BYTES_FREED,
+        Unit::Bytes,
+        "Bytes released to the system by defragmentation."
+    );
+}
+
+pub(crate) fn publish_budgets(budgets: &[Budget]) {
+    for (budget, &heap) in budgets.iter().zip(HEAP_LABELS.iter()) {
+        let statistics = &budget.statistics;
+        gauge!(HEAP_ALLOCATION_BYTES, "heap" => heap).set(statistics.allocation_bytes as f64);
+        gauge!(HEAP_BLOCK_BYTES, "heap" => heap).set(statistics.block_bytes as f64);
+        gauge!(HEAP_ALLOCATIONS, "heap" => heap).set(statistics.allocation_count as f64);
+        gauge!(HEAP_BLOCKS, "heap" => heap).set(statistics.block_count as f64);
+        gauge!(HEAP_USAGE_BYTES, "heap" => heap).set(budget.usage as f64);
+        gauge!(HEAP_BUDGET_BYTES, "heap" => heap).set(budget.budget as f64);
+    }
+}
+
+pub(crate) fn record_defragmentation(stats: &DefragmentationStats) {
+    counter!(DEFRAGMENTATION_BYTES_MOVED).increment(stats.bytes_moved);
+    counter!(DEFRAGMENTATION_ALLOCATIONS_MOVED).increment(stats.allocations_moved as u64);
+    counter!(DEFRAGMENTATION_BYTES_FREED).increment(stats.bytes_freed);
+}
@@ -0,0 +1,82 @@
+//! Diagnostics captured when an allocation runs out of device memory.
+//!
+//! By the time an `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY` reaches the application, the state
+//! that explains it (which heaps were full, what filled them) is often gone. With
+//! `Allocator::set_oom_dump`, the allocating functions capture an `OomDump` — the detailed
+//! `Allocator::build_stats_string` and the heap budgets — right before they return the error, and
+//! hand it to a callback or write it to a file.
+
+use crate::{format_bytes, Budget, Error};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// State of an allocator when an allocation failed with `ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY`.
+#[derive(Clone)]
+pub struct OomDump {
+    /// The error about to be returned.
+    pub error: Error,
+
+    /// Budgets of all heaps.
+    pub budgets: Vec<Budget>,
+
+    /// Output of `Allocator::build_stats_string` with the detailed map, or empty if it could not
+    /// be built.
+    pub stats_json: String,
+}
+
+impl fmt::Display for OomDump {
+    /// Writes the error, one line per heap and the statistics JSON.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.error)?;
+        for (heap_index, budget) in self.budgets.iter().enumerate() {
+            writeln!(
+                f,
+                "heap {}: {} used of {} budget, {} in {} blocks, {} allocations",
+                heap_index,
+                format_bytes(budget.usage),
+                format_bytes(budget.budget),
+                format_bytes(budget.statistics.block_bytes),
+                budget.statistics.block_count,
+                budget.statistics.allocation_count
+            )?;
+        }
+        writeln!(f, "{}", self.stats_json)
+    }
+}
+
+/// Where `OomDump`s go, see `Allocator::set_oom_dump`.
+#[derive(Clone)]
+pub enum OomDumpTarget {
+    /// Called with every dump, on the thread of the failing allocation.
+    Callback(Arc<dyn Fn(&OomDump) + Send + Sync>),
+
+    /// File every dump is written to, replacing the previous one.
+    File(PathBuf),
+}
+
+impl fmt::Debug for OomDumpTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OomDumpTarget::Callback(_) => f.write_str("Callback(..)"),
+            OomDumpTarget::File(path) => f.debug_tuple("File").field(path).finish(),
+        }
+    }
+}
+
+impl OomDumpTarget {
+    pub(crate) fn deliver(&self, dump: &OomDump) {
+        match self {
+            OomDumpTarget::Callback(callback) => callback(dump),
+            OomDumpTarget::File(path) => {
+                if let Err(error) = std::fs::write(path, dump.to_string()) {
+                    log::error!(
+                        "Failed to write out-of-memory dump to {}: {}",
+                        path.display(),
+                        error
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Custom pools that grow under a policy instead of with one fixed block size.
+//!
+//! A VMA custom pool allocates all its blocks with the same size. `GrowingPool` layers a cascade of
+//! pools on top: each pool holds at most `GrowthConfig::blocks_per_pool` blocks, and when all of
+//! them are full a new pool is created whose block size follows the `GrowthPolicy`. With
+//! `GrowthPolicy::Doubling`, a pool that starts small for a light workload still reaches large
+//! blocks quickly under a heavy one, without committing to large blocks up front.
+//!
+//! `GrowingPool::slack` reports how much of the memory of each pool isn't used by allocations, to
+//! tune the policy.
+
+use crate::{
+    Allocation, AllocationCreateInfo, AllocationInfo, Allocator, AllocatorPool,
+    AllocatorPoolCreateInfo, Error, Result,
+};
+use ash::vk;
+
+/// How the block size of the pools of a `GrowingPool` evolves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthPolicy {
+    /// Every pool uses `GrowthConfig::initial_block_size`.
+    Fixed,
+
+    /// Every pool uses twice the block size of the previous one, up to `max_block_size`.
+    Doubling { max_block_size: vk::DeviceSize },
+}
+
+/// Tuning parameters of a `GrowingPool`.
+#[derive(Debug, Clone, Copy)]
+pub struct GrowthConfig {
+    /// Block size of the first pool.
+    pub initial_block_size: vk::DeviceSize,
+
+    /// Maximum number of blocks of each pool before the next one is created.
+    pub blocks_per_pool: usize,
+
+    /// How the block size grows from pool to pool.
+    pub policy: GrowthPolicy,
+}
+
+impl Default for GrowthConfig {
+    fn default() -> Self {
+        GrowthConfig {
+            initial_block_size: 16 * 1024 * 1024,
+            blocks_per_pool: 2,
+            policy: GrowthPolicy::Doubling {
+                max_block_size: 256 * 1024 * 1024,
+            },
+        }
+    }
+}
+
+/// Memory use of one pool of a `GrowingPool`, as returned by `GrowingPool::slack`.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSlack {
+    pub pool: AllocatorPool,
+    pub block_size: vk::DeviceSize,
+    pub block_count: u32,
+
+    /// Bytes allocated from Vulkan for the blocks of the pool.
+    pub block_bytes: vk::DeviceSize,
+
+    /// Bytes used by allocations.
+    pub allocation_bytes: vk::DeviceSize,
+}
+
+impl PoolSlack {
+    /// Bytes of the blocks not used by any allocation.
+    pub fn slack(&self) -> vk::DeviceSize {
+        self.block_bytes - self.allocation_bytes
+    }
+}
+
+struct GrowingPoolEntry {
+    pool: AllocatorPool,
+    block_size: vk::DeviceSize,
+}
+
+/// Cascade of custom pools of one memory type that grows under a `GrowthPolicy`.
+///
+/// Allocations are made from the oldest pool that has room. Pools are destroyed when the
+/// `GrowingPool` is dropped, so all allocations must have been freed by then.
+pub struct GrowingPool<'a> {
+    allocator: &'a Allocator,
+    pool_info: AllocatorPoolCreateInfo,
+    config: GrowthConfig,
+    pools: Vec<GrowingPoolEntry>,
+}
+
+impl<'a> GrowingPool<'a> {
+    /// Creates an empty cascade. Its pools are created from `pool_info`, with `block_size` and
+    /// `max_block_count` replaced as configured by `config`. No pool is created until the first
+    /// allocation.
+    pub fn new(
+        allocator: &'a Allocator,
+        pool_info: AllocatorPoolCreateInfo,
+        config: GrowthConfig,
+    ) -> Self {
+        GrowingPool {
+            allocator,
+            pool_info,
+            config,
+            pools: Vec::new(),
+        }
+    }
+
+    /// The pools created so far, oldest first.
+    pub fn pools(&self) -> impl Iterator<Item = AllocatorPool> + '_ {
+        self.pools.iter().map(|entry| entry.pool)
+    }
+
+    /// Creates a buffer in the cascade, like `Allocator::create_buffer` with
+    /// `AllocationCreateInfo::pool` set. The `pool` of `allocation_info` is ignored.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`.
+    pub unsafe fn create_buffer(
+        &mut self,
+        buffer_info: &vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Buffer, Allocation, AllocationInfo)> {
+        self.allocate(
+            "GrowingPool::create_buffer",
+            buffer_info.size,
+            allocation_info,
+            |allocator, allocation_info| allocator.create_buffer(buffer_info, allocation_info),
+        )
+    }
+
+    /// Creates an image in the cascade, like `Allocator::create_image` with
+    /// `AllocationCreateInfo::pool` set. The `pool` of `allocation_info` is ignored.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_image`.
+    pub unsafe fn create_image(
+        &mut self,
+        image_info: &vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Image, Allocation, AllocationInfo)> {
+        self.allocate(
+            "GrowingPool::create_image",
+            0,
+            allocation_info,
+            |allocator, allocation_info| allocator.create_image(image_info, allocation_info),
+        )
+    }
+
+    /// Memory use of every pool, oldest first.
+    pub fn slack(&self) -> Vec<PoolSlack> {
+        self.pools
+            .iter()
+            .map(|entry| {
+                let statistics = self.allocator.get_pool_statistics(&entry.pool);
+                PoolSlack {
+                    pool: entry.pool,
+                    block_size: entry.block_size,
+                    block_count: statistics.block_count,
+                    block_bytes: statistics.block_bytes,
+                    allocation_bytes: statistics.allocation_bytes,
+                }
+            })
+            .collect()
+    }
+
+    /// Total slack of all pools.
+    pub fn total_slack(&self) -> vk::DeviceSize {
+        self.slack().iter().map(PoolSlack::slack).sum()
+    }
+
+    /// Tries the pools oldest first, then creates a new pool big enough for `size` bytes and tries
+    /// it. A size of 0 means unknown, e.g. for images.
+    unsafe fn allocate<T>(
+        &mut self,
+        operation: &'static str,
+        size: vk::DeviceSize,
+        allocation_info: &AllocationCreateInfo,
+        create: impl Fn(&Allocator, &AllocationCreateInfo) -> Result<T>,
+    ) -> Result<T> {
+        let mut allocation_info = allocation_info.clone();
+        for entry in &self.pools {
+            allocation_info.pool = Some(entry.pool);
+            match create(self.allocator, &allocation_info) {
+                Err(error) if error.result() == vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {}
+                created => return created,
+            }
+        }
+
+        let block_size = self.next_block_size().max(size);
+        let pool = self
+            .allocator
+            .create_pool(&AllocatorPoolCreateInfo {
+                block_size,
+                max_block_count: self.config.blocks_per_pool.max(1),
+                ..self.pool_info.clone()
+            })
+            .map_err(|error| Error::new(error.result(), operation).with_size(block_size))?;
+        log::debug!(
+            "GrowingPool created pool {} with {} blocks",
+            self.pools.len(),
+            crate::format_bytes(block_size)
+        );
+        self.pools.push(GrowingPoolEntry { pool, block_size });
+        allocation_info.pool = Some(pool);
+        create(self.allocator, &allocation_info)
+    }
+
+    fn next_block_size(&self) -> vk::DeviceSize {
+        match (self.config.policy, self.pools.last()) {
+            (GrowthPolicy::Doubling { max_block_size }, Some(last)) => {
+                (last.block_size * 2).min(max_block_size.max(self.config.initial_block_size))
+            }
+            _ => self.config.initial_block_size,
+        }
+    }
+}
+
+impl<'a> Drop for GrowingPool<'a> {
+    fn drop(&mut self) {
+        for entry in self.pools.drain(..) {
+            unsafe { self.allocator.destroy_pool(entry.pool) };
+        }
+    }
+}
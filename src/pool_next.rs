@@ -0,0 +1,112 @@
+//! Typed `pNext` chains for the memory allocations of custom pools.
+//!
+//! VMA keeps the `pNext` pointer of `VmaPoolCreateInfo` and chains it into every
+//! `VkMemoryAllocateInfo` of the pool, so the structures behind it must outlive the pool. With
+//! `AllocatorPoolCreateInfo::memory_allocate_next` set to a `PoolAllocateNext`, `Allocator::create_pool`
+//! builds the structure itself and keeps it until `Allocator::destroy_pool`.
+
+use crate::ash_compat;
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::AllocatorPool;
+use ash::vk;
+use std::collections::HashMap;
+use std::os::raw::c_void;
+use std::sync::{Mutex, MutexGuard};
+
+/// Structure to chain into every memory allocation of a custom pool.
+#[derive(Debug, Clone, Copy)]
+pub enum PoolAllocateNext {
+    /// `VkExportMemoryAllocateInfo` with these handle types, to export the memory of the pool.
+    ExportMemory(vk::ExternalMemoryHandleTypeFlags),
+
+    /// `VkImportMemoryWin32HandleInfoKHR`, to import memory from a Win32 handle or a named object.
+    ImportWin32 {
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        handle: vk::HANDLE,
+        name: vk::LPCWSTR,
+    },
+
+    /// Any other chain. The structures it points to are not copied: they must remain alive and
+    /// unchanged until the pool is destroyed.
+    Raw(*mut c_void),
+}
+
+/// The structure a `PoolAllocateNext` points VMA to.
+#[derive(Debug)]
+enum Storage {
+    ExportMemory(Box<ash_compat::ExportMemoryAllocateInfo<'static>>),
+    ImportWin32(Box<ash_compat::ImportMemoryWin32HandleInfoKHR<'static>>),
+    Raw(*mut c_void),
+}
+
+impl Storage {
+    fn new(next: &PoolAllocateNext) -> Self {
+        match *next {
+            PoolAllocateNext::ExportMemory(handle_types) => Storage::ExportMemory(Box::new(
+                vk::ExportMemoryAllocateInfo::builder()
+                    .handle_types(handle_types)
+                    .build(),
+            )),
+            PoolAllocateNext::ImportWin32 {
+                handle_type,
+                handle,
+                name,
+            } => Storage::ImportWin32(Box::new(
+                vk::ImportMemoryWin32HandleInfoKHR::builder()
+                    .handle_type(handle_type)
+                    .handle(handle)
+                    .name(name)
+                    .build(),
+            )),
+            PoolAllocateNext::Raw(next) => Storage::Raw(next),
+        }
+    }
+
+    fn as_ptr(&mut self) -> *mut c_void {
+        match self {
+            Storage::ExportMemory(info) => &mut **info as *mut _ as *mut c_void,
+            Storage::ImportWin32(info) => &mut **info as *mut _ as *mut c_void,
+            Storage::Raw(next) => *next,
+        }
+    }
+}
+
+/// A chain built for a pool that is being created, to hand to `PoolNextStore::keep` once the
+/// pool exists.
+#[derive(Debug)]
+pub(crate) struct PendingNext(Storage);
+
+impl PendingNext {
+    pub(crate) fn new(next: &PoolAllocateNext) -> Self {
+        PendingNext(Storage::new(next))
+    }
+
+    /// Pointer for `VmaPoolCreateInfo::pMemoryAllocateNext`. It stays valid when `self` is moved.
+    pub(crate) fn as_ptr(&mut self) -> *mut c_void {
+        self.0.as_ptr()
+    }
+}
+
+/// Chains of live pools, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct PoolNextStore {
+    chains: Mutex<HashMap<usize, PendingNext>>,
+}
+
+impl PoolNextStore {
+    fn chains(&self) -> MutexGuard<'_, HashMap<usize, PendingNext>> {
+        self.chains
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn keep(&self, pool: &AllocatorPool, next: PendingNext) {
+        self.chains().insert(*pool as usize, next);
+    }
+
+    /// Drops the chain of a pool that has been destroyed.
+    pub(crate) fn release(&self, pool: &AllocatorPool) {
+        drop(self.chains().remove(&(*pool as usize)));
+    }
+}
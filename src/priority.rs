@@ -0,0 +1,160 @@
+//! Priority tiers of allocations and eviction under budget pressure.
+//!
+//! `MemoryPriority` names the values of `VK_EXT_memory_priority` applications usually pick from;
+//! convert it with `MemoryPriority::value` for `AllocationCreateInfo::priority` and
+//! `AllocatorPoolCreateInfo::priority`. The tier of an allocation is recorded with
+//! `Allocator::set_memory_priority`, optionally along with a callback that evicts it.
+//! `Allocator::reclaim` calls those callbacks, lowest tier first, when a heap has less budget left
+//! than the caller needs.
+
+use crate::{Allocation, Allocator};
+use ash::vk;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// Priority tier of an allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum MemoryPriority {
+    /// Evicted first, e.g. streaming caches that can be rebuilt.
+    Low,
+
+    /// The default priority of `VK_EXT_memory_priority`.
+    #[default]
+    Normal,
+
+    /// Evicted only when evicting lower tiers is not enough.
+    High,
+
+    /// Never evicted by `Allocator::reclaim`, e.g. render targets and swapchain-sized resources.
+    Critical,
+}
+
+impl MemoryPriority {
+    /// Value of the tier for `VK_EXT_memory_priority`, between 0 and 1.
+    pub fn value(self) -> f32 {
+        match self {
+            MemoryPriority::Low => 0.25,
+            MemoryPriority::Normal => 0.5,
+            MemoryPriority::High => 0.75,
+            MemoryPriority::Critical => 1.0,
+        }
+    }
+}
+
+impl From<MemoryPriority> for f32 {
+    fn from(priority: MemoryPriority) -> f32 {
+        priority.value()
+    }
+}
+
+/// Callback evicting an allocation for `Allocator::reclaim`. It returns whether it freed the
+/// allocation, e.g. with `Allocator::free_memory` or `Allocator::destroy_buffer`.
+pub type EvictCallback = Box<dyn FnMut(&Allocator, &Allocation) -> bool + Send>;
+
+struct Entry {
+    priority: MemoryPriority,
+    size: vk::DeviceSize,
+    memory_type_index: u32,
+
+    /// Taken out while the callback runs, so the lock isn't held.
+    evict: Option<EvictCallback>,
+}
+
+/// Tiers and eviction callbacks of live allocations, shared between clones of an `Allocator`.
+#[derive(Default)]
+pub(crate) struct PriorityStore {
+    entries: Mutex<HashMap<usize, Entry>>,
+}
+
+impl std::fmt::Debug for PriorityStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PriorityStore")
+            .field("allocations", &self.entries().len())
+            .finish()
+    }
+}
+
+impl PriorityStore {
+    fn entries(&self) -> MutexGuard<'_, HashMap<usize, Entry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn set(
+        &self,
+        allocation: &Allocation,
+        priority: MemoryPriority,
+        size: vk::DeviceSize,
+        memory_type_index: u32,
+        evict: Option<EvictCallback>,
+    ) {
+        self.entries().insert(
+            *allocation as usize,
+            Entry {
+                priority,
+                size,
+                memory_type_index,
+                evict,
+            },
+        );
+    }
+
+    pub(crate) fn priority(&self, allocation: &Allocation) -> Option<MemoryPriority> {
+        self.entries()
+            .get(&(*allocation as usize))
+            .map(|entry| entry.priority)
+    }
+
+    /// Forgets an allocation that is being freed. The callback is returned so it is dropped after
+    /// the lock is released.
+    pub(crate) fn remove(&self, allocation: &Allocation) -> Option<EvictCallback> {
+        self.entries()
+            .remove(&(*allocation as usize))
+            .and_then(|entry| entry.evict)
+    }
+
+    /// Evictable allocations in memory types accepted by `in_scope`, as `(allocation, size)` in
+    /// eviction order: lowest tier first, larger allocations first within a tier.
+    pub(crate) fn candidates(
+        &self,
+        in_scope: impl Fn(u32) -> bool,
+    ) -> Vec<(Allocation, vk::DeviceSize)> {
+        let entries = self.entries();
+        let mut candidates: Vec<(MemoryPriority, vk::DeviceSize, usize)> = entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.priority != MemoryPriority::Critical
+                    && entry.evict.is_some()
+                    && in_scope(entry.memory_type_index)
+            })
+            .map(|(&key, entry)| (entry.priority, entry.size, key))
+            .collect();
+        candidates.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+        candidates
+            .into_iter()
+            .map(|(_, size, key)| (key as Allocation, size))
+            .collect()
+    }
+
+    /// Calls the eviction callback of `allocation` without holding the lock. Returns whether the
+    /// allocation was freed.
+    pub(crate) fn evict(&self, allocator: &Allocator, allocation: &Allocation) -> bool {
+        let key = *allocation as usize;
+        let mut evict = match self
+            .entries()
+            .get_mut(&key)
+            .and_then(|entry| entry.evict.take())
+        {
+            Some(evict) => evict,
+            None => return false,
+        };
+        let freed = evict(allocator, allocation);
+        if !freed {
+            if let Some(entry) = self.entries().get_mut(&key) {
+                entry.evict = Some(evict);
+            }
+        }
+        freed
+    }
+}
@@ -0,0 +1,224 @@
+//! Memory for `VK_KHR_acceleration_structure`.
+//!
+//! Acceleration structures live in buffers with the `ACCELERATION_STRUCTURE_STORAGE_KHR` usage, at
+//! offsets that are multiples of 256 bytes, and are built with scratch memory passed by device
+//! address, aligned to `minAccelerationStructureScratchOffsetAlignment`.
+//! `create_acceleration_structure_buffer` creates the former; `ScratchPool` hands out the latter
+//! from a few large buffers that are reused across the builds of a frame and across frames:
+//!
+//! ```ignore
+//! let mut scratch = ScratchPool::new(&allocator, &device, &as_properties, 32 * 1024 * 1024)?;
+//! // every frame, once the builds of the previous use of the pool have completed:
+//! unsafe { scratch.reset() };
+//! for build in &mut builds {
+//!     build.geometry_info.scratch_data.device_address =
+//!         scratch.allocate_for(&build.sizes, false)?;
+//! }
+//! ```
+//!
+//! All of it requires an allocator created with
+//! `AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT`.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::staging::align_up;
+use crate::{
+    Allocation, AllocationCreateInfo, AllocationInfo, Allocator, AllocatorCreateFlags, Error,
+    MemoryUsage, Result,
+};
+use ash::vk;
+
+/// Required alignment of the offset of an acceleration structure in its buffer.
+pub const ACCELERATION_STRUCTURE_OFFSET_ALIGNMENT: vk::DeviceSize = 256;
+
+fn check_device_address(allocator: &Allocator, operation: &'static str) -> Result<()> {
+    if allocator
+        .flags
+        .contains(AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT)
+    {
+        Ok(())
+    } else {
+        Err(Error::new(vk::Result::ERROR_FEATURE_NOT_PRESENT, operation))
+    }
+}
+
+/// Creates a device-local buffer of `size` bytes, as reported in
+/// `accelerationStructureSize` of `vkGetAccelerationStructureBuildSizesKHR`, to create
+/// acceleration structures in.
+///
+/// # Safety
+///
+/// Same as `Allocator::create_buffer`.
+pub unsafe fn create_acceleration_structure_buffer(
+    allocator: &Allocator,
+    size: vk::DeviceSize,
+) -> Result<(vk::Buffer, Allocation, AllocationInfo)> {
+    const OPERATION: &str = "raytracing::create_acceleration_structure_buffer";
+    check_device_address(allocator, OPERATION)?;
+    let buffer_info = vk::BufferCreateInfo::builder()
+        .size(size)
+        .usage(
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .build();
+    let allocation_info = AllocationCreateInfo {
+        usage: MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    allocator
+        .create_buffer_with_alignment(
+            &buffer_info,
+            &allocation_info,
+            ACCELERATION_STRUCTURE_OFFSET_ALIGNMENT,
+        )
+        .map_err(|result| Error::new(result, OPERATION).with_size(size))
+}
+
+struct Chunk {
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    address: vk::DeviceAddress,
+    size: vk::DeviceSize,
+    used: vk::DeviceSize,
+}
+
+/// Scratch memory for acceleration structure builds, reused across builds.
+///
+/// Scratch ranges handed out since the last `ScratchPool::reset` never overlap, so the builds
+/// using them can run concurrently. A build larger than the free space of every buffer gets a new
+/// buffer of at least `chunk_size` bytes. All buffers are destroyed when the pool is dropped.
+pub struct ScratchPool<'a> {
+    allocator: &'a Allocator,
+    device: &'a ash::Device,
+    alignment: vk::DeviceSize,
+    chunk_size: vk::DeviceSize,
+    chunks: Vec<Chunk>,
+}
+
+impl<'a> ScratchPool<'a> {
+    /// Creates an empty pool. No memory is allocated until the first call to
+    /// `ScratchPool::allocate`.
+    ///
+    /// Fails with `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if the allocator wasn't created
+    /// with `AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_BUFFER_DEVICE_ADDRESS_BIT`.
+    pub fn new(
+        allocator: &'a Allocator,
+        device: &'a ash::Device,
+        properties: &vk::PhysicalDeviceAccelerationStructurePropertiesKHR,
+        chunk_size: vk::DeviceSize,
+    ) -> Result<Self> {
+        check_device_address(allocator, "ScratchPool::new")?;
+        Ok(ScratchPool {
+            allocator,
+            device,
+            alignment: (properties.min_acceleration_structure_scratch_offset_alignment
+                as vk::DeviceSize)
+                .max(1),
+            chunk_size,
+            chunks: Vec::new(),
+        })
+    }
+
+    /// Returns the device address of `size` bytes of scratch memory, aligned to
+    /// `minAccelerationStructureScratchOffsetAlignment`.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device of the allocator, with the `bufferDeviceAddress` feature
+    /// enabled.
+    pub unsafe fn allocate(&mut self, size: vk::DeviceSize) -> Result<vk::DeviceAddress> {
+        for chunk in &mut self.chunks {
+            let address = align_up(chunk.address + chunk.used, self.alignment);
+            let end = address - chunk.address + size;
+            if end <= chunk.size {
+                chunk.used = end;
+                return Ok(address);
+            }
+        }
+
+        let mut chunk = self.create_chunk(size.max(self.chunk_size))?;
+        let address = align_up(chunk.address, self.alignment);
+        chunk.used = address - chunk.address + size;
+        self.chunks.push(chunk);
+        Ok(address)
+    }
+
+    /// Returns scratch memory for a build with `sizes` from
+    /// `vkGetAccelerationStructureBuildSizesKHR`: `buildScratchSize` bytes, or
+    /// `updateScratchSize` bytes if `update` is set.
+    ///
+    /// # Safety
+    ///
+    /// Same as `ScratchPool::allocate`.
+    pub unsafe fn allocate_for(
+        &mut self,
+        sizes: &vk::AccelerationStructureBuildSizesInfoKHR,
+        update: bool,
+    ) -> Result<vk::DeviceAddress> {
+        self.allocate(if update {
+            sizes.update_scratch_size
+        } else {
+            sizes.build_scratch_size
+        })
+    }
+
+    /// Makes all scratch memory available again.
+    ///
+    /// # Safety
+    ///
+    /// The device must be done with all builds using scratch memory of the pool.
+    pub unsafe fn reset(&mut self) {
+        for chunk in &mut self.chunks {
+            chunk.used = 0;
+        }
+    }
+
+    /// Total size of the scratch buffers.
+    pub fn capacity(&self) -> vk::DeviceSize {
+        self.chunks.iter().map(|chunk| chunk.size).sum()
+    }
+
+    unsafe fn create_chunk(&self, size: vk::DeviceSize) -> Result<Chunk> {
+        const OPERATION: &str = "ScratchPool::allocate";
+        // Room to align the start of the chunk in case its address isn't aligned already.
+        let size = size + self.alignment - 1;
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let allocation_info = AllocationCreateInfo {
+            usage: MemoryUsage::AutoPreferDevice,
+            ..Default::default()
+        };
+        let (buffer, allocation, _) = self
+            .allocator
+            .create_buffer_with_alignment(&buffer_info, &allocation_info, self.alignment)
+            .map_err(|result| Error::new(result, OPERATION).with_size(size))?;
+        let address = self
+            .device
+            .get_buffer_device_address(&vk::BufferDeviceAddressInfo::builder().buffer(buffer));
+        Ok(Chunk {
+            buffer,
+            allocation,
+            address,
+            size,
+            used: 0,
+        })
+    }
+}
+
+impl<'a> Drop for ScratchPool<'a> {
+    fn drop(&mut self) {
+        for chunk in self.chunks.drain(..) {
+            unsafe {
+                self.allocator
+                    .destroy_buffer(chunk.buffer, &chunk.allocation)
+            };
+        }
+    }
+}
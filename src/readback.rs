@@ -0,0 +1,294 @@
+//! Reading buffer contents back to the host without stalling the device.
+//!
+//! `Readback::request` records a copy from a device buffer into a host-visible, host-cached
+//! readback buffer and returns a `PendingReadback`. The caller submits the command buffer with the
+//! fence or timeline semaphore value named in the request; the handle can then be polled with
+//! `PendingReadback::is_ready` and read with `PendingReadback::data` once the copy completed.
+//! Readback buffers come from a dedicated custom pool and are cached for later requests.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{
+    Allocation, AllocationCreateFlags, AllocationCreateInfo, Allocator, AllocatorPool,
+    AllocatorPoolCreateInfo, MemoryUsage, Result,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::cell::RefCell;
+use std::ops::Range;
+
+/// Synchronization primitive signaled when the command buffer recording a readback completes.
+#[derive(Debug, Clone, Copy)]
+pub enum Signal {
+    /// A fence passed to the submission.
+    Fence(vk::Fence),
+
+    /// A timeline semaphore reaching `value`.
+    Timeline {
+        semaphore: vk::Semaphore,
+        value: u64,
+    },
+}
+
+struct ReadbackBuffer {
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    size: vk::DeviceSize,
+    mapped: *const u8,
+}
+
+/// Records copies of device buffers into cached host-visible readback buffers.
+///
+/// All readback buffers, cached or not, are destroyed when the `Readback` is dropped, which the
+/// borrow checker only allows once every `PendingReadback` is gone.
+pub struct Readback<'a> {
+    allocator: &'a Allocator,
+    device: &'a ash::Device,
+    pool: AllocatorPool,
+    cache: RefCell<Vec<ReadbackBuffer>>,
+}
+
+impl<'a> Readback<'a> {
+    /// Creates the pool readback buffers are allocated from, in the memory type `allocator` picks
+    /// for `ash::vk::BufferUsageFlags::TRANSFER_DST` buffers with
+    /// `AllocationCreateFlags::HOST_ACCESS_RANDOM`.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the device `allocator` was created for.
+    pub unsafe fn new(allocator: &'a Allocator, device: &'a ash::Device) -> Result<Self> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(4096)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let pool = allocator.create_pool_for_buffer_info(
+            &buffer_info,
+            &Self::allocation_info(None),
+            &AllocatorPoolCreateInfo::default(),
+        )?;
+        Ok(Readback {
+            allocator,
+            device,
+            pool,
+            cache: RefCell::new(Vec::new()),
+        })
+    }
+
+    fn allocation_info(pool: Option<AllocatorPool>) -> AllocationCreateInfo {
+        AllocationCreateInfo {
+            flags: AllocationCreateFlags::MAPPED | AllocationCreateFlags::HOST_ACCESS_RANDOM,
+            usage: MemoryUsage::Auto,
+            pool,
+            ..Default::default()
+        }
+    }
+
+    /// Records a copy of `range` of `src_buffer` into a readback buffer, followed by a barrier
+    /// making the data available to the host.
+    ///
+    /// `src_buffer` must have been created with `ash::vk::BufferUsageFlags::TRANSFER_SRC`. The
+    /// smallest cached readback buffer that fits is reused, otherwise a new one is created.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be in the recording state, and it must be submitted, signaling
+    /// `signal`, before the returned handle is dropped. `range` must lie within `src_buffer`.
+    pub unsafe fn request(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        src_buffer: vk::Buffer,
+        range: Range<vk::DeviceSize>,
+        signal: Signal,
+    ) -> Result<PendingReadback<'_, 'a>> {
+        let size = range.end - range.start;
+        let buffer = match self.take_cached(size) {
+            Some(buffer) => buffer,
+            None => self.create_buffer(size)?,
+        };
+
+        let copy = vk::BufferCopy {
+            src_offset: range.start,
+            dst_offset: 0,
+            size,
+        };
+        self.device
+            .cmd_copy_buffer(command_buffer, src_buffer, buffer.buffer, &[copy]);
+        let to_host = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer.buffer)
+            .offset(0)
+            .size(size)
+            .build();
+        self.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[to_host],
+            &[],
+        );
+
+        Ok(PendingReadback {
+            readback: self,
+            buffer: Some(buffer),
+            size,
+            signal,
+        })
+    }
+
+    /// Number of readback buffers waiting to be reused.
+    pub fn cached_buffers(&self) -> usize {
+        self.cache.borrow().len()
+    }
+
+    /// Destroys the cached readback buffers.
+    pub fn clear_cache(&self) {
+        for buffer in self.cache.borrow_mut().drain(..) {
+            unsafe {
+                self.allocator
+                    .destroy_buffer(buffer.buffer, &buffer.allocation)
+            };
+        }
+    }
+
+    fn take_cached(&self, size: vk::DeviceSize) -> Option<ReadbackBuffer> {
+        let mut cache = self.cache.borrow_mut();
+        let index = cache
+            .iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.size >= size)
+            .min_by_key(|(_, buffer)| buffer.size)
+            .map(|(index, _)| index)?;
+        Some(cache.swap_remove(index))
+    }
+
+    unsafe fn create_buffer(&self, size: vk::DeviceSize) -> Result<ReadbackBuffer> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let (buffer, allocation, info) = self
+            .allocator
+            .create_buffer(&buffer_info, &Self::allocation_info(Some(self.pool)))?;
+        Ok(ReadbackBuffer {
+            buffer,
+            allocation,
+            size,
+            mapped: info.get_mapped_data(),
+        })
+    }
+}
+
+impl<'a> Drop for Readback<'a> {
+    fn drop(&mut self) {
+        self.clear_cache();
+        unsafe { self.allocator.destroy_pool(self.pool) };
+    }
+}
+
+/// A copy recorded by `Readback::request` that may not have completed yet.
+///
+/// Dropping the handle returns its readback buffer to the cache of the `Readback`, waiting for the
+/// copy to complete first if it hasn't yet.
+pub struct PendingReadback<'r, 'a> {
+    readback: &'r Readback<'a>,
+    buffer: Option<ReadbackBuffer>,
+    size: vk::DeviceSize,
+    signal: Signal,
+}
+
+impl<'r, 'a> PendingReadback<'r, 'a> {
+    /// Number of bytes read back.
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// Whether the signal of the request was signaled, i.e. the data can be read.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer of the request must have been submitted.
+    pub unsafe fn is_ready(&self) -> VkResult<bool> {
+        match self.signal {
+            Signal::Fence(fence) => self.readback.device.get_fence_status(fence),
+            Signal::Timeline { semaphore, value } => Ok(self
+                .readback
+                .device
+                .get_semaphore_counter_value(semaphore)?
+                >= value),
+        }
+    }
+
+    /// Blocks until the copy completed or `timeout` nanoseconds passed, in which case
+    /// `ash::vk::Result::TIMEOUT` is returned.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer of the request must have been submitted.
+    pub unsafe fn wait(&self, timeout: u64) -> VkResult<()> {
+        match self.signal {
+            Signal::Fence(fence) => self
+                .readback
+                .device
+                .wait_for_fences(&[fence], true, timeout),
+            Signal::Timeline { semaphore, value } => {
+                let semaphores = [semaphore];
+                let values = [value];
+                let wait_info = vk::SemaphoreWaitInfo::builder()
+                    .semaphores(&semaphores)
+                    .values(&values);
+                self.readback.device.wait_semaphores(&wait_info, timeout)
+            }
+        }
+    }
+
+    /// The data read back, mapped in place. Fails with `ash::vk::Result::NOT_READY` if the copy
+    /// hasn't completed yet.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer of the request must have been submitted.
+    pub unsafe fn data(&self) -> VkResult<&[u8]> {
+        if !self.is_ready()? {
+            return Err(vk::Result::NOT_READY);
+        }
+        let buffer = self.buffer.as_ref().unwrap();
+        self.readback
+            .allocator
+            .invalidate_allocation(&buffer.allocation, 0, self.size as usize)?;
+        Ok(std::slice::from_raw_parts(
+            buffer.mapped,
+            self.size as usize,
+        ))
+    }
+
+    /// Copies the data read back into a `Vec`, releasing the readback buffer. Fails with
+    /// `ash::vk::Result::NOT_READY` if the copy hasn't completed yet.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer of the request must have been submitted.
+    pub unsafe fn into_vec(self) -> VkResult<Vec<u8>> {
+        self.data().map(<[u8]>::to_vec)
+    }
+}
+
+impl<'r, 'a> Drop for PendingReadback<'r, 'a> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            unsafe {
+                // The device may still be writing to the buffer.
+                if !self.is_ready().unwrap_or(false) {
+                    let _ = self.wait(u64::MAX);
+                }
+            }
+            self.readback.cache.borrow_mut().push(buffer);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Stable, copyable handles to allocations.
+//!
+//! `Allocation` is a raw pointer: it can't be sent between threads, and nothing tells a stale copy
+//! from a live allocation that happens to reuse the same address. `AllocationRegistry` hands out
+//! `AllocId`s instead, generational indices that are `Copy + Send + Sync`, cheap to store in ECS
+//! components, and that stop resolving once their allocation is removed, even if the slot is reused.
+
+use crate::{Allocation, Allocator};
+use std::fmt;
+
+/// Handle to an allocation in an `AllocationRegistry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AllocId {
+    index: u32,
+    generation: u32,
+}
+
+impl AllocId {
+    /// Index of the slot, unique among the live allocations of the registry.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Number of times the slot was reused before this allocation.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Display for AllocId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    generation: u32,
+
+    /// The allocation, as an address so the registry is `Send + Sync`.
+    allocation: Option<usize>,
+}
+
+/// Maps `AllocId`s to allocations.
+///
+/// The registry only keeps track of handles; allocations are created and freed with the
+/// `Allocator` as usual, or freed through `AllocationRegistry::free_memory`.
+#[derive(Debug, Default)]
+pub struct AllocationRegistry {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl AllocationRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `allocation` and returns its handle.
+    pub fn insert(&mut self, allocation: Allocation) -> AllocId {
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.allocation = Some(allocation as usize);
+            return AllocId {
+                index,
+                generation: slot.generation,
+            };
+        }
+        self.slots.push(Slot {
+            generation: 0,
+            allocation: Some(allocation as usize),
+        });
+        AllocId {
+            index: self.slots.len() as u32 - 1,
+            generation: 0,
+        }
+    }
+
+    /// The allocation of `id`, or `None` if it was removed.
+    pub fn get(&self, id: AllocId) -> Option<Allocation> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.allocation)
+            .map(|allocation| allocation as Allocation)
+    }
+
+    /// Whether `id` still refers to a registered allocation.
+    pub fn contains(&self, id: AllocId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Unregisters `id` and returns its allocation, or `None` if it was already removed. The
+    /// allocation itself is left alive.
+    pub fn remove(&mut self, id: AllocId) -> Option<Allocation> {
+        let allocation = self.get(id)?;
+        let slot = &mut self.slots[id.index as usize];
+        slot.allocation = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(allocation)
+    }
+
+    /// Unregisters `id` and frees its allocation with `Allocator::free_memory`. Returns whether
+    /// `id` was registered.
+    ///
+    /// # Safety
+    ///
+    /// The allocation must belong to `allocator`, and nothing may use it anymore. Allocations of
+    /// buffers and images must be freed with `Allocator::destroy_buffer` or
+    /// `Allocator::destroy_image` after `AllocationRegistry::remove` instead.
+    pub unsafe fn free_memory(&mut self, allocator: &Allocator, id: AllocId) -> bool {
+        match self.remove(id) {
+            Some(allocation) => {
+                allocator.free_memory(&allocation);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of registered allocations.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no allocation is registered.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The registered allocations and their handles, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (AllocId, Allocation)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.allocation.map(|allocation| {
+                (
+                    AllocId {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    allocation as Allocation,
+                )
+            })
+        })
+    }
+}
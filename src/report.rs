@@ -0,0 +1,141 @@
+//! Per-allocation reports for memory audits.
+//!
+//! `records` lists every live allocation of an allocator with the parameters an audit cares about,
+//! and `write_csv` writes them as CSV with a header row, for spreadsheets or for budget regression
+//! checks in CI:
+//!
+//! ```ignore
+//! let mut file = std::fs::File::create("memory.csv")?;
+//! vk_mem::report::write_csv(&allocator, &mut file)?;
+//! ```
+
+use crate::category::Category;
+use crate::{Allocation, Allocator, AllocatorPool};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::io;
+
+/// One live allocation in a report.
+#[derive(Debug, Clone)]
+pub struct AllocationRecord {
+    /// The allocation.
+    pub allocation: Allocation,
+
+    /// Name set with `Allocator::set_allocation_name`.
+    pub name: Option<String>,
+
+    /// Category set with `AllocationCreateInfo::category`.
+    pub category: Option<Category>,
+
+    /// Custom pool the allocation was made from, or `None` for the default pools.
+    pub pool: Option<AllocatorPool>,
+
+    /// Name of `pool`, empty if none was set or for the default pools.
+    pub pool_name: String,
+
+    pub memory_type_index: u32,
+    pub device_memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+
+    /// Whether the allocation has its `ash::vk::DeviceMemory` to itself: it was created with
+    /// `AllocationCreateFlags::DEDICATED_MEMORY`, or no other live allocation shares its memory.
+    pub dedicated: bool,
+
+    /// Whether the allocation is currently mapped.
+    pub mapped: bool,
+}
+
+/// Header row written by `write_csv`.
+pub const CSV_HEADER: &str = "name,category,pool,memory_type,size,offset,dedicated,mapped";
+
+/// Every live allocation of `allocator`, ordered by memory type, memory object and offset.
+pub fn records(allocator: &Allocator) -> VkResult<Vec<AllocationRecord>> {
+    let mut records = Vec::new();
+    for live in allocator.categories.live() {
+        let allocation = live.allocation();
+        let info = unsafe { allocator.get_allocation_info(&allocation)? };
+        let name = unsafe { info.get_name() }.map(str::to_owned);
+        let pool = live.pool();
+        records.push(AllocationRecord {
+            allocation,
+            name,
+            category: live.category,
+            pool,
+            pool_name: pool
+                .map(|pool| allocator.get_pool_name(&pool).to_owned())
+                .unwrap_or_default(),
+            memory_type_index: info.get_memory_type(),
+            device_memory: info.get_device_memory(),
+            offset: info.get_offset() as vk::DeviceSize,
+            size: info.get_size() as vk::DeviceSize,
+            dedicated: live.dedicated,
+            mapped: !info.get_mapped_data().is_null(),
+        });
+    }
+    records.sort_by_key(|record| {
+        (
+            record.memory_type_index,
+            vk::Handle::as_raw(record.device_memory),
+            record.offset,
+        )
+    });
+
+    let mut start = 0;
+    while start < records.len() {
+        let memory = records[start].device_memory;
+        let end = start
+            + records[start..]
+                .iter()
+                .take_while(|record| record.device_memory == memory)
+                .count();
+        if end - start == 1 {
+            records[start].dedicated = true;
+        }
+        start = end;
+    }
+    Ok(records)
+}
+
+/// Writes one row per live allocation of `allocator`, after `CSV_HEADER`.
+///
+/// Sizes and offsets are in bytes, booleans are `true` or `false`, and missing names, categories
+/// and pool names are empty. Allocations from unnamed custom pools have their pool written as its
+/// handle.
+pub fn write_csv<W: io::Write>(allocator: &Allocator, mut writer: W) -> io::Result<()> {
+    let records = records(allocator).map_err(|result| io::Error::other(result.to_string()))?;
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for record in &records {
+        let pool = match record.pool {
+            Some(pool) if record.pool_name.is_empty() => format!("{:p}", pool),
+            _ => record.pool_name.clone(),
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            csv_field(record.name.as_deref().unwrap_or("")),
+            csv_field(
+                &record
+                    .category
+                    .map(|category| category.to_string())
+                    .unwrap_or_default()
+            ),
+            csv_field(&pool),
+            record.memory_type_index,
+            record.size,
+            record.offset,
+            record.dedicated,
+            record.mapped
+        )?;
+    }
+    writer.flush()
+}
+
+/// Quotes `text` if it contains a separator, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
@@ -0,0 +1,313 @@
+//! Automatic routing of allocations into custom pools.
+//!
+//! Custom pools with a fixed block size keep allocations of similar size and kind together, which
+//! limits fragmentation and makes budgets easier to read, but they have to be set up by hand. With
+//! `Allocator::set_smart_routing`, `Allocator::create_buffer` and `Allocator::create_image` do it
+//! themselves: allocations without an explicit pool go to a pool created on first use for their
+//! memory type, `UsageClass` and size bucket.
+//!
+//! Allocations larger than the last bucket, allocations that request dedicated memory and
+//! allocations the routed pool can't serve (e.g. because the driver requires dedicated memory for
+//! the resource) are made from the default pools as before.
+
+use crate::{
+    allocation_create_info_to_ffi, format_bytes, AllocationCreateFlags, AllocationCreateInfo,
+    Allocator, AllocatorPool, AllocatorPoolCreateInfo,
+};
+use ash::vk;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// Kind of resource an allocation is made for. Kinds get separate pools so linear and optimal
+/// resources never share a block, which avoids `bufferImageGranularity` padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UsageClass {
+    Buffer,
+    LinearImage,
+    OptimalImage,
+}
+
+impl fmt::Display for UsageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UsageClass::Buffer => "buffers",
+            UsageClass::LinearImage => "linear images",
+            UsageClass::OptimalImage => "optimal images",
+        })
+    }
+}
+
+/// Tuning parameters of smart routing.
+#[derive(Debug, Clone)]
+pub struct RoutingConfig {
+    /// Upper size limits of the buckets, in increasing order. An allocation goes to the first
+    /// bucket whose limit is at least its size; larger allocations are not routed.
+    pub bucket_limits: Vec<vk::DeviceSize>,
+
+    /// Number of allocations of the bucket limit a block of the pool of a bucket can hold.
+    pub allocations_per_block: vk::DeviceSize,
+
+    /// Upper bound of the block size of the pools.
+    pub max_block_size: vk::DeviceSize,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        RoutingConfig {
+            bucket_limits: vec![256 * 1024, 4 * 1024 * 1024, 32 * 1024 * 1024],
+            allocations_per_block: 32,
+            max_block_size: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl RoutingConfig {
+    fn bucket_limit(&self, size: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        self.bucket_limits
+            .iter()
+            .copied()
+            .find(|&limit| size <= limit)
+    }
+
+    fn block_size(&self, bucket_limit: vk::DeviceSize) -> vk::DeviceSize {
+        bucket_limit
+            .saturating_mul(self.allocations_per_block)
+            .min(self.max_block_size)
+            .max(bucket_limit)
+    }
+}
+
+/// A pool created by smart routing.
+#[derive(Debug, Clone, Copy)]
+pub struct RoutedPool {
+    pub pool: AllocatorPool,
+    pub memory_type_index: u32,
+    pub usage_class: UsageClass,
+
+    /// Size limit of the bucket the pool serves.
+    pub bucket_limit: vk::DeviceSize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RouteKey {
+    memory_type_index: u32,
+    usage_class: UsageClass,
+    bucket_limit: vk::DeviceSize,
+}
+
+/// Everything the memory type of a resource depends on.
+type TypeKey = (UsageClass, [u64; 9]);
+
+#[derive(Debug, Default)]
+struct State {
+    config: Option<RoutingConfig>,
+
+    /// Pools by route, as addresses so the state is `Send`.
+    pools: HashMap<RouteKey, usize>,
+
+    /// Memory types already looked up, since that creates a temporary resource.
+    memory_types: HashMap<TypeKey, u32>,
+}
+
+/// Routing configuration and pools, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct Router {
+    state: Mutex<State>,
+}
+
+impl Router {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn set_config(&self, config: Option<RoutingConfig>) {
+        self.state().config = config;
+    }
+
+    /// Pool to create the buffer in, or `None` to use the default pools.
+    pub(crate) unsafe fn buffer_pool(
+        &self,
+        allocator: &Allocator,
+        buffer_info: &vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Option<AllocatorPool> {
+        let key = type_key(
+            UsageClass::Buffer,
+            [
+                buffer_info.usage.as_raw() as u64,
+                buffer_info.flags.as_raw() as u64,
+                0,
+                0,
+            ],
+            allocation_info,
+        );
+        self.route(allocator, key, buffer_info.size, allocation_info, || {
+            allocator.find_memory_type_index_for_buffer_info(buffer_info, allocation_info)
+        })
+    }
+
+    /// Pool to create the image in, or `None` to use the default pools.
+    ///
+    /// The bucket is chosen from an estimate of the size of the image, assuming 4 bytes per texel.
+    pub(crate) unsafe fn image_pool(
+        &self,
+        allocator: &Allocator,
+        image_info: &vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Option<AllocatorPool> {
+        let usage_class = if image_info.tiling == vk::ImageTiling::LINEAR {
+            UsageClass::LinearImage
+        } else {
+            UsageClass::OptimalImage
+        };
+        let key = type_key(
+            usage_class,
+            [
+                image_info.usage.as_raw() as u64,
+                image_info.flags.as_raw() as u64,
+                image_info.format.as_raw() as u64,
+                image_info.tiling.as_raw() as u64 | (image_info.samples.as_raw() as u64) << 32,
+            ],
+            allocation_info,
+        );
+        let extent = image_info.extent;
+        let base_size = extent.width as vk::DeviceSize
+            * extent.height as vk::DeviceSize
+            * extent.depth as vk::DeviceSize
+            * image_info.array_layers as vk::DeviceSize
+            * image_info.samples.as_raw() as vk::DeviceSize
+            * 4;
+        let size = if image_info.mip_levels > 1 {
+            base_size / 3 * 4
+        } else {
+            base_size
+        };
+        self.route(allocator, key, size, allocation_info, || {
+            allocator.find_memory_type_index_for_image_info(image_info, allocation_info)
+        })
+    }
+
+    unsafe fn route(
+        &self,
+        allocator: &Allocator,
+        type_key: TypeKey,
+        size: vk::DeviceSize,
+        allocation_info: &AllocationCreateInfo,
+        find_memory_type: impl FnOnce() -> crate::Result<u32>,
+    ) -> Option<AllocatorPool> {
+        if allocation_info.pool.is_some()
+            || allocation_info.flags.intersects(
+                AllocationCreateFlags::DEDICATED_MEMORY | AllocationCreateFlags::NEVER_ALLOCATE,
+            )
+        {
+            return None;
+        }
+        let (bucket_limit, block_size, memory_type_index) = {
+            let state = self.state();
+            let config = state.config.as_ref()?;
+            let bucket_limit = config.bucket_limit(size)?;
+            (
+                bucket_limit,
+                config.block_size(bucket_limit),
+                state.memory_types.get(&type_key).copied(),
+            )
+        };
+        // The lock is released while VMA creates the temporary resource and the pool.
+        let memory_type_index = match memory_type_index {
+            Some(memory_type_index) => memory_type_index,
+            None => {
+                let memory_type_index = find_memory_type().ok()?;
+                self.state()
+                    .memory_types
+                    .insert(type_key, memory_type_index);
+                memory_type_index
+            }
+        };
+        let route = RouteKey {
+            memory_type_index,
+            usage_class: type_key.0,
+            bucket_limit,
+        };
+        if let Some(&pool) = self.state().pools.get(&route) {
+            return Some(pool as AllocatorPool);
+        }
+
+        let pool = match allocator.create_pool(&AllocatorPoolCreateInfo {
+            memory_type_index,
+            block_size,
+            ..Default::default()
+        }) {
+            Ok(pool) => pool,
+            Err(error) => {
+                log::warn!("Smart routing could not create a pool: {}", error);
+                return None;
+            }
+        };
+        let mut state = self.state();
+        if let Some(&existing) = state.pools.get(&route) {
+            // Another thread created the pool in the meantime.
+            drop(state);
+            allocator.destroy_pool(pool);
+            return Some(existing as AllocatorPool);
+        }
+        state.pools.insert(route, pool as usize);
+        drop(state);
+        let name = format!(
+            "smart: type {} {} <= {}",
+            memory_type_index,
+            route.usage_class,
+            format_bytes(bucket_limit)
+        );
+        let _ = allocator.set_pool_name(&pool, &name);
+        Some(pool)
+    }
+
+    pub(crate) fn pools(&self) -> Vec<RoutedPool> {
+        let mut pools: Vec<RoutedPool> = self
+            .state()
+            .pools
+            .iter()
+            .map(|(route, &pool)| RoutedPool {
+                pool: pool as AllocatorPool,
+                memory_type_index: route.memory_type_index,
+                usage_class: route.usage_class,
+                bucket_limit: route.bucket_limit,
+            })
+            .collect();
+        pools.sort_by_key(|pool| (pool.memory_type_index, pool.usage_class, pool.bucket_limit));
+        pools
+    }
+
+    /// Destroys all routed pools, when the allocator is destroyed.
+    pub(crate) unsafe fn destroy_all(&self, allocator: &Allocator) {
+        let pools: Vec<usize> = self.state().pools.drain().map(|(_, pool)| pool).collect();
+        for pool in pools {
+            allocator.destroy_pool(pool as AllocatorPool);
+        }
+    }
+}
+
+fn type_key(
+    usage_class: UsageClass,
+    resource: [u64; 4],
+    allocation_info: &AllocationCreateInfo,
+) -> TypeKey {
+    let create_info = allocation_create_info_to_ffi(allocation_info);
+    (
+        usage_class,
+        [
+            resource[0],
+            resource[1],
+            resource[2],
+            resource[3],
+            create_info.flags as u64,
+            create_info.usage as u64,
+            create_info.requiredFlags.as_raw() as u64,
+            create_info.preferredFlags.as_raw() as u64,
+            create_info.memoryTypeBits as u64,
+        ],
+    )
+}
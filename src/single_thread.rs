@@ -0,0 +1,204 @@
+//! An allocator without internal synchronization, for applications that allocate from one thread.
+//!
+//! VMA skips its internal mutexes when the allocator is created with
+//! `AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED`. An `Allocator` is `Sync`, so it can't be
+//! created with that flag; `SingleThreadAllocator` is the allocator for it. It can be moved to
+//! another thread but not shared between threads, and it can't be cloned or borrowed as an
+//! `Allocator`, so the compiler ensures it is only used from one thread at a time.
+
+use crate::{
+    library_get_instance_proc_addr, Allocation, AllocationCreateInfo, AllocationInfo, Allocator,
+    AllocatorCreateFlags, AllocatorCreateInfo, Budget, Result, TotalStatistics,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// An `Allocator` created with `AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED`, which is `Send`
+/// but not `Sync`.
+///
+/// It offers the allocation, mapping and statistics functions of `Allocator`, which behave the same
+/// but skip VMA's internal locking.
+#[derive(Debug)]
+pub struct SingleThreadAllocator {
+    inner: Allocator,
+
+    /// Opts out of `Sync`.
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl SingleThreadAllocator {
+    /// Creates an allocator as `Allocator::new` does, with
+    /// `AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED` added to `AllocatorCreateInfo::flags`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::new`. Objects created from the allocator, like `Allocation`s, must not
+    /// be used concurrently with it from other threads either.
+    pub unsafe fn new(create_info: &AllocatorCreateInfo) -> VkResult<SingleThreadAllocator> {
+        let get_instance_proc_addr = match create_info.get_instance_proc_addr {
+            Some(get_instance_proc_addr) => get_instance_proc_addr,
+            None => library_get_instance_proc_addr()?,
+        };
+        let inner = Allocator::create_with_flags(
+            create_info,
+            get_instance_proc_addr,
+            create_info.flags | AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED,
+        )?;
+        Ok(SingleThreadAllocator {
+            inner,
+            _not_sync: PhantomData,
+        })
+    }
+
+    /// See `Allocator::allocate_memory`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::allocate_memory`.
+    pub unsafe fn allocate_memory(
+        &self,
+        memory_requirements: &vk::MemoryRequirements,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(Allocation, AllocationInfo)> {
+        self.inner
+            .allocate_memory(memory_requirements, allocation_info)
+    }
+
+    /// See `Allocator::free_memory`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::free_memory`.
+    pub unsafe fn free_memory(&self, allocation: &Allocation) {
+        self.inner.free_memory(allocation);
+    }
+
+    /// See `Allocator::create_buffer`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`.
+    pub unsafe fn create_buffer(
+        &self,
+        buffer_info: &vk::BufferCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Buffer, Allocation, AllocationInfo)> {
+        self.inner.create_buffer(buffer_info, allocation_info)
+    }
+
+    /// See `Allocator::destroy_buffer`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::destroy_buffer`.
+    pub unsafe fn destroy_buffer(&self, buffer: vk::Buffer, allocation: &Allocation) {
+        self.inner.destroy_buffer(buffer, allocation);
+    }
+
+    /// See `Allocator::create_image`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_image`.
+    pub unsafe fn create_image(
+        &self,
+        image_info: &vk::ImageCreateInfo,
+        allocation_info: &AllocationCreateInfo,
+    ) -> Result<(vk::Image, Allocation, AllocationInfo)> {
+        self.inner.create_image(image_info, allocation_info)
+    }
+
+    /// See `Allocator::destroy_image`.
+    pub fn destroy_image(&self, image: vk::Image, allocation: &Allocation) {
+        self.inner.destroy_image(image, allocation);
+    }
+
+    /// See `Allocator::get_allocation_info`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::get_allocation_info`.
+    pub unsafe fn get_allocation_info(&self, allocation: &Allocation) -> VkResult<AllocationInfo> {
+        self.inner.get_allocation_info(allocation)
+    }
+
+    /// See `Allocator::map_memory`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::map_memory`.
+    pub unsafe fn map_memory(&self, allocation: &Allocation) -> VkResult<*mut u8> {
+        self.inner.map_memory(allocation)
+    }
+
+    /// See `Allocator::unmap_memory`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::unmap_memory`.
+    pub unsafe fn unmap_memory(&self, allocation: &Allocation) {
+        self.inner.unmap_memory(allocation);
+    }
+
+    /// See `Allocator::flush_allocation`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::flush_allocation`.
+    pub unsafe fn flush_allocation(
+        &self,
+        allocation: &Allocation,
+        offset: usize,
+        size: usize,
+    ) -> VkResult<()> {
+        self.inner.flush_allocation(allocation, offset, size)
+    }
+
+    /// See `Allocator::invalidate_allocation`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::invalidate_allocation`.
+    pub unsafe fn invalidate_allocation(
+        &self,
+        allocation: &Allocation,
+        offset: usize,
+        size: usize,
+    ) -> VkResult<()> {
+        self.inner.invalidate_allocation(allocation, offset, size)
+    }
+
+    /// See `Allocator::set_current_frame_index`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::set_current_frame_index`.
+    pub unsafe fn set_current_frame_index(&self, frame_index: u32) {
+        self.inner.set_current_frame_index(frame_index);
+    }
+
+    /// See `Allocator::get_memory_properties`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::get_memory_properties`.
+    pub unsafe fn get_memory_properties(&self) -> VkResult<vk::PhysicalDeviceMemoryProperties> {
+        self.inner.get_memory_properties()
+    }
+
+    /// See `Allocator::get_heap_budgets`.
+    pub fn get_heap_budgets(&self, budget_count: usize) -> Vec<Budget> {
+        self.inner.get_heap_budgets(budget_count)
+    }
+
+    /// See `Allocator::calculate_statistics`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::calculate_statistics`.
+    pub unsafe fn calculate_statistics(&self) -> VkResult<TotalStatistics> {
+        self.inner.calculate_statistics()
+    }
+}
@@ -0,0 +1,292 @@
+//! Suballocation of tiny buffers from shared buffers.
+//!
+//! Thousands of small uniform or vertex buffers each cost a `VmaAllocation`, a `VkBuffer` and, when
+//! they share blocks with images, `bufferImageGranularity` padding. `SmallBufferPool` creates a few
+//! large buffers instead and hands out ranges of them, placed with a `VirtualBlock` per buffer.
+//! Requests above `SmallBufferConfig::threshold` get a buffer of their own, so callers can route
+//! every buffer of a kind through the pool.
+
+use crate::ash_compat;
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{
+    Allocation, AllocationCreateFlags, AllocationCreateInfo, Allocator, Error, Result,
+    VirtualAllocation, VirtualBlock, VirtualBlockCreateFlags, VirtualBlockCreateInfo,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Tuning parameters of a `SmallBufferPool`.
+#[derive(Debug, Clone, Copy)]
+pub struct SmallBufferConfig {
+    /// Largest size suballocated from a shared buffer. Larger requests get their own buffer.
+    pub threshold: vk::DeviceSize,
+
+    /// Size of the shared buffers.
+    pub block_size: vk::DeviceSize,
+}
+
+impl Default for SmallBufferConfig {
+    fn default() -> Self {
+        SmallBufferConfig {
+            threshold: 64 * 1024,
+            block_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Slot {
+    Shared {
+        block: usize,
+        allocation: VirtualAllocation,
+    },
+    Own {
+        allocation: Allocation,
+    },
+}
+
+/// Range of a buffer returned by `SmallBufferPool::allocate`.
+#[derive(Debug)]
+pub struct SmallBuffer {
+    /// Buffer to bind, shared with other small buffers unless `size` was above the threshold.
+    pub buffer: vk::Buffer,
+
+    /// Offset of the range inside `buffer`.
+    pub offset: vk::DeviceSize,
+
+    /// Size of the range in bytes.
+    pub size: vk::DeviceSize,
+
+    /// Host pointer to the first byte of the range, or null if the pool's memory isn't mapped.
+    pub mapped: *mut u8,
+
+    slot: Slot,
+}
+
+struct Block {
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    mapped: *mut u8,
+    virtual_block: VirtualBlock,
+    live: usize,
+}
+
+/// Pool of shared buffers with one usage and one kind of memory, handing out small ranges of them.
+///
+/// Ranges are freed with `SmallBufferPool::free`. Shared buffers are destroyed when they become
+/// empty, except for the last one, and all of them when the pool is dropped.
+pub struct SmallBufferPool<'a> {
+    allocator: &'a Allocator,
+    buffer_info: ash_compat::BufferCreateInfo<'static>,
+    allocation_info: AllocationCreateInfo,
+    config: SmallBufferConfig,
+    min_alignment: vk::DeviceSize,
+    blocks: Vec<Option<Block>>,
+}
+
+impl<'a> SmallBufferPool<'a> {
+    /// Creates an empty pool for buffers with `usage`, made with `allocation_info`. No memory is
+    /// allocated until the first call to `SmallBufferPool::allocate`.
+    ///
+    /// With `AllocationCreateFlags::MAPPED` in `allocation_info`, every `SmallBuffer` comes with a
+    /// host pointer.
+    pub fn new(
+        allocator: &'a Allocator,
+        usage: vk::BufferUsageFlags,
+        allocation_info: AllocationCreateInfo,
+        config: SmallBufferConfig,
+    ) -> Self {
+        let mut min_alignment = 1;
+        if usage.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+            min_alignment = min_alignment.max(allocator.min_uniform_buffer_offset_alignment());
+        }
+        if usage.contains(vk::BufferUsageFlags::STORAGE_BUFFER) {
+            min_alignment = min_alignment.max(allocator.min_storage_buffer_offset_alignment());
+        }
+        SmallBufferPool {
+            allocator,
+            buffer_info: vk::BufferCreateInfo::builder()
+                .usage(usage)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .build(),
+            allocation_info,
+            config,
+            min_alignment,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Returns a range of at least `size` bytes whose offset is a multiple of `alignment`.
+    ///
+    /// The alignment is raised to the descriptor offset alignment of the usage of the pool, so
+    /// ranges can be bound as uniform or storage buffers directly.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::create_buffer`.
+    pub unsafe fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Result<SmallBuffer> {
+        const OPERATION: &str = "SmallBufferPool::allocate";
+        let alignment = alignment.max(self.min_alignment);
+        if size > self.config.threshold || size > self.config.block_size {
+            let buffer_info = vk::BufferCreateInfo {
+                size,
+                ..self.buffer_info
+            };
+            let (buffer, allocation, info) = self
+                .allocator
+                .create_buffer_with_alignment(&buffer_info, &self.allocation_info, alignment)
+                .map_err(|result| Error::new(result, OPERATION).with_size(size))?;
+            return Ok(SmallBuffer {
+                buffer,
+                offset: 0,
+                size,
+                mapped: info.get_mapped_data(),
+                slot: Slot::Own { allocation },
+            });
+        }
+
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            if let Some(block) = block {
+                if let Ok(range) = block.allocate(index, size, alignment) {
+                    return Ok(range);
+                }
+            }
+        }
+
+        let block = self
+            .create_block()
+            .map_err(|result| Error::new(result, OPERATION).with_size(size))?;
+        let index = match self.blocks.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                self.blocks.push(None);
+                self.blocks.len() - 1
+            }
+        };
+        let block = self.blocks[index].insert(block);
+        block
+            .allocate(index, size, alignment)
+            .map_err(|result| Error::new(result, OPERATION).with_size(size))
+    }
+
+    /// Frees a range returned by `SmallBufferPool::allocate` on this pool.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must come from this pool, and the device must be done with it.
+    pub unsafe fn free(&mut self, buffer: SmallBuffer) {
+        match buffer.slot {
+            Slot::Own { allocation } => self.allocator.destroy_buffer(buffer.buffer, &allocation),
+            Slot::Shared { block, allocation } => {
+                let empty = match &mut self.blocks[block] {
+                    Some(shared) => {
+                        shared.virtual_block.free(allocation);
+                        shared.live -= 1;
+                        shared.live == 0
+                    }
+                    None => false,
+                };
+                if empty && self.block_count() > 1 {
+                    if let Some(shared) = self.blocks[block].take() {
+                        shared.destroy(self.allocator);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of shared buffers currently alive.
+    pub fn block_count(&self) -> usize {
+        self.blocks.iter().filter(|block| block.is_some()).count()
+    }
+
+    unsafe fn create_block(&self) -> VkResult<Block> {
+        let buffer_info = vk::BufferCreateInfo {
+            size: self.config.block_size,
+            ..self.buffer_info
+        };
+        let (buffer, allocation, info) = self.allocator.create_buffer_with_alignment(
+            &buffer_info,
+            &self.allocation_info,
+            self.min_alignment,
+        )?;
+        let virtual_block = match VirtualBlock::new(VirtualBlockCreateInfo {
+            size: self.config.block_size,
+            flags: VirtualBlockCreateFlags::empty(),
+            allocation_callbacks: None,
+        }) {
+            Ok(virtual_block) => virtual_block,
+            Err(result) => {
+                self.allocator.destroy_buffer(buffer, &allocation);
+                return Err(result);
+            }
+        };
+        let mapped = if self
+            .allocation_info
+            .flags
+            .contains(AllocationCreateFlags::MAPPED)
+        {
+            info.get_mapped_data()
+        } else {
+            std::ptr::null_mut()
+        };
+        Ok(Block {
+            buffer,
+            allocation,
+            mapped,
+            virtual_block,
+            live: 0,
+        })
+    }
+}
+
+impl Block {
+    unsafe fn allocate(
+        &mut self,
+        index: usize,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> VkResult<SmallBuffer> {
+        let (allocation, offset) = self.virtual_block.allocate(size, alignment, None, None)?;
+        self.live += 1;
+        Ok(SmallBuffer {
+            buffer: self.buffer,
+            offset,
+            size,
+            mapped: if self.mapped.is_null() {
+                std::ptr::null_mut()
+            } else {
+                self.mapped.add(offset as usize)
+            },
+            slot: Slot::Shared {
+                block: index,
+                allocation,
+            },
+        })
+    }
+
+    unsafe fn destroy(mut self, allocator: &Allocator) {
+        if self.live > 0 {
+            log::warn!(
+                "SmallBufferPool dropped with {} small buffers still allocated",
+                self.live
+            );
+            self.virtual_block.clear();
+        }
+        self.virtual_block.destroy();
+        allocator.destroy_buffer(self.buffer, &self.allocation);
+    }
+}
+
+impl<'a> Drop for SmallBufferPool<'a> {
+    fn drop(&mut self) {
+        for block in self.blocks.drain(..).flatten() {
+            unsafe { block.destroy(self.allocator) };
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Statistics snapshots, for accounting of memory gained or lost between two points in time.
+//!
+//! `StatsSnapshot::capture` records the statistics of every memory type and of every custom pool
+//! that is alive at the time. Comparing a snapshot taken before a level is loaded (or a test runs)
+//! with one taken after it was unloaded shows which memory types and pools did not return to their
+//! previous state:
+//!
+//! ```ignore
+//! let before = StatsSnapshot::capture(&allocator)?;
+//! run_level(&allocator);
+//! let delta = before.diff(&StatsSnapshot::capture(&allocator)?);
+//! assert!(delta.is_empty(), "level leaked memory:\n{}", delta);
+//! ```
+
+use crate::{Allocator, AllocatorPool, Statistics};
+use ash::prelude::VkResult;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// Custom pools of an allocator that have not been destroyed yet, in creation order, shared between
+/// clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct PoolRegistry {
+    pools: Mutex<Vec<usize>>,
+}
+
+impl PoolRegistry {
+    fn pools(&self) -> MutexGuard<'_, Vec<usize>> {
+        self.pools
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn register(&self, pool: &AllocatorPool) {
+        self.pools().push(*pool as usize);
+    }
+
+    pub(crate) fn unregister(&self, pool: &AllocatorPool) {
+        self.pools().retain(|&live| live != *pool as usize);
+    }
+
+    pub(crate) fn live(&self) -> Vec<AllocatorPool> {
+        self.pools()
+            .iter()
+            .map(|&pool| pool as AllocatorPool)
+            .collect()
+    }
+}
+
+/// Statistics of a custom pool at the time a snapshot was captured.
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    /// The pool.
+    pub pool: AllocatorPool,
+
+    /// Name of the pool, empty if none was set.
+    pub name: String,
+
+    /// Statistics of the pool.
+    pub statistics: Statistics,
+}
+
+/// Statistics of an allocator at one point in time.
+#[derive(Debug, Clone)]
+pub struct StatsSnapshot {
+    /// Statistics of every memory type of the physical device, indexed by memory type index. They
+    /// include allocations made from custom pools.
+    pub memory_types: Vec<Statistics>,
+
+    /// Statistics of the custom pools that were alive, in creation order.
+    pub pools: Vec<PoolSnapshot>,
+}
+
+impl StatsSnapshot {
+    /// Captures the statistics of all memory types and custom pools of `allocator`.
+    ///
+    /// This uses `Allocator::calculate_statistics`, which is slow, so it is meant for debugging and
+    /// tests rather than for every frame.
+    pub fn capture(allocator: &Allocator) -> VkResult<StatsSnapshot> {
+        let total = unsafe { allocator.calculate_statistics()? };
+        let memory_types = total
+            .memory_type
+            .iter()
+            .take(total.memory_type_count as usize)
+            .map(|detailed| detailed.statistics)
+            .collect();
+        let pools = allocator
+            .pools()
+            .into_iter()
+            .map(|pool| PoolSnapshot {
+                pool,
+                name: allocator.get_pool_name(&pool).to_owned(),
+                statistics: allocator.get_pool_statistics(&pool),
+            })
+            .collect();
+        Ok(StatsSnapshot {
+            memory_types,
+            pools,
+        })
+    }
+
+    /// Changes from this snapshot to the `later` one.
+    ///
+    /// Only memory types and pools whose statistics changed are reported. A pool that exists in just
+    /// one of the snapshots is compared against empty statistics, so destroying a pool reports its
+    /// memory as lost and creating one reports its memory as gained.
+    pub fn diff(&self, later: &StatsSnapshot) -> StatsDelta {
+        let empty = Statistics::default();
+        let type_count = self.memory_types.len().max(later.memory_types.len());
+        let memory_types = (0..type_count)
+            .filter_map(|index| {
+                let before = self.memory_types.get(index).unwrap_or(&empty);
+                let after = later.memory_types.get(index).unwrap_or(&empty);
+                let delta = StatisticsDelta::between(before, after);
+                (!delta.is_empty()).then_some((index as u32, delta))
+            })
+            .collect();
+
+        let mut pools = Vec::new();
+        for before in &self.pools {
+            let after = later.pools.iter().find(|after| after.pool == before.pool);
+            let delta = StatisticsDelta::between(
+                &before.statistics,
+                after.map_or(&empty, |after| &after.statistics),
+            );
+            if !delta.is_empty() {
+                pools.push(PoolDelta {
+                    pool: before.pool,
+                    name: after.map_or(&before.name, |after| &after.name).clone(),
+                    delta,
+                });
+            }
+        }
+        for after in &later.pools {
+            if self.pools.iter().any(|before| before.pool == after.pool) {
+                continue;
+            }
+            let delta = StatisticsDelta::between(&empty, &after.statistics);
+            if !delta.is_empty() {
+                pools.push(PoolDelta {
+                    pool: after.pool,
+                    name: after.name.clone(),
+                    delta,
+                });
+            }
+        }
+
+        StatsDelta {
+            memory_types,
+            pools,
+        }
+    }
+}
+
+/// Signed change of `Statistics` between two snapshots. Positive values were gained, negative values
+/// were lost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatisticsDelta {
+    /// Change in the number of `ash::vk::DeviceMemory` blocks.
+    pub block_count: i64,
+
+    /// Change in the number of allocations.
+    pub allocation_count: i64,
+
+    /// Change in the bytes of `ash::vk::DeviceMemory` blocks.
+    pub block_bytes: i64,
+
+    /// Change in the bytes occupied by allocations.
+    pub allocation_bytes: i64,
+}
+
+impl StatisticsDelta {
+    fn between(before: &Statistics, after: &Statistics) -> StatisticsDelta {
+        StatisticsDelta {
+            block_count: after.block_count as i64 - before.block_count as i64,
+            allocation_count: after.allocation_count as i64 - before.allocation_count as i64,
+            block_bytes: after.block_bytes as i64 - before.block_bytes as i64,
+            allocation_bytes: after.allocation_bytes as i64 - before.allocation_bytes as i64,
+        }
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        *self == StatisticsDelta::default()
+    }
+}
+
+impl fmt::Display for StatisticsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:+} allocations ({:+} bytes), {:+} blocks ({:+} bytes)",
+            self.allocation_count, self.allocation_bytes, self.block_count, self.block_bytes
+        )
+    }
+}
+
+/// Change of a custom pool between two snapshots.
+#[derive(Debug, Clone)]
+pub struct PoolDelta {
+    /// The pool.
+    pub pool: AllocatorPool,
+
+    /// Name of the pool in the later snapshot, or in the earlier one if the pool was destroyed.
+    pub name: String,
+
+    /// Change of the pool's statistics.
+    pub delta: StatisticsDelta,
+}
+
+/// Changes between two snapshots, returned by `StatsSnapshot::diff`.
+#[derive(Debug, Clone, Default)]
+pub struct StatsDelta {
+    /// Memory types whose statistics changed, with their memory type index.
+    pub memory_types: Vec<(u32, StatisticsDelta)>,
+
+    /// Custom pools whose statistics changed, including pools created or destroyed in between.
+    pub pools: Vec<PoolDelta>,
+}
+
+impl StatsDelta {
+    /// Whether no memory type and no pool changed.
+    pub fn is_empty(&self) -> bool {
+        self.memory_types.is_empty() && self.pools.is_empty()
+    }
+
+    /// Total change over all memory types.
+    pub fn total(&self) -> StatisticsDelta {
+        self.memory_types
+            .iter()
+            .fold(StatisticsDelta::default(), |total, (_, delta)| {
+                StatisticsDelta {
+                    block_count: total.block_count + delta.block_count,
+                    allocation_count: total.allocation_count + delta.allocation_count,
+                    block_bytes: total.block_bytes + delta.block_bytes,
+                    allocation_bytes: total.allocation_bytes + delta.allocation_bytes,
+                }
+            })
+    }
+}
+
+impl fmt::Display for StatsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        for (index, delta) in &self.memory_types {
+            writeln!(f, "memory type {}: {}", index, delta)?;
+        }
+        for pool in &self.pools {
+            if pool.name.is_empty() {
+                writeln!(f, "pool {:?}: {}", pool.pool, pool.delta)?;
+            } else {
+                writeln!(f, "pool {:?} {:?}: {}", pool.pool, pool.name, pool.delta)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,447 @@
+//! Memory management for sparse buffers and images.
+//!
+//! `SparseBuffer` and `SparseImage` allocate pages of memory with `Allocator::allocate_memory_pages`
+//! at the page size and alignment the resource requires, and return the `ash::vk::SparseMemoryBind`
+//! or `ash::vk::SparseImageMemoryBind` entries to submit with `vkQueueBindSparse`:
+//!
+//! ```ignore
+//! let mut sparse = SparseBuffer::new(&allocator, &device, buffer, allocation_info);
+//! let binds = sparse.bind_pages(0, 4)?;
+//! let buffer_binds = [sparse.bind_info(&binds)];
+//! let bind_info = vk::BindSparseInfo::builder().buffer_binds(&buffer_binds);
+//! device.queue_bind_sparse(queue, &[bind_info.build()], fence)?;
+//! ```
+//!
+//! Unbinding returns an `Unbound` with the entries that unbind the memory and the allocations that
+//! backed it, which are freed with `Unbound::free` once the unbinding has completed on the queue.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{ash_compat, Allocation, AllocationCreateInfo, Allocator, Error, Result};
+use ash::vk;
+use std::collections::HashMap;
+
+/// Memory unbound from a sparse resource.
+///
+/// The allocations still back the resource until `binds` have been submitted with
+/// `vkQueueBindSparse` and that submission has completed. Only then can they be freed.
+#[must_use = "the unbound allocations must be freed with `Unbound::free`"]
+pub struct Unbound<B> {
+    /// Entries that bind `ash::vk::DeviceMemory::null()` to the unbound pages.
+    pub binds: Vec<B>,
+
+    /// Allocations that backed the unbound pages.
+    pub allocations: Vec<Allocation>,
+}
+
+impl<B> Unbound<B> {
+    /// Frees the unbound allocations.
+    ///
+    /// # Safety
+    ///
+    /// The unbinding must have completed on the queue, and the allocations must come from
+    /// `allocator`.
+    pub unsafe fn free(self, allocator: &Allocator) {
+        allocator.free_memory_pages(&self.allocations);
+    }
+}
+
+/// Memory requirements of a single page of a resource with requirements `requirements`.
+fn page_requirements(requirements: &vk::MemoryRequirements) -> vk::MemoryRequirements {
+    vk::MemoryRequirements {
+        size: requirements.alignment,
+        alignment: requirements.alignment,
+        memory_type_bits: requirements.memory_type_bits,
+    }
+}
+
+/// Pages of a buffer created with `ash::vk::BufferCreateFlags::SPARSE_BINDING`.
+///
+/// Remaining pages are freed when this is dropped, so the device must no longer use the buffer.
+/// The buffer itself is not destroyed.
+pub struct SparseBuffer<'a> {
+    allocator: &'a Allocator,
+    buffer: vk::Buffer,
+    requirements: vk::MemoryRequirements,
+    allocation_info: AllocationCreateInfo,
+    pages: Vec<Option<Allocation>>,
+}
+
+impl<'a> SparseBuffer<'a> {
+    /// Tracks the pages of `buffer`, allocated with `allocation_info`.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be a sparse buffer of `device`, the device `allocator` was created for.
+    pub unsafe fn new(
+        allocator: &'a Allocator,
+        device: &ash::Device,
+        buffer: vk::Buffer,
+        allocation_info: AllocationCreateInfo,
+    ) -> SparseBuffer<'a> {
+        let requirements = device.get_buffer_memory_requirements(buffer);
+        let page_count = requirements.size.div_ceil(requirements.alignment);
+        SparseBuffer {
+            allocator,
+            buffer,
+            requirements,
+            allocation_info,
+            pages: (0..page_count).map(|_| None).collect(),
+        }
+    }
+
+    /// The buffer.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Size of a page in bytes, the sparse block size of the buffer.
+    pub fn page_size(&self) -> vk::DeviceSize {
+        self.requirements.alignment
+    }
+
+    /// Number of pages covering the buffer.
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Whether page `page` has memory bound.
+    pub fn is_bound(&self, page: usize) -> bool {
+        self.pages[page].is_some()
+    }
+
+    /// Allocates memory for the pages in `first_page..first_page + page_count` that have none yet,
+    /// and returns the entries binding it.
+    ///
+    /// Panics if the range is out of bounds.
+    ///
+    /// # Safety
+    ///
+    /// The returned entries must be submitted with `vkQueueBindSparse` before the pages are used.
+    pub unsafe fn bind_pages(
+        &mut self,
+        first_page: usize,
+        page_count: usize,
+    ) -> Result<Vec<vk::SparseMemoryBind>> {
+        let missing: Vec<usize> = (first_page..first_page + page_count)
+            .filter(|&page| self.pages[page].is_none())
+            .collect();
+        if missing.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let allocations = self.allocator.allocate_memory_pages(
+            &page_requirements(&self.requirements),
+            &self.allocation_info,
+            missing.len(),
+        )?;
+        let page_size = self.page_size();
+        let binds = missing
+            .into_iter()
+            .zip(allocations)
+            .map(|(page, (allocation, info))| {
+                self.pages[page] = Some(allocation);
+                let resource_offset = page as vk::DeviceSize * page_size;
+                vk::SparseMemoryBind {
+                    resource_offset,
+                    size: page_size.min(self.requirements.size - resource_offset),
+                    memory: info.get_device_memory(),
+                    memory_offset: info.get_offset() as vk::DeviceSize,
+                    flags: vk::SparseMemoryBindFlags::empty(),
+                }
+            })
+            .collect();
+        Ok(binds)
+    }
+
+    /// Unbinds the pages in `first_page..first_page + page_count` that have memory.
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn unbind_pages(
+        &mut self,
+        first_page: usize,
+        page_count: usize,
+    ) -> Unbound<vk::SparseMemoryBind> {
+        let page_size = self.page_size();
+        let mut unbound = Unbound {
+            binds: Vec::new(),
+            allocations: Vec::new(),
+        };
+        for page in first_page..first_page + page_count {
+            if let Some(allocation) = self.pages[page].take() {
+                let resource_offset = page as vk::DeviceSize * page_size;
+                unbound.binds.push(vk::SparseMemoryBind {
+                    resource_offset,
+                    size: page_size.min(self.requirements.size - resource_offset),
+                    memory: vk::DeviceMemory::null(),
+                    memory_offset: 0,
+                    flags: vk::SparseMemoryBindFlags::empty(),
+                });
+                unbound.allocations.push(allocation);
+            }
+        }
+        unbound
+    }
+
+    /// `ash::vk::SparseBufferMemoryBindInfo` submitting `binds` for the buffer.
+    ///
+    /// The result points into `binds`, which must outlive its use.
+    pub fn bind_info<'b>(
+        &self,
+        binds: &'b [vk::SparseMemoryBind],
+    ) -> ash_compat::SparseBufferMemoryBindInfo<'b> {
+        vk::SparseBufferMemoryBindInfo::builder()
+            .buffer(self.buffer)
+            .binds(binds)
+            .build()
+    }
+}
+
+impl<'a> Drop for SparseBuffer<'a> {
+    fn drop(&mut self) {
+        let allocations: Vec<Allocation> = self.pages.iter_mut().filter_map(Option::take).collect();
+        if !allocations.is_empty() {
+            unsafe { self.allocator.free_memory_pages(&allocations) };
+        }
+    }
+}
+
+/// A sparse block of a `SparseImage`, in units of the image granularity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Tile {
+    aspect_mask: vk::ImageAspectFlags,
+    mip_level: u32,
+    array_layer: u32,
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+/// Sparse blocks of an image created with `ash::vk::ImageCreateFlags::SPARSE_RESIDENCY`.
+///
+/// Regions outside of the mip tail are bound one sparse block at a time. The mip tail, starting at
+/// `ash::vk::SparseImageMemoryRequirements::image_mip_tail_first_lod`, is bound with opaque binds
+/// and is not managed here.
+///
+/// Remaining blocks are freed when this is dropped, so the device must no longer use the image.
+/// The image itself is not destroyed.
+pub struct SparseImage<'a> {
+    allocator: &'a Allocator,
+    image: vk::Image,
+    requirements: vk::MemoryRequirements,
+    sparse_requirements: Vec<vk::SparseImageMemoryRequirements>,
+    allocation_info: AllocationCreateInfo,
+    tiles: HashMap<Tile, Allocation>,
+}
+
+impl<'a> SparseImage<'a> {
+    /// Tracks the sparse blocks of `image`, allocated with `allocation_info`.
+    ///
+    /// # Safety
+    ///
+    /// `image` must be a sparse resident image of `device`, the device `allocator` was created
+    /// for.
+    pub unsafe fn new(
+        allocator: &'a Allocator,
+        device: &ash::Device,
+        image: vk::Image,
+        allocation_info: AllocationCreateInfo,
+    ) -> SparseImage<'a> {
+        SparseImage {
+            allocator,
+            image,
+            requirements: device.get_image_memory_requirements(image),
+            sparse_requirements: device.get_image_sparse_memory_requirements(image),
+            allocation_info,
+            tiles: HashMap::new(),
+        }
+    }
+
+    /// The image.
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    /// Size of the memory backing one sparse block, in bytes.
+    pub fn page_size(&self) -> vk::DeviceSize {
+        self.requirements.alignment
+    }
+
+    /// Sparse memory requirements of the image, one per aspect (or group of aspects) of its format.
+    pub fn sparse_requirements(&self) -> &[vk::SparseImageMemoryRequirements] {
+        &self.sparse_requirements
+    }
+
+    /// Extent of a sparse block of `aspect_mask`, or `None` if the image has no such aspect.
+    pub fn granularity(&self, aspect_mask: vk::ImageAspectFlags) -> Option<vk::Extent3D> {
+        self.sparse_requirements
+            .iter()
+            .find(|requirements| {
+                requirements
+                    .format_properties
+                    .aspect_mask
+                    .contains(aspect_mask)
+            })
+            .map(|requirements| requirements.format_properties.image_granularity)
+    }
+
+    /// Blocks covering a region with their offset and extent, clipped to the region.
+    fn tiles(
+        &self,
+        operation: &'static str,
+        subresource: &vk::ImageSubresource,
+        offset: vk::Offset3D,
+        extent: vk::Extent3D,
+    ) -> Result<Vec<(Tile, vk::Offset3D, vk::Extent3D)>> {
+        let granularity = self
+            .granularity(subresource.aspect_mask)
+            .ok_or_else(|| Error::new(vk::Result::ERROR_FORMAT_NOT_SUPPORTED, operation))?;
+        let (start_x, start_y, start_z) = (offset.x as u32, offset.y as u32, offset.z as u32);
+        let (end_x, end_y, end_z) = (
+            start_x + extent.width,
+            start_y + extent.height,
+            start_z + extent.depth,
+        );
+
+        let mut tiles = Vec::new();
+        for z in (start_z / granularity.depth..).take_while(|z| z * granularity.depth < end_z) {
+            for y in (start_y / granularity.height..).take_while(|y| y * granularity.height < end_y)
+            {
+                for x in
+                    (start_x / granularity.width..).take_while(|x| x * granularity.width < end_x)
+                {
+                    let origin = (
+                        x * granularity.width,
+                        y * granularity.height,
+                        z * granularity.depth,
+                    );
+                    tiles.push((
+                        Tile {
+                            aspect_mask: subresource.aspect_mask,
+                            mip_level: subresource.mip_level,
+                            array_layer: subresource.array_layer,
+                            x,
+                            y,
+                            z,
+                        },
+                        vk::Offset3D {
+                            x: origin.0 as i32,
+                            y: origin.1 as i32,
+                            z: origin.2 as i32,
+                        },
+                        vk::Extent3D {
+                            width: granularity.width.min(end_x - origin.0),
+                            height: granularity.height.min(end_y - origin.1),
+                            depth: granularity.depth.min(end_z - origin.2),
+                        },
+                    ));
+                }
+            }
+        }
+        Ok(tiles)
+    }
+
+    /// Allocates memory for the sparse blocks of `subresource` covering `offset..offset + extent`
+    /// that have none yet, and returns the entries binding it.
+    ///
+    /// As required by Vulkan, `offset` must be a multiple of the granularity of the aspect, and
+    /// `extent` a multiple of it or reach the edge of the subresource. Fails with
+    /// `ash::vk::Result::ERROR_FORMAT_NOT_SUPPORTED` if the image has no such aspect.
+    ///
+    /// # Safety
+    ///
+    /// The returned entries must be submitted with `vkQueueBindSparse` before the blocks are used.
+    pub unsafe fn bind_region(
+        &mut self,
+        subresource: vk::ImageSubresource,
+        offset: vk::Offset3D,
+        extent: vk::Extent3D,
+    ) -> Result<Vec<vk::SparseImageMemoryBind>> {
+        let missing: Vec<_> = self
+            .tiles("SparseImage::bind_region", &subresource, offset, extent)?
+            .into_iter()
+            .filter(|(tile, _, _)| !self.tiles.contains_key(tile))
+            .collect();
+        if missing.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let allocations = self.allocator.allocate_memory_pages(
+            &page_requirements(&self.requirements),
+            &self.allocation_info,
+            missing.len(),
+        )?;
+        let binds = missing
+            .into_iter()
+            .zip(allocations)
+            .map(|((tile, offset, extent), (allocation, info))| {
+                self.tiles.insert(tile, allocation);
+                vk::SparseImageMemoryBind {
+                    subresource,
+                    offset,
+                    extent,
+                    memory: info.get_device_memory(),
+                    memory_offset: info.get_offset() as vk::DeviceSize,
+                    flags: vk::SparseMemoryBindFlags::empty(),
+                }
+            })
+            .collect();
+        Ok(binds)
+    }
+
+    /// Unbinds the sparse blocks of `subresource` covering `offset..offset + extent` that have
+    /// memory.
+    pub fn unbind_region(
+        &mut self,
+        subresource: vk::ImageSubresource,
+        offset: vk::Offset3D,
+        extent: vk::Extent3D,
+    ) -> Result<Unbound<vk::SparseImageMemoryBind>> {
+        let mut unbound = Unbound {
+            binds: Vec::new(),
+            allocations: Vec::new(),
+        };
+        for (tile, offset, extent) in
+            self.tiles("SparseImage::unbind_region", &subresource, offset, extent)?
+        {
+            if let Some(allocation) = self.tiles.remove(&tile) {
+                unbound.binds.push(vk::SparseImageMemoryBind {
+                    subresource,
+                    offset,
+                    extent,
+                    memory: vk::DeviceMemory::null(),
+                    memory_offset: 0,
+                    flags: vk::SparseMemoryBindFlags::empty(),
+                });
+                unbound.allocations.push(allocation);
+            }
+        }
+        Ok(unbound)
+    }
+
+    /// `ash::vk::SparseImageMemoryBindInfo` submitting `binds` for the image.
+    ///
+    /// The result points into `binds`, which must outlive its use.
+    pub fn bind_info<'b>(
+        &self,
+        binds: &'b [vk::SparseImageMemoryBind],
+    ) -> ash_compat::SparseImageMemoryBindInfo<'b> {
+        vk::SparseImageMemoryBindInfo::builder()
+            .image(self.image)
+            .binds(binds)
+            .build()
+    }
+}
+
+impl<'a> Drop for SparseImage<'a> {
+    fn drop(&mut self) {
+        let allocations: Vec<Allocation> = self
+            .tiles
+            .drain()
+            .map(|(_, allocation)| allocation)
+            .collect();
+        if !allocations.is_empty() {
+            unsafe { self.allocator.free_memory_pages(&allocations) };
+        }
+    }
+}
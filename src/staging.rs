@@ -0,0 +1,330 @@
+//! Host-visible staging memory for uploads, sized from observed throughput and the memory budget.
+//!
+//! `StagingManager` hands out linear sub-ranges of persistently mapped `TRANSFER_SRC` buffers. It
+//! grows by adding chunks when a frame needs more staging memory than it has, and chooses the size of
+//! new chunks from the average upload volume of recent frames, capped by the remaining budget of the
+//! memory heap the staging buffers live in. After a run of frames without uploads it releases every
+//! chunk beyond the configured minimum, so a loading burst doesn't pin host-visible memory forever.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{Allocation, AllocationCreateFlags, AllocationCreateInfo, Allocator, MemoryUsage};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Tuning parameters of a `StagingManager`.
+#[derive(Debug, Clone, Copy)]
+pub struct StagingConfig {
+    /// Size of the smallest chunk the manager creates and the capacity it shrinks back to when idle.
+    pub min_chunk_size: vk::DeviceSize,
+
+    /// Upper bound for the size of a single chunk. A request larger than this gets a chunk of exactly its size.
+    pub max_chunk_size: vk::DeviceSize,
+
+    /// Fraction of the remaining budget of the staging memory heap (`budget - usage`) the manager may grow into.
+    pub budget_fraction: f32,
+
+    /// Number of frames worth of the average upload volume the manager tries to keep resident.
+    pub headroom_frames: u32,
+
+    /// Number of consecutive frames without uploads after which chunks beyond `min_chunk_size` are released.
+    pub idle_frames_before_shrink: u32,
+}
+
+impl Default for StagingConfig {
+    fn default() -> Self {
+        StagingConfig {
+            min_chunk_size: 1024 * 1024,
+            max_chunk_size: 64 * 1024 * 1024,
+            budget_fraction: 0.25,
+            headroom_frames: 2,
+            idle_frames_before_shrink: 120,
+        }
+    }
+}
+
+/// Mapped sub-range of a staging buffer returned by `StagingManager::allocate`.
+#[derive(Debug, Clone, Copy)]
+pub struct StagingRegion {
+    /// Buffer to use as the source of the transfer command.
+    pub buffer: vk::Buffer,
+
+    /// Offset of the region inside `buffer`.
+    pub offset: vk::DeviceSize,
+
+    /// Size of the region in bytes.
+    pub size: vk::DeviceSize,
+
+    /// Host pointer to the first byte of the region.
+    pub mapped: *mut u8,
+}
+
+struct Chunk {
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    mapped: *mut u8,
+    size: vk::DeviceSize,
+    used: vk::DeviceSize,
+}
+
+/// Pool of host-visible staging buffers that sizes itself from upload throughput and the heap budget.
+///
+/// Regions returned by `StagingManager::allocate` stay valid until the next call to
+/// `StagingManager::end_frame`, which recycles all chunks. The caller must make sure the device has
+/// finished reading from the regions of a frame before ending it, e.g. by waiting on that frame's fence.
+///
+/// All staging buffers are destroyed when the manager is dropped.
+pub struct StagingManager<'a> {
+    allocator: &'a Allocator,
+    config: StagingConfig,
+    chunks: Vec<Chunk>,
+    heap_index: Option<usize>,
+    frame_bytes: vk::DeviceSize,
+    average_frame_bytes: f64,
+    idle_frames: u32,
+}
+
+impl<'a> StagingManager<'a> {
+    /// Creates an empty manager. No memory is allocated until the first call to `StagingManager::allocate`.
+    pub fn new(allocator: &'a Allocator, config: StagingConfig) -> Self {
+        StagingManager {
+            allocator,
+            config,
+            chunks: Vec::new(),
+            heap_index: None,
+            frame_bytes: 0,
+            average_frame_bytes: 0.0,
+            idle_frames: 0,
+        }
+    }
+
+    /// Returns a mapped region of at least `size` bytes whose offset is a multiple of `alignment`.
+    ///
+    /// A new chunk is created if none of the existing ones has enough space left in the current frame.
+    /// Its size is derived from `StagingManager::target_capacity`, but it is never smaller than `size`.
+    ///
+    /// # Safety
+    ///
+    /// The region must only be written by the host before the commands reading it are submitted, and
+    /// it must not be used after the next call to `StagingManager::end_frame`.
+    pub unsafe fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> VkResult<StagingRegion> {
+        let alignment = alignment.max(1);
+        self.frame_bytes += size;
+
+        for chunk in &mut self.chunks {
+            let offset = align_up(chunk.used, alignment);
+            if offset + size <= chunk.size {
+                chunk.used = offset + size;
+                return Ok(StagingRegion {
+                    buffer: chunk.buffer,
+                    offset,
+                    size,
+                    mapped: chunk.mapped.add(offset as usize),
+                });
+            }
+        }
+
+        let chunk_size = self.next_chunk_size(size);
+        let mut chunk = self.create_chunk(chunk_size)?;
+        chunk.used = size;
+        let region = StagingRegion {
+            buffer: chunk.buffer,
+            offset: 0,
+            size,
+            mapped: chunk.mapped,
+        };
+        self.chunks.push(chunk);
+        Ok(region)
+    }
+
+    /// Flushes the host writes to `region` so they become visible to the device.
+    ///
+    /// Does nothing if the staging memory is `ash::vk::MemoryPropertyFlags::HOST_COHERENT`.
+    ///
+    /// # Safety
+    ///
+    /// `region` must have been returned by `StagingManager::allocate` of this manager since the last
+    /// call to `StagingManager::end_frame`.
+    pub unsafe fn flush(&self, region: &StagingRegion) -> VkResult<()> {
+        match self
+            .chunks
+            .iter()
+            .find(|chunk| chunk.buffer == region.buffer)
+        {
+            Some(chunk) => self.allocator.flush_allocation(
+                &chunk.allocation,
+                region.offset as usize,
+                region.size as usize,
+            ),
+            None => Err(vk::Result::ERROR_UNKNOWN),
+        }
+    }
+
+    /// Marks the end of a frame, recycling all regions handed out since the previous call.
+    ///
+    /// The upload volume of the frame is folded into the running average used to size new chunks.
+    /// Chunks beyond `StagingConfig::min_chunk_size` are released after
+    /// `StagingConfig::idle_frames_before_shrink` frames without uploads, or immediately when the
+    /// staging heap is over its budget.
+    ///
+    /// # Safety
+    ///
+    /// The device must have finished executing every command reading from the regions handed out
+    /// since the previous call.
+    pub unsafe fn end_frame(&mut self) {
+        const SMOOTHING: f64 = 0.1;
+        self.average_frame_bytes +=
+            (self.frame_bytes as f64 - self.average_frame_bytes) * SMOOTHING;
+
+        if self.frame_bytes == 0 {
+            self.idle_frames = self.idle_frames.saturating_add(1);
+        } else {
+            self.idle_frames = 0;
+        }
+        self.frame_bytes = 0;
+
+        for chunk in &mut self.chunks {
+            chunk.used = 0;
+        }
+
+        if self.idle_frames >= self.config.idle_frames_before_shrink {
+            self.average_frame_bytes = 0.0;
+            self.shrink_to(self.config.min_chunk_size);
+        } else if self.over_budget() {
+            self.shrink_to(self.target_capacity());
+        }
+    }
+
+    /// Total size of all staging chunks currently allocated.
+    pub fn capacity(&self) -> vk::DeviceSize {
+        self.chunks.iter().map(|chunk| chunk.size).sum()
+    }
+
+    /// Capacity the manager currently aims for: `StagingConfig::headroom_frames` times the average
+    /// upload volume per frame (or the volume of the current frame, if that is larger), at least `StagingConfig::min_chunk_size` and at most what the heap
+    /// budget allows.
+    pub fn target_capacity(&self) -> vk::DeviceSize {
+        let observed = self.average_frame_bytes.max(self.frame_bytes as f64);
+        let wanted = (observed * self.config.headroom_frames as f64) as vk::DeviceSize;
+        let wanted = wanted.max(self.config.min_chunk_size);
+        match self.budget_headroom() {
+            Some(headroom) => wanted.min(self.capacity() + headroom),
+            None => wanted,
+        }
+    }
+
+    fn next_chunk_size(&self, size: vk::DeviceSize) -> vk::DeviceSize {
+        let growth = self
+            .target_capacity()
+            .saturating_sub(self.capacity())
+            .clamp(self.config.min_chunk_size, self.config.max_chunk_size);
+        let growth = match self.budget_headroom() {
+            Some(headroom) => growth.min(headroom),
+            None => growth,
+        };
+        growth.max(size)
+    }
+
+    /// Part of the remaining unreserved budget of the staging heap the manager is allowed to grow into, if known.
+    fn budget_headroom(&self) -> Option<vk::DeviceSize> {
+        let heap_index = self.heap_index?;
+        let available = self.allocator.available_budget(heap_index as u32);
+        Some((available as f64 * self.config.budget_fraction as f64) as vk::DeviceSize)
+    }
+
+    fn over_budget(&self) -> bool {
+        match self.heap_index {
+            Some(heap_index) => {
+                let budgets = self.allocator.get_heap_budgets(heap_index + 1);
+                budgets[heap_index].usage > budgets[heap_index].budget
+            }
+            None => false,
+        }
+    }
+
+    /// Releases chunks, most recent first, until the capacity is down to `capacity`. A chunk that
+    /// overshoots it by at least `StagingConfig::min_chunk_size`, e.g. one created for a single
+    /// oversized request, is replaced by a chunk of the missing size, at least
+    /// `StagingConfig::min_chunk_size`. Nothing is allocated if no chunk was released.
+    unsafe fn shrink_to(&mut self, capacity: vk::DeviceSize) {
+        let mut current = self.capacity();
+        let mut released = false;
+        while let Some(last) = self.chunks.last() {
+            if current - last.size < capacity
+                && current < capacity.saturating_add(self.config.min_chunk_size)
+            {
+                break;
+            }
+            current -= last.size;
+            let chunk = self.chunks.pop().unwrap();
+            self.allocator
+                .destroy_buffer(chunk.buffer, &chunk.allocation);
+            released = true;
+        }
+
+        if released && current < capacity {
+            let size = (capacity - current).max(self.config.min_chunk_size);
+            // If this fails, the next `StagingManager::allocate` creates a chunk instead.
+            if let Ok(chunk) = self.create_chunk(size) {
+                self.chunks.push(chunk);
+            }
+        }
+    }
+
+    unsafe fn create_chunk(&mut self, size: vk::DeviceSize) -> VkResult<Chunk> {
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+        let allocation_info = AllocationCreateInfo {
+            flags: AllocationCreateFlags::MAPPED
+                | AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            usage: MemoryUsage::Auto,
+            ..Default::default()
+        };
+        let (buffer, allocation, info) = self
+            .allocator
+            .create_buffer(&buffer_info, &allocation_info)?;
+
+        if self.heap_index.is_none() {
+            self.heap_index = Some(self.heap_index_of(info.get_memory_type()));
+        }
+
+        Ok(Chunk {
+            buffer,
+            allocation,
+            mapped: info.get_mapped_data(),
+            size,
+            used: 0,
+        })
+    }
+
+    unsafe fn heap_index_of(&self, memory_type_index: u32) -> usize {
+        match self.allocator.get_memory_properties() {
+            Ok(properties) => {
+                properties.memory_types[memory_type_index as usize].heap_index as usize
+            }
+            Err(_) => 0,
+        }
+    }
+}
+
+impl<'a> Drop for StagingManager<'a> {
+    fn drop(&mut self) {
+        for chunk in self.chunks.drain(..) {
+            unsafe {
+                self.allocator
+                    .destroy_buffer(chunk.buffer, &chunk.allocation)
+            };
+        }
+    }
+}
+
+pub(crate) fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    value.div_ceil(alignment) * alignment
+}
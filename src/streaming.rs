@@ -0,0 +1,238 @@
+//! Residency decisions for streamed textures.
+//!
+//! `ResidencyManager` tracks textures registered with their size and a priority the application
+//! updates every frame (e.g. from screen coverage). `ResidencyManager::update` ranks them by
+//! priority and keeps the highest ranked ones that fit in the streaming budget, derived from
+//! `VK_EXT_memory_budget` of the heap textures live in. It answers which textures to load and which
+//! to evict; the application performs the loads and reports them with
+//! `ResidencyManager::mark_resident`, and evictions go through the deferred deletion queue of a
+//! `frame::FrameGuard`, so the device can finish using an evicted texture first.
+
+use crate::frame::FrameGuard;
+use crate::{Allocation, Allocator};
+use ash::vk;
+use std::collections::HashMap;
+
+/// Identifier of a texture chosen by the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextureId(pub u64);
+
+/// Tuning parameters of a `ResidencyManager`.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamingConfig {
+    /// Heap the streamed textures are allocated from.
+    pub heap_index: u32,
+
+    /// Fraction of the budget of the heap that memory not owned by the manager and the resident
+    /// textures together may use.
+    pub budget_fraction: f32,
+
+    /// Upper bound for the size of all resident textures, in addition to the budget, if any.
+    pub max_bytes: Option<vk::DeviceSize>,
+
+    /// Maximum number of loads `ResidencyManager::update` requests at once, to spread loading over
+    /// several frames.
+    pub max_loads_per_update: usize,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        StreamingConfig {
+            heap_index: 0,
+            budget_fraction: 0.8,
+            max_bytes: None,
+            max_loads_per_update: 16,
+        }
+    }
+}
+
+/// What `ResidencyManager::update` wants changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Decisions {
+    /// Textures to load, highest priority first. Report them with `ResidencyManager::mark_resident`.
+    pub load: Vec<TextureId>,
+
+    /// Resident textures to evict, lowest priority first. See `ResidencyManager::evict`.
+    pub evict: Vec<TextureId>,
+}
+
+struct Texture {
+    size: vk::DeviceSize,
+    priority: f32,
+    resident: Option<(vk::Image, Allocation)>,
+}
+
+/// Tracks streamed textures and decides which of them should be resident.
+pub struct ResidencyManager<'a> {
+    allocator: &'a Allocator,
+    config: StreamingConfig,
+    textures: HashMap<TextureId, Texture>,
+    resident_bytes: vk::DeviceSize,
+}
+
+impl<'a> ResidencyManager<'a> {
+    /// Creates a manager without textures.
+    pub fn new(allocator: &'a Allocator, config: StreamingConfig) -> Self {
+        ResidencyManager {
+            allocator,
+            config,
+            textures: HashMap::new(),
+            resident_bytes: 0,
+        }
+    }
+
+    /// Registers a texture that needs `size` bytes when resident. A texture with a priority of
+    /// zero or less is never loaded. Registering an id again updates its size and priority.
+    pub fn register(&mut self, id: TextureId, size: vk::DeviceSize, priority: f32) {
+        let texture = self.textures.entry(id).or_insert(Texture {
+            size,
+            priority,
+            resident: None,
+        });
+        if texture.resident.is_some() {
+            self.resident_bytes = self.resident_bytes - texture.size + size;
+        }
+        texture.size = size;
+        texture.priority = priority;
+    }
+
+    /// Forgets a texture, returning its image and allocation if it is resident. The caller is
+    /// responsible for destroying them.
+    pub fn unregister(&mut self, id: TextureId) -> Option<(vk::Image, Allocation)> {
+        let texture = self.textures.remove(&id)?;
+        let resident = texture.resident?;
+        self.resident_bytes -= texture.size;
+        Some(resident)
+    }
+
+    /// Updates the priority of a registered texture.
+    pub fn set_priority(&mut self, id: TextureId, priority: f32) {
+        if let Some(texture) = self.textures.get_mut(&id) {
+            texture.priority = priority;
+        }
+    }
+
+    /// Records that a registered texture was loaded into `image`, bound to `allocation`.
+    pub fn mark_resident(&mut self, id: TextureId, image: vk::Image, allocation: Allocation) {
+        if let Some(texture) = self.textures.get_mut(&id) {
+            if texture.resident.is_none() {
+                self.resident_bytes += texture.size;
+            }
+            texture.resident = Some((image, allocation));
+        }
+    }
+
+    /// Whether a texture is resident.
+    pub fn is_resident(&self, id: TextureId) -> bool {
+        self.textures
+            .get(&id)
+            .is_some_and(|texture| texture.resident.is_some())
+    }
+
+    /// Total size of the resident textures.
+    pub fn resident_bytes(&self) -> vk::DeviceSize {
+        self.resident_bytes
+    }
+
+    /// Number of bytes the resident textures may take: the configured fraction of the budget of
+    /// the heap minus what memory not owned by the manager uses and what other systems reserved
+    /// with `Allocator::reserve_budget`, capped by `StreamingConfig::max_bytes`.
+    pub fn streaming_budget(&self) -> vk::DeviceSize {
+        let heap_index = self.config.heap_index as usize;
+        let budgets = self.allocator.get_heap_budgets(heap_index + 1);
+        let limit = budgets.get(heap_index).map_or(0, |budget| {
+            let other_usage = budget.usage.saturating_sub(self.resident_bytes);
+            let allowed =
+                (budget.budget as f64 * self.config.budget_fraction as f64) as vk::DeviceSize;
+            allowed
+                .saturating_sub(other_usage)
+                .saturating_sub(self.allocator.reserved_budget(self.config.heap_index))
+        });
+        match self.config.max_bytes {
+            Some(max_bytes) => limit.min(max_bytes),
+            None => limit,
+        }
+    }
+
+    /// Whether a texture of `size` bytes fits in the streaming budget next to the resident ones.
+    pub fn fits(&self, size: vk::DeviceSize) -> bool {
+        self.resident_bytes + size <= self.streaming_budget()
+    }
+
+    /// Ranks the textures by priority and decides which to load and which to evict so the highest
+    /// ranked ones that fit in `ResidencyManager::streaming_budget` are resident.
+    ///
+    /// Lower ranked textures that fit in the space left by a higher ranked one that doesn't are
+    /// still kept. Loads beyond `StreamingConfig::max_loads_per_update` are left for later updates.
+    pub fn update(&self) -> Decisions {
+        let budget = self.streaming_budget();
+        let mut ranked: Vec<(&TextureId, &Texture)> = self
+            .textures
+            .iter()
+            .filter(|(_, texture)| texture.priority > 0.0)
+            .collect();
+        ranked
+            .sort_by(|(a_id, a), (b_id, b)| b.priority.total_cmp(&a.priority).then(a_id.cmp(b_id)));
+
+        let mut used = 0;
+        let mut wanted = Vec::new();
+        for (&id, texture) in ranked {
+            if used + texture.size <= budget {
+                used += texture.size;
+                wanted.push(id);
+            }
+        }
+
+        let load = wanted
+            .iter()
+            .copied()
+            .filter(|id| self.textures[id].resident.is_none())
+            .take(self.config.max_loads_per_update)
+            .collect();
+        let mut evict: Vec<TextureId> = self
+            .textures
+            .iter()
+            .filter(|(id, texture)| texture.resident.is_some() && !wanted.contains(id))
+            .map(|(&id, _)| id)
+            .collect();
+        evict.sort_by(|a, b| {
+            self.textures[a]
+                .priority
+                .total_cmp(&self.textures[b].priority)
+                .then(a.cmp(b))
+        });
+        Decisions { load, evict }
+    }
+
+    /// Evicts a resident texture, destroying its image once the frames in flight of `frame` have
+    /// ended. Returns whether the texture was resident.
+    ///
+    /// # Safety
+    ///
+    /// The image must not be used by the host afterwards. See `FrameGuard::defer_destroy_image`.
+    pub unsafe fn evict(&mut self, id: TextureId, frame: &FrameGuard<'_>) -> bool {
+        let texture = match self.textures.get_mut(&id) {
+            Some(texture) => texture,
+            None => return false,
+        };
+        match texture.resident.take() {
+            Some((image, allocation)) => {
+                self.resident_bytes -= texture.size;
+                frame.defer_destroy_image(image, &allocation);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Evicts every texture in `decisions.evict`. See `ResidencyManager::evict`.
+    ///
+    /// # Safety
+    ///
+    /// See `ResidencyManager::evict`.
+    pub unsafe fn apply_evictions(&mut self, decisions: &Decisions, frame: &FrameGuard<'_>) {
+        for &id in &decisions.evict {
+            self.evict(id, frame);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Latency of allocator calls, enabled with the `timing` feature.
+//!
+//! Every allocating, freeing, mapping and unmapping call of `Allocator` is timed from entry to
+//! return, including the time spent waiting for VMA's internal mutexes, and recorded in a histogram
+//! of its `Operation`. Comparing the distributions of a single-threaded run with those of parallel
+//! streaming shows how much of the latency is lock contention. Recording only uses atomics, so it
+//! doesn't add contention of its own.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Number of histogram buckets in `TimingStatistics::histogram`.
+///
+/// Bucket `i` counts calls that took `[2^i, 2^(i+1))` nanoseconds; the first bucket also counts
+/// calls below one nanosecond and the last one everything from `2^(BUCKET_COUNT-1)` nanoseconds
+/// (about 1 second) up.
+pub const BUCKET_COUNT: usize = 31;
+
+/// Kind of allocator call that is timed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// `Allocator::allocate_memory*`, `Allocator::create_buffer*` and `Allocator::create_image`,
+    /// once per allocation.
+    Allocate,
+
+    /// `Allocator::free_memory*`, `Allocator::destroy_buffer` and `Allocator::destroy_image`.
+    Free,
+
+    /// `Allocator::map_memory`.
+    Map,
+
+    /// `Allocator::unmap_memory`.
+    Unmap,
+}
+
+impl Operation {
+    /// All operations, in the order of `TimingReport::operations`.
+    pub const ALL: [Operation; 4] = [
+        Operation::Allocate,
+        Operation::Free,
+        Operation::Map,
+        Operation::Unmap,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Allocate => "allocate",
+            Operation::Free => "free",
+            Operation::Map => "map",
+            Operation::Unmap => "unmap",
+        }
+    }
+}
+
+/// Latency distribution of one `Operation`.
+#[derive(Debug, Clone)]
+pub struct TimingStatistics {
+    /// The timed operation.
+    pub operation: Operation,
+
+    /// Number of calls.
+    pub count: u64,
+
+    /// Shortest call, zero if there were none.
+    pub min: Duration,
+
+    /// Longest call.
+    pub max: Duration,
+
+    /// Sum of the durations of all calls.
+    pub total: Duration,
+
+    /// Number of calls per power-of-two bucket of nanoseconds. See `BUCKET_COUNT`.
+    pub histogram: [u64; BUCKET_COUNT],
+}
+
+impl TimingStatistics {
+    /// Average duration of a call, or zero if there were none.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+        }
+    }
+
+    /// Range of durations counted by bucket `index` of `TimingStatistics::histogram`.
+    pub fn bucket_range(index: usize) -> (Duration, Duration) {
+        let lower = if index == 0 { 0 } else { 1u64 << index };
+        let upper = if index + 1 >= BUCKET_COUNT {
+            Duration::MAX
+        } else {
+            Duration::from_nanos(1u64 << (index + 1))
+        };
+        (Duration::from_nanos(lower), upper)
+    }
+
+    /// Upper bound of the duration below which a `fraction` (0 to 1) of the calls completed, at
+    /// the resolution of the histogram buckets.
+    pub fn percentile(&self, fraction: f64) -> Duration {
+        let target = (self.count as f64 * fraction.clamp(0.0, 1.0)).ceil() as u64;
+        let mut seen = 0;
+        for (index, &count) in self.histogram.iter().enumerate() {
+            seen += count;
+            if count > 0 && seen >= target {
+                return Self::bucket_range(index).1.min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+impl fmt::Display for TimingStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} calls, mean {:?}, p50 {:?}, p99 {:?}, max {:?}",
+            self.operation.name(),
+            self.count,
+            self.mean(),
+            self.percentile(0.5),
+            self.percentile(0.99),
+            self.max
+        )
+    }
+}
+
+/// Latency distributions of all operations, returned by `Allocator::timing_report`.
+#[derive(Debug, Clone)]
+pub struct TimingReport {
+    /// One entry per `Operation`, in the order of `Operation::ALL`.
+    pub operations: Vec<TimingStatistics>,
+}
+
+impl TimingReport {
+    /// Statistics of `operation`.
+    pub fn get(&self, operation: Operation) -> &TimingStatistics {
+        self.operations
+            .iter()
+            .find(|statistics| statistics.operation == operation)
+            .expect("every operation is reported")
+    }
+}
+
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for statistics in &self.operations {
+            writeln!(f, "{}", statistics)?;
+        }
+        Ok(())
+    }
+}
+
+fn bucket_index(duration: Duration) -> usize {
+    let nanos = duration.as_nanos().max(1);
+    ((127 - nanos.leading_zeros()) as usize).min(BUCKET_COUNT - 1)
+}
+
+/// Counters of one operation.
+#[derive(Debug)]
+struct Counters {
+    count: AtomicU64,
+    min_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+    total_nanos: AtomicU64,
+    histogram: [AtomicU64; BUCKET_COUNT],
+}
+
+impl Default for Counters {
+    fn default() -> Self {
+        Counters {
+            count: AtomicU64::new(0),
+            min_nanos: AtomicU64::new(u64::MAX),
+            max_nanos: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            histogram: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Counters {
+    fn record(&self, duration: Duration) {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.min_nanos.fetch_min(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.histogram[bucket_index(duration)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.min_nanos.store(u64::MAX, Ordering::Relaxed);
+        self.max_nanos.store(0, Ordering::Relaxed);
+        self.total_nanos.store(0, Ordering::Relaxed);
+        for bucket in &self.histogram {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn statistics(&self, operation: Operation) -> TimingStatistics {
+        let count = self.count.load(Ordering::Relaxed);
+        TimingStatistics {
+            operation,
+            count,
+            min: if count == 0 {
+                Duration::ZERO
+            } else {
+                Duration::from_nanos(self.min_nanos.load(Ordering::Relaxed))
+            },
+            max: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+            total: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+            histogram: std::array::from_fn(|index| self.histogram[index].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Per-allocator timings, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct Timings {
+    operations: [Counters; 4],
+}
+
+impl Timings {
+    fn counters(&self, operation: Operation) -> &Counters {
+        &self.operations[operation as usize]
+    }
+
+    /// Starts timing a call, which is recorded when the returned timer is dropped.
+    pub(crate) fn start(&self, operation: Operation) -> Timer<'_> {
+        Timer {
+            counters: self.counters(operation),
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn report(&self) -> TimingReport {
+        TimingReport {
+            operations: Operation::ALL
+                .iter()
+                .map(|&operation| self.counters(operation).statistics(operation))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        for counters in &self.operations {
+            counters.reset();
+        }
+    }
+}
+
+/// A call being timed.
+pub(crate) struct Timer<'a> {
+    counters: &'a Counters,
+    start: Instant,
+}
+
+impl<'a> Drop for Timer<'a> {
+    fn drop(&mut self) {
+        self.counters.record(self.start.elapsed());
+    }
+}
@@ -0,0 +1,71 @@
+//! Tracy memory events, enabled with the `tracy` feature.
+//!
+//! Every allocation is reported to Tracy as a named memory allocation, so GPU memory shows up in
+//! Tracy's memory view next to the host heap. Allocations from the default pools are reported under
+//! `DEFAULT_POOL_NAME`, allocations from custom pools under the pool's name (see
+//! `Allocator::set_pool_name`), or `UNNAMED_POOL_NAME` if it has none.
+
+use crate::Allocation;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::sync::{Mutex, MutexGuard};
+use tracy_client::sys;
+
+/// Tracy memory pool of allocations from the default pools.
+pub const DEFAULT_POOL_NAME: &str = "vk-mem";
+
+/// Tracy memory pool of allocations from custom pools without a name.
+pub const UNNAMED_POOL_NAME: &str = "vk-mem custom pool";
+
+#[derive(Debug, Default)]
+struct State {
+    /// Tracy identifies memory pools by the address of their name, which must stay valid for the
+    /// rest of the program, so every distinct name is leaked once.
+    names: HashMap<String, &'static CStr>,
+    allocations: HashMap<usize, &'static CStr>,
+}
+
+/// Names the live allocations were reported under, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct TracyMemory {
+    state: Mutex<State>,
+}
+
+impl TracyMemory {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn on_create(&self, allocation: &Allocation, size: u64, pool_name: Option<&str>) {
+        let pool_name = match pool_name {
+            None => DEFAULT_POOL_NAME,
+            Some("") => UNNAMED_POOL_NAME,
+            Some(name) => name,
+        };
+        let mut state = self.state();
+        let name: &'static CStr = match state.names.get(pool_name) {
+            Some(name) => name,
+            None => {
+                let leaked: &'static CStr = Box::leak(
+                    CString::new(pool_name.replace('\0', ""))
+                        .unwrap_or_default()
+                        .into_boxed_c_str(),
+                );
+                state.names.insert(pool_name.to_owned(), leaked);
+                leaked
+            }
+        };
+        state.allocations.insert(*allocation as usize, name);
+        unsafe {
+            sys::___tracy_emit_memory_alloc_named(*allocation as _, size as usize, name.as_ptr())
+        };
+    }
+
+    pub(crate) fn on_free(&self, allocation: &Allocation) {
+        if let Some(name) = self.state().allocations.remove(&(*allocation as usize)) {
+            unsafe { sys::___tracy_emit_memory_free_named(*allocation as _, name.as_ptr()) };
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Copying buffer contents between allocations that live on different allocators or devices.
+
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::{
+    Allocation, AllocationCreateFlags, AllocationCreateInfo, AllocationInfo, Allocator, MemoryUsage,
+};
+use ash::prelude::VkResult;
+use ash::vk;
+
+/// Device, queue and command pool used to record and submit transfer commands on one side of a copy.
+///
+/// The queue must support transfer operations and `command_pool` must have been created for its
+/// queue family. Access to the queue and the pool must be externally synchronized for the duration of the call.
+#[derive(Clone, Copy)]
+pub struct TransferQueue<'a> {
+    pub device: &'a ash::Device,
+    pub queue: vk::Queue,
+    pub command_pool: vk::CommandPool,
+}
+
+impl<'a> TransferQueue<'a> {
+    /// Records commands into a fresh one-time command buffer, submits it and waits for it to finish.
+    pub(crate) unsafe fn submit_and_wait<F>(&self, record: F) -> VkResult<()>
+    where
+        F: FnOnce(vk::CommandBuffer),
+    {
+        let allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let command_buffer = self.device.allocate_command_buffers(&allocate_info)?[0];
+
+        let result = self.record_and_submit(command_buffer, record);
+        self.device
+            .free_command_buffers(self.command_pool, &[command_buffer]);
+        result
+    }
+
+    unsafe fn record_and_submit<F>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        record: F,
+    ) -> VkResult<()>
+    where
+        F: FnOnce(vk::CommandBuffer),
+    {
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        self.device
+            .begin_command_buffer(command_buffer, &begin_info)?;
+        record(command_buffer);
+        self.device.end_command_buffer(command_buffer)?;
+
+        let fence = self
+            .device
+            .create_fence(&vk::FenceCreateInfo::default(), None)?;
+        let command_buffers = [command_buffer];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .build();
+        let result = self
+            .device
+            .queue_submit(self.queue, &[submit_info], fence)
+            .and_then(|_| self.device.wait_for_fences(&[fence], true, u64::MAX));
+        self.device.destroy_fence(fence, None);
+        result
+    }
+}
+
+/// Copies `size` bytes from the beginning of `src_buffer` (bound to `src_allocation` of `src_allocator`)
+/// into a new buffer created by `dst_allocator` from `buffer_info` and `allocation_info`.
+///
+/// The data always travels through host memory, so the two allocators may belong to different
+/// devices or even different physical devices. If either allocation ends up in a memory type that is
+/// not `ash::vk::MemoryPropertyFlags::HOST_VISIBLE`, a temporary staging buffer is created on that side
+/// and the copy is recorded and submitted on the corresponding `TransferQueue`, waiting for completion
+/// before returning. All intermediate resources are destroyed before the function returns.
+///
+/// `src_buffer` must have been created with `ash::vk::BufferUsageFlags::TRANSFER_SRC` unless its memory
+/// is host visible. `ash::vk::BufferUsageFlags::TRANSFER_DST` is added to `buffer_info.usage` automatically.
+///
+/// The returned buffer and allocation are owned by the caller and must be destroyed with
+/// `Allocator::destroy_buffer` on `dst_allocator`.
+///
+/// # Safety
+///
+/// `src_buffer` must be bound to `src_allocation`, be at least `size` bytes large, and not be written
+/// by the device or the host during the call. The device of each `TransferQueue` must be the device
+/// of the allocator on the same side, and both devices must stay valid for the duration of the call.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn copy_buffer_between_allocators(
+    src_allocator: &Allocator,
+    src_queue: &TransferQueue,
+    src_buffer: vk::Buffer,
+    src_allocation: &Allocation,
+    size: vk::DeviceSize,
+    dst_allocator: &Allocator,
+    dst_queue: &TransferQueue,
+    buffer_info: &vk::BufferCreateInfo,
+    allocation_info: &AllocationCreateInfo,
+) -> VkResult<(vk::Buffer, Allocation, AllocationInfo)> {
+    let data = read_buffer(src_allocator, src_queue, src_buffer, src_allocation, size)?;
+
+    let mut dst_buffer_info = *buffer_info;
+    dst_buffer_info.usage |= vk::BufferUsageFlags::TRANSFER_DST;
+    let (dst_buffer, dst_allocation, dst_allocation_info) =
+        dst_allocator.create_buffer(&dst_buffer_info, allocation_info)?;
+
+    if let Err(err) = write_buffer(dst_allocator, dst_queue, dst_buffer, &dst_allocation, &data) {
+        dst_allocator.destroy_buffer(dst_buffer, &dst_allocation);
+        return Err(err);
+    }
+
+    Ok((dst_buffer, dst_allocation, dst_allocation_info))
+}
+
+/// Reads the first `size` bytes of `buffer` into host memory, going through a staging buffer if needed.
+unsafe fn read_buffer(
+    allocator: &Allocator,
+    queue: &TransferQueue,
+    buffer: vk::Buffer,
+    allocation: &Allocation,
+    size: vk::DeviceSize,
+) -> VkResult<Vec<u8>> {
+    let mut data = vec![0u8; size as usize];
+    if is_host_visible(allocator, allocation) {
+        allocator.copy_from_allocation(allocation, 0, &mut data)?;
+        return Ok(data);
+    }
+
+    let (staging_buffer, staging_allocation) = create_staging_buffer(
+        allocator,
+        size,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        AllocationCreateFlags::HOST_ACCESS_RANDOM,
+    )?;
+    let result = queue
+        .submit_and_wait(|command_buffer| {
+            let region = vk::BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size,
+            };
+            queue
+                .device
+                .cmd_copy_buffer(command_buffer, buffer, staging_buffer, &[region]);
+        })
+        .and_then(|_| allocator.copy_from_allocation(&staging_allocation, 0, &mut data));
+    allocator.destroy_buffer(staging_buffer, &staging_allocation);
+
+    result.map(|_| data)
+}
+
+/// Writes `data` to the beginning of `buffer`, going through a staging buffer if needed.
+unsafe fn write_buffer(
+    allocator: &Allocator,
+    queue: &TransferQueue,
+    buffer: vk::Buffer,
+    allocation: &Allocation,
+    data: &[u8],
+) -> VkResult<()> {
+    if is_host_visible(allocator, allocation) {
+        return allocator.copy_to_allocation(data, allocation, 0);
+    }
+
+    let size = data.len() as vk::DeviceSize;
+    let (staging_buffer, staging_allocation) = create_staging_buffer(
+        allocator,
+        size,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+    )?;
+    let result = allocator
+        .copy_to_allocation(data, &staging_allocation, 0)
+        .and_then(|_| {
+            queue.submit_and_wait(|command_buffer| {
+                let region = vk::BufferCopy {
+                    src_offset: 0,
+                    dst_offset: 0,
+                    size,
+                };
+                queue
+                    .device
+                    .cmd_copy_buffer(command_buffer, staging_buffer, buffer, &[region]);
+            })
+        });
+    allocator.destroy_buffer(staging_buffer, &staging_allocation);
+
+    result
+}
+
+unsafe fn create_staging_buffer(
+    allocator: &Allocator,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    host_access: AllocationCreateFlags,
+) -> VkResult<(vk::Buffer, Allocation)> {
+    let buffer_info = vk::BufferCreateInfo::builder()
+        .size(size)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .build();
+    let allocation_info = AllocationCreateInfo {
+        flags: host_access,
+        usage: MemoryUsage::Auto,
+        ..Default::default()
+    };
+    let (buffer, allocation, _) = allocator.create_buffer(&buffer_info, &allocation_info)?;
+    Ok((buffer, allocation))
+}
+
+fn is_host_visible(allocator: &Allocator, allocation: &Allocation) -> bool {
+    allocator
+        .get_allocation_memory_properties(allocation)
+        .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+}
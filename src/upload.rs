@@ -0,0 +1,887 @@
+//! Recording uploads of host data into device resources through a `StagingManager`.
+//!
+//! An `Uploader` either records into command buffers of the caller, or, when created with
+//! `Uploader::with_transfer_queue`, owns a command pool for a (usually dedicated) transfer queue.
+//! In that mode uploads are batched into its own command buffer and submitted with
+//! `Uploader::submit`, which signals a timeline semaphore once the data is resident. Resources
+//! used by another queue family are released by the transfer queue; the returned `Submission`
+//! holds the matching acquire barriers to record on the queue that uses them.
+
+use crate::ash_compat;
+#[cfg(feature = "ash_0_38")]
+use crate::ash_compat::Builder;
+use crate::staging::{StagingConfig, StagingManager, StagingRegion};
+use crate::{Allocation, Allocator};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::collections::VecDeque;
+
+/// Alignment of staging offsets used for image copies.
+///
+/// `VkBufferImageCopy::bufferOffset` must be a multiple of 4 and of the texel block size of the
+/// format. 96 is a multiple of every texel block size of the formats this is meant for
+/// (1, 2, 3, 4, 6, 8, 12, 16, 24 and 32 bytes).
+const IMAGE_COPY_ALIGNMENT: vk::DeviceSize = 96;
+
+/// Staging buffer holding the texels of an image created by `Allocator::create_image_init`.
+///
+/// It must be destroyed with `InitStaging::destroy` once the device has finished executing the
+/// command buffer the copies were recorded into.
+#[must_use = "the staging buffer leaks unless it is destroyed"]
+#[derive(Debug)]
+pub struct InitStaging {
+    buffer: vk::Buffer,
+    allocation: Allocation,
+}
+
+impl InitStaging {
+    pub(crate) fn new(buffer: vk::Buffer, allocation: Allocation) -> Self {
+        InitStaging { buffer, allocation }
+    }
+
+    pub(crate) fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Destroys the staging buffer and frees its memory.
+    ///
+    /// # Safety
+    ///
+    /// The copies reading from the staging buffer must have completed.
+    pub unsafe fn destroy(self, allocator: &Allocator) {
+        allocator.destroy_buffer(self.buffer, &self.allocation);
+    }
+}
+
+/// Offsets of `mips` packed into one staging buffer, and the size of that buffer.
+pub(crate) fn mip_offsets(mips: &[&[u8]]) -> (Vec<vk::DeviceSize>, vk::DeviceSize) {
+    let mut offsets = Vec::with_capacity(mips.len());
+    let mut size: vk::DeviceSize = 0;
+    for mip in mips {
+        let offset = size.div_ceil(IMAGE_COPY_ALIGNMENT) * IMAGE_COPY_ALIGNMENT;
+        offsets.push(offset);
+        size = offset + mip.len() as vk::DeviceSize;
+    }
+    (offsets, size)
+}
+
+/// Records the copies of `Allocator::create_image_init`: every mip level and array layer of
+/// `image` is transitioned to `ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL`, level `i` is copied
+/// from `offsets[i]` of `staging_buffer`, and the whole image is transitioned to `final_layout`.
+pub(crate) unsafe fn record_image_init(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    image_info: &vk::ImageCreateInfo,
+    staging_buffer: vk::Buffer,
+    offsets: &[vk::DeviceSize],
+    final_layout: vk::ImageLayout,
+) {
+    let subresource_range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: image_info.mip_levels,
+        base_array_layer: 0,
+        layer_count: image_info.array_layers,
+    };
+    let to_transfer = vk::ImageMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::empty())
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range)
+        .build();
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[to_transfer],
+    );
+
+    let copies: Vec<vk::BufferImageCopy> = offsets
+        .iter()
+        .enumerate()
+        .map(|(level, &offset)| vk::BufferImageCopy {
+            buffer_offset: offset,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: level as u32,
+                base_array_layer: 0,
+                layer_count: image_info.array_layers,
+            },
+            image_offset: vk::Offset3D::default(),
+            image_extent: vk::Extent3D {
+                width: (image_info.extent.width >> level).max(1),
+                height: (image_info.extent.height >> level).max(1),
+                depth: (image_info.extent.depth >> level).max(1),
+            },
+        })
+        .collect();
+    if !copies.is_empty() {
+        device.cmd_copy_buffer_to_image(
+            command_buffer,
+            staging_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &copies,
+        );
+    }
+
+    if final_layout != vk::ImageLayout::TRANSFER_DST_OPTIMAL {
+        let from_transfer = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(final_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build();
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[from_transfer],
+        );
+    }
+}
+
+/// Part of a single subresource of an image.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRegion {
+    /// Image to write to. It must have been created with `ash::vk::ImageUsageFlags::TRANSFER_DST`.
+    pub image: vk::Image,
+
+    /// Format of the image, which sets the number of bytes the region takes.
+    pub format: vk::Format,
+
+    /// Aspect of the image to write, e.g. `ash::vk::ImageAspectFlags::COLOR`.
+    pub aspect_mask: vk::ImageAspectFlags,
+
+    /// Mip level to write.
+    pub mip_level: u32,
+
+    /// Array layer to write.
+    pub array_layer: u32,
+
+    /// Offset of the region in texels.
+    pub offset: vk::Offset3D,
+
+    /// Size of the region in texels.
+    pub extent: vk::Extent3D,
+}
+
+impl ImageRegion {
+    /// Number of bytes the texels of the region take when tightly packed, or `None` if the size
+    /// of a texel block of `format` is not known.
+    pub fn data_size(&self) -> Option<vk::DeviceSize> {
+        let (block_size, block_width, block_height) = texel_block(self.format, self.aspect_mask)?;
+        let blocks = vk::DeviceSize::from(self.extent.width.div_ceil(block_width))
+            * vk::DeviceSize::from(self.extent.height.div_ceil(block_height))
+            * vk::DeviceSize::from(self.extent.depth);
+        Some(blocks * block_size)
+    }
+}
+
+/// Size in bytes, width and height of a texel block of `format` as laid out in a buffer for a
+/// copy of `aspect_mask`. Covers the formats of Vulkan 1.0.
+fn texel_block(
+    format: vk::Format,
+    aspect_mask: vk::ImageAspectFlags,
+) -> Option<(vk::DeviceSize, u32, u32)> {
+    let stencil = aspect_mask == vk::ImageAspectFlags::STENCIL;
+    let block = match format.as_raw() {
+        // R4G4_UNORM_PACK8, R8_*
+        1 | 9..=15 => (1, 1, 1),
+        // 16-bit packed formats, R8G8_*, R16_*, D16_UNORM
+        2..=8 | 16..=22 | 70..=76 | 124 => (2, 1, 1),
+        // R8G8B8_*, B8G8R8_*
+        23..=36 => (3, 1, 1),
+        // R8G8B8A8_*, B8G8R8A8_*, 32-bit packed formats, R16G16_*, R32_*, X8_D24_UNORM_PACK32,
+        // D32_SFLOAT
+        37..=69 | 77..=83 | 98..=100 | 122 | 123 | 125 | 126 => (4, 1, 1),
+        // R16G16B16_*
+        84..=90 => (6, 1, 1),
+        // R16G16B16A16_*, R32G32_*, R64_*
+        91..=97 | 101..=103 | 110..=112 => (8, 1, 1),
+        // R32G32B32_*
+        104..=106 => (12, 1, 1),
+        // R32G32B32A32_*, R64G64_*
+        107..=109 | 113..=115 => (16, 1, 1),
+        // R64G64B64_*
+        116..=118 => (24, 1, 1),
+        // R64G64B64A64_*
+        119..=121 => (32, 1, 1),
+        // S8_UINT, and the stencil aspect of the depth/stencil formats
+        127 => (1, 1, 1),
+        128..=130 if stencil => (1, 1, 1),
+        // Depth aspect of D16_UNORM_S8_UINT
+        128 => (2, 1, 1),
+        // Depth aspect of D24_UNORM_S8_UINT and D32_SFLOAT_S8_UINT
+        129 | 130 => (4, 1, 1),
+        // BC1, BC4, ETC2 without 8-bit alpha, EAC_R11
+        131..=134 | 139 | 140 | 147..=150 | 153 | 154 => (8, 4, 4),
+        // BC2, BC3, BC5, BC6H, BC7, ETC2 with 8-bit alpha, EAC_R11G11
+        135..=138 | 141..=146 | 151 | 152 | 155 | 156 => (16, 4, 4),
+        // ASTC, an UNORM and an SRGB format per block extent
+        157..=184 => {
+            const EXTENTS: [(u32, u32); 14] = [
+                (4, 4),
+                (5, 4),
+                (5, 5),
+                (6, 5),
+                (6, 6),
+                (8, 5),
+                (8, 6),
+                (8, 8),
+                (10, 5),
+                (10, 6),
+                (10, 8),
+                (10, 10),
+                (12, 10),
+                (12, 12),
+            ];
+            let (width, height) = EXTENTS[(format.as_raw() - 157) as usize / 2];
+            (16, width, height)
+        }
+        _ => return None,
+    };
+    Some(block)
+}
+
+/// Fails if `data` is too short for the texels of `region`, so the copy can't read past the
+/// staging region.
+fn check_image_data(region: &ImageRegion, data: &[u8]) -> VkResult<()> {
+    let too_short = match region.data_size() {
+        Some(size) => (data.len() as vk::DeviceSize) < size,
+        None => data.is_empty(),
+    };
+    if too_short {
+        log::error!(
+            "{} bytes of data for a {:?} region of {:?}, which needs {:?} bytes",
+            data.len(),
+            region.extent,
+            region.format,
+            region.data_size()
+        );
+        return Err(vk::Result::ERROR_VALIDATION_FAILED_EXT);
+    }
+    Ok(())
+}
+
+/// Uploads submitted by `Uploader::submit`.
+#[derive(Debug, Clone, Default)]
+pub struct Submission {
+    /// Value the timeline semaphore of the uploader reaches once the uploads are resident.
+    pub value: u64,
+
+    /// Barriers acquiring the ownership of buffers released to another queue family.
+    pub buffer_barriers: Vec<ash_compat::BufferMemoryBarrier<'static>>,
+
+    /// Barriers acquiring the ownership of images released to another queue family, performing the
+    /// same layout transition as the release.
+    pub image_barriers: Vec<ash_compat::ImageMemoryBarrier<'static>>,
+}
+
+impl Submission {
+    /// Records the acquire barriers into `command_buffer`, which must be submitted to a queue of
+    /// the destination family after waiting for `Submission::value` on the timeline semaphore.
+    ///
+    /// Does nothing if no resource changed its queue family.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be in the recording state and belong to `device`.
+    pub unsafe fn record_acquire(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        if self.buffer_barriers.is_empty() && self.image_barriers.is_empty() {
+            return;
+        }
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::DependencyFlags::empty(),
+            &[],
+            &self.buffer_barriers,
+            &self.image_barriers,
+        );
+    }
+}
+
+/// Transfer queue, command pool and timeline semaphore owned by an `Uploader`.
+struct OwnedQueue {
+    queue: vk::Queue,
+    queue_family_index: u32,
+    command_pool: vk::CommandPool,
+    semaphore: vk::Semaphore,
+
+    /// Value signaled by the most recent submission.
+    submitted_value: u64,
+
+    /// Command buffer the current batch is recorded into.
+    recording: Option<vk::CommandBuffer>,
+
+    /// Submitted command buffers with the value signaled when they complete.
+    pending: VecDeque<(u64, vk::CommandBuffer)>,
+
+    /// Completed command buffers that can be recorded again.
+    free: Vec<vk::CommandBuffer>,
+
+    buffer_acquires: Vec<ash_compat::BufferMemoryBarrier<'static>>,
+    image_acquires: Vec<ash_compat::ImageMemoryBarrier<'static>>,
+}
+
+/// Records copies from host memory into buffers and images, staging the data through a `StagingManager`.
+///
+/// Data is copied into staging memory immediately. An uploader created with `Uploader::new` records the
+/// transfer commands into a command buffer provided by the caller, who is responsible for submitting
+/// it. Staging memory is recycled by `Uploader::end_frame`, which must only be called once the device
+/// has finished executing the command buffers recorded since the previous call.
+///
+/// An uploader created with `Uploader::with_transfer_queue` records into its own command buffers
+/// instead, see `Uploader::upload_buffer`, `Uploader::upload_image` and `Uploader::submit`. Its
+/// staging memory is recycled by `Uploader::recycle` once the device is done with it.
+pub struct Uploader<'a> {
+    device: &'a ash::Device,
+    staging: StagingManager<'a>,
+    transfer: Option<OwnedQueue>,
+}
+
+impl<'a> Uploader<'a> {
+    /// Creates an uploader that records commands for `device` and stages data in memory of `allocator`.
+    pub fn new(allocator: &'a Allocator, device: &'a ash::Device, config: StagingConfig) -> Self {
+        Uploader {
+            device,
+            staging: StagingManager::new(allocator, config),
+            transfer: None,
+        }
+    }
+
+    /// Creates an uploader that submits its uploads to `queue` of family `queue_family_index`,
+    /// creating a command pool for that family and a timeline semaphore.
+    ///
+    /// The `timelineSemaphore` feature of Vulkan 1.2 must be enabled on `device`. Access to `queue`
+    /// must be externally synchronized during `Uploader::submit`.
+    ///
+    /// # Safety
+    ///
+    /// `queue` must belong to `device` and family `queue_family_index`. The uploader must be dropped
+    /// before `device` is destroyed.
+    pub unsafe fn with_transfer_queue(
+        allocator: &'a Allocator,
+        device: &'a ash::Device,
+        config: StagingConfig,
+        queue: vk::Queue,
+        queue_family_index: u32,
+    ) -> VkResult<Self> {
+        let pool_info = vk::CommandPoolCreateInfo::builder()
+            .flags(
+                vk::CommandPoolCreateFlags::TRANSIENT
+                    | vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            )
+            .queue_family_index(queue_family_index);
+        let command_pool = device.create_command_pool(&pool_info, None)?;
+
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let semaphore_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
+        let semaphore = match device.create_semaphore(&semaphore_info, None) {
+            Ok(semaphore) => semaphore,
+            Err(err) => {
+                device.destroy_command_pool(command_pool, None);
+                return Err(err);
+            }
+        };
+
+        Ok(Uploader {
+            device,
+            staging: StagingManager::new(allocator, config),
+            transfer: Some(OwnedQueue {
+                queue,
+                queue_family_index,
+                command_pool,
+                semaphore,
+                submitted_value: 0,
+                recording: None,
+                pending: VecDeque::new(),
+                free: Vec::new(),
+                buffer_acquires: Vec::new(),
+                image_acquires: Vec::new(),
+            }),
+        })
+    }
+
+    /// Staging memory used by this uploader.
+    pub fn staging(&mut self) -> &mut StagingManager<'a> {
+        &mut self.staging
+    }
+
+    /// Writes `data` into `region` of an image, e.g. a tile of a texture atlas or a ring of a clipmap.
+    ///
+    /// `data` holds the texels of the region tightly packed. Fails with
+    /// `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` if it is shorter than
+    /// `ImageRegion::data_size`, before anything is recorded. Only the affected subresource is
+    /// transitioned from `old_layout` to `ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL` before the copy and
+    /// to `new_layout` after it; the rest of the image is left untouched. Pass the layout the
+    /// subresource is in as `old_layout` to preserve the texels outside of `region`.
+    ///
+    /// The barriers synchronize with all commands before and after the copy on the same queue.
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must be in the recording state and belong to the device of the uploader.
+    /// The subresource must be in `old_layout` when the copy executes, and the staging memory must
+    /// not be recycled with `Uploader::end_frame` before the command buffer has completed.
+    pub unsafe fn update_image_region(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        region: &ImageRegion,
+        data: &[u8],
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+    ) -> VkResult<()> {
+        check_image_data(region, data)?;
+        let subresource_range = self.record_image_copy(command_buffer, region, data, old_layout)?;
+
+        if new_layout != vk::ImageLayout::TRANSFER_DST_OPTIMAL {
+            let from_transfer = vk::ImageMemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(new_layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(region.image)
+                .subresource_range(subresource_range)
+                .build();
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[from_transfer],
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Recycles the staging memory of all uploads recorded since the previous call.
+    ///
+    /// See `StagingManager::end_frame`.
+    ///
+    /// # Safety
+    ///
+    /// The device must have finished executing every command buffer recorded since the previous
+    /// call.
+    pub unsafe fn end_frame(&mut self) {
+        self.staging.end_frame();
+    }
+
+    /// Writes `data` into `buffer` at `offset`, recording the copy into the current batch of the
+    /// transfer queue.
+    ///
+    /// If `dst_queue_family_index` is a family other than the one of the transfer queue, the
+    /// buffer is released to it and the `Submission` returned by the next `Uploader::submit` holds
+    /// the barrier that acquires it. Pass the family of the transfer queue or
+    /// `ash::vk::QUEUE_FAMILY_IGNORED` for buffers created with `ash::vk::SharingMode::CONCURRENT`.
+    ///
+    /// `buffer` must have been created with `ash::vk::BufferUsageFlags::TRANSFER_DST`. Does nothing
+    /// if `data` is empty. Fails with `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if the uploader
+    /// was not created with `Uploader::with_transfer_queue`.
+    ///
+    /// # Safety
+    ///
+    /// The range written must not be in use by the device until the next `Submission` is resident.
+    pub unsafe fn upload_buffer(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        data: &[u8],
+        dst_queue_family_index: u32,
+    ) -> VkResult<()> {
+        if self.transfer.is_none() {
+            return Err(vk::Result::ERROR_FEATURE_NOT_PRESENT);
+        }
+        // A copy of size 0 is invalid.
+        if data.is_empty() {
+            return Ok(());
+        }
+        let command_buffer = self.command_buffer()?;
+        let staging = self.stage(data, 4)?;
+        let copy = vk::BufferCopy {
+            src_offset: staging.offset,
+            dst_offset: offset,
+            size: data.len() as vk::DeviceSize,
+        };
+        self.device
+            .cmd_copy_buffer(command_buffer, staging.buffer, buffer, &[copy]);
+
+        let transfer = self.transfer.as_mut().unwrap();
+        if !needs_ownership_transfer(transfer.queue_family_index, dst_queue_family_index) {
+            return Ok(());
+        }
+        let release = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::empty())
+            .src_queue_family_index(transfer.queue_family_index)
+            .dst_queue_family_index(dst_queue_family_index)
+            .buffer(buffer)
+            .offset(offset)
+            .size(data.len() as vk::DeviceSize)
+            .build();
+        self.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[release],
+            &[],
+        );
+        transfer.buffer_acquires.push(vk::BufferMemoryBarrier {
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            ..release
+        });
+        Ok(())
+    }
+
+    /// Writes `data` into `region` of an image like `Uploader::update_image_region`, recording the
+    /// copy into the current batch of the transfer queue.
+    ///
+    /// The subresource ends up in `new_layout`. If `dst_queue_family_index` is a family other than
+    /// the one of the transfer queue, the layout transition is part of the release of the image to
+    /// that family, and the `Submission` returned by the next `Uploader::submit` holds the barrier
+    /// that acquires it.
+    ///
+    /// Does nothing if `data` is empty. Fails with `ash::vk::Result::ERROR_FEATURE_NOT_PRESENT` if
+    /// the uploader was not created with `Uploader::with_transfer_queue`, and with
+    /// `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT` if `data` is shorter than
+    /// `ImageRegion::data_size`.
+    ///
+    /// # Safety
+    ///
+    /// The subresource must not be in use by the device until the next `Submission` is resident.
+    pub unsafe fn upload_image(
+        &mut self,
+        region: &ImageRegion,
+        data: &[u8],
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+        dst_queue_family_index: u32,
+    ) -> VkResult<()> {
+        if self.transfer.is_none() {
+            return Err(vk::Result::ERROR_FEATURE_NOT_PRESENT);
+        }
+        // Nothing to copy, and a copy from an empty staging region would read out of bounds.
+        if data.is_empty() {
+            return Ok(());
+        }
+        check_image_data(region, data)?;
+        let command_buffer = self.command_buffer()?;
+        let subresource_range = self.record_image_copy(command_buffer, region, data, old_layout)?;
+
+        let transfer = self.transfer.as_mut().unwrap();
+        let transfers_ownership =
+            needs_ownership_transfer(transfer.queue_family_index, dst_queue_family_index);
+        if !transfers_ownership && new_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL {
+            return Ok(());
+        }
+        let (src_family, dst_family) = if transfers_ownership {
+            (transfer.queue_family_index, dst_queue_family_index)
+        } else {
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        };
+        let release = vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::empty())
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(new_layout)
+            .src_queue_family_index(src_family)
+            .dst_queue_family_index(dst_family)
+            .image(region.image)
+            .subresource_range(subresource_range)
+            .build();
+        self.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[release],
+        );
+        if transfers_ownership {
+            transfer.image_acquires.push(vk::ImageMemoryBarrier {
+                src_access_mask: vk::AccessFlags::empty(),
+                dst_access_mask: vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+                ..release
+            });
+        }
+        Ok(())
+    }
+
+    /// Submits the uploads recorded since the previous call to the transfer queue.
+    ///
+    /// The timeline semaphore of the uploader reaches `Submission::value` once the data is
+    /// resident. Queues using the uploaded resources must wait for that value, and record
+    /// `Submission::record_acquire` for resources released to their family. If nothing was
+    /// recorded, nothing is submitted and the value of the previous submission is returned.
+    ///
+    /// # Safety
+    ///
+    /// `queue` must not be used by other threads during the call.
+    pub unsafe fn submit(&mut self) -> VkResult<Submission> {
+        let transfer = self
+            .transfer
+            .as_mut()
+            .ok_or(vk::Result::ERROR_FEATURE_NOT_PRESENT)?;
+        let command_buffer = match transfer.recording.take() {
+            Some(command_buffer) => command_buffer,
+            None => {
+                return Ok(Submission {
+                    value: transfer.submitted_value,
+                    ..Default::default()
+                })
+            }
+        };
+        self.device.end_command_buffer(command_buffer)?;
+
+        let value = transfer.submitted_value + 1;
+        let signal_values = [value];
+        let mut timeline_info =
+            vk::TimelineSemaphoreSubmitInfo::builder().signal_semaphore_values(&signal_values);
+        let command_buffers = [command_buffer];
+        let signal_semaphores = [transfer.semaphore];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&signal_semaphores)
+            .push_next(&mut timeline_info)
+            .build();
+        if let Err(err) =
+            self.device
+                .queue_submit(transfer.queue, &[submit_info], vk::Fence::null())
+        {
+            transfer.free.push(command_buffer);
+            return Err(err);
+        }
+        transfer.submitted_value = value;
+        transfer.pending.push_back((value, command_buffer));
+
+        Ok(Submission {
+            value,
+            buffer_barriers: std::mem::take(&mut transfer.buffer_acquires),
+            image_barriers: std::mem::take(&mut transfer.image_acquires),
+        })
+    }
+
+    /// Timeline semaphore signaled by `Uploader::submit`, or a null handle if the uploader was not
+    /// created with `Uploader::with_transfer_queue`.
+    pub fn timeline_semaphore(&self) -> vk::Semaphore {
+        self.transfer
+            .as_ref()
+            .map_or(vk::Semaphore::null(), |transfer| transfer.semaphore)
+    }
+
+    /// Current value of the timeline semaphore: every `Submission` with a value up to it is resident.
+    ///
+    /// # Safety
+    ///
+    /// The device must not have been lost.
+    pub unsafe fn completed_value(&self) -> VkResult<u64> {
+        let transfer = self
+            .transfer
+            .as_ref()
+            .ok_or(vk::Result::ERROR_FEATURE_NOT_PRESENT)?;
+        self.device.get_semaphore_counter_value(transfer.semaphore)
+    }
+
+    /// Waits until the uploads of the submission with `value` are resident, or `timeout`
+    /// nanoseconds passed, in which case `ash::vk::Result::TIMEOUT` is returned.
+    ///
+    /// # Safety
+    ///
+    /// The device must not have been lost.
+    pub unsafe fn wait(&self, value: u64, timeout: u64) -> VkResult<()> {
+        let transfer = self
+            .transfer
+            .as_ref()
+            .ok_or(vk::Result::ERROR_FEATURE_NOT_PRESENT)?;
+        let semaphores = [transfer.semaphore];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values);
+        self.device.wait_semaphores(&wait_info, timeout)
+    }
+
+    /// Reuses the command buffers of completed submissions, and recycles the staging memory like
+    /// `Uploader::end_frame` once every submission completed and no batch is being recorded.
+    /// Returns whether the staging memory was recycled.
+    ///
+    /// # Safety
+    ///
+    /// The device must not have been lost.
+    pub unsafe fn recycle(&mut self) -> VkResult<bool> {
+        let completed = self.completed_value()?;
+        let transfer = self.transfer.as_mut().unwrap();
+        while let Some(&(value, command_buffer)) = transfer.pending.front() {
+            if value > completed {
+                break;
+            }
+            transfer.pending.pop_front();
+            transfer.free.push(command_buffer);
+        }
+        if !transfer.pending.is_empty() || transfer.recording.is_some() {
+            return Ok(false);
+        }
+        self.staging.end_frame();
+        Ok(true)
+    }
+
+    /// Command buffer of the current batch, which is begun if needed.
+    unsafe fn command_buffer(&mut self) -> VkResult<vk::CommandBuffer> {
+        let transfer = self
+            .transfer
+            .as_mut()
+            .ok_or(vk::Result::ERROR_FEATURE_NOT_PRESENT)?;
+        if let Some(command_buffer) = transfer.recording {
+            return Ok(command_buffer);
+        }
+        let command_buffer = match transfer.free.pop() {
+            Some(command_buffer) => command_buffer,
+            None => {
+                let allocate_info = vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(transfer.command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1);
+                self.device.allocate_command_buffers(&allocate_info)?[0]
+            }
+        };
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        if let Err(err) = self
+            .device
+            .begin_command_buffer(command_buffer, &begin_info)
+        {
+            transfer.free.push(command_buffer);
+            return Err(err);
+        }
+        transfer.recording = Some(command_buffer);
+        Ok(command_buffer)
+    }
+
+    /// Stages `data` and writes it into the first bytes of a fresh staging region.
+    unsafe fn stage(&mut self, data: &[u8], alignment: vk::DeviceSize) -> VkResult<StagingRegion> {
+        let staging = self
+            .staging
+            .allocate(data.len() as vk::DeviceSize, alignment)?;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), staging.mapped, data.len());
+        self.staging.flush(&staging)?;
+        Ok(staging)
+    }
+
+    /// Stages `data`, transitions the subresource of `region` from `old_layout` to
+    /// `ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL` and records the copy. Returns the subresource.
+    unsafe fn record_image_copy(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        region: &ImageRegion,
+        data: &[u8],
+        old_layout: vk::ImageLayout,
+    ) -> VkResult<vk::ImageSubresourceRange> {
+        let staging = self.stage(data, IMAGE_COPY_ALIGNMENT)?;
+
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: region.aspect_mask,
+            base_mip_level: region.mip_level,
+            level_count: 1,
+            base_array_layer: region.array_layer,
+            layer_count: 1,
+        };
+
+        if old_layout != vk::ImageLayout::TRANSFER_DST_OPTIMAL {
+            let to_transfer = vk::ImageMemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(old_layout)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(region.image)
+                .subresource_range(subresource_range)
+                .build();
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+        }
+
+        let copy = vk::BufferImageCopy {
+            buffer_offset: staging.offset,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: region.aspect_mask,
+                mip_level: region.mip_level,
+                base_array_layer: region.array_layer,
+                layer_count: 1,
+            },
+            image_offset: region.offset,
+            image_extent: region.extent,
+        };
+        self.device.cmd_copy_buffer_to_image(
+            command_buffer,
+            staging.buffer,
+            region.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[copy],
+        );
+        Ok(subresource_range)
+    }
+}
+
+impl<'a> Drop for Uploader<'a> {
+    fn drop(&mut self) {
+        if let Some(transfer) = self.transfer.take() {
+            unsafe {
+                // The staging memory and command buffers must outlive the submitted uploads.
+                let semaphores = [transfer.semaphore];
+                let values = [transfer.submitted_value];
+                let wait_info = vk::SemaphoreWaitInfo::builder()
+                    .semaphores(&semaphores)
+                    .values(&values);
+                let _ = self.device.wait_semaphores(&wait_info, u64::MAX);
+                self.device
+                    .destroy_command_pool(transfer.command_pool, None);
+                self.device.destroy_semaphore(transfer.semaphore, None);
+            }
+        }
+    }
+}
+
+/// Whether a resource written on the queue family `transfer_family` must be released to `dst_family`.
+fn needs_ownership_transfer(transfer_family: u32, dst_family: u32) -> bool {
+    dst_family != vk::QUEUE_FAMILY_IGNORED && dst_family != transfer_family
+}
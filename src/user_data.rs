@@ -0,0 +1,58 @@
+//! Typed user data attached to allocations.
+//!
+//! VMA's `pUserData` is a raw pointer the application has to keep alive and free itself, which makes
+//! it easy to leak the data or to free it while the allocation still points at it. Data set with
+//! `Allocator::set_user_data` is owned by the allocator instead and dropped when the allocation is
+//! freed. It is kept next to VMA's own field, so `AllocationInfo::get_user_data` is not affected.
+
+use crate::Allocation;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// Boxed data of live allocations, shared between clones of an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct UserDataStore {
+    data: Mutex<HashMap<usize, Box<dyn Any + Send>>>,
+}
+
+impl UserDataStore {
+    fn data(&self) -> MutexGuard<'_, HashMap<usize, Box<dyn Any + Send>>> {
+        self.data
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn set(
+        &self,
+        allocation: &Allocation,
+        data: Box<dyn Any + Send>,
+    ) -> Option<Box<dyn Any + Send>> {
+        self.data().insert(*allocation as usize, data)
+    }
+
+    pub(crate) fn with<T: Any, R>(
+        &self,
+        allocation: &Allocation,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        let mut data = self.data();
+        let value = data.get_mut(&(*allocation as usize))?.downcast_mut::<T>()?;
+        Some(f(value))
+    }
+
+    pub(crate) fn take<T: Any>(&self, allocation: &Allocation) -> Option<Box<T>> {
+        let key = *allocation as usize;
+        let mut data = self.data();
+        if !data.get(&key)?.is::<T>() {
+            return None;
+        }
+        data.remove(&key)?.downcast().ok()
+    }
+
+    /// Removes the data of an allocation that is being freed. The data is returned so it is dropped
+    /// after the lock is released, in case its `Drop` uses the allocator.
+    pub(crate) fn remove(&self, allocation: &Allocation) -> Option<Box<dyn Any + Send>> {
+        self.data().remove(&(*allocation as usize))
+    }
+}
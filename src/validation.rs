@@ -0,0 +1,330 @@
+//! Runtime validation of this crate's API usage, enabled with the `validation` feature.
+//!
+//! The allocator keeps a shadow record of every allocation and pool it hands out (how many times it
+//! is mapped, which resource it is bound to, which pool it came from, whether it has been freed) and
+//! checks each wrapper call against it before it reaches VMA. Misuse such as unmapping memory that is
+//! not mapped, binding an allocation twice or freeing an allocation while it is still mapped is
+//! reported with the name of the offending call instead of surfacing later as undefined behavior
+//! inside the C++ library.
+//!
+//! Allocation handles are raw VMA pointers, and VMA recycles the memory of freed allocation objects,
+//! so a new allocation often gets the handle of one freed just before. Every record is therefore
+//! tagged with a generation that is bumped each time its handle is handed out again, and messages
+//! about freed handles name the generation that was freed and the call that freed it. A stale handle
+//! that has already been handed out again is indistinguishable from the new allocation and is not
+//! caught.
+//!
+//! How a violation is reported is chosen with `Allocator::set_violation_policy`, so the same build can
+//! panic during development and log in a shipping configuration.
+
+use crate::{ffi, Allocation, AllocationCreateFlags, AllocatorPool, BoundResource};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// What the allocator does when validation detects misuse of its API.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationPolicy {
+    /// Panic with a message naming the offending call. This is the default.
+    #[default]
+    Panic,
+
+    /// Log the violation with `log::error!` and forward the call to VMA anyway.
+    Log,
+
+    /// Log the violation and reject the call without forwarding it to VMA. Calls that return a
+    /// `VkResult` fail with `ash::vk::Result::ERROR_VALIDATION_FAILED_EXT`; calls without a result,
+    /// like `Allocator::free_memory`, are skipped.
+    Error,
+}
+
+#[derive(Debug)]
+struct AllocationState {
+    generation: u64,
+    map_count: u32,
+    persistently_mapped: bool,
+    can_alias: bool,
+    bound: Option<BoundResource>,
+    pool: Option<usize>,
+}
+
+/// Last generation of a handle that is currently freed.
+#[derive(Debug)]
+struct FreedState {
+    generation: u64,
+    operation: &'static str,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    allocations: HashMap<usize, AllocationState>,
+    freed: HashMap<usize, FreedState>,
+    /// Number of times each handle has been handed out, including the current one.
+    generations: HashMap<usize, u64>,
+    pools: HashMap<usize, usize>,
+}
+
+impl State {
+    fn live(&mut self, allocation: &Allocation) -> Result<&mut AllocationState, String> {
+        let key = *allocation as usize;
+        if let Some(freed) = self.freed.get(&key) {
+            return Err(format!(
+                "allocation {:p} (generation {}) is used after it has been freed by `{}`",
+                *allocation, freed.generation, freed.operation
+            ));
+        }
+        self.allocations.get_mut(&key).ok_or_else(|| {
+            format!(
+                "allocation {:p} was not created by this allocator",
+                *allocation
+            )
+        })
+    }
+}
+
+/// Shadow state used to validate calls made through an `Allocator`.
+#[derive(Debug, Default)]
+pub(crate) struct Validator {
+    state: Mutex<State>,
+    policy: Mutex<ViolationPolicy>,
+}
+
+impl Validator {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn policy(&self) -> ViolationPolicy {
+        *self
+            .policy
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn set_policy(&self, policy: ViolationPolicy) {
+        *self
+            .policy
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = policy;
+    }
+
+    fn report(&self, operation: &str, result: Result<(), String>) -> VkResult<()> {
+        let message = match result {
+            Ok(()) => return Ok(()),
+            Err(message) => message,
+        };
+        match self.policy() {
+            ViolationPolicy::Panic => {
+                panic!("vk-mem validation error in `{}`: {}", operation, message)
+            }
+            ViolationPolicy::Log => {
+                log::error!("vk-mem validation error in `{}`: {}", operation, message);
+                Ok(())
+            }
+            ViolationPolicy::Error => {
+                log::error!("vk-mem validation error in `{}`: {}", operation, message);
+                Err(vk::Result::ERROR_VALIDATION_FAILED_EXT)
+            }
+        }
+    }
+
+    pub(crate) fn register_pool(&self, pool: &AllocatorPool) {
+        self.state().pools.insert(*pool as usize, 0);
+    }
+
+    pub(crate) fn unregister_pool(&self, operation: &str, pool: &AllocatorPool) -> VkResult<()> {
+        let key = *pool as usize;
+        let result = match self.state().pools.get(&key) {
+            None => Err(format!(
+                "pool {:p} was not created by this allocator",
+                *pool
+            )),
+            Some(0) => Ok(()),
+            Some(live) => Err(format!(
+                "pool {:p} is destroyed while {} of its allocations are still alive",
+                *pool, live
+            )),
+        };
+        self.report(operation, result)?;
+        self.state().pools.remove(&key);
+        Ok(())
+    }
+
+    pub(crate) fn register_allocation(
+        &self,
+        operation: &str,
+        allocation: &Allocation,
+        create_info: &ffi::VmaAllocationCreateInfo,
+        bound: Option<BoundResource>,
+    ) {
+        let flags = AllocationCreateFlags::from_bits_truncate(create_info.flags);
+        let bound = bound.filter(|_| !flags.contains(AllocationCreateFlags::CREATE_DONT_BIND));
+        let result = {
+            let mut state = self.state();
+            let key = *allocation as usize;
+            let pool = Some(create_info.pool as usize).filter(|&pool| pool != 0);
+            let pool_result = match pool {
+                Some(pool) => match state.pools.get_mut(&pool) {
+                    Some(live) => {
+                        *live += 1;
+                        Ok(())
+                    }
+                    None => Err(format!(
+                        "allocation {:p} was made from pool {:p} which was not created by this allocator",
+                        *allocation, pool as *const u8
+                    )),
+                },
+                None => Ok(()),
+            };
+            state.freed.remove(&key);
+            let generation = state.generations.entry(key).or_default();
+            *generation += 1;
+            let generation = *generation;
+            state.allocations.insert(
+                key,
+                AllocationState {
+                    generation,
+                    map_count: 0,
+                    persistently_mapped: flags.contains(AllocationCreateFlags::MAPPED),
+                    can_alias: flags.contains(AllocationCreateFlags::CAN_ALIAS),
+                    bound,
+                    pool,
+                },
+            );
+            pool_result
+        };
+        // The allocation exists either way, so the violation is only reported.
+        let _ = self.report(operation, result);
+    }
+
+    pub(crate) fn release_allocation(
+        &self,
+        operation: &'static str,
+        allocation: &Allocation,
+    ) -> VkResult<()> {
+        if allocation.is_null() {
+            return Ok(());
+        }
+
+        let key = *allocation as usize;
+        let result = {
+            let state = self.state();
+            match (state.allocations.get(&key), state.freed.get(&key)) {
+                (None, Some(freed)) => Err(format!(
+                    "allocation {:p} (generation {}) is freed twice, it was already freed by `{}`",
+                    *allocation, freed.generation, freed.operation
+                )),
+                (None, None) => Err(format!(
+                    "allocation {:p} was not created by this allocator",
+                    *allocation
+                )),
+                (Some(tracked), _) if tracked.map_count > 0 => Err(format!(
+                    "allocation {:p} is freed while still mapped ({} outstanding `map_memory` calls)",
+                    *allocation, tracked.map_count
+                )),
+                (Some(_), _) => Ok(()),
+            }
+        };
+        self.report(operation, result)?;
+
+        let mut state = self.state();
+        if let Some(released) = state.allocations.remove(&key) {
+            state.freed.insert(
+                key,
+                FreedState {
+                    generation: released.generation,
+                    operation,
+                },
+            );
+            if let Some(live) = released.pool.and_then(|pool| state.pools.get_mut(&pool)) {
+                *live -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_allocation(
+        &self,
+        operation: &str,
+        allocation: &Allocation,
+    ) -> VkResult<()> {
+        let result = self.state().live(allocation).map(|_| ());
+        self.report(operation, result)
+    }
+
+    /// Records a successful mapping. The allocation is checked with `Validator::check_allocation`
+    /// before it is mapped.
+    pub(crate) fn on_map(&self, allocation: &Allocation) {
+        if let Ok(tracked) = self.state().live(allocation) {
+            tracked.map_count += 1;
+        }
+    }
+
+    pub(crate) fn on_unmap(&self, operation: &str, allocation: &Allocation) -> VkResult<()> {
+        let result = self.state().live(allocation).and_then(|tracked| {
+            if tracked.map_count == 0 {
+                Err(format!(
+                    "allocation {:p} is unmapped without a matching `map_memory` call{}",
+                    *allocation,
+                    if tracked.persistently_mapped {
+                        " (the mapping made by `AllocationCreateFlags::MAPPED` must not be unmapped)"
+                    } else {
+                        ""
+                    }
+                ))
+            } else {
+                tracked.map_count -= 1;
+                Ok(())
+            }
+        });
+        self.report(operation, result)
+    }
+
+    pub(crate) fn check_bind(
+        &self,
+        operation: &str,
+        allocation: &Allocation,
+        resource: BoundResource,
+    ) -> VkResult<()> {
+        let result = self.state().live(allocation).and_then(|tracked| {
+            match tracked.bound {
+                Some(existing) if !tracked.can_alias => Err(format!(
+                    "allocation {:p} is bound to {} while already bound to {}; create it with `AllocationCreateFlags::CAN_ALIAS` to alias resources",
+                    *allocation, resource, existing
+                )),
+                _ => Ok(()),
+            }
+        });
+        self.report(operation, result)
+    }
+
+    /// Records a successful bind that was checked with `Validator::check_bind`.
+    pub(crate) fn on_bind(&self, allocation: &Allocation, resource: BoundResource) {
+        if let Ok(tracked) = self.state().live(allocation) {
+            tracked.bound = Some(resource);
+        }
+    }
+
+    pub(crate) fn check_destroy(&self) {
+        let result = {
+            let state = self.state();
+            if state.allocations.is_empty() && state.pools.is_empty() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "allocator is destroyed while {} allocations and {} pools are still alive",
+                    state.allocations.len(),
+                    state.pools.len()
+                ))
+            }
+        };
+        // Don't turn an unwinding panic into an abort.
+        if !std::thread::panicking() {
+            // VMA has to be destroyed regardless, so the violation is only reported.
+            let _ = self.report("Allocator::destroy", result);
+        }
+    }
+}
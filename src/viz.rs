@@ -0,0 +1,381 @@
+//! Renders the memory blocks of an allocator and the allocations inside them as SVG or Graphviz DOT.
+//!
+//! `MemoryLayout::capture` reads the detailed map of `Allocator::build_stats_string`, so allocations
+//! are labeled with the names set with `Allocator::set_allocation_name`. Blocks are drawn to scale
+//! relative to the largest block and colored by the custom pool they belong to, or by memory type
+//! for the default pools, which makes fragmentation (many small gaps between allocations) and
+//! half-empty blocks easy to spot.
+
+use crate::json::Value;
+use crate::{format_bytes, Allocator};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::fmt::Write;
+
+/// An allocation inside a `BlockLayout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationLayout {
+    /// Offset of the allocation inside its block.
+    pub offset: vk::DeviceSize,
+
+    /// Size of the allocation in bytes.
+    pub size: vk::DeviceSize,
+
+    /// Kind of resource as reported by VMA, e.g. `BUFFER` or `IMAGE_OPTIMAL`.
+    pub kind: String,
+
+    /// Name set with `Allocator::set_allocation_name`.
+    pub name: Option<String>,
+}
+
+/// A `ash::vk::DeviceMemory` block and the allocations made from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockLayout {
+    /// Memory type of the block.
+    pub memory_type_index: u32,
+
+    /// Name of the custom pool the block belongs to (`pool #N` for unnamed pools), or `None` for the
+    /// default pools.
+    pub pool: Option<String>,
+
+    /// Whether the block is a dedicated allocation, holding exactly one allocation.
+    pub dedicated: bool,
+
+    /// Size of the block in bytes.
+    pub size: vk::DeviceSize,
+
+    /// Allocations in the block, ordered by offset. Everything between them is free.
+    pub allocations: Vec<AllocationLayout>,
+}
+
+impl BlockLayout {
+    /// Pool or memory type the block is colored and grouped by.
+    pub fn category(&self) -> String {
+        match &self.pool {
+            Some(pool) => pool.clone(),
+            None => format!("memory type {}", self.memory_type_index),
+        }
+    }
+
+    fn label(&self, index: usize) -> String {
+        format!(
+            "{} {} {} ({})",
+            self.category(),
+            if self.dedicated { "dedicated" } else { "block" },
+            index,
+            format_bytes(self.size)
+        )
+    }
+
+    /// Used and free ranges of the block, ordered by offset.
+    fn ranges(&self) -> Vec<(vk::DeviceSize, vk::DeviceSize, Option<&AllocationLayout>)> {
+        let mut ranges = Vec::new();
+        let mut end = 0;
+        for allocation in &self.allocations {
+            if allocation.offset > end {
+                ranges.push((end, allocation.offset - end, None));
+            }
+            ranges.push((allocation.offset, allocation.size, Some(allocation)));
+            end = end.max(allocation.offset + allocation.size);
+        }
+        if self.size > end {
+            ranges.push((end, self.size - end, None));
+        }
+        ranges
+    }
+}
+
+/// Snapshot of all blocks of an allocator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryLayout {
+    pub blocks: Vec<BlockLayout>,
+}
+
+const PALETTE: [&str; 10] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+    "#9c755f", "#bab0ac",
+];
+const FREE_COLOR: &str = "#eeeeee";
+
+const SVG_LABEL_WIDTH: f64 = 300.0;
+const SVG_BAR_WIDTH: f64 = 900.0;
+const SVG_ROW_HEIGHT: f64 = 28.0;
+const SVG_BAR_HEIGHT: f64 = 20.0;
+
+impl MemoryLayout {
+    /// Captures the current layout of `allocator`.
+    pub fn capture(allocator: &Allocator) -> VkResult<Self> {
+        let json = allocator.build_stats_string(true)?;
+        Self::from_stats_json(&json).ok_or(vk::Result::ERROR_UNKNOWN)
+    }
+
+    /// Reads the layout from the output of `Allocator::build_stats_string` with `detailed_map` set.
+    ///
+    /// Returns `None` if `json` can't be parsed.
+    pub fn from_stats_json(json: &str) -> Option<Self> {
+        let root = Value::parse(json)?;
+        let mut blocks = Vec::new();
+
+        if let Some(default_pools) = root.get("DefaultPools") {
+            for (memory_type, pool) in default_pools.entries() {
+                if let Some(memory_type_index) = parse_memory_type(memory_type) {
+                    read_pool(pool, memory_type_index, None, &mut blocks);
+                }
+            }
+        }
+
+        if let Some(custom_pools) = root.get("CustomPools") {
+            for (memory_type, pools) in custom_pools.entries() {
+                if let Some(memory_type_index) = parse_memory_type(memory_type) {
+                    for (index, pool) in pools.elements().iter().enumerate() {
+                        let name = pool
+                            .get("Name")
+                            .and_then(Value::as_str)
+                            .map(str::to_owned)
+                            .unwrap_or_else(|| format!("pool #{}", index));
+                        read_pool(pool, memory_type_index, Some(name), &mut blocks);
+                    }
+                }
+            }
+        }
+
+        Some(MemoryLayout { blocks })
+    }
+
+    /// Renders one bar per block, scaled relative to the largest block. Hovering an allocation shows
+    /// its name, kind, offset and size.
+    pub fn to_svg(&self) -> String {
+        let largest = self
+            .blocks
+            .iter()
+            .map(|block| block.size)
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        let width = SVG_LABEL_WIDTH + SVG_BAR_WIDTH + 10.0;
+        let height = SVG_ROW_HEIGHT * self.blocks.len() as f64 + 10.0;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"#,
+            width, height
+        );
+        for (index, block) in self.blocks.iter().enumerate() {
+            let y = 5.0 + SVG_ROW_HEIGHT * index as f64;
+            let scale = SVG_BAR_WIDTH / largest as f64;
+            let color = category_color(&block.category());
+            let _ = writeln!(
+                svg,
+                r#"<text x="5" y="{}">{}</text>"#,
+                y + SVG_BAR_HEIGHT * 0.75,
+                escape_xml(&block.label(index))
+            );
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{}" y="{}" width="{:.2}" height="{}" fill="{}" stroke="#999999"/>"##,
+                SVG_LABEL_WIDTH,
+                y,
+                (block.size as f64 * scale).max(1.0),
+                SVG_BAR_HEIGHT,
+                FREE_COLOR
+            );
+            for allocation in &block.allocations {
+                let _ = writeln!(
+                    svg,
+                    r##"<rect x="{:.2}" y="{}" width="{:.2}" height="{}" fill="{}" stroke="#333333" stroke-width="0.5"><title>{}</title></rect>"##,
+                    SVG_LABEL_WIDTH + allocation.offset as f64 * scale,
+                    y,
+                    (allocation.size as f64 * scale).max(1.0),
+                    SVG_BAR_HEIGHT,
+                    color,
+                    escape_xml(&describe(allocation))
+                );
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Renders a Graphviz graph with one cluster per pool or memory type and one node per block,
+    /// whose cells are the allocations and free ranges of the block, sized by their share of it.
+    pub fn to_dot(&self) -> String {
+        let mut categories: Vec<String> = Vec::new();
+        for block in &self.blocks {
+            let category = block.category();
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+
+        let mut dot = String::from("digraph memory {\n    rankdir=LR;\n    node [shape=plaintext, fontname=\"monospace\"];\n");
+        for (cluster, category) in categories.iter().enumerate() {
+            let _ = writeln!(
+                dot,
+                "    subgraph cluster_{} {{\n        label=\"{}\";\n        color=\"{}\";",
+                cluster,
+                escape_dot(category),
+                category_color(category)
+            );
+            for (index, block) in self
+                .blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| &block.category() == category)
+            {
+                let _ = write!(
+                    dot,
+                    "        block_{} [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"><tr><td>{}</td>",
+                    index,
+                    escape_xml(&block.label(index))
+                );
+                let block_size = block.size.max(1) as f64;
+                for (_, size, allocation) in block.ranges() {
+                    let cell_width = ((size as f64 / block_size) * 600.0).max(8.0) as u32;
+                    let (color, text) = match allocation {
+                        Some(allocation) => (
+                            category_color(category),
+                            allocation
+                                .name
+                                .clone()
+                                .unwrap_or_else(|| allocation.kind.clone()),
+                        ),
+                        None => (FREE_COLOR, String::new()),
+                    };
+                    let _ = write!(
+                        dot,
+                        "<td width=\"{}\" bgcolor=\"{}\" title=\"{}\">{}</td>",
+                        cell_width,
+                        color,
+                        escape_xml(&match allocation {
+                            Some(allocation) => describe(allocation),
+                            None => format!("free, {}", format_bytes(size)),
+                        }),
+                        escape_xml(&text)
+                    );
+                }
+                dot.push_str("</tr></table>>];\n");
+            }
+            dot.push_str("    }\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn parse_memory_type(key: &str) -> Option<u32> {
+    key.strip_prefix("Type ")?.trim().parse().ok()
+}
+
+fn read_pool(
+    pool: &Value,
+    memory_type_index: u32,
+    name: Option<String>,
+    blocks: &mut Vec<BlockLayout>,
+) {
+    if let Some(pool_blocks) = pool.get("Blocks") {
+        for (_, block) in pool_blocks.entries() {
+            let mut allocations: Vec<AllocationLayout> = block
+                .get("Suballocations")
+                .map(Value::elements)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|suballocation| {
+                    let kind = suballocation.get("Type")?.as_str()?;
+                    if kind == "FREE" {
+                        return None;
+                    }
+                    Some(AllocationLayout {
+                        offset: suballocation.get("Offset")?.as_u64()?,
+                        size: suballocation.get("Size")?.as_u64()?,
+                        kind: kind.to_owned(),
+                        name: allocation_name(suballocation),
+                    })
+                })
+                .collect();
+            allocations.sort_by_key(|allocation| allocation.offset);
+            blocks.push(BlockLayout {
+                memory_type_index,
+                pool: name.clone(),
+                dedicated: false,
+                size: block.get("TotalBytes").and_then(Value::as_u64).unwrap_or(0),
+                allocations,
+            });
+        }
+    }
+
+    for dedicated in pool
+        .get("DedicatedAllocations")
+        .map(Value::elements)
+        .unwrap_or_default()
+    {
+        let size = dedicated.get("Size").and_then(Value::as_u64).unwrap_or(0);
+        blocks.push(BlockLayout {
+            memory_type_index,
+            pool: name.clone(),
+            dedicated: true,
+            size,
+            allocations: vec![AllocationLayout {
+                offset: 0,
+                size,
+                kind: dedicated
+                    .get("Type")
+                    .and_then(Value::as_str)
+                    .unwrap_or("UNKNOWN")
+                    .to_owned(),
+                name: allocation_name(dedicated),
+            }],
+        });
+    }
+}
+
+fn allocation_name(allocation: &Value) -> Option<String> {
+    allocation
+        .get("Name")
+        .and_then(Value::as_str)
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+}
+
+fn describe(allocation: &AllocationLayout) -> String {
+    format!(
+        "{}{}, {} at offset {}",
+        allocation
+            .name
+            .as_ref()
+            .map(|name| format!("{}: ", name))
+            .unwrap_or_default(),
+        allocation.kind,
+        format_bytes(allocation.size),
+        allocation.offset
+    )
+}
+
+fn category_color(category: &str) -> &'static str {
+    // FNV-1a, so colors stay stable between runs.
+    let hash = category
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    PALETTE[(hash % PALETTE.len() as u64) as usize]
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
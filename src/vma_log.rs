@@ -0,0 +1,33 @@
+//! Forwarding of VMA's internal log messages to the `log` crate.
+//!
+//! `wrapper/vma_lib.cpp` defines `VMA_LEAK_LOG_FORMAT`, and `VMA_DEBUG_LOG_FORMAT` with the
+//! `debug_log` feature, to call `vk_mem_rs_log` instead of printing to stdout. Leaks VMA finds when
+//! an allocator or virtual block is destroyed are logged as warnings, its debug messages (one per
+//! API call and some internal events) at debug level. Both use the `vk_mem::vma` target.
+//! Applications using `tracing` receive them through `tracing-log`.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+/// Target of all messages logged by VMA.
+const TARGET: &str = "vk_mem::vma";
+
+/// `VK_MEM_RS_LOG_DEBUG` in `wrapper/vma_lib.cpp`.
+const LEVEL_DEBUG: c_int = 0;
+
+/// `VK_MEM_RS_LOG_LEAK` in `wrapper/vma_lib.cpp`.
+const LEVEL_LEAK: c_int = 1;
+
+#[no_mangle]
+extern "C" fn vk_mem_rs_log(level: c_int, message: *const c_char) {
+    if message.is_null() {
+        return;
+    }
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    let message = message.trim_end();
+    match level {
+        LEVEL_LEAK => log::warn!(target: TARGET, "{}", message),
+        LEVEL_DEBUG => log::debug!(target: TARGET, "{}", message),
+        _ => log::info!(target: TARGET, "{}", message),
+    }
+}
@@ -0,0 +1,116 @@
+//! Checks that every function of the generated VMA bindings is sorted into one of the `ffi`
+//! submodules and is either called by a safe wrapper or listed in `UNWRAPPED` with the reason.
+//!
+//! Regenerating the bindings against a newer VMA makes this test fail until the new functions are
+//! wrapped or tracked.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// VMA functions deliberately without a safe wrapper, with the reason.
+const UNWRAPPED: &[(&str, &str)] = &[];
+
+const DOMAIN_MODULES: &[&str] = &["allocation", "allocator", "defrag", "pool", "virtual_block"];
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Identifiers starting with `vma` that follow `prefix` in `source`.
+fn vma_identifiers_after(source: &str, prefix: &str) -> BTreeSet<String> {
+    source
+        .match_indices(prefix)
+        .map(|(index, _)| {
+            source[index + prefix.len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect::<String>()
+        })
+        .filter(|name| name.starts_with("vma"))
+        .collect()
+}
+
+/// Identifiers starting with `vma` anywhere in `source`.
+fn vma_identifiers(source: &str) -> BTreeSet<String> {
+    source
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| word.starts_with("vma"))
+        .map(str::to_owned)
+        .collect()
+}
+
+fn rust_sources(dir: &Path, sources: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            rust_sources(&path, sources);
+        } else if path
+            .extension()
+            .map_or(false, |extension| extension == "rs")
+        {
+            sources.push(path);
+        }
+    }
+}
+
+fn bound_functions() -> BTreeSet<String> {
+    let bindings = fs::read_to_string(manifest_dir().join("gen/bindings.rs")).unwrap();
+    vma_identifiers_after(&bindings, "pub fn ")
+}
+
+#[test]
+fn every_vma_function_is_wrapped_or_tracked() {
+    let ffi_dir = manifest_dir().join("src/ffi");
+    let mut sources = Vec::new();
+    rust_sources(&manifest_dir().join("src"), &mut sources);
+
+    let mut called = BTreeSet::new();
+    for path in sources.iter().filter(|path| !path.starts_with(&ffi_dir)) {
+        let source = fs::read_to_string(path).unwrap();
+        called.extend(vma_identifiers_after(&source, "ffi::"));
+    }
+
+    let unwrapped: BTreeSet<String> = UNWRAPPED.iter().map(|(name, _)| name.to_string()).collect();
+    let missing: Vec<_> = bound_functions()
+        .into_iter()
+        .filter(|name| !called.contains(name) && !unwrapped.contains(name))
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "VMA functions without a safe wrapper or an entry in UNWRAPPED: {:?}",
+        missing
+    );
+
+    let stale: Vec<_> = unwrapped.intersection(&called).collect();
+    assert!(
+        stale.is_empty(),
+        "VMA functions listed in UNWRAPPED that are wrapped now: {:?}",
+        stale
+    );
+}
+
+#[test]
+fn every_vma_function_belongs_to_one_domain() {
+    let mut domains: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for module in DOMAIN_MODULES {
+        let path = manifest_dir()
+            .join("src/ffi")
+            .join(format!("{}.rs", module));
+        let source = fs::read_to_string(path).unwrap();
+        for name in vma_identifiers(&source) {
+            domains.entry(name).or_default().push(module);
+        }
+    }
+
+    for name in bound_functions() {
+        match domains.get(&name).map(Vec::as_slice) {
+            Some([_]) => {}
+            Some(modules) => panic!(
+                "{} is re-exported by several ffi modules: {:?}",
+                name, modules
+            ),
+            None => panic!("{} is not re-exported by any ffi module", name),
+        }
+    }
+}
@@ -1,6 +1,7 @@
-extern crate ash;
 extern crate vk_mem;
 
+use vk_mem::ash;
+
 use ash::extensions::ext::DebugUtils;
 // use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
 use std::os::raw::{c_char, c_void};
@@ -27,6 +28,7 @@ pub struct TestHarness {
     pub instance: ash::Instance,
     pub device: ash::Device,
     pub physical_device: ash::vk::PhysicalDevice,
+    pub queue_family_index: u32,
     pub debug_callback: ash::vk::DebugUtilsMessengerEXT,
     pub debug_report_loader: ash::extensions::ext::DebugUtils,
 }
@@ -44,13 +46,27 @@ impl Drop for TestHarness {
 }
 impl TestHarness {
     pub fn new() -> Self {
+        Self::create(false)
+    }
+
+    /// A Vulkan 1.2 device with the `timelineSemaphore` feature enabled.
+    pub fn new_with_timeline_semaphores() -> Self {
+        Self::create(true)
+    }
+
+    fn create(timeline_semaphores: bool) -> Self {
+        let api_version = if timeline_semaphores {
+            ash::vk::make_api_version(0, 1, 2, 0)
+        } else {
+            ash::vk::make_api_version(0, 1, 0, 0)
+        };
         let app_name = ::std::ffi::CString::new("vk-mem testing").unwrap();
         let app_info = ash::vk::ApplicationInfo::builder()
             .application_name(&app_name)
             .application_version(0)
             .engine_name(&app_name)
             .engine_version(0)
-            .api_version(ash::vk::make_api_version(0, 1, 0, 0));
+            .api_version(api_version);
 
         let layer_names = [::std::ffi::CString::new("VK_LAYER_KHRONOS_validation").unwrap()];
         let layers_names_raw: Vec<*const i8> = layer_names
@@ -115,8 +131,13 @@ impl TestHarness {
             .queue_priorities(&priorities)
             .build()];
 
-        let device_create_info =
+        let mut timeline_features =
+            ash::vk::PhysicalDeviceTimelineSemaphoreFeatures::builder().timeline_semaphore(true);
+        let mut device_create_info =
             ash::vk::DeviceCreateInfo::builder().queue_create_infos(&queue_info);
+        if timeline_semaphores {
+            device_create_info = device_create_info.push_next(&mut timeline_features);
+        }
 
         let device: ash::Device = unsafe {
             instance
@@ -129,6 +150,7 @@ impl TestHarness {
             instance,
             device,
             physical_device,
+            queue_family_index: queue_family_index as u32,
             debug_report_loader,
             debug_callback,
         }
@@ -156,6 +178,119 @@ fn create_allocator() {
     let _ = harness.create_allocator();
 }
 
+#[test]
+fn create_allocator_from_raw_handles() {
+    let harness = TestHarness::new();
+    unsafe {
+        let allocator = vk_mem::Allocator::new_from_raw(
+            harness.instance.handle(),
+            harness.device.handle(),
+            harness.physical_device,
+            harness.entry.static_fn().get_instance_proc_addr,
+            ash::vk::API_VERSION_1_0,
+            vk_mem::AllocatorCreateFlags::NONE,
+        )
+        .unwrap();
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[test]
+fn externally_synchronized_allocator_is_single_threaded() {
+    let harness = TestHarness::new();
+    let create_info = vk_mem::AllocatorCreateInfo {
+        flags: vk_mem::AllocatorCreateFlags::EXTERNALLY_SYNCHRONIZED,
+        physical_device: harness.physical_device,
+        device: harness.device.clone(),
+        preferred_large_heap_block_size: 0,
+        allocation_callbacks: None,
+        device_memory_callbacks: None,
+        heap_size_limit: None,
+        heap_size_limit_fractions: None,
+        vulkan_functions: None,
+        get_instance_proc_addr: Some(harness.entry.static_fn().get_instance_proc_addr),
+        instance: harness.instance.clone(),
+        vulkan_api_version: ash::vk::API_VERSION_1_0,
+        api_version_from_instance: false,
+        external_memory_handle_type: std::ptr::null(),
+    };
+    unsafe {
+        assert_eq!(
+            vk_mem::Allocator::new(&create_info).unwrap_err(),
+            ash::vk::Result::ERROR_INITIALIZATION_FAILED
+        );
+
+        let allocator = vk_mem::single_thread::SingleThreadAllocator::new(&create_info).unwrap();
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[test]
+fn allocator_group_routes_by_device_index() {
+    let harness = TestHarness::new();
+    unsafe {
+        let group = vk_mem::group::AllocatorGroup::new(
+            &harness.instance,
+            &[vk_mem::group::GroupDevice {
+                physical_device: harness.physical_device,
+                device: harness.device.clone(),
+            }],
+            &vk_mem::group::GroupConfig {
+                get_instance_proc_addr: Some(harness.entry.static_fn().get_instance_proc_addr),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(group.len(), 1);
+
+        let (buffer, allocation, _) = group
+            .create_buffer(
+                0,
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let statistics = group.statistics();
+        assert_eq!(statistics.allocation_count, 1);
+        assert_eq!(statistics.allocation_bytes, 16 * 1024);
+        let (usage, budget) = group.total_budget();
+        assert!(usage <= budget);
+
+        group.allocator(0).destroy_buffer(buffer, &allocation);
+        assert_eq!(group.statistics().allocation_count, 0);
+    }
+}
+
 #[test]
 fn default_allocator_create_info() {
     let _ = vk_mem::AllocatorCreateInfo::default();
@@ -187,6 +322,63 @@ fn create_gpu_buffer() {
     allocator.destroy_buffer(buffer, &allocation);
 }
 
+#[test]
+fn create_buffers_rolls_back_on_failure() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(4 * 1024)
+        .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+        .build();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    unsafe {
+        let buffers = allocator
+            .create_buffers(&vec![(buffer_info, allocation_info.clone()); 16])
+            .unwrap();
+        assert_eq!(buffers.len(), 16);
+        assert_eq!(
+            allocator
+                .calculate_statistics()
+                .unwrap()
+                .total
+                .statistics
+                .allocation_count,
+            16
+        );
+        for (buffer, allocation, _) in &buffers {
+            allocator.destroy_buffer(*buffer, allocation);
+        }
+
+        // Lazily allocated memory is never host visible, so the last buffer fails and the first
+        // ones are destroyed again.
+        let impossible = vk_mem::AllocationCreateInfo {
+            required_flags: ash::vk::MemoryPropertyFlags::LAZILY_ALLOCATED
+                | ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+            ..allocation_info.clone()
+        };
+        let error = allocator
+            .create_buffers(&[
+                (buffer_info, allocation_info.clone()),
+                (buffer_info, allocation_info),
+                (buffer_info, impossible),
+            ])
+            .unwrap_err();
+        assert_eq!(error.operation(), "Allocator::create_buffers");
+        assert_eq!(
+            allocator
+                .calculate_statistics()
+                .unwrap()
+                .total
+                .statistics
+                .allocation_count,
+            0
+        );
+    }
+}
+
 #[test]
 fn create_cpu_buffer_preferred() {
     let harness = TestHarness::new();
@@ -198,7 +390,7 @@ fn create_cpu_buffer_preferred() {
         flags: vk_mem::AllocationCreateFlags::MAPPED,
         ..Default::default()
     };
-    let (buffer, allocation, allocation_info) = allocator
+    let (buffer, allocation, mut allocation_info) = allocator
         .create_buffer(
             &ash::vk::BufferCreateInfo::builder()
                 .size(16 * 1024)
@@ -211,6 +403,12 @@ fn create_cpu_buffer_preferred() {
         )
         .unwrap();
     assert_ne!(allocation_info.get_mapped_data(), std::ptr::null_mut());
+    unsafe {
+        let mapped = allocation_info.mapped_slice_mut().unwrap();
+        assert!(mapped.len() >= 16 * 1024);
+        mapped[..4].copy_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(&allocation_info.mapped_slice().unwrap()[..4], &[1, 2, 3, 4]);
+    }
     allocator.destroy_buffer(buffer, &allocation);
 }
 
@@ -255,78 +453,3532 @@ fn create_gpu_buffer_pool() {
 }
 
 #[test]
-fn test_gpu_stats() {
+fn find_memory_type_index_rejects_auto_usage() {
     let harness = TestHarness::new();
     let allocator = harness.create_allocator();
     let allocation_info = vk_mem::AllocationCreateInfo {
-        usage: vk_mem::MemoryUsage::GpuOnly,
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
         ..Default::default()
     };
+    let error =
+        unsafe { allocator.find_memory_type_index(u32::MAX, &allocation_info) }.unwrap_err();
+    assert_eq!(error.result(), ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+    assert_eq!(error.operation(), "Allocator::find_memory_type_index");
+    assert_eq!(
+        ash::vk::Result::from(error),
+        ash::vk::Result::ERROR_FEATURE_NOT_PRESENT
+    );
+}
 
-    let stats_1 = allocator.calculate_stats().unwrap();
-    assert_eq!(stats_1.total.blockCount, 0);
-    assert_eq!(stats_1.total.allocationCount, 0);
-    assert_eq!(stats_1.total.usedBytes, 0);
-
-    let (buffer, allocation, _allocation_info) = allocator
-        .create_buffer(
+#[test]
+fn create_buffer_with_address_requires_allocator_flag() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let error = unsafe {
+        allocator.create_buffer_with_address(
+            &harness.device,
             &ash::vk::BufferCreateInfo::builder()
                 .size(16 * 1024)
-                .usage(
-                    ash::vk::BufferUsageFlags::VERTEX_BUFFER
-                        | ash::vk::BufferUsageFlags::TRANSFER_DST,
-                )
+                .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER)
                 .build(),
-            &allocation_info,
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                ..Default::default()
+            },
         )
+    }
+    .unwrap_err();
+    assert_eq!(error.result(), ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+    assert_eq!(error.operation(), "Allocator::create_buffer_with_address");
+}
+
+#[test]
+fn explain_memory_type_choice_matches_vma() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::Unknown,
+        required_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+            | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+        ..Default::default()
+    };
+    let report = allocator
+        .explain_memory_type_choice(&allocation_info, u32::MAX)
         .unwrap();
+    let expected = unsafe { allocator.find_memory_type_index(u32::MAX, &allocation_info) }.unwrap();
+    assert_eq!(report.chosen, Some(expected));
+    assert!(report.rejected().all(|verdict| matches!(
+        verdict.rejection,
+        Some(vk_mem::explain::Rejection::MissingRequiredFlags(_))
+    )));
 
-    let stats_2 = allocator.calculate_stats().unwrap();
-    assert_eq!(stats_2.total.blockCount, 1);
-    assert_eq!(stats_2.total.allocationCount, 1);
-    assert_eq!(stats_2.total.usedBytes, 16 * 1024);
+    let report = allocator
+        .explain_memory_type_choice(&allocation_info, 0)
+        .unwrap();
+    assert_eq!(report.chosen, None);
+    assert!(report.to_string().ends_with("no memory type is suitable"));
+}
 
-    allocator.destroy_buffer(buffer, &allocation);
+#[test]
+fn check_corruption_takes_memory_type_bits() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    // Corruption detection is compiled out of the default build, so no memory type supports it.
+    let result = unsafe { allocator.check_corruption(u32::MAX) };
+    assert_eq!(result, Err(ash::vk::Result::ERROR_FEATURE_NOT_PRESENT));
+    let result = unsafe {
+        allocator.check_corruption_for_property_flags(
+            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
+    };
+    assert_eq!(result, Err(ash::vk::Result::ERROR_FEATURE_NOT_PRESENT));
+}
+
+#[cfg(feature = "validation")]
+#[test]
+fn error_violation_policy_rejects_misuse() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    allocator.set_violation_policy(vk_mem::ViolationPolicy::Error);
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::CpuToGpu,
+        ..Default::default()
+    };
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+                    .build(),
+                &allocation_info,
+            )
+            .unwrap();
+        allocator.destroy_buffer(buffer, &allocation);
 
-    let stats_3 = allocator.calculate_stats().unwrap();
-    assert_eq!(stats_3.total.blockCount, 1);
-    assert_eq!(stats_3.total.allocationCount, 0);
-    assert_eq!(stats_3.total.usedBytes, 0);
+        // Neither call reaches VMA.
+        assert_eq!(
+            allocator.map_memory(&allocation),
+            Err(ash::vk::Result::ERROR_VALIDATION_FAILED_EXT)
+        );
+        allocator.free_memory(&allocation);
+    }
 }
 
+#[cfg(feature = "validation")]
 #[test]
-fn test_stats_string() {
+#[should_panic(expected = "is freed twice, it was already freed by `Allocator::free_memory`")]
+fn double_free_names_the_first_free() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..Default::default()
+    };
+    unsafe {
+        let (allocation, _) = allocator
+            .allocate_memory(
+                &ash::vk::MemoryRequirements {
+                    size: 4096,
+                    alignment: 256,
+                    memory_type_bits: !0,
+                },
+                &allocation_info,
+            )
+            .unwrap();
+        allocator.free_memory(&allocation);
+        allocator.free_memory(&allocation);
+    }
+}
+
+#[cfg(feature = "leak_track")]
+#[test]
+fn report_leaks_lists_live_allocations() {
     let harness = TestHarness::new();
     let allocator = harness.create_allocator();
     let allocation_info = vk_mem::AllocationCreateInfo {
         usage: vk_mem::MemoryUsage::GpuOnly,
         ..Default::default()
     };
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+                    .build(),
+                &allocation_info,
+            )
+            .unwrap();
+        allocator
+            .set_allocation_name(&allocation, "vertices")
+            .unwrap();
 
-    let stats_1 = allocator.build_stats_string(true).unwrap();
-    assert!(stats_1.len() > 0);
+        let leaks = allocator.report_leaks();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].allocation, allocation);
+        assert_eq!(leaks[0].operation, "Allocator::create_buffer");
+        assert!(leaks[0].size >= 16 * 1024);
+        assert_eq!(leaks[0].name.as_deref(), Some("vertices"));
 
-    let (buffer, allocation, _allocation_info) = allocator
-        .create_buffer(
-            &ash::vk::BufferCreateInfo::builder()
-                .size(16 * 1024)
-                .usage(
-                    ash::vk::BufferUsageFlags::VERTEX_BUFFER
-                        | ash::vk::BufferUsageFlags::TRANSFER_DST,
-                )
-                .build(),
-            &allocation_info,
+        allocator.destroy_buffer(buffer, &allocation);
+        assert!(allocator.report_leaks().is_empty());
+    }
+}
+
+#[cfg(feature = "timing")]
+#[test]
+fn timing_report_counts_calls() {
+    use vk_mem::timing::Operation;
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferHost,
+        flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+        ..Default::default()
+    };
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &allocation_info,
+            )
+            .unwrap();
+        allocator.map_memory(&allocation).unwrap();
+        allocator.unmap_memory(&allocation);
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+
+    let report = allocator.timing_report();
+    for operation in Operation::ALL {
+        let statistics = report.get(operation);
+        assert_eq!(statistics.count, 1, "{:?}", operation);
+        assert_eq!(statistics.histogram.iter().sum::<u64>(), 1);
+        assert!(statistics.min <= statistics.max);
+    }
+    assert_eq!(report.to_string().lines().count(), Operation::ALL.len());
+
+    allocator.reset_timings();
+    assert_eq!(allocator.timing_report().get(Operation::Allocate).count, 0);
+}
+
+#[cfg(any(
+    feature = "android_interop",
+    feature = "cuda_interop",
+    feature = "dma_buf_interop",
+    feature = "metal_interop",
+    feature = "win32_interop"
+))]
+#[test]
+fn interop_reports_support_and_stubs_unsupported_targets() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let not_present = Err(ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        #[cfg(feature = "android_interop")]
+        {
+            use vk_mem::interop::android;
+            assert_eq!(android::SUPPORTED, cfg!(target_os = "android"));
+            if !android::SUPPORTED {
+                let export = android::HardwareBufferExport::new(&harness.instance, &harness.device);
+                assert_eq!(
+                    export
+                        .get_hardware_buffer(&allocator, &allocation)
+                        .map(|_| ()),
+                    not_present
+                );
+            }
+        }
+
+        #[cfg(feature = "cuda_interop")]
+        assert_eq!(
+            vk_mem::interop::cuda::SUPPORTED,
+            cfg!(any(target_os = "linux", windows))
+        );
+
+        #[cfg(feature = "dma_buf_interop")]
+        {
+            use vk_mem::interop::dma_buf;
+            assert_eq!(
+                dma_buf::SUPPORTED,
+                cfg!(any(target_os = "linux", target_os = "android"))
+            );
+            if !dma_buf::SUPPORTED {
+                let dma_buf = dma_buf::DmaBuf::new(&harness.instance, &harness.device);
+                assert_eq!(
+                    dma_buf.get_fd(&allocator, &allocation).map(|_| ()),
+                    not_present
+                );
+            }
+        }
+
+        #[cfg(feature = "metal_interop")]
+        {
+            use vk_mem::interop::metal;
+            assert_eq!(
+                metal::SUPPORTED,
+                cfg!(any(target_os = "macos", target_os = "ios"))
+            );
+            if !metal::SUPPORTED {
+                let export = metal::MetalObjectsExport::new(&harness.instance, &harness.device);
+                assert_eq!(
+                    export.get_metal_buffer(&allocator, &allocation).map(|_| ()),
+                    not_present
+                );
+            }
+        }
+
+        #[cfg(feature = "win32_interop")]
+        {
+            use vk_mem::interop::win32;
+            assert_eq!(win32::SUPPORTED, cfg!(windows));
+            if !win32::SUPPORTED {
+                assert_eq!(
+                    win32::get_memory_win32_handle(&allocator, &allocation, None).map(|_| ()),
+                    not_present
+                );
+            }
+        }
+
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[cfg(feature = "alloc_log")]
+#[test]
+fn allocation_log_writes_one_line_per_event() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedWriter {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let writer = SharedWriter::default();
+    allocator.set_allocation_log(Some(Box::new(writer.clone())));
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferHost,
+        flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+        ..Default::default()
+    };
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &allocation_info,
+            )
+            .unwrap();
+        allocator
+            .set_allocation_name(&allocation, "upload \"ring\"")
+            .unwrap();
+        allocator.map_memory(&allocation).unwrap();
+        allocator.unmap_memory(&allocation);
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+    allocator.set_allocation_log(None);
+
+    let log = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", log);
+    let events = ["allocate", "name", "map", "free"];
+    for (sequence, (line, event)) in lines.iter().zip(events).enumerate() {
+        assert!(line.starts_with(&format!(
+            "{{\"event\":\"{}\",\"sequence\":{},",
+            event, sequence
+        )));
+        assert!(line.contains("\"pool\":null"));
+        assert!(line.ends_with('}'));
+    }
+    assert!(lines[0].contains("\"name\":null"));
+    assert!(lines[0].contains("\"operation\":\"Allocator::create_buffer\""));
+    assert!(lines[3].contains("\"name\":\"upload \\\"ring\\\"\""));
+}
+
+#[cfg(feature = "alloc_log")]
+#[test]
+fn chrome_trace_spans_allocation_lifetimes() {
+    let log = concat!(
+        r#"{"event":"allocate","sequence":0,"timestamp_us":1000,"allocation":"0x10","size":256,"#,
+        r#""memory_type":1,"memory_heap":0,"pool":null,"name":null,"#,
+        r#""operation":"Allocator::create_buffer"}"#,
+        "\n",
+        r#"{"event":"allocate","sequence":1,"timestamp_us":1500,"allocation":"0x20","size":512,"#,
+        r#""memory_type":2,"memory_heap":1,"pool":"staging","name":null,"#,
+        r#""operation":"Allocator::allocate_memory"}"#,
+        "\n",
+        r#"{"event":"name","sequence":2,"timestamp_us":1600,"allocation":"0x10","size":256,"#,
+        r#""memory_type":1,"memory_heap":0,"pool":null,"name":"vertices"}"#,
+        "\n",
+        r#"{"event":"free","sequence":3,"timestamp_us":3000,"allocation":"0x10","size":256,"#,
+        r#""memory_type":1,"memory_heap":0,"pool":null,"name":"vertices"}"#,
+        "\n",
+    );
+    let mut trace = Vec::new();
+    vk_mem::alloc_log::write_chrome_trace(log.as_bytes(), &mut trace).unwrap();
+    let trace = String::from_utf8(trace).unwrap();
+
+    assert!(trace.starts_with("{\"traceEvents\":["));
+    assert!(trace.contains(
+        "{\"name\":\"default pools\",\"cat\":\"allocation\",\"ph\":\"b\",\"id\":\"0x0\",\"pid\":0,\
+         \"tid\":0,\"ts\":0,\"args\":{\"size\":256,\"memory_type\":1,\
+         \"operation\":\"Allocator::create_buffer\",\"name\":\"vertices\"}}"
+    ));
+    assert!(trace.contains(
+        "\"ph\":\"e\",\"id\":\"0x0\",\"pid\":0,\"tid\":0,\"ts\":2000,\"args\":{\"freed\":true}"
+    ));
+    // Never freed, so it ends with the log.
+    assert!(trace.contains(
+        "{\"name\":\"staging\",\"cat\":\"allocation\",\"ph\":\"b\",\"id\":\"0x1\",\"pid\":1,"
+    ));
+    assert!(trace.contains(
+        "\"ph\":\"e\",\"id\":\"0x1\",\"pid\":1,\"tid\":0,\"ts\":2000,\"args\":{\"freed\":false}"
+    ));
+    assert!(trace.contains("\"args\":{\"name\":\"Heap 1\"}"));
+
+    let error = vk_mem::alloc_log::write_chrome_trace(&b"not json\n"[..], Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn frame_guard_runs_hooks_and_deferred_frees() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct CountingHook {
+        begun: AtomicU32,
+        ended: AtomicU32,
+    }
+
+    impl vk_mem::frame::FrameHook for CountingHook {
+        fn begin_frame(&self, _allocator: &vk_mem::Allocator, _frame_index: u32) {
+            self.begun.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn end_frame(&self, _allocator: &vk_mem::Allocator, _frame_index: u32) {
+            self.ended.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let hook = Arc::new(CountingHook::default());
+    allocator.add_frame_hook(hook.clone());
+    allocator.set_frames_in_flight(2);
+
+    let allocation_count = || {
+        unsafe { allocator.calculate_statistics() }
+            .unwrap()
+            .total
+            .statistics
+            .allocation_count
+    };
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let frame = allocator.begin_frame(0);
+        assert_eq!(frame.frame_index(), 0);
+        frame.defer_destroy_buffer(buffer, &allocation);
+        drop(frame);
+        assert_eq!(allocation_count(), 1);
+
+        drop(allocator.begin_frame(1));
+        assert_eq!(allocation_count(), 1);
+        drop(allocator.begin_frame(2));
+        assert_eq!(allocation_count(), 0);
+    }
+    assert_eq!(hook.begun.load(Ordering::Relaxed), 3);
+    assert_eq!(hook.ended.load(Ordering::Relaxed), 3);
+
+    let hook: Arc<dyn vk_mem::frame::FrameHook> = hook;
+    allocator.remove_frame_hook(&hook);
+    drop(unsafe { allocator.begin_frame(3) });
+    assert_eq!(Arc::strong_count(&hook), 1);
+}
+
+#[test]
+fn deferred_resources_survive_frames_in_flight() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    let memory_requirements = ash::vk::MemoryRequirements {
+        size: 16 * 1024,
+        alignment: 256,
+        memory_type_bits: u32::MAX,
+    };
+
+    let allocation_count = || {
+        unsafe { allocator.calculate_statistics() }
+            .unwrap()
+            .total
+            .statistics
+            .allocation_count
+    };
+    for frames_in_flight in 1..=3 {
+        allocator.set_frames_in_flight(frames_in_flight);
+        unsafe {
+            let (allocation, _) = allocator
+                .allocate_memory(&memory_requirements, &allocation_info)
+                .unwrap();
+            allocator.begin_frame(0).defer_free(&allocation);
+
+            // The device may still use the allocation until `frames_in_flight` frames after the
+            // one that deferred it have ended.
+            for frame_index in 1..=frames_in_flight {
+                assert_eq!(
+                    allocation_count(),
+                    1,
+                    "{} frames in flight",
+                    frames_in_flight
+                );
+                drop(allocator.begin_frame(frame_index));
+            }
+            assert_eq!(
+                allocation_count(),
+                0,
+                "{} frames in flight",
+                frames_in_flight
+            );
+        }
+    }
+}
+
+#[test]
+fn uploader_submits_to_the_transfer_queue() {
+    use vk_mem::staging::StagingConfig;
+    use vk_mem::upload::Uploader;
+
+    let harness = TestHarness::new_with_timeline_semaphores();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let (buffer, allocation, info) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_DST)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferHost,
+                    flags: vk_mem::AllocationCreateFlags::MAPPED
+                        | vk_mem::AllocationCreateFlags::HOST_ACCESS_RANDOM,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+        let mut uploader = Uploader::with_transfer_queue(
+            &allocator,
+            &harness.device,
+            StagingConfig::default(),
+            queue,
+            harness.queue_family_index,
         )
         .unwrap();
 
-    let stats_2 = allocator.build_stats_string(true).unwrap();
-    assert!(stats_2.len() > 0);
-    assert_ne!(stats_1, stats_2);
+        // Empty uploads record nothing, so there is nothing to submit.
+        uploader
+            .upload_buffer(buffer, 0, &[], harness.queue_family_index)
+            .unwrap();
+        assert_eq!(uploader.submit().unwrap().value, 0);
 
-    allocator.destroy_buffer(buffer, &allocation);
+        let contents: Vec<u8> = (0..=255).collect();
+        uploader
+            .upload_buffer(buffer, 256, &contents, harness.queue_family_index)
+            .unwrap();
+        let submission = uploader.submit().unwrap();
+        assert_eq!(submission.value, 1);
+        assert!(submission.buffer_barriers.is_empty());
+        uploader.wait(submission.value, u64::MAX).unwrap();
+        assert!(uploader.completed_value().unwrap() >= submission.value);
 
-    let stats_3 = allocator.build_stats_string(true).unwrap();
-    assert!(stats_3.len() > 0);
-    assert_ne!(stats_3, stats_1);
-    assert_ne!(stats_3, stats_2);
+        allocator
+            .invalidate_allocation(&allocation, 0, ash::vk::WHOLE_SIZE as usize)
+            .unwrap();
+        let uploaded = std::slice::from_raw_parts(info.get_mapped_data().add(256), contents.len());
+        assert_eq!(uploaded, &contents[..]);
+        assert!(uploader.recycle().unwrap());
+
+        drop(uploader);
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[test]
+fn upload_image_rejects_data_shorter_than_the_region() {
+    use vk_mem::staging::StagingConfig;
+    use vk_mem::upload::{ImageRegion, Uploader};
+
+    let mut region = ImageRegion {
+        image: ash::vk::Image::null(),
+        format: ash::vk::Format::R8G8B8A8_UNORM,
+        aspect_mask: ash::vk::ImageAspectFlags::COLOR,
+        mip_level: 0,
+        array_layer: 0,
+        offset: ash::vk::Offset3D::default(),
+        extent: ash::vk::Extent3D {
+            width: 6,
+            height: 5,
+            depth: 1,
+        },
+    };
+    assert_eq!(region.data_size(), Some(6 * 5 * 4));
+    region.format = ash::vk::Format::BC1_RGBA_UNORM_BLOCK;
+    assert_eq!(region.data_size(), Some(2 * 2 * 8));
+    region.format = ash::vk::Format::ASTC_6X5_SRGB_BLOCK;
+    assert_eq!(region.data_size(), Some(16));
+    region.format = ash::vk::Format::R8G8B8A8_UNORM;
+
+    let harness = TestHarness::new_with_timeline_semaphores();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+        let mut uploader = Uploader::with_transfer_queue(
+            &allocator,
+            &harness.device,
+            StagingConfig::default(),
+            queue,
+            harness.queue_family_index,
+        )
+        .unwrap();
+
+        // Neither call records anything, so the null image is never used.
+        let layouts = (
+            ash::vk::ImageLayout::UNDEFINED,
+            ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        uploader
+            .upload_image(
+                &region,
+                &[],
+                layouts.0,
+                layouts.1,
+                harness.queue_family_index,
+            )
+            .unwrap();
+        assert_eq!(
+            uploader.upload_image(
+                &region,
+                &[0; 6 * 5 * 4 - 1],
+                layouts.0,
+                layouts.1,
+                harness.queue_family_index
+            ),
+            Err(ash::vk::Result::ERROR_VALIDATION_FAILED_EXT)
+        );
+        assert_eq!(uploader.submit().unwrap().value, 0);
+    }
+}
+
+#[test]
+fn staging_manager_grows_for_uploads_and_shrinks_when_idle() {
+    use vk_mem::staging::{StagingConfig, StagingManager};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let config = StagingConfig {
+        min_chunk_size: 64 * 1024,
+        max_chunk_size: 256 * 1024,
+        idle_frames_before_shrink: 2,
+        ..Default::default()
+    };
+    let mut staging = StagingManager::new(&allocator, config);
+    assert_eq!(staging.capacity(), 0);
+    unsafe {
+        let first = staging.allocate(100, 16).unwrap();
+        let second = staging.allocate(100, 256).unwrap();
+        assert_eq!(first.buffer, second.buffer);
+        assert_eq!(second.offset % 256, 0);
+        assert!(second.offset >= first.offset + first.size);
+        std::ptr::write_bytes(second.mapped, 0xab, second.size as usize);
+        staging.flush(&second).unwrap();
+        staging.end_frame();
+
+        // A request larger than `max_chunk_size` gets a chunk of its own.
+        let oversized = staging.allocate(1024 * 1024, 16).unwrap();
+        assert_ne!(oversized.buffer, first.buffer);
+        assert!(staging.capacity() >= 1024 * 1024);
+        staging.end_frame();
+
+        // After the idle frames, even the oversized chunk is given back.
+        staging.end_frame();
+        staging.end_frame();
+        assert_eq!(staging.capacity(), config.min_chunk_size);
+    }
+}
+
+#[test]
+fn idle_staging_manager_allocates_nothing() {
+    use vk_mem::staging::{StagingConfig, StagingManager};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let mut staging = StagingManager::new(
+        &allocator,
+        StagingConfig {
+            idle_frames_before_shrink: 1,
+            ..Default::default()
+        },
+    );
+    unsafe {
+        staging.end_frame();
+        staging.end_frame();
+    }
+    assert_eq!(staging.capacity(), 0);
+}
+
+#[test]
+fn update_image_region_writes_one_subresource_rectangle() {
+    use vk_mem::staging::StagingConfig;
+    use vk_mem::upload::{ImageRegion, Uploader};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let (image, image_allocation, _) = allocator
+            .create_image(
+                &ash::vk::ImageCreateInfo::builder()
+                    .image_type(ash::vk::ImageType::TYPE_2D)
+                    .format(ash::vk::Format::R8G8B8A8_UNORM)
+                    .extent(ash::vk::Extent3D {
+                        width: 16,
+                        height: 16,
+                        depth: 1,
+                    })
+                    .mip_levels(2)
+                    .array_layers(2)
+                    .samples(ash::vk::SampleCountFlags::TYPE_1)
+                    .tiling(ash::vk::ImageTiling::OPTIMAL)
+                    .usage(
+                        ash::vk::ImageUsageFlags::TRANSFER_DST
+                            | ash::vk::ImageUsageFlags::TRANSFER_SRC,
+                    )
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let (readback, readback_allocation, readback_info) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(4 * 4 * 4)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_DST)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferHost,
+                    flags: vk_mem::AllocationCreateFlags::MAPPED
+                        | vk_mem::AllocationCreateFlags::HOST_ACCESS_RANDOM,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let command_buffer = harness
+            .device
+            .allocate_command_buffers(
+                &ash::vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .command_buffer_count(1),
+            )
+            .unwrap()[0];
+        let fence = harness
+            .device
+            .create_fence(&ash::vk::FenceCreateInfo::default(), None)
+            .unwrap();
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+
+        // A 4x4 rectangle of mip 1 (8x8) of layer 1.
+        let region = ImageRegion {
+            image,
+            format: ash::vk::Format::R8G8B8A8_UNORM,
+            aspect_mask: ash::vk::ImageAspectFlags::COLOR,
+            mip_level: 1,
+            array_layer: 1,
+            offset: ash::vk::Offset3D { x: 2, y: 3, z: 0 },
+            extent: ash::vk::Extent3D {
+                width: 4,
+                height: 4,
+                depth: 1,
+            },
+        };
+        let texels: Vec<u8> = (0..4 * 4 * 4).map(|index| index as u8).collect();
+        let mut uploader = Uploader::new(&allocator, &harness.device, StagingConfig::default());
+        harness
+            .device
+            .begin_command_buffer(command_buffer, &ash::vk::CommandBufferBeginInfo::default())
+            .unwrap();
+        uploader
+            .update_image_region(
+                command_buffer,
+                &region,
+                &texels,
+                ash::vk::ImageLayout::UNDEFINED,
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            )
+            .unwrap();
+        harness.device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            readback,
+            &[ash::vk::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: ash::vk::ImageSubresourceLayers {
+                    aspect_mask: region.aspect_mask,
+                    mip_level: region.mip_level,
+                    base_array_layer: region.array_layer,
+                    layer_count: 1,
+                },
+                image_offset: region.offset,
+                image_extent: region.extent,
+            }],
+        );
+        harness.device.end_command_buffer(command_buffer).unwrap();
+        harness
+            .device
+            .queue_submit(
+                queue,
+                &[ash::vk::SubmitInfo::builder()
+                    .command_buffers(&[command_buffer])
+                    .build()],
+                fence,
+            )
+            .unwrap();
+        harness
+            .device
+            .wait_for_fences(&[fence], true, u64::MAX)
+            .unwrap();
+        uploader.end_frame();
+
+        allocator
+            .invalidate_allocation(&readback_allocation, 0, ash::vk::WHOLE_SIZE as usize)
+            .unwrap();
+        let read = std::slice::from_raw_parts(readback_info.get_mapped_data(), texels.len());
+        assert_eq!(read, &texels[..]);
+
+        drop(uploader);
+        harness.device.destroy_fence(fence, None);
+        harness.device.destroy_command_pool(command_pool, None);
+        allocator.destroy_buffer(readback, &readback_allocation);
+        allocator.destroy_image(image, &image_allocation);
+    }
+}
+
+#[test]
+fn copy_buffer_between_allocators_preserves_contents() {
+    use vk_mem::transfer::{copy_buffer_between_allocators, TransferQueue};
+
+    let harness = TestHarness::new();
+    let src_allocator = harness.create_allocator();
+    let dst_allocator = harness.create_allocator();
+    let contents: Vec<u8> = (0..4096).map(|index| (index * 7) as u8).collect();
+    let size = contents.len() as ash::vk::DeviceSize;
+    unsafe {
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let queue = TransferQueue {
+            device: &harness.device,
+            queue: harness
+                .device
+                .get_device_queue(harness.queue_family_index, 0),
+            command_pool,
+        };
+
+        let (src_buffer, src_allocation, _) = src_allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(size)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferHost,
+                    flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        src_allocator
+            .copy_to_allocation(&contents, &src_allocation, 0)
+            .unwrap();
+
+        // Into device memory of the other allocator, staged if it isn't host visible...
+        let (device_buffer, device_allocation, _) = copy_buffer_between_allocators(
+            &src_allocator,
+            &queue,
+            src_buffer,
+            &src_allocation,
+            size,
+            &dst_allocator,
+            &queue,
+            &ash::vk::BufferCreateInfo::builder()
+                .size(size)
+                .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                .build(),
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // ...and back into host memory of the first one.
+        let (host_buffer, host_allocation, _) = copy_buffer_between_allocators(
+            &dst_allocator,
+            &queue,
+            device_buffer,
+            &device_allocation,
+            size,
+            &src_allocator,
+            &queue,
+            &ash::vk::BufferCreateInfo::builder().size(size).build(),
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::AutoPreferHost,
+                flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_RANDOM,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut copied = vec![0u8; contents.len()];
+        src_allocator
+            .copy_from_allocation(&host_allocation, 0, &mut copied)
+            .unwrap();
+        assert_eq!(copied, contents);
+
+        src_allocator.destroy_buffer(host_buffer, &host_allocation);
+        dst_allocator.destroy_buffer(device_buffer, &device_allocation);
+        src_allocator.destroy_buffer(src_buffer, &src_allocation);
+        harness.device.destroy_command_pool(command_pool, None);
+    }
+}
+
+#[test]
+fn readback_copies_buffer_range_to_host() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let (src_buffer, src_allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::Auto,
+                    flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let contents: Vec<u8> = (0..1024).map(|index| index as u8).collect();
+        allocator
+            .copy_to_allocation(&contents, &src_allocation, 0)
+            .unwrap();
+
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let command_buffer = harness
+            .device
+            .allocate_command_buffers(
+                &ash::vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .command_buffer_count(1),
+            )
+            .unwrap()[0];
+        let fence = harness
+            .device
+            .create_fence(&ash::vk::FenceCreateInfo::default(), None)
+            .unwrap();
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+
+        let readback = vk_mem::readback::Readback::new(&allocator, &harness.device).unwrap();
+        harness
+            .device
+            .begin_command_buffer(command_buffer, &ash::vk::CommandBufferBeginInfo::default())
+            .unwrap();
+        let pending = readback
+            .request(
+                command_buffer,
+                src_buffer,
+                256..768,
+                vk_mem::readback::Signal::Fence(fence),
+            )
+            .unwrap();
+        harness.device.end_command_buffer(command_buffer).unwrap();
+        harness
+            .device
+            .queue_submit(
+                queue,
+                &[ash::vk::SubmitInfo::builder()
+                    .command_buffers(&[command_buffer])
+                    .build()],
+                fence,
+            )
+            .unwrap();
+
+        pending.wait(u64::MAX).unwrap();
+        assert!(pending.is_ready().unwrap());
+        assert_eq!(pending.size(), 512);
+        assert_eq!(pending.into_vec().unwrap(), &contents[256..768]);
+        assert_eq!(readback.cached_buffers(), 1);
+
+        drop(readback);
+        harness.device.destroy_fence(fence, None);
+        harness.device.destroy_command_pool(command_pool, None);
+        allocator.destroy_buffer(src_buffer, &src_allocation);
+    }
+}
+
+#[test]
+fn create_image_init_uploads_mip_chain() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let image_info = ash::vk::ImageCreateInfo::builder()
+        .image_type(ash::vk::ImageType::TYPE_2D)
+        .format(ash::vk::Format::R8G8B8A8_UNORM)
+        .extent(ash::vk::Extent3D {
+            width: 8,
+            height: 8,
+            depth: 1,
+        })
+        .mip_levels(4)
+        .array_layers(1)
+        .samples(ash::vk::SampleCountFlags::TYPE_1)
+        .tiling(ash::vk::ImageTiling::OPTIMAL)
+        .usage(ash::vk::ImageUsageFlags::SAMPLED)
+        .build();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    let mips: Vec<Vec<u8>> = (0..4u32)
+        .map(|level| vec![level as u8; ((8 >> level) * (8 >> level) * 4) as usize])
+        .collect();
+    let mips: Vec<&[u8]> = mips.iter().map(Vec::as_slice).collect();
+
+    unsafe {
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let command_buffer = harness
+            .device
+            .allocate_command_buffers(
+                &ash::vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .command_buffer_count(1),
+            )
+            .unwrap()[0];
+        harness
+            .device
+            .begin_command_buffer(command_buffer, &ash::vk::CommandBufferBeginInfo::default())
+            .unwrap();
+
+        let too_many = [mips[0]; 5];
+        let err = allocator
+            .create_image_init(
+                &harness.device,
+                &image_info,
+                &allocation_info,
+                &too_many,
+                command_buffer,
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .unwrap_err();
+        assert_eq!(err.result(), ash::vk::Result::ERROR_VALIDATION_FAILED_EXT);
+        assert_eq!(err.operation(), "Allocator::create_image_init");
+
+        let (image, allocation, _, staging) = allocator
+            .create_image_init(
+                &harness.device,
+                &image_info,
+                &allocation_info,
+                &mips,
+                command_buffer,
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .unwrap();
+        harness.device.end_command_buffer(command_buffer).unwrap();
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+        harness
+            .device
+            .queue_submit(
+                queue,
+                &[ash::vk::SubmitInfo::builder()
+                    .command_buffers(&[command_buffer])
+                    .build()],
+                ash::vk::Fence::null(),
+            )
+            .unwrap();
+        harness.device.queue_wait_idle(queue).unwrap();
+
+        staging.destroy(&allocator);
+        allocator.destroy_image(image, &allocation);
+        harness.device.destroy_command_pool(command_pool, None);
+        assert_eq!(
+            allocator
+                .calculate_statistics()
+                .unwrap()
+                .total
+                .statistics
+                .allocation_count,
+            0
+        );
+    }
+}
+
+#[test]
+fn residency_manager_keeps_highest_priority_textures_in_budget() {
+    use vk_mem::streaming::{ResidencyManager, StreamingConfig, TextureId};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let heap_index = unsafe {
+        let properties = allocator.get_memory_properties().unwrap();
+        properties.memory_types[0].heap_index
+    };
+    let mut manager = ResidencyManager::new(
+        &allocator,
+        StreamingConfig {
+            heap_index,
+            max_bytes: Some(300),
+            ..Default::default()
+        },
+    );
+    let (a, b, c) = (TextureId(1), TextureId(2), TextureId(3));
+    manager.register(a, 100, 3.0);
+    manager.register(b, 200, 2.0);
+    manager.register(c, 50, 1.0);
+    assert_eq!(manager.streaming_budget(), 300);
+
+    let decisions = manager.update();
+    assert_eq!(decisions.load, vec![a, b]);
+    assert!(decisions.evict.is_empty());
+
+    let image_info = ash::vk::ImageCreateInfo::builder()
+        .image_type(ash::vk::ImageType::TYPE_2D)
+        .format(ash::vk::Format::R8G8B8A8_UNORM)
+        .extent(ash::vk::Extent3D {
+            width: 4,
+            height: 4,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(ash::vk::SampleCountFlags::TYPE_1)
+        .tiling(ash::vk::ImageTiling::OPTIMAL)
+        .usage(ash::vk::ImageUsageFlags::SAMPLED)
+        .build();
+    unsafe {
+        for id in decisions.load {
+            let (image, allocation, _) = allocator
+                .create_image(&image_info, &vk_mem::AllocationCreateInfo::default())
+                .unwrap();
+            manager.mark_resident(id, image, allocation);
+        }
+        assert_eq!(manager.resident_bytes(), 300);
+        assert!(!manager.fits(1));
+
+        manager.set_priority(c, 10.0);
+        let decisions = manager.update();
+        assert_eq!(decisions.load, vec![c]);
+        assert_eq!(decisions.evict, vec![b]);
+
+        allocator.set_frames_in_flight(1);
+        let frame = allocator.begin_frame(0);
+        manager.apply_evictions(&decisions, &frame);
+        assert!(!manager.is_resident(b));
+        assert_eq!(manager.resident_bytes(), 100);
+        drop(frame);
+
+        let (image, allocation) = manager.unregister(a).unwrap();
+        allocator.destroy_image(image, &allocation);
+        assert_eq!(
+            allocator
+                .calculate_statistics()
+                .unwrap()
+                .total
+                .statistics
+                .allocation_count,
+            0
+        );
+    }
+}
+
+#[test]
+fn bindless_table_recycles_and_compacts_slots() {
+    use vk_mem::bindless::{BindlessTable, BindlessTableInfo, SlotMove};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let mut table = BindlessTable::new(
+            &allocator,
+            &harness.device,
+            &BindlessTableInfo {
+                slot_size: 256,
+                slot_count: 4,
+                usage: ash::vk::BufferUsageFlags::STORAGE_BUFFER,
+                allocation_info: vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                    ..Default::default()
+                },
+            },
+        )
+        .unwrap();
+        let slots: Vec<u32> = (0..4).map(|_| table.allocate().unwrap()).collect();
+        assert_eq!(slots, vec![0, 1, 2, 3]);
+        assert_eq!(table.allocate(), None);
+        assert_eq!(table.descriptor_info(2).offset, 512);
+        assert_eq!(table.descriptor_info(2).range, 256);
+        assert_eq!(table.device_address(2), None);
+
+        table.free(1);
+        assert_eq!(table.allocate(), Some(1));
+        table.free(0);
+        table.free(2);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.fragmentation(), 0.5);
+
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let command_buffer = harness
+            .device
+            .allocate_command_buffers(
+                &ash::vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .command_buffer_count(1),
+            )
+            .unwrap()[0];
+        harness
+            .device
+            .begin_command_buffer(command_buffer, &ash::vk::CommandBufferBeginInfo::default())
+            .unwrap();
+        let moves = table.defragment(&harness.device, command_buffer);
+        harness.device.end_command_buffer(command_buffer).unwrap();
+        assert_eq!(moves, vec![SlotMove { from: 3, to: 0 }]);
+        assert_eq!(table.fragmentation(), 0.0);
+        assert_eq!(table.allocate(), Some(2));
+
+        harness.device.destroy_command_pool(command_pool, None);
+    }
+}
+
+#[test]
+fn descriptor_buffer_requires_device_address_flag() {
+    use vk_mem::descriptor_buffer::{
+        DescriptorBuffer, DescriptorBufferProperties, RESOURCE_DESCRIPTOR_BUFFER,
+    };
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let properties = DescriptorBufferProperties {
+        offset_alignment: 64,
+        max_sampler_range: 1 << 20,
+        max_resource_range: 1 << 20,
+    };
+    let result = unsafe {
+        DescriptorBuffer::new(
+            &allocator,
+            &harness.device,
+            64 * 1024,
+            RESOURCE_DESCRIPTOR_BUFFER,
+            &properties,
+        )
+    };
+    let err = result.err().unwrap();
+    assert_eq!(err.result(), ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+    assert_eq!(err.operation(), "DescriptorBuffer::new");
+}
+
+#[test]
+fn aliasing_validator_flags_overlapping_resources_in_one_pass() {
+    use vk_mem::aliasing::{AliasedResource, AliasingValidator};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let (allocation, _) = allocator
+            .allocate_memory(
+                &ash::vk::MemoryRequirements {
+                    size: 64 * 1024,
+                    alignment: 256,
+                    memory_type_bits: !0,
+                },
+                &vk_mem::AllocationCreateInfo {
+                    flags: vk_mem::AllocationCreateFlags::CAN_ALIAS,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let buffer_info = ash::vk::BufferCreateInfo::builder()
+            .size(32 * 1024)
+            .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build();
+        let a = allocator
+            .create_aliasing_buffer(&allocation, &buffer_info)
+            .unwrap();
+        let b = allocator
+            .create_aliasing_buffer(&allocation, &buffer_info)
+            .unwrap();
+
+        let mut validator = AliasingValidator::new();
+        validator.bind(AliasedResource::Buffer(a), &allocation, 0, 32 * 1024);
+        validator.bind(
+            AliasedResource::Buffer(b),
+            &allocation,
+            16 * 1024,
+            32 * 1024,
+        );
+        assert_eq!(
+            validator.aliases_of(AliasedResource::Buffer(a)),
+            vec![AliasedResource::Buffer(b)]
+        );
+
+        validator.begin_pass("first");
+        assert!(validator
+            .use_resource(AliasedResource::Buffer(a))
+            .is_empty());
+        validator.end_pass();
+        validator.begin_pass("second");
+        assert!(validator
+            .use_resource(AliasedResource::Buffer(b))
+            .is_empty());
+        validator.end_pass();
+        assert!(validator.hazards().is_empty());
+
+        validator.begin_pass("both");
+        validator.use_resource(AliasedResource::Buffer(a));
+        let hazards = validator.use_resource(AliasedResource::Buffer(b));
+        validator.end_pass();
+        assert_eq!(hazards.len(), 1);
+        assert_eq!(hazards[0].pass, "both");
+        assert_eq!(hazards[0].overlap, 16 * 1024..32 * 1024);
+        assert_eq!(validator.hazards(), &hazards[..]);
+
+        harness.device.destroy_buffer(a, None);
+        harness.device.destroy_buffer(b, None);
+        validator.unbind_allocation(&allocation);
+        allocator.free_memory(&allocation);
+    }
+}
+
+#[test]
+fn reclaim_evicts_lowest_priority_first() {
+    use vk_mem::priority::MemoryPriority;
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        priority: MemoryPriority::Low.value(),
+        ..Default::default()
+    };
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+        .build();
+    unsafe {
+        let (low_buffer, low, low_info) = allocator
+            .create_buffer(&buffer_info, &allocation_info)
+            .unwrap();
+        let (critical_buffer, critical, _) = allocator
+            .create_buffer(&buffer_info, &allocation_info)
+            .unwrap();
+        allocator
+            .set_memory_priority(
+                &low,
+                MemoryPriority::Low,
+                Some(Box::new(move |allocator, allocation| {
+                    allocator.destroy_buffer(low_buffer, allocation);
+                    true
+                })),
+            )
+            .unwrap();
+        allocator
+            .set_memory_priority(
+                &critical,
+                MemoryPriority::Critical,
+                Some(Box::new(|_, _| panic!("evicted a critical allocation"))),
+            )
+            .unwrap();
+        assert_eq!(allocator.memory_priority(&low), Some(MemoryPriority::Low));
+
+        // Nothing has that much budget left, so every evictable allocation goes.
+        let reclaimed = allocator.reclaim(1 << 50);
+        assert_eq!(reclaimed, low_info.get_size() as ash::vk::DeviceSize);
+        assert_eq!(
+            allocator.memory_priority(&critical),
+            Some(MemoryPriority::Critical)
+        );
+
+        allocator.destroy_buffer(critical_buffer, &critical);
+        assert_eq!(allocator.memory_priority(&critical), None);
+    }
+}
+
+#[test]
+fn pool_create_info_priority_and_alignment_are_passed_to_vma() {
+    use vk_mem::priority::MemoryPriority;
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(256)
+        .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+        .sharing_mode(ash::vk::SharingMode::EXCLUSIVE);
+    unsafe {
+        let memory_type_index = allocator
+            .find_memory_type_index_for_buffer_info(&buffer_info, &Default::default())
+            .unwrap();
+        let pool = allocator
+            .create_pool(&vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                priority: MemoryPriority::High.value(),
+                min_allocation_alignment: 64 * 1024,
+                ..Default::default()
+            })
+            .unwrap();
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            pool: Some(pool),
+            priority: MemoryPriority::High.value(),
+            ..Default::default()
+        };
+
+        // Without the pool's alignment, small buffers would be packed next to each other.
+        let mut buffers = Vec::new();
+        for _ in 0..4 {
+            let (buffer, allocation, info) = allocator
+                .create_buffer(&buffer_info, &allocation_info)
+                .unwrap();
+            assert_eq!(info.get_offset() % (64 * 1024), 0);
+            buffers.push((buffer, allocation));
+        }
+        for (buffer, allocation) in buffers {
+            allocator.destroy_buffer(buffer, &allocation);
+        }
+        allocator.destroy_pool(pool);
+    }
+}
+
+#[test]
+fn budget_reservations_shrink_streaming_budget() {
+    use vk_mem::streaming::{ResidencyManager, StreamingConfig};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let heap_index = unsafe {
+        let properties = allocator.get_memory_properties().unwrap();
+        properties.memory_types[0].heap_index
+    };
+    let manager = ResidencyManager::new(
+        &allocator,
+        StreamingConfig {
+            heap_index,
+            budget_fraction: 1.0,
+            ..Default::default()
+        },
+    );
+    let unreserved = manager.streaming_budget();
+    let reserved = unreserved.min(1024 * 1024);
+    let token = allocator.reserve_budget(heap_index, reserved).unwrap();
+    assert_eq!(manager.streaming_budget(), unreserved - reserved);
+    drop(token);
+    assert_eq!(manager.streaming_budget(), unreserved);
+}
+
+#[test]
+fn reserve_budget_rejects_unknown_heap() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let heap_count = allocator.memory_properties().memory_heap_count;
+    assert_eq!(
+        allocator.reserve_budget(heap_count, 1).err(),
+        Some(ash::vk::Result::ERROR_VALIDATION_FAILED_EXT)
+    );
+    assert_eq!(allocator.reserved_budget(heap_count), 0);
+}
+
+#[test]
+fn one_line_summary_counts_dedicated_allocations() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+        .build();
+    unsafe {
+        let (block_buffer, block_allocation, _) = allocator
+            .create_buffer(&buffer_info, &vk_mem::AllocationCreateInfo::default())
+            .unwrap();
+        let (dedicated_buffer, dedicated_allocation, _) = allocator
+            .create_buffer(
+                &buffer_info,
+                &vk_mem::AllocationCreateInfo {
+                    flags: vk_mem::AllocationCreateFlags::DEDICATED_MEMORY,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let summary = allocator.one_line_summary();
+        assert!(summary.starts_with("DL "), "{}", summary);
+        assert!(summary.contains(" | allocs 2 | "), "{}", summary);
+        assert!(summary.ends_with(" | dedicated 1"), "{}", summary);
+
+        allocator.destroy_buffer(dedicated_buffer, &dedicated_allocation);
+        assert!(allocator.one_line_summary().ends_with(" | dedicated 0"));
+        allocator.destroy_buffer(block_buffer, &block_allocation);
+    }
+}
+
+#[test]
+fn category_stats_sum_tagged_allocations() {
+    use vk_mem::category::Category;
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+        .build();
+    let allocation_info = |category| vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        category,
+        ..Default::default()
+    };
+    unsafe {
+        let created = allocator
+            .create_buffers(&[
+                (buffer_info, allocation_info(Some(Category::Meshes))),
+                (buffer_info, allocation_info(Some(Category::Meshes))),
+                (
+                    buffer_info,
+                    allocation_info(Some(Category::Other("scratch a"))),
+                ),
+                (buffer_info, allocation_info(None)),
+            ])
+            .unwrap();
+        let (image_buffer, image_allocation, image_info) = allocator
+            .create_buffer(&buffer_info, &allocation_info(Some(Category::Textures)))
+            .unwrap();
+
+        let stats = allocator.category_stats();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[&Category::Meshes].allocation_count, 2);
+        assert_eq!(
+            stats[&Category::Meshes].allocation_bytes,
+            (created[0].2.get_size() + created[1].2.get_size()) as ash::vk::DeviceSize
+        );
+        assert_eq!(
+            stats[&Category::Textures].allocation_bytes,
+            image_info.get_size() as ash::vk::DeviceSize
+        );
+        assert_eq!(allocator.get_allocation_category(&created[3].1), None);
+        assert_eq!(
+            allocator.get_allocation_category(&image_allocation),
+            Some(Category::Textures)
+        );
+
+        allocator.destroy_buffer(image_buffer, &image_allocation);
+        for (buffer, allocation, _) in &created {
+            allocator.destroy_buffer(*buffer, allocation);
+        }
+    }
+    assert!(allocator.category_stats().is_empty());
+}
+
+#[test]
+fn report_writes_one_csv_row_per_allocation() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+        .build();
+    unsafe {
+        let (mesh_buffer, mesh, _) = allocator
+            .create_buffer(
+                &buffer_info,
+                &vk_mem::AllocationCreateInfo {
+                    usage: vk_mem::MemoryUsage::GpuOnly,
+                    category: Some(vk_mem::category::Category::Meshes),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        allocator
+            .set_allocation_name(&mesh, "level, geometry")
+            .unwrap();
+        let (mapped_buffer, mapped, _) = allocator
+            .create_buffer(
+                &buffer_info,
+                &vk_mem::AllocationCreateInfo {
+                    flags: vk_mem::AllocationCreateFlags::MAPPED
+                        | vk_mem::AllocationCreateFlags::DEDICATED_MEMORY
+                        | vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+                    usage: vk_mem::MemoryUsage::Auto,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let mut csv = Vec::new();
+        vk_mem::report::write_csv(&allocator, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], vk_mem::report::CSV_HEADER);
+        assert!(lines.iter().any(
+            |line| line.starts_with("\"level, geometry\",meshes,,") && line.ends_with(",false")
+        ));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with(",,,") && line.ends_with(",true,true")));
+
+        allocator.destroy_buffer(mesh_buffer, &mesh);
+        allocator.destroy_buffer(mapped_buffer, &mapped);
+    }
+    assert!(vk_mem::report::records(&allocator).unwrap().is_empty());
+}
+
+#[test]
+fn out_of_memory_failures_are_dumped() {
+    let harness = TestHarness::new();
+    let mut allocator = harness.create_allocator();
+    let dumps = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = dumps.clone();
+    allocator.set_oom_dump(Some(vk_mem::oom::OomDumpTarget::Callback(
+        std::sync::Arc::new(move |dump: &vk_mem::oom::OomDump| {
+            sink.lock().unwrap().push(dump.to_string())
+        }),
+    )));
+
+    let error = unsafe {
+        allocator.allocate_memory(
+            &ash::vk::MemoryRequirements {
+                size: 1 << 50,
+                alignment: 1,
+                memory_type_bits: !0,
+            },
+            &vk_mem::AllocationCreateInfo {
+                flags: vk_mem::AllocationCreateFlags::WITHIN_BUDGET,
+                usage: vk_mem::MemoryUsage::GpuOnly,
+                ..Default::default()
+            },
+        )
+    }
+    .unwrap_err();
+    assert_eq!(error.result(), ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+
+    let dumps = dumps.lock().unwrap();
+    assert_eq!(dumps.len(), 1);
+    assert!(dumps[0].starts_with(&error.to_string()));
+    assert!(dumps[0].contains("heap 0:"));
+}
+
+#[test]
+fn fallback_chain_skips_unavailable_tiers() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        // Lazily allocated and protected memory types can't be host visible.
+        required_flags: ash::vk::MemoryPropertyFlags::LAZILY_ALLOCATED
+            | ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+        fallback_required_flags: vec![
+            ash::vk::MemoryPropertyFlags::PROTECTED | ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+            ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+        ],
+        ..Default::default()
+    };
+    unsafe {
+        let (buffer, allocation, info, tier) = allocator
+            .create_buffer_with_fallback(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &allocation_info,
+            )
+            .unwrap();
+        assert_eq!(tier, 2);
+        assert!(allocator.get_memory_properties().unwrap().memory_types
+            [info.get_memory_type() as usize]
+            .property_flags
+            .contains(ash::vk::MemoryPropertyFlags::HOST_VISIBLE));
+        allocator.destroy_buffer(buffer, &allocation);
+
+        let error = allocator
+            .create_buffer_with_fallback(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    fallback_required_flags: Vec::new(),
+                    ..allocation_info
+                },
+            )
+            .unwrap_err();
+        assert_eq!(error.result(), ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+    }
+}
+
+#[test]
+fn allocation_registry_invalidates_removed_ids() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let memory_requirements = ash::vk::MemoryRequirements {
+        size: 4096,
+        alignment: 256,
+        memory_type_bits: !0,
+    };
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..Default::default()
+    };
+    let mut registry = vk_mem::registry::AllocationRegistry::new();
+    unsafe {
+        let (first, _) = allocator
+            .allocate_memory(&memory_requirements, &allocation_info)
+            .unwrap();
+        let (second, _) = allocator
+            .allocate_memory(&memory_requirements, &allocation_info)
+            .unwrap();
+        let first_id = registry.insert(first);
+        let second_id = registry.insert(second);
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.get(first_id), Some(first));
+
+        assert!(registry.free_memory(&allocator, first_id));
+        assert!(!registry.contains(first_id));
+        assert!(!registry.free_memory(&allocator, first_id));
+
+        // The slot is reused, but the stale id doesn't resolve to the new allocation.
+        let (third, _) = allocator
+            .allocate_memory(&memory_requirements, &allocation_info)
+            .unwrap();
+        let third_id = registry.insert(third);
+        assert_eq!(third_id.index(), first_id.index());
+        assert_eq!(registry.get(first_id), None);
+        assert_eq!(
+            registry.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![third_id, second_id]
+        );
+
+        for (_, allocation) in registry.iter() {
+            allocator.free_memory(&allocation);
+        }
+    }
+}
+
+#[test]
+fn smart_routing_groups_small_buffers_into_pools() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    allocator.set_smart_routing(Some(vk_mem::routing::RoutingConfig::default()));
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..Default::default()
+    };
+    let buffer_info = |size| {
+        ash::vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build()
+    };
+    unsafe {
+        let small = [
+            allocator
+                .create_buffer(&buffer_info(1024), &allocation_info)
+                .unwrap(),
+            allocator
+                .create_buffer(&buffer_info(64 * 1024), &allocation_info)
+                .unwrap(),
+        ];
+        let large = allocator
+            .create_buffer(&buffer_info(64 * 1024 * 1024), &allocation_info)
+            .unwrap();
+
+        let pools = allocator.smart_pools();
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].usage_class, vk_mem::routing::UsageClass::Buffer);
+        assert_eq!(pools[0].bucket_limit, 256 * 1024);
+        let records = vk_mem::report::records(&allocator).unwrap();
+        let pool_of = |allocation: &vk_mem::Allocation| {
+            records
+                .iter()
+                .find(|record| record.allocation == *allocation)
+                .unwrap()
+                .pool
+        };
+        assert_eq!(pool_of(&small[0].1), Some(pools[0].pool));
+        assert_eq!(pool_of(&small[1].1), Some(pools[0].pool));
+        assert_eq!(pool_of(&large.1), None);
+
+        for (buffer, allocation, _) in small.iter().chain(Some(&large)) {
+            allocator.destroy_buffer(*buffer, allocation);
+        }
+    }
+}
+
+#[test]
+fn pool_allocate_next_outlives_pool_creation() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER)
+        .sharing_mode(ash::vk::SharingMode::EXCLUSIVE);
+    unsafe {
+        let memory_type_index = allocator
+            .find_memory_type_index_for_buffer_info(&buffer_info, &Default::default())
+            .unwrap();
+        // The chain is built from a temporary; the pool keeps its own copy.
+        let pool = allocator
+            .create_pool(&vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                memory_allocate_next: Some(vk_mem::pool_next::PoolAllocateNext::ExportMemory(
+                    ash::vk::ExternalMemoryHandleTypeFlags::empty(),
+                )),
+                ..Default::default()
+            })
+            .unwrap();
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &buffer_info,
+                &vk_mem::AllocationCreateInfo {
+                    pool: Some(pool),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        allocator.destroy_buffer(buffer, &allocation);
+        allocator.destroy_pool(pool);
+    }
+}
+
+#[test]
+fn destroyed_allocator_is_poisoned_for_clones() {
+    let harness = TestHarness::new();
+    let mut allocator = harness.create_allocator();
+    let clone = allocator.clone();
+    assert!(!clone.is_destroyed());
+    unsafe {
+        allocator.destroy();
+        allocator.destroy();
+    }
+    assert!(allocator.is_destroyed());
+    assert!(clone.is_destroyed());
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+        clone.get_info();
+    }));
+    assert!(result.is_err());
+}
+
+#[test]
+fn device_properties_are_cached_at_creation() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let properties = unsafe {
+        harness
+            .instance
+            .get_physical_device_properties(harness.physical_device)
+    };
+    assert_eq!(
+        allocator.physical_device_properties().device_id,
+        properties.device_id
+    );
+    assert_eq!(
+        allocator.non_coherent_atom_size(),
+        properties.limits.non_coherent_atom_size
+    );
+    assert_eq!(
+        allocator.min_uniform_buffer_offset_alignment(),
+        properties.limits.min_uniform_buffer_offset_alignment
+    );
+    assert_eq!(
+        allocator.memory_properties().memory_type_count,
+        unsafe { allocator.get_memory_properties() }
+            .unwrap()
+            .memory_type_count
+    );
+}
+
+#[test]
+fn flush_ranges_skip_coherent_memory() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(4096)
+        .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC);
+    unsafe {
+        let (buffer, allocation, info) = allocator
+            .create_buffer(
+                &buffer_info,
+                &vk_mem::AllocationCreateInfo {
+                    required_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                        | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(
+            allocator.memory_properties().memory_types[info.get_memory_type() as usize]
+                .property_flags
+                .contains(ash::vk::MemoryPropertyFlags::HOST_COHERENT)
+        );
+        allocator.flush_range(&allocation, 3..17).unwrap();
+        allocator.invalidate_range(&allocation, ..).unwrap();
+        assert!(!allocator.maybe_flush(&allocation, 100..).unwrap());
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+
+    let atom = allocator.non_coherent_atom_size();
+    assert_eq!(allocator.non_coherent_range(0..atom), 0..atom);
+    let range = allocator.non_coherent_range(1..atom + 1);
+    assert!(range.start <= 1 && range.end >= atom + 1);
+    assert_eq!((range.start % atom, range.end % atom), (0, 0));
+    assert_eq!(allocator.non_coherent_range(atom..atom), atom..atom);
+}
+
+#[test]
+fn batch_flush_takes_optional_ranges() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(4096)
+        .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC);
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        required_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+        ..Default::default()
+    };
+    unsafe {
+        let (buffer_a, allocation_a, _) = allocator
+            .create_buffer(&buffer_info, &allocation_info)
+            .unwrap();
+        let (buffer_b, allocation_b, _) = allocator
+            .create_buffer(&buffer_info, &allocation_info)
+            .unwrap();
+        let mut allocations = [allocation_a, allocation_b];
+        allocator
+            .flush_allocations(&mut allocations, None, None)
+            .unwrap();
+        allocator
+            .invalidate_allocations(&mut allocations, Some(&[0, 64]), None)
+            .unwrap();
+        assert_eq!(
+            allocator.flush_allocations(&mut allocations, None, Some(&[128])),
+            Err(ash::vk::Result::ERROR_VALIDATION_FAILED_EXT)
+        );
+        allocator.destroy_buffer(buffer_a, &allocation_a);
+        allocator.destroy_buffer(buffer_b, &allocation_b);
+    }
+}
+
+#[test]
+fn dedicated_preference_queries_succeed() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(64 * 1024)
+        .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER);
+    let image_info = ash::vk::ImageCreateInfo::builder()
+        .image_type(ash::vk::ImageType::TYPE_2D)
+        .format(ash::vk::Format::R8G8B8A8_UNORM)
+        .extent(ash::vk::Extent3D {
+            width: 64,
+            height: 64,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(ash::vk::SampleCountFlags::TYPE_1)
+        .tiling(ash::vk::ImageTiling::OPTIMAL)
+        .usage(ash::vk::ImageUsageFlags::SAMPLED);
+    unsafe {
+        allocator
+            .prefers_dedicated_for_buffer(&buffer_info)
+            .unwrap();
+        allocator.prefers_dedicated_for_image(&image_info).unwrap();
+    }
+}
+
+#[test]
+fn small_buffers_share_blocks() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let mut pool = vk_mem::small_buffer::SmallBufferPool::new(
+        &allocator,
+        ash::vk::BufferUsageFlags::UNIFORM_BUFFER,
+        vk_mem::AllocationCreateInfo {
+            flags: vk_mem::AllocationCreateFlags::MAPPED
+                | vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            usage: vk_mem::MemoryUsage::Auto,
+            ..Default::default()
+        },
+        vk_mem::small_buffer::SmallBufferConfig {
+            threshold: 1024,
+            block_size: 64 * 1024,
+        },
+    );
+    let alignment = allocator.min_uniform_buffer_offset_alignment();
+    unsafe {
+        let small: Vec<_> = (0..32).map(|_| pool.allocate(200, 1).unwrap()).collect();
+        assert_eq!(pool.block_count(), 1);
+        assert!(small.iter().all(|range| range.buffer == small[0].buffer));
+        assert!(small.iter().all(|range| range.offset % alignment == 0));
+        assert!(small.iter().all(|range| !range.mapped.is_null()));
+
+        let large = pool.allocate(4096, 1).unwrap();
+        assert_ne!(large.buffer, small[0].buffer);
+        assert_eq!(large.offset, 0);
+        pool.free(large);
+
+        for range in small {
+            pool.free(range);
+        }
+        assert_eq!(pool.block_count(), 1);
+    }
+}
+
+#[test]
+fn dynamic_uniform_offsets_are_aligned_per_frame() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let mut uniforms =
+        vk_mem::dynamic_uniform::DynamicUniformAllocator::new(&allocator, 2, 4096).unwrap();
+    let alignment = uniforms.alignment();
+    let first = uniforms.push(&[1.0f32; 16]).unwrap();
+    let second = uniforms.push(&7u32).unwrap();
+    assert_eq!(first, 0);
+    assert_eq!(second as u64 % alignment, 0);
+    assert!(second as u64 >= 64);
+    uniforms.flush().unwrap();
+
+    uniforms.begin_frame(1);
+    assert_eq!(uniforms.used(), 0);
+    let next_frame = uniforms.push(&7u32).unwrap();
+    assert!(next_frame as u64 >= 4096);
+    assert!(uniforms.push_bytes(&vec![0; 8192]).is_err());
+
+    uniforms.begin_frame(2);
+    assert_eq!(uniforms.push(&7u32).unwrap(), 0);
+}
+
+#[test]
+fn geometry_pool_compacts_into_free_ranges() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let mut geometry = vk_mem::geometry::GeometryPool::new(
+        &allocator,
+        64 * 1024,
+        ash::vk::BufferUsageFlags::VERTEX_BUFFER | ash::vk::BufferUsageFlags::INDEX_BUFFER,
+    )
+    .unwrap();
+    let meshes: Vec<_> = (0..8)
+        .map(|_| geometry.allocate(4096, 16).unwrap().0)
+        .collect();
+    assert!(geometry.allocate(64 * 1024, 16).is_err());
+    for &mesh in meshes.iter().step_by(2) {
+        assert!(geometry.free(mesh));
+    }
+    assert_eq!(geometry.used(), 4 * 4096);
+    assert!(geometry.free_range_count() > 1);
+
+    let last = meshes[7];
+    let compaction = geometry.compact(4096);
+    assert_eq!(compaction.remaps().len(), 1);
+    let remap = compaction.remaps()[0];
+    assert_eq!(remap.mesh, last);
+    assert_eq!(remap.old_offset, 7 * 4096);
+    assert_eq!(remap.new_offset, 0);
+    assert_eq!(geometry.range(last).unwrap().offset, 0);
+    let region = compaction.regions()[0];
+    assert_eq!((region.src_offset, region.dst_offset), (7 * 4096, 0));
+
+    // The old range stays reserved until the copy is done.
+    let largest = geometry.largest_free_range();
+    geometry.finish_compaction(compaction);
+    assert!(geometry.largest_free_range() > largest);
+}
+
+#[test]
+fn raytracing_helpers_require_device_address_flag() {
+    use vk_mem::raytracing::{create_acceleration_structure_buffer, ScratchPool};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let properties = ash::vk::PhysicalDeviceAccelerationStructurePropertiesKHR {
+        min_acceleration_structure_scratch_offset_alignment: 128,
+        ..Default::default()
+    };
+    let err = ScratchPool::new(&allocator, &harness.device, &properties, 1 << 20)
+        .err()
+        .unwrap();
+    assert_eq!(err.result(), ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+    assert_eq!(err.operation(), "ScratchPool::new");
+    let err = unsafe { create_acceleration_structure_buffer(&allocator, 1 << 16) }
+        .err()
+        .unwrap();
+    assert_eq!(err.result(), ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+}
+
+#[test]
+fn host_image_copy_falls_back_to_staging() {
+    use vk_mem::host_image_copy::HostImageCopy;
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let host_copy = HostImageCopy::new(&harness.instance, &harness.device, false);
+    assert!(!host_copy.is_available());
+
+    let image_info = ash::vk::ImageCreateInfo::builder()
+        .image_type(ash::vk::ImageType::TYPE_2D)
+        .format(ash::vk::Format::R8G8B8A8_UNORM)
+        .extent(ash::vk::Extent3D {
+            width: 4,
+            height: 4,
+            depth: 1,
+        })
+        .mip_levels(2)
+        .array_layers(1)
+        .samples(ash::vk::SampleCountFlags::TYPE_1)
+        .tiling(ash::vk::ImageTiling::OPTIMAL)
+        .usage(ash::vk::ImageUsageFlags::SAMPLED)
+        .build();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    let mips = [vec![1u8; 4 * 4 * 4], vec![2u8; 2 * 2 * 4]];
+    let mips: Vec<&[u8]> = mips.iter().map(Vec::as_slice).collect();
+
+    unsafe {
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let command_buffer = harness
+            .device
+            .allocate_command_buffers(
+                &ash::vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .command_buffer_count(1),
+            )
+            .unwrap()[0];
+        harness
+            .device
+            .begin_command_buffer(command_buffer, &ash::vk::CommandBufferBeginInfo::default())
+            .unwrap();
+
+        let (image, allocation, _) = allocator
+            .create_image_host_copyable(&host_copy, &image_info, &allocation_info)
+            .unwrap();
+        let staging = allocator
+            .copy_host_to_image(
+                &host_copy,
+                &harness.device,
+                image,
+                &ash::vk::ImageCreateInfo {
+                    usage: image_info.usage | ash::vk::ImageUsageFlags::TRANSFER_DST,
+                    ..image_info
+                },
+                &mips,
+                command_buffer,
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .unwrap()
+            .expect("without host image copy the upload is staged");
+
+        harness.device.end_command_buffer(command_buffer).unwrap();
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+        harness
+            .device
+            .queue_submit(
+                queue,
+                &[ash::vk::SubmitInfo::builder()
+                    .command_buffers(&[command_buffer])
+                    .build()],
+                ash::vk::Fence::null(),
+            )
+            .unwrap();
+        harness.device.queue_wait_idle(queue).unwrap();
+
+        staging.destroy(&allocator);
+        allocator.destroy_image(image, &allocation);
+        harness.device.destroy_command_pool(command_pool, None);
+        assert_eq!(
+            allocator
+                .calculate_statistics()
+                .unwrap()
+                .total
+                .statistics
+                .allocation_count,
+            0
+        );
+    }
+}
+
+#[test]
+fn import_host_memory_checks_alignment() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let alignment = match allocator.min_imported_host_pointer_alignment() {
+        Some(alignment) => alignment,
+        None => {
+            let mut data = vec![0u8; 4096];
+            let err = unsafe {
+                allocator.import_host_memory(data.as_mut_ptr().cast(), data.len() as u64)
+            }
+            .unwrap_err();
+            assert_eq!(err.result(), ash::vk::Result::ERROR_FEATURE_NOT_PRESENT);
+            assert_eq!(err.operation(), "Allocator::import_host_memory");
+            return;
+        }
+    };
+
+    let layout =
+        std::alloc::Layout::from_size_align(2 * alignment as usize, alignment as usize).unwrap();
+    unsafe {
+        let data = std::alloc::alloc_zeroed(layout);
+        let err = allocator
+            .import_host_memory(data.add(1).cast(), alignment)
+            .unwrap_err();
+        assert_eq!(err.result(), ash::vk::Result::ERROR_VALIDATION_FAILED_EXT);
+
+        let imported = allocator
+            .import_host_memory(data.cast(), 2 * alignment)
+            .unwrap();
+        assert_eq!(imported.size(), 2 * alignment);
+        assert_eq!(imported.host_pointer(), data.cast());
+        drop(imported);
+        std::alloc::dealloc(data, layout);
+    }
+}
+
+#[test]
+fn upload_strategy_follows_memory_architecture() {
+    use vk_mem::architecture::{MemoryArchitecture, UploadStrategy};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let architecture = allocator.memory_architecture();
+    let strategy =
+        allocator.suggest_upload_strategy(64 * 1024, ash::vk::BufferUsageFlags::VERTEX_BUFFER);
+    if architecture == MemoryArchitecture::Discrete {
+        assert_ne!(strategy, UploadStrategy::Mapped);
+    }
+    if architecture == MemoryArchitecture::Uma {
+        assert_eq!(strategy, UploadStrategy::Mapped);
+    }
+
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(64 * 1024)
+        .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER | ash::vk::BufferUsageFlags::TRANSFER_DST)
+        .build();
+    unsafe {
+        let (buffer, allocation, info) = allocator
+            .create_buffer(&buffer_info, &strategy.allocation_create_info())
+            .unwrap();
+        assert_eq!(
+            strategy.resolve(&allocator, &allocation) == UploadStrategy::Mapped,
+            !info.get_mapped_data().is_null()
+        );
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[test]
+fn rebar_strategy_resolves_to_mapped_or_staging() {
+    use vk_mem::architecture::{MemoryArchitecture, UploadStrategy};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let strategy =
+        allocator.suggest_upload_strategy(1024 * 1024, ash::vk::BufferUsageFlags::STORAGE_BUFFER);
+    if allocator.memory_architecture() == MemoryArchitecture::Discrete {
+        assert_eq!(
+            strategy == UploadStrategy::MappedOrStaging,
+            allocator.has_rebar()
+        );
+    }
+
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(1024 * 1024)
+        .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER | ash::vk::BufferUsageFlags::TRANSFER_DST)
+        .build();
+    unsafe {
+        let (buffer, allocation, info) = allocator
+            .create_buffer(
+                &buffer_info,
+                &UploadStrategy::MappedOrStaging.allocation_create_info(),
+            )
+            .unwrap();
+        let resolved = UploadStrategy::MappedOrStaging.resolve(&allocator, &allocation);
+        assert_ne!(resolved, UploadStrategy::MappedOrStaging);
+        if resolved == UploadStrategy::Mapped {
+            assert!(!info.get_mapped_data().is_null());
+        }
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[test]
+fn auto_block_size_scales_with_small_heaps() {
+    const MIB: u64 = 1024 * 1024;
+    let heap_sizes = |sizes: &[u64]| {
+        let mut properties = ash::vk::PhysicalDeviceMemoryProperties::default();
+        properties.memory_heap_count = sizes.len() as u32;
+        for (heap, &size) in properties.memory_heaps.iter_mut().zip(sizes) {
+            heap.size = size;
+            heap.flags = ash::vk::MemoryHeapFlags::DEVICE_LOCAL;
+        }
+        properties
+    };
+    let auto = vk_mem::AllocatorCreateInfo::auto_block_size;
+    assert_eq!(auto(&heap_sizes(&[512 * MIB])), 0);
+    assert_eq!(auto(&heap_sizes(&[2048 * MIB])), 128 * MIB);
+    assert_eq!(auto(&heap_sizes(&[3072 * MIB])), 128 * MIB);
+    assert_eq!(auto(&heap_sizes(&[8192 * MIB, 2048 * MIB])), 128 * MIB);
+    assert_eq!(auto(&heap_sizes(&[24576 * MIB])), 256 * MIB);
+
+    let harness = TestHarness::new();
+    let create_info = vk_mem::AllocatorCreateInfo {
+        flags: vk_mem::AllocatorCreateFlags::NONE,
+        physical_device: harness.physical_device,
+        device: harness.device.clone(),
+        preferred_large_heap_block_size: vk_mem::AllocatorCreateInfo::AUTO_BLOCK_SIZE,
+        allocation_callbacks: None,
+        device_memory_callbacks: None,
+        heap_size_limit: None,
+        heap_size_limit_fractions: None,
+        vulkan_functions: None,
+        get_instance_proc_addr: Some(harness.entry.static_fn().get_instance_proc_addr),
+        instance: harness.instance.clone(),
+        vulkan_api_version: ash::vk::API_VERSION_1_0,
+        api_version_from_instance: false,
+        external_memory_handle_type: std::ptr::null(),
+    };
+    unsafe {
+        let allocator = vk_mem::Allocator::new(&create_info).unwrap();
+        let buffer_info = ash::vk::BufferCreateInfo::builder()
+            .size(1024)
+            .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+            .build();
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::AutoPreferDevice,
+            ..Default::default()
+        };
+        let (buffer, allocation, _) = allocator
+            .create_buffer(&buffer_info, &allocation_info)
+            .unwrap();
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[test]
+fn growing_pool_doubles_block_size() {
+    use vk_mem::pool_growth::{GrowingPool, GrowthConfig, GrowthPolicy};
+
+    const MIB: u64 = 1024 * 1024;
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(768 * 1024)
+        .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER)
+        .sharing_mode(ash::vk::SharingMode::EXCLUSIVE)
+        .build();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    unsafe {
+        let memory_type_index = allocator
+            .find_memory_type_index_for_buffer_info(&buffer_info, &allocation_info)
+            .unwrap();
+        let mut pool = GrowingPool::new(
+            &allocator,
+            vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                ..Default::default()
+            },
+            GrowthConfig {
+                initial_block_size: MIB,
+                blocks_per_pool: 1,
+                policy: GrowthPolicy::Doubling {
+                    max_block_size: 4 * MIB,
+                },
+            },
+        );
+
+        let buffers: Vec<_> = (0..4)
+            .map(|_| pool.create_buffer(&buffer_info, &allocation_info).unwrap())
+            .collect();
+        let slack = pool.slack();
+        let block_sizes: Vec<u64> = slack.iter().map(|pool| pool.block_size).collect();
+        assert_eq!(block_sizes, [MIB, 2 * MIB, 4 * MIB]);
+        assert_eq!(slack[0].block_bytes, MIB);
+        assert_eq!(slack[0].slack(), 256 * 1024);
+        assert_eq!(slack[1].allocation_bytes, 2 * 768 * 1024);
+        assert_eq!(pool.total_slack(), 7 * MIB - 4 * 768 * 1024);
+
+        for (buffer, allocation, _) in buffers {
+            allocator.destroy_buffer(buffer, &allocation);
+        }
+        drop(pool);
+        assert_eq!(
+            allocator
+                .calculate_statistics()
+                .unwrap()
+                .total
+                .statistics
+                .block_count,
+            0
+        );
+    }
+}
+
+#[test]
+fn trim_pool_releases_kept_empty_block() {
+    const MIB: u64 = 1024 * 1024;
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = |size| {
+        ash::vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER)
+            .sharing_mode(ash::vk::SharingMode::EXCLUSIVE)
+            .build()
+    };
+    unsafe {
+        let memory_type_index = allocator
+            .find_memory_type_index_for_buffer_info(&buffer_info(MIB), &Default::default())
+            .unwrap();
+        let pool = allocator
+            .create_pool(&vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                block_size: MIB,
+                ..Default::default()
+            })
+            .unwrap();
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            pool: Some(pool),
+            ..Default::default()
+        };
+        let kept = allocator
+            .create_buffer(&buffer_info(600 * 1024), &allocation_info)
+            .unwrap();
+        let unloaded = allocator
+            .create_buffer(&buffer_info(600 * 1024), &allocation_info)
+            .unwrap();
+        assert_eq!(allocator.get_pool_statistics(&pool).block_count, 2);
+
+        // VMA keeps the block that just became empty.
+        allocator.destroy_buffer(unloaded.0, &unloaded.1);
+        assert_eq!(allocator.get_pool_statistics(&pool).block_count, 2);
+        assert_eq!(allocator.trim_pool(&pool), MIB);
+        assert_eq!(allocator.get_pool_statistics(&pool).block_count, 1);
+        assert_eq!(allocator.trim_all(), 0);
+
+        allocator.destroy_buffer(kept.0, &kept.1);
+        allocator.destroy_pool(pool);
+    }
+}
+
+#[test]
+fn heap_size_limit_fractions_scale_reported_heaps() {
+    let harness = TestHarness::new();
+    let reported = unsafe {
+        harness
+            .instance
+            .get_physical_device_memory_properties(harness.physical_device)
+    };
+    let heap_count = reported.memory_heap_count as usize;
+    let halves = vec![0.5f32; heap_count];
+    let mut create_info = vk_mem::AllocatorCreateInfo {
+        flags: vk_mem::AllocatorCreateFlags::NONE,
+        physical_device: harness.physical_device,
+        device: harness.device.clone(),
+        preferred_large_heap_block_size: 0,
+        allocation_callbacks: None,
+        device_memory_callbacks: None,
+        heap_size_limit: None,
+        heap_size_limit_fractions: Some(&halves),
+        vulkan_functions: None,
+        get_instance_proc_addr: Some(harness.entry.static_fn().get_instance_proc_addr),
+        instance: harness.instance.clone(),
+        vulkan_api_version: ash::vk::API_VERSION_1_0,
+        api_version_from_instance: false,
+        external_memory_handle_type: std::ptr::null(),
+    };
+    unsafe {
+        let allocator = vk_mem::Allocator::new(&create_info).unwrap();
+        let limited = allocator.memory_properties();
+        for heap_index in 0..heap_count {
+            assert_eq!(
+                limited.memory_heaps[heap_index].size,
+                reported.memory_heaps[heap_index].size / 2
+            );
+        }
+        drop(allocator);
+
+        let invalid = vec![0.0f32; heap_count];
+        create_info.heap_size_limit_fractions = Some(&invalid);
+        assert_eq!(
+            vk_mem::Allocator::new(&create_info).unwrap_err(),
+            ash::vk::Result::ERROR_INITIALIZATION_FAILED
+        );
+    }
+}
+
+#[test]
+fn call_site_macros_name_allocations_after_their_location() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+        .build();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    unsafe {
+        let (buffer, allocation, _) =
+            vk_mem::create_buffer!(allocator, &buffer_info, &allocation_info).unwrap();
+        let line = line!() - 1;
+        let name = allocator.get_allocation_name(&allocation).unwrap().unwrap();
+        assert_eq!(name, format!("{}:{}", file!(), line));
+        assert_eq!(vk_mem::call_site::split(&name), (None, name.as_str()));
+        allocator.destroy_buffer(buffer, &allocation);
+
+        let requirements = ash::vk::MemoryRequirements {
+            size: 4096,
+            alignment: 256,
+            memory_type_bits: u32::MAX,
+        };
+        let label = String::from("scratch");
+        let (allocation, _) =
+            vk_mem::allocate_memory!(&allocator, &requirements, &allocation_info, label).unwrap();
+        let name = allocator.get_allocation_name(&allocation).unwrap().unwrap();
+        let (label, location) = vk_mem::call_site::split(&name);
+        assert_eq!(label, Some("scratch"));
+        assert!(location.starts_with(file!()));
+        allocator.free_memory(&allocation);
+    }
+}
+
+#[test]
+fn create_buffer_within_budget_reports_budgets() {
+    let harness = TestHarness::new();
+    let heap_count = unsafe {
+        harness
+            .instance
+            .get_physical_device_memory_properties(harness.physical_device)
+    }
+    .memory_heap_count as usize;
+    let limits = vec![64 * 1024 * 1024; heap_count];
+    let create_info = vk_mem::AllocatorCreateInfo {
+        flags: vk_mem::AllocatorCreateFlags::NONE,
+        physical_device: harness.physical_device,
+        device: harness.device.clone(),
+        preferred_large_heap_block_size: 0,
+        allocation_callbacks: None,
+        device_memory_callbacks: None,
+        heap_size_limit: Some(&limits),
+        heap_size_limit_fractions: None,
+        vulkan_functions: None,
+        get_instance_proc_addr: Some(harness.entry.static_fn().get_instance_proc_addr),
+        instance: harness.instance.clone(),
+        vulkan_api_version: ash::vk::API_VERSION_1_0,
+        api_version_from_instance: false,
+        external_memory_handle_type: std::ptr::null(),
+    };
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+    let buffer_info = |size| {
+        ash::vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(ash::vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build()
+    };
+    unsafe {
+        let allocator = vk_mem::Allocator::new(&create_info).unwrap();
+        let (buffer, allocation, _, placement) = allocator
+            .create_buffer_within_budget(
+                &buffer_info(1024 * 1024),
+                &allocation_info,
+                vk_mem::budget::BudgetFallback::Fail,
+            )
+            .unwrap();
+        assert_eq!(placement, vk_mem::budget::BudgetPlacement::Requested);
+        allocator.destroy_buffer(buffer, &allocation);
+
+        let error = allocator
+            .create_buffer_within_budget(
+                &buffer_info(128 * 1024 * 1024),
+                &allocation_info,
+                vk_mem::budget::BudgetFallback::Fail,
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.error.result(),
+            ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY
+        );
+        assert_eq!(error.budgets.len(), heap_count);
+        assert!(error
+            .budgets
+            .iter()
+            .all(|heap| heap.budget <= 64 * 1024 * 1024));
+        assert!(error.to_string().contains("heap 0: "));
+    }
+}
+
+#[test]
+fn memory_type_mask_narrows_memory_types() {
+    use ash::vk::MemoryPropertyFlags;
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let properties = *allocator.memory_properties();
+    let types = &properties.memory_types[..properties.memory_type_count as usize];
+    let expected = |keep: &dyn Fn(&ash::vk::MemoryType) -> bool| {
+        types
+            .iter()
+            .enumerate()
+            .filter(|(_, memory_type)| keep(memory_type))
+            .fold(0u32, |bits, (index, _)| bits | 1 << index)
+    };
+
+    let all = allocator.memory_type_mask();
+    assert_eq!(all.bits(), expected(&|_| true));
+    assert_eq!(all.indices().count(), types.len());
+
+    let device_only = allocator
+        .memory_type_mask()
+        .require(MemoryPropertyFlags::DEVICE_LOCAL)
+        .forbid(MemoryPropertyFlags::HOST_VISIBLE);
+    assert_eq!(
+        device_only.bits(),
+        expected(&|memory_type| {
+            memory_type
+                .property_flags
+                .contains(MemoryPropertyFlags::DEVICE_LOCAL)
+                && !memory_type
+                    .property_flags
+                    .contains(MemoryPropertyFlags::HOST_VISIBLE)
+        })
+    );
+    assert_eq!(
+        allocator
+            .memory_type_mask()
+            .exclude_host_visible_device_local()
+            .bits(),
+        expected(&|memory_type| {
+            !memory_type
+                .property_flags
+                .contains(MemoryPropertyFlags::DEVICE_LOCAL | MemoryPropertyFlags::HOST_VISIBLE)
+        })
+    );
+    assert_eq!(
+        u32::from(allocator.memory_type_mask().only_heap(0).intersect(0b1)),
+        expected(&|memory_type| memory_type.heap_index == 0) & 0b1
+    );
+    assert!(allocator.memory_type_mask().only_heap(u32::MAX).is_empty());
+}
+
+#[test]
+fn enabled_features_follow_flags_and_api_version() {
+    use vk_mem::{AllocatorCreateFlags, EnabledFeatures};
+
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    assert_eq!(
+        allocator.enabled_features(),
+        EnabledFeatures::new(AllocatorCreateFlags::NONE, ash::vk::API_VERSION_1_0)
+    );
+
+    let legacy = EnabledFeatures::new(
+        AllocatorCreateFlags::KHR_DEDICATED_ALLOCATION
+            | AllocatorCreateFlags::VMA_ALLOCATOR_CREATE_EXT_MEMORY_BUDGET_BIT,
+        ash::vk::API_VERSION_1_0,
+    );
+    assert!(legacy.dedicated_allocation && legacy.memory_budget);
+    assert!(!legacy.bind_memory2 && !legacy.buffer_device_address && !legacy.maintenance4);
+
+    let core = EnabledFeatures::new(AllocatorCreateFlags::NONE, ash::vk::API_VERSION_1_3);
+    assert!(core.dedicated_allocation && core.bind_memory2 && core.maintenance4);
+    assert!(!core.memory_budget && !core.memory_priority && !core.amd_device_coherent_memory);
+}
+
+#[test]
+fn vulkan_api_version_is_validated_and_detected() {
+    let harness = TestHarness::new();
+    let device_version = unsafe {
+        harness
+            .instance
+            .get_physical_device_properties(harness.physical_device)
+    }
+    .api_version;
+    let mut create_info = vk_mem::AllocatorCreateInfo {
+        flags: vk_mem::AllocatorCreateFlags::NONE,
+        physical_device: harness.physical_device,
+        device: harness.device.clone(),
+        preferred_large_heap_block_size: 0,
+        allocation_callbacks: None,
+        device_memory_callbacks: None,
+        heap_size_limit: None,
+        heap_size_limit_fractions: None,
+        vulkan_functions: None,
+        get_instance_proc_addr: Some(harness.entry.static_fn().get_instance_proc_addr),
+        instance: harness.instance.clone(),
+        vulkan_api_version: ash::vk::make_api_version(0, 1, 9, 0),
+        api_version_from_instance: false,
+        external_memory_handle_type: std::ptr::null(),
+    };
+    unsafe {
+        assert_eq!(
+            vk_mem::Allocator::new(&create_info).unwrap_err(),
+            ash::vk::Result::ERROR_INITIALIZATION_FAILED
+        );
+
+        create_info.vulkan_api_version = 0;
+        let allocator = vk_mem::Allocator::new(&create_info).unwrap();
+        assert_eq!(allocator.vulkan_api_version(), ash::vk::API_VERSION_1_0);
+        drop(allocator);
+
+        create_info.api_version_from_instance = true;
+        let allocator = vk_mem::Allocator::new(&create_info).unwrap();
+        let detected = allocator.vulkan_api_version();
+        assert!(detected >= ash::vk::API_VERSION_1_0);
+        assert!(detected <= ash::vk::make_api_version(0, 1, 4, 0));
+        assert!(detected <= device_version);
+        assert_eq!(ash::vk::api_version_patch(detected), 0);
+    }
+}
+
+#[test]
+fn poll_deferred_frees_resources_behind_signaled_fences() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+        .build();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+
+    let allocation_count = || {
+        unsafe { allocator.calculate_statistics() }
+            .unwrap()
+            .total
+            .statistics
+            .allocation_count
+    };
+    unsafe {
+        let signaled = harness
+            .device
+            .create_fence(
+                &ash::vk::FenceCreateInfo::builder()
+                    .flags(ash::vk::FenceCreateFlags::SIGNALED)
+                    .build(),
+                None,
+            )
+            .unwrap();
+        let unsignaled = harness
+            .device
+            .create_fence(&ash::vk::FenceCreateInfo::default(), None)
+            .unwrap();
+
+        let (buffer, allocation, _) = allocator
+            .create_buffer(&buffer_info, &allocation_info)
+            .unwrap();
+        allocator.destroy_buffer_when(signaled, buffer, &allocation);
+        let (buffer, allocation, _) = allocator
+            .create_buffer(&buffer_info, &allocation_info)
+            .unwrap();
+        allocator.destroy_buffer_when(unsignaled, buffer, &allocation);
+        assert_eq!(allocator.deferred_count(), 2);
+
+        assert_eq!(allocator.poll_deferred().unwrap(), 1);
+        assert_eq!(allocator.deferred_count(), 1);
+        assert_eq!(allocation_count(), 1);
+        assert_eq!(allocator.poll_deferred().unwrap(), 0);
+
+        harness.device.destroy_fence(signaled, None);
+        harness.device.destroy_fence(unsignaled, None);
+    }
+}
+
+#[test]
+fn timeline_queue_frees_resources_once_values_are_reached() {
+    use vk_mem::deferred::{Deferred, TimelineQueue};
+
+    let harness = TestHarness::new_with_timeline_semaphores();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(16 * 1024)
+        .usage(ash::vk::BufferUsageFlags::UNIFORM_BUFFER)
+        .build();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::AutoPreferDevice,
+        ..Default::default()
+    };
+
+    unsafe {
+        let mut type_info = ash::vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(ash::vk::SemaphoreType::TIMELINE)
+            .initial_value(1);
+        let semaphore = harness
+            .device
+            .create_semaphore(
+                &ash::vk::SemaphoreCreateInfo::builder().push_next(&mut type_info),
+                None,
+            )
+            .unwrap();
+
+        let queue = TimelineQueue::new(&allocator);
+        for value in 1..=2 {
+            let (buffer, allocation, _) = allocator
+                .create_buffer(&buffer_info, &allocation_info)
+                .unwrap();
+            queue.defer(semaphore, value, Deferred::Buffer(buffer, allocation));
+        }
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.collect(&harness.device).unwrap(), 1);
+        assert_eq!(queue.len(), 1);
+
+        harness
+            .device
+            .signal_semaphore(
+                &ash::vk::SemaphoreSignalInfo::builder()
+                    .semaphore(semaphore)
+                    .value(2),
+            )
+            .unwrap();
+        assert_eq!(queue.collect(&harness.device).unwrap(), 1);
+        assert!(queue.is_empty());
+
+        drop(queue);
+        harness.device.destroy_semaphore(semaphore, None);
+    }
+}
+
+#[test]
+fn typed_user_data_is_dropped_with_the_allocation() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..Default::default()
+    };
+    let marker = std::sync::Arc::new(());
+    unsafe {
+        let (buffer, allocation, _) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+                    .build(),
+                &allocation_info,
+            )
+            .unwrap();
+        assert!(allocator
+            .set_user_data(&allocation, Box::new((42u32, marker.clone())))
+            .is_none());
+        assert_eq!(
+            allocator
+                .get_user_data::<(u32, std::sync::Arc<()>)>(&allocation)
+                .unwrap()
+                .0,
+            42
+        );
+        assert_eq!(allocator.get_user_data::<String>(&allocation), None);
+        allocator.with_user_data(&allocation, |data: &mut (u32, std::sync::Arc<()>)| {
+            data.0 += 1
+        });
+        assert_eq!(allocator.take_user_data::<String>(&allocation), None);
+        assert_eq!(std::sync::Arc::strong_count(&marker), 2);
+
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+    assert_eq!(std::sync::Arc::strong_count(&marker), 1);
+}
+
+#[test]
+fn allocation_name_is_readable() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..Default::default()
+    };
+    unsafe {
+        let (buffer, allocation, info) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(16 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+                    .build(),
+                &allocation_info,
+            )
+            .unwrap();
+        assert_eq!(info.get_name(), None);
+        assert_eq!(allocator.get_allocation_name(&allocation).unwrap(), None);
+
+        allocator
+            .set_allocation_name(&allocation, "vertices")
+            .unwrap();
+        assert_eq!(
+            allocator
+                .get_allocation_name(&allocation)
+                .unwrap()
+                .as_deref(),
+            Some("vertices")
+        );
+        let info = allocator.get_allocation_info(&allocation).unwrap();
+        assert_eq!(info.get_name(), Some("vertices"));
+
+        allocator.destroy_buffer(buffer, &allocation);
+    }
+}
+
+#[test]
+fn defragmentation_pass_exposes_moves() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let memory_requirements = ash::vk::MemoryRequirements {
+        size: 64 * 1024,
+        alignment: 256,
+        memory_type_bits: u32::MAX,
+    };
+    unsafe {
+        let memory_type_index = allocator
+            .find_memory_type_index(
+                memory_requirements.memory_type_bits,
+                &vk_mem::AllocationCreateInfo {
+                    required_flags: ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let pool = allocator
+            .create_pool(&vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                block_size: 64 * 1024 * 4,
+                ..Default::default()
+            })
+            .unwrap();
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            pool: Some(pool),
+            ..Default::default()
+        };
+        let mut kept = Vec::new();
+        for index in 0..16 {
+            let (allocation, _) = allocator
+                .allocate_memory(&memory_requirements, &allocation_info)
+                .unwrap();
+            if index % 2 == 0 {
+                allocator.free_memory(&allocation);
+            } else {
+                kept.push(allocation);
+            }
+        }
+
+        let mut defragmentation = allocator
+            .begin_defragmentation(&vk_mem::DefragmentationInfo {
+                pool: Some(pool),
+                ..Default::default()
+            })
+            .unwrap();
+        let (result, mut pass) = allocator.begin_defragmentation_pass(&mut defragmentation);
+        assert_eq!(result, Err(ash::vk::Result::INCOMPLETE));
+        assert!(!pass.moves().is_empty());
+        for defrag_move in pass.moves() {
+            assert!(kept.contains(&defrag_move.src_allocation));
+            assert_eq!(
+                defrag_move.operation,
+                vk_mem::DefragmentationMoveOperation::Copy
+            );
+        }
+        pass.set_operation(0, vk_mem::DefragmentationMoveOperation::Ignore);
+        assert_eq!(
+            pass.moves()[0].operation,
+            vk_mem::DefragmentationMoveOperation::Ignore
+        );
+        let _ = allocator.end_defragmentation_pass(&mut defragmentation, &mut pass);
+        allocator.end_defragmentation(&mut defragmentation).unwrap();
+
+        for allocation in &kept {
+            allocator.free_memory(allocation);
+        }
+        allocator.destroy_pool(pool);
+    }
+}
+
+/// Buffers by allocation, for the defragmentation tests.
+struct Buffers(std::collections::HashMap<usize, (ash::vk::Buffer, ash::vk::BufferCreateInfo)>);
+
+impl vk_mem::defrag::DefragHandler for Buffers {
+    fn resource(
+        &mut self,
+        allocation: &vk_mem::Allocation,
+    ) -> Option<vk_mem::defrag::MovableResource> {
+        let (buffer, create_info) = *self.0.get(&(*allocation as usize))?;
+        Some(vk_mem::defrag::MovableResource::Buffer {
+            buffer,
+            create_info,
+        })
+    }
+
+    fn moved(&mut self, allocation: &vk_mem::Allocation, moved: vk_mem::defrag::MovedResource) {
+        if let vk_mem::defrag::MovedResource::Buffer(buffer) = moved {
+            self.0.get_mut(&(*allocation as usize)).unwrap().0 = buffer;
+        }
+    }
+}
+
+#[test]
+fn defrag_run_moves_buffers_and_keeps_contents() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(64 * 1024)
+        .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC | ash::vk::BufferUsageFlags::TRANSFER_DST)
+        .build();
+    unsafe {
+        let memory_type_index = allocator
+            .find_memory_type_index_for_buffer_info(
+                &buffer_info,
+                &vk_mem::AllocationCreateInfo {
+                    required_flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                        | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let pool = allocator
+            .create_pool(&vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                block_size: 64 * 1024 * 4,
+                ..Default::default()
+            })
+            .unwrap();
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            pool: Some(pool),
+            ..Default::default()
+        };
+
+        let mut buffers = Buffers(Default::default());
+        let mut kept = Vec::new();
+        for index in 0..16u8 {
+            let (buffer, allocation, _) = allocator
+                .create_buffer(&buffer_info, &allocation_info)
+                .unwrap();
+            if index % 2 == 0 {
+                allocator.destroy_buffer(buffer, &allocation);
+            } else {
+                allocator
+                    .copy_to_allocation(&[index; 64 * 1024], &allocation, 0)
+                    .unwrap();
+                buffers.0.insert(allocation as usize, (buffer, buffer_info));
+                kept.push((index, allocation));
+            }
+        }
+
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+        let stats = vk_mem::defrag::run(
+            &allocator,
+            &vk_mem::defrag::DefragOptions {
+                pool: Some(pool),
+                ..Default::default()
+            },
+            &harness.device,
+            queue,
+            command_pool,
+            &mut buffers,
+        )
+        .unwrap();
+        assert!(stats.allocations_moved > 0);
+
+        for (index, allocation) in kept {
+            let mut contents = vec![0u8; 64 * 1024];
+            allocator
+                .copy_from_allocation(&allocation, 0, &mut contents)
+                .unwrap();
+            assert!(contents.iter().all(|&byte| byte == index));
+            let (buffer, _) = buffers.0[&(allocation as usize)];
+            allocator.destroy_buffer(buffer, &allocation);
+        }
+        harness.device.destroy_command_pool(command_pool, None);
+        allocator.destroy_pool(pool);
+    }
+}
+
+#[test]
+fn defrag_scheduler_spreads_moves_over_frames() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let buffer_info = ash::vk::BufferCreateInfo::builder()
+        .size(64 * 1024)
+        .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC | ash::vk::BufferUsageFlags::TRANSFER_DST)
+        .build();
+    unsafe {
+        let memory_type_index = allocator
+            .find_memory_type_index_for_buffer_info(&buffer_info, &Default::default())
+            .unwrap();
+        let pool = allocator
+            .create_pool(&vk_mem::AllocatorPoolCreateInfo {
+                memory_type_index,
+                block_size: 64 * 1024 * 4,
+                ..Default::default()
+            })
+            .unwrap();
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            pool: Some(pool),
+            ..Default::default()
+        };
+
+        let mut buffers = Buffers(Default::default());
+        for index in 0..16 {
+            let (buffer, allocation, _) = allocator
+                .create_buffer(&buffer_info, &allocation_info)
+                .unwrap();
+            if index % 2 == 0 {
+                allocator.destroy_buffer(buffer, &allocation);
+            } else {
+                buffers.0.insert(allocation as usize, (buffer, buffer_info));
+            }
+        }
+        assert!(vk_mem::defrag::DefragScheduler::fragmentation(&allocator, pool) >= 0.25);
+
+        let command_pool = harness
+            .device
+            .create_command_pool(
+                &ash::vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(harness.queue_family_index),
+                None,
+            )
+            .unwrap();
+        let queue = harness
+            .device
+            .get_device_queue(harness.queue_family_index, 0);
+        let mut scheduler = vk_mem::defrag::DefragScheduler::new(ash::vk::WHOLE_SIZE, 1);
+        scheduler.register_pool(pool);
+
+        let mut frames = 0;
+        let stats = loop {
+            frames += 1;
+            assert!(frames < 100);
+            let finished = scheduler
+                .run_frame(
+                    &allocator,
+                    &harness.device,
+                    queue,
+                    command_pool,
+                    &mut buffers,
+                )
+                .unwrap();
+            if let Some((finished_pool, stats)) = finished {
+                assert_eq!(finished_pool, pool);
+                break stats;
+            }
+            assert_eq!(scheduler.current_pool(), Some(pool));
+        };
+        // At most one move per frame.
+        assert!(stats.allocations_moved > 1);
+        assert!(frames >= stats.allocations_moved);
+
+        scheduler.unregister_pool(&allocator, pool).unwrap();
+        for (&allocation, &(buffer, _)) in &buffers.0 {
+            allocator.destroy_buffer(buffer, &(allocation as vk_mem::Allocation));
+        }
+        harness.device.destroy_command_pool(command_pool, None);
+        allocator.destroy_pool(pool);
+    }
+}
+
+#[test]
+fn pool_presets_pick_matching_memory_types() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let properties = allocator.get_memory_properties().unwrap();
+        let staging = allocator.create_staging_pool(1024 * 1024).unwrap();
+        let readback = allocator.create_readback_pool(1024 * 1024).unwrap();
+        let device = allocator
+            .create_device_pool(ash::vk::BufferUsageFlags::VERTEX_BUFFER)
+            .unwrap();
+
+        let (buffer, allocation, info) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(64 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE
+                        | vk_mem::AllocationCreateFlags::MAPPED,
+                    pool: Some(staging),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(properties.memory_types[info.get_memory_type() as usize]
+            .property_flags
+            .contains(ash::vk::MemoryPropertyFlags::HOST_VISIBLE));
+        assert!(!info.get_mapped_data().is_null());
+        allocator.destroy_buffer(buffer, &allocation);
+
+        for pool in [staging, readback, device] {
+            allocator.destroy_pool(pool);
+        }
+    }
+}
+
+#[test]
+fn stats_snapshot_diff_reports_gained_and_released_memory() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    unsafe {
+        let pool = allocator.create_staging_pool(1024 * 1024).unwrap();
+        allocator.set_pool_name(&pool, "Staging").unwrap();
+        let before = vk_mem::snapshot::StatsSnapshot::capture(&allocator).unwrap();
+
+        let (buffer, allocation, info) = allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(64 * 1024)
+                    .usage(ash::vk::BufferUsageFlags::TRANSFER_SRC)
+                    .build(),
+                &vk_mem::AllocationCreateInfo {
+                    flags: vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+                    pool: Some(pool),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let during = vk_mem::snapshot::StatsSnapshot::capture(&allocator).unwrap();
+        let delta = before.diff(&during);
+        assert_eq!(delta.total().allocation_count, 1);
+        assert_eq!(delta.memory_types.len(), 1);
+        assert_eq!(delta.memory_types[0].0, info.get_memory_type());
+        assert_eq!(delta.pools.len(), 1);
+        assert_eq!(delta.pools[0].name, "Staging");
+        assert_eq!(delta.pools[0].delta.allocation_count, 1);
+        assert!(delta.pools[0].delta.allocation_bytes >= 64 * 1024);
+
+        allocator.destroy_buffer(buffer, &allocation);
+        let after = vk_mem::snapshot::StatsSnapshot::capture(&allocator).unwrap();
+        assert!(before.diff(&after).is_empty());
+
+        allocator.destroy_pool(pool);
+        let delta = after.diff(&vk_mem::snapshot::StatsSnapshot::capture(&allocator).unwrap());
+        assert_eq!(delta.pools.len(), 1);
+        assert_eq!(delta.pools[0].delta.block_count, -1);
+    }
+}
+
+#[test]
+fn memory_allocator_trait_allocates_and_maps() {
+    use vk_mem::memory_allocator::{AllocationRequest, MemoryAllocator, MemoryLocation};
+
+    fn upload<A: MemoryAllocator>(allocator: &mut A) {
+        let request = AllocationRequest {
+            requirements: ash::vk::MemoryRequirements {
+                size: 4096,
+                alignment: 256,
+                memory_type_bits: !0,
+            },
+            location: MemoryLocation::CpuToGpu,
+            linear: true,
+            name: "upload",
+        };
+        unsafe {
+            let mut allocation = allocator.allocate(&request).unwrap();
+            let range = allocator.memory(&allocation).unwrap();
+            assert_ne!(range.memory, ash::vk::DeviceMemory::null());
+            assert_eq!(range.offset % 256, 0);
+            assert!(range.size >= 4096);
+            assert_eq!(allocator.statistics().allocation_count, 1);
+
+            let pointer = allocator.map(&mut allocation).unwrap();
+            pointer.as_ptr().write_bytes(0xab, 4096);
+            allocator.unmap(&mut allocation);
+            allocator.free(allocation).unwrap();
+        }
+        assert_eq!(allocator.statistics().allocation_count, 0);
+    }
+
+    let harness = TestHarness::new();
+    let mut allocator = harness.create_allocator();
+    upload(&mut allocator);
+}
+
+#[test]
+fn test_gpu_stats() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..Default::default()
+    };
+
+    let stats_1 = unsafe { allocator.calculate_statistics() }.unwrap();
+    assert_eq!(stats_1.total.statistics.block_count, 0);
+    assert_eq!(stats_1.total.statistics.allocation_count, 0);
+    assert_eq!(stats_1.total.statistics.allocation_bytes, 0);
+
+    let (buffer, allocation, _allocation_info) = allocator
+        .create_buffer(
+            &ash::vk::BufferCreateInfo::builder()
+                .size(16 * 1024)
+                .usage(
+                    ash::vk::BufferUsageFlags::VERTEX_BUFFER
+                        | ash::vk::BufferUsageFlags::TRANSFER_DST,
+                )
+                .build(),
+            &allocation_info,
+        )
+        .unwrap();
+
+    let stats_2 = unsafe { allocator.calculate_statistics() }.unwrap();
+    assert_eq!(stats_2.total.statistics.block_count, 1);
+    assert_eq!(stats_2.total.statistics.allocation_count, 1);
+    assert_eq!(stats_2.total.statistics.allocation_bytes, 16 * 1024);
+
+    allocator.destroy_buffer(buffer, &allocation);
+
+    let stats_3 = unsafe { allocator.calculate_statistics() }.unwrap();
+    assert_eq!(stats_3.total.statistics.block_count, 1);
+    assert_eq!(stats_3.total.statistics.allocation_count, 0);
+    assert_eq!(stats_3.total.statistics.allocation_bytes, 0);
+}
+
+#[test]
+fn statistics_display_is_human_readable() {
+    let statistics = vk_mem::Statistics {
+        block_count: 2,
+        allocation_count: 3,
+        block_bytes: 64 * 1024 * 1024,
+        allocation_bytes: 16 * 1024 * 1024,
+    };
+    assert_eq!(
+        statistics.to_string(),
+        "3 allocations (16.0 MiB) in 2 blocks (64.0 MiB, 25.0% used)"
+    );
+
+    let budget = vk_mem::Budget {
+        statistics,
+        usage: 1024 * 1024 * 1024,
+        budget: 4 * 1024 * 1024 * 1024,
+    };
+    assert!(budget
+        .to_string()
+        .starts_with("usage 1.0 GiB of 4.0 GiB budget (25.0%), 3 allocations"));
+
+    let defragmentation = vk_mem::DefragmentationStats {
+        bytes_moved: 512,
+        bytes_freed: 2048,
+        allocations_moved: 1,
+        device_memory_blocks_freed: 1,
+    };
+    assert_eq!(
+        defragmentation.to_string(),
+        "moved 1 allocations (512 B), freed 1 blocks (2.0 KiB)"
+    );
+}
+
+#[test]
+#[cfg(not(feature = "stats_string_disabled"))]
+fn test_stats_string() {
+    let harness = TestHarness::new();
+    let allocator = harness.create_allocator();
+    let allocation_info = vk_mem::AllocationCreateInfo {
+        usage: vk_mem::MemoryUsage::GpuOnly,
+        ..Default::default()
+    };
+
+    let stats_1 = allocator.build_stats_string(true).unwrap();
+    assert!(stats_1.len() > 0);
+
+    let (buffer, allocation, _allocation_info) = allocator
+        .create_buffer(
+            &ash::vk::BufferCreateInfo::builder()
+                .size(16 * 1024)
+                .usage(
+                    ash::vk::BufferUsageFlags::VERTEX_BUFFER
+                        | ash::vk::BufferUsageFlags::TRANSFER_DST,
+                )
+                .build(),
+            &allocation_info,
+        )
+        .unwrap();
+
+    let stats_2 = allocator.build_stats_string(true).unwrap();
+    assert!(stats_2.len() > 0);
+    assert_ne!(stats_1, stats_2);
+
+    allocator.destroy_buffer(buffer, &allocation);
+
+    let stats_3 = allocator.build_stats_string(true).unwrap();
+    assert!(stats_3.len() > 0);
+    assert_ne!(stats_3, stats_1);
+    assert_ne!(stats_3, stats_2);
+}
+
+#[test]
+fn memory_layout_reads_detailed_stats_json() {
+    let json = r#"{
+        "DefaultPools": {
+            "Type 1": {
+                "PreferredBlockSize": 1024,
+                "Blocks": {
+                    "0": {
+                        "TotalBytes": 1024,
+                        "Suballocations": [
+                            { "Offset": 0, "Type": "BUFFER", "Size": 256, "Name": "vertices" },
+                            { "Offset": 256, "Type": "FREE", "Size": 256 },
+                            { "Offset": 512, "Type": "IMAGE_OPTIMAL", "Size": 512 }
+                        ]
+                    }
+                },
+                "DedicatedAllocations": [
+                    { "Type": "IMAGE_OPTIMAL", "Size": 4096, "Name": "shadow <map>" }
+                ]
+            }
+        },
+        "CustomPools": {
+            "Type 2": [ { "Name": "staging", "Blocks": { "0": { "TotalBytes": 2048, "Suballocations": [] } } } ]
+        }
+    }"#;
+    let layout = vk_mem::viz::MemoryLayout::from_stats_json(json).unwrap();
+    assert_eq!(layout.blocks.len(), 3);
+
+    let block = &layout.blocks[0];
+    assert_eq!(block.memory_type_index, 1);
+    assert_eq!(block.pool, None);
+    assert_eq!(block.allocations.len(), 2);
+    assert_eq!(block.allocations[0].name.as_deref(), Some("vertices"));
+    assert_eq!(block.allocations[1].offset, 512);
+
+    assert!(layout.blocks[1].dedicated);
+    assert_eq!(layout.blocks[2].pool.as_deref(), Some("staging"));
+
+    let svg = layout.to_svg();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("shadow &lt;map&gt;"));
+    let dot = layout.to_dot();
+    assert!(dot.starts_with("digraph memory"));
+    assert!(dot.contains("cluster_"));
 }
@@ -1,2 +1,40 @@
+#include <cstdarg>
+#include <cstdio>
+#include <vector>
+
+// Implemented in src/vma_log.rs, forwards a message to the `log` crate.
+extern "C" void vk_mem_rs_log(int level, const char* message);
+
+static void vk_mem_rs_log_format(int level, const char* format, ...)
+{
+    va_list args;
+    va_start(args, format);
+    va_list args_copy;
+    va_copy(args_copy, args);
+    const int length = vsnprintf(nullptr, 0, format, args_copy);
+    va_end(args_copy);
+    if (length >= 0)
+    {
+        std::vector<char> message(static_cast<size_t>(length) + 1);
+        vsnprintf(message.data(), message.size(), format, args);
+        vk_mem_rs_log(level, message.data());
+    }
+    va_end(args);
+}
+
+// Levels understood by vk_mem_rs_log.
+#define VK_MEM_RS_LOG_DEBUG 0
+#define VK_MEM_RS_LOG_LEAK 1
+
+#ifdef VK_MEM_RS_DEBUG_LOG
+#define VMA_DEBUG_LOG_FORMAT(...) vk_mem_rs_log_format(VK_MEM_RS_LOG_DEBUG, __VA_ARGS__)
+#endif
+#define VMA_LEAK_LOG_FORMAT(...) vk_mem_rs_log_format(VK_MEM_RS_LOG_LEAK, __VA_ARGS__)
+
+// VMA_EXTERNAL_MEMORY_WIN32 is defined by build.rs on Windows.
+#if defined(_WIN32) && defined(VMA_EXTERNAL_MEMORY_WIN32) && VMA_EXTERNAL_MEMORY_WIN32
+#include "vma_win32.h"
+#endif
+
 #define VMA_IMPLEMENTATION
 #include "../include/vk_mem_alloc.h"
@@ -0,0 +1,31 @@
+// Parts of VK_KHR_external_memory_win32 VMA uses. The vendored headers don't include
+// vulkan_win32.h, so they are declared here for wrapper/vma_lib.cpp and for the bindings.
+#ifndef VK_MEM_RS_VMA_WIN32_H
+#define VK_MEM_RS_VMA_WIN32_H
+
+#include <vulkan/vulkan_core.h>
+
+#ifdef _WIN32
+#ifndef NOMINMAX
+#define NOMINMAX
+#endif
+#ifndef WIN32_LEAN_AND_MEAN
+#define WIN32_LEAN_AND_MEAN
+#endif
+#include <windows.h>
+#else
+// Only reached when generating the bindings on another platform, which put these items behind
+// cfg(windows) and take HANDLE from ash.
+typedef void* HANDLE;
+#endif
+
+typedef struct VkMemoryGetWin32HandleInfoKHR {
+    VkStructureType                       sType;
+    const void*                           pNext;
+    VkDeviceMemory                        memory;
+    VkExternalMemoryHandleTypeFlagBits    handleType;
+} VkMemoryGetWin32HandleInfoKHR;
+
+typedef VkResult (VKAPI_PTR *PFN_vkGetMemoryWin32HandleKHR)(VkDevice device, const VkMemoryGetWin32HandleInfoKHR* pGetWin32HandleInfo, HANDLE* pHandle);
+
+#endif